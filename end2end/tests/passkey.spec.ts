@@ -0,0 +1,43 @@
+import { test, expect } from "@playwright/test";
+
+// uses the virtual authenticator of chromium as a software authenticator
+test("should register a passkey and sign in with it", async ({ page, browserName }) => {
+  test.skip(browserName !== "chromium", "virtual authenticator needs chromium");
+
+  const client = await page.context().newCDPSession(page);
+  await client.send("WebAuthn.enable");
+  await client.send("WebAuthn.addVirtualAuthenticator", {
+    options: {
+      protocol: "ctap2",
+      transport: "internal",
+      hasResidentKey: true,
+      hasUserVerification: true,
+      isUserVerified: true,
+    },
+  });
+
+  // expects a fresh database, whose administrator must change the initial password
+  const password = process.env.DVORAK_ADMIN_PASSWORD ?? "dvorak";
+  await page.goto("http://localhost:3000/login");
+  await page.fill("#username", "Dvorak");
+  await page.fill("#password", password);
+  await page.click("text=Login");
+  await page.waitForURL("**/change-password");
+
+  await page.fill("#current_password", password);
+  await page.fill("#new_password", `${password}-changed`);
+  await page.fill("#confirm_password", `${password}-changed`);
+  await page.click("text=Change");
+  await page.waitForURL("**/admin");
+
+  await page.goto("http://localhost:3000/admin/profile");
+  await page.fill("input[placeholder='Passkey name']", "virtual");
+  await page.click("text=Add passkey");
+  await expect(page.locator("td", { hasText: "virtual" })).toBeVisible();
+
+  await page.context().clearCookies();
+  await page.goto("http://localhost:3000/login");
+  await page.fill("#username", "Dvorak");
+  await page.click("text=Sign in with passkey");
+  await page.waitForURL("**/admin");
+});
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS passkeys (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id     TEXT NOT NULL,
+    username    TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    credential  TEXT NOT NULL,
+    created_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_id ON passkeys (user_id);
+CREATE INDEX IF NOT EXISTS idx_passkeys_username ON passkeys (username);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS login_failures (
+    key             TEXT PRIMARY KEY,
+    count           INTEGER NOT NULL,
+    last_failed_at  INTEGER NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS users (
+    id                      TEXT PRIMARY KEY,
+    username                TEXT NOT NULL UNIQUE,
+    password_hash           TEXT NOT NULL,
+    password_set_at         INTEGER NOT NULL,
+    must_change_password    INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id TEXT NOT NULL,
+    role    TEXT NOT NULL,
+    PRIMARY KEY (user_id, role)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE sessions ADD COLUMN last_active_at INTEGER NOT NULL DEFAULT 0;
+
+UPDATE sessions SET last_active_at = last_seen_at;
@@ -0,0 +1,20 @@
+ALTER TABLE users ADD COLUMN email TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN display_name TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'Active';
+ALTER TABLE users ADD COLUMN avatar TEXT NOT NULL DEFAULT '/images/avatar.png';
+
+UPDATE users SET display_name = username WHERE display_name = '';
+
+CREATE TABLE IF NOT EXISTS roles (
+    name        TEXT PRIMARY KEY,
+    description TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO roles (name, description) VALUES
+    ('admin', 'manage users and settings'),
+    ('manager', 'approve leaves of the team'),
+    ('hr', 'manage leaves of all employees'),
+    ('employee', 'request leaves');
+
+-- users before roles existed were all administrators
+INSERT OR IGNORE INTO user_roles (user_id, role) SELECT id, 'admin' FROM users;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS departments (
+    id         TEXT PRIMARY KEY,
+    name       TEXT NOT NULL,
+    parent_id  TEXT,
+    manager_id TEXT
+);
+
+ALTER TABLE users ADD COLUMN department_id TEXT;
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS invitations (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL,
+    expires_at  INTEGER NOT NULL,
+    created_by  TEXT NOT NULL,
+    created_at  INTEGER NOT NULL,
+    accepted_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS invitations_user_id ON invitations (user_id);
+
+-- keys generated once and kept across restarts, such as the key signing invitation links
+CREATE TABLE IF NOT EXISTS secrets (
+    name  TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
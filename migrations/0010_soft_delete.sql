@@ -0,0 +1,6 @@
+-- deleted records stay in the recycle bin until restored or purged
+ALTER TABLE users ADD COLUMN deleted_at INTEGER;
+ALTER TABLE users ADD COLUMN deleted_by TEXT;
+
+ALTER TABLE departments ADD COLUMN deleted_at INTEGER;
+ALTER TABLE departments ADD COLUMN deleted_by TEXT;
@@ -0,0 +1,2 @@
+-- id of the user in the identity provider, kept by SCIM provisioning
+ALTER TABLE users ADD COLUMN external_id TEXT;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS leave_requests (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id     TEXT NOT NULL,
+    leave_type  TEXT NOT NULL,
+    start_date  TEXT NOT NULL,
+    end_date    TEXT NOT NULL,
+    remark      TEXT NOT NULL DEFAULT '',
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS leave_requests_user_id ON leave_requests (user_id);
+CREATE INDEX IF NOT EXISTS leave_requests_start_date ON leave_requests (start_date);
@@ -0,0 +1,5 @@
+-- status of leave requests, and who decided it with a comment
+ALTER TABLE leave_requests ADD COLUMN status TEXT NOT NULL DEFAULT 'Pending';
+ALTER TABLE leave_requests ADD COLUMN decided_by TEXT;
+ALTER TABLE leave_requests ADD COLUMN comment TEXT NOT NULL DEFAULT '';
+ALTER TABLE leave_requests ADD COLUMN decided_at INTEGER;
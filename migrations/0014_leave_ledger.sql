@@ -0,0 +1,32 @@
+-- how days of each leave type are granted, and what happens to them at year end
+CREATE TABLE IF NOT EXISTS leave_policies (
+    leave_type               TEXT PRIMARY KEY,
+    accrual                  TEXT NOT NULL,
+    days_per_year            REAL NOT NULL,
+    max_carry_over           REAL NOT NULL DEFAULT 0,
+    carry_over_expiry_months INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT OR IGNORE INTO leave_policies VALUES ('Annual', 'Monthly', 20, 5, 3);
+INSERT OR IGNORE INTO leave_policies VALUES ('Sick', 'Yearly', 10, 0, 0);
+INSERT OR IGNORE INTO leave_policies VALUES ('Personal', 'Yearly', 5, 0, 0);
+
+-- every change of leave balances, the balance of a year is the sum of its days
+CREATE TABLE IF NOT EXISTS leave_ledger (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id     TEXT NOT NULL,
+    leave_type  TEXT NOT NULL,
+    year        INTEGER NOT NULL,
+    kind        TEXT NOT NULL,
+    days        REAL NOT NULL,
+    -- accruals, carry-overs and expiries of a period are recorded once
+    period      TEXT,
+    leave_id    INTEGER,
+    reason      TEXT NOT NULL DEFAULT '',
+    created_by  TEXT,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS leave_ledger_user_id ON leave_ledger (user_id, year);
+CREATE UNIQUE INDEX IF NOT EXISTS leave_ledger_period
+    ON leave_ledger (user_id, leave_type, kind, period) WHERE period IS NOT NULL;
@@ -0,0 +1,30 @@
+-- steps of the approval chain, in the order of position
+CREATE TABLE IF NOT EXISTS approval_rules (
+    position      INTEGER PRIMARY KEY,
+    -- Manager or Role
+    approver      TEXT NOT NULL,
+    manager_level INTEGER NOT NULL DEFAULT 1,
+    role          TEXT NOT NULL DEFAULT '',
+    -- NULL applies to every leave type
+    leave_type    TEXT,
+    over_days     INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT OR IGNORE INTO approval_rules VALUES (1, 'Manager', 1, '', NULL, 0);
+INSERT OR IGNORE INTO approval_rules VALUES (2, 'Manager', 2, '', NULL, 5);
+INSERT OR IGNORE INTO approval_rules VALUES (3, 'Role', 1, 'hr', 'Sick', 3);
+
+-- the approval chain of each leave request, resolved when submitted
+CREATE TABLE IF NOT EXISTS leave_approvals (
+    leave_id      INTEGER NOT NULL,
+    step          INTEGER NOT NULL,
+    approver      TEXT NOT NULL,
+    manager_level INTEGER NOT NULL DEFAULT 1,
+    role          TEXT NOT NULL DEFAULT '',
+    approver_id   TEXT,
+    status        TEXT NOT NULL DEFAULT 'Pending',
+    decided_by    TEXT,
+    comment       TEXT NOT NULL DEFAULT '',
+    decided_at    INTEGER,
+    PRIMARY KEY (leave_id, step)
+);
@@ -0,0 +1,20 @@
+-- half days, the first day may start in the afternoon and the last day may end in the morning
+ALTER TABLE leave_requests ADD COLUMN start_part TEXT NOT NULL DEFAULT 'Full';
+ALTER TABLE leave_requests ADD COLUMN end_part TEXT NOT NULL DEFAULT 'Full';
+-- working days computed when submitted, so that later holidays do not change it
+ALTER TABLE leave_requests ADD COLUMN duration REAL NOT NULL DEFAULT 0;
+
+-- existing requests counted Monday to Friday: whole weeks, then the days left from the weekday of the start
+UPDATE leave_requests SET duration = (
+    SELECT (n / 7) * 5 + max(0, min(w + n % 7, 5) - w) + min(max(0, w + n % 7 - 7), 5)
+    FROM (
+        SELECT CAST(julianday(end_date) - julianday(start_date) AS INTEGER) + 1 AS n,
+            (CAST(strftime('%w', start_date) AS INTEGER) + 6) % 7 AS w
+    )
+);
+
+-- days off for everyone besides weekends
+CREATE TABLE IF NOT EXISTS holidays (
+    date TEXT PRIMARY KEY,
+    name TEXT NOT NULL
+);
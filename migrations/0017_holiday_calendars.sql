@@ -0,0 +1,36 @@
+-- holidays differ by region, users and departments are assigned a calendar,
+-- the others follow the calendar of their department or its ancestors, or the default calendar
+CREATE TABLE IF NOT EXISTS holiday_calendars (
+    id      INTEGER PRIMARY KEY AUTOINCREMENT,
+    name    TEXT NOT NULL UNIQUE,
+    -- days of the week such as 'Sat,Sun', empty for DVORAK_WEEKEND
+    weekend TEXT NOT NULL DEFAULT ''
+);
+
+INSERT OR IGNORE INTO holiday_calendars (id, name) VALUES (1, 'Default');
+
+-- holidays by calendar, a working holiday is a weekend day worked in exchange for a holiday
+CREATE TABLE IF NOT EXISTS calendar_holidays (
+    calendar_id INTEGER NOT NULL,
+    date        TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    working     INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (calendar_id, date)
+);
+
+INSERT OR IGNORE INTO calendar_holidays (calendar_id, date, name) SELECT 1, date, name FROM holidays;
+DROP TABLE IF EXISTS holidays;
+ALTER TABLE calendar_holidays RENAME TO holidays;
+
+-- holidays on the same day every year
+CREATE TABLE IF NOT EXISTS holiday_rules (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    calendar_id INTEGER NOT NULL,
+    month       INTEGER NOT NULL,
+    day         INTEGER NOT NULL,
+    name        TEXT NOT NULL,
+    UNIQUE (calendar_id, month, day)
+);
+
+ALTER TABLE departments ADD COLUMN holiday_calendar_id INTEGER;
+ALTER TABLE users ADD COLUMN holiday_calendar_id INTEGER;
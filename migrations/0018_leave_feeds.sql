@@ -0,0 +1,12 @@
+-- iCalendar feeds of approved leaves, calendar apps fetch them by a secret token instead of a session,
+-- a feed of the leaves of the user or of the members of a department, only digests of tokens are kept
+CREATE TABLE IF NOT EXISTS leave_feeds (
+    id            TEXT PRIMARY KEY,
+    user_id       TEXT NOT NULL,
+    -- NULL for the feed of the user's own leaves
+    department_id TEXT,
+    token_digest  TEXT NOT NULL UNIQUE,
+    created_at    INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS leave_feeds_user_id ON leave_feeds (user_id);
@@ -0,0 +1,20 @@
+-- files attached to leave requests such as sick notes, the content is kept by the attachment storage
+-- under the id, only the requester uploads them
+CREATE TABLE IF NOT EXISTS leave_attachments (
+    id           TEXT PRIMARY KEY,
+    leave_id     INTEGER NOT NULL,
+    user_id      TEXT NOT NULL,
+    file_name    TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size         INTEGER NOT NULL,
+    created_at   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS leave_attachments_leave_id ON leave_attachments (leave_id);
+CREATE INDEX IF NOT EXISTS leave_attachments_user_id ON leave_attachments (user_id);
+
+-- requests of a leave type longer than this many working days must have an attachment,
+-- NULL means never
+ALTER TABLE leave_policies ADD COLUMN attachment_over_days REAL;
+
+UPDATE leave_policies SET attachment_over_days = 2 WHERE leave_type = 'Sick';
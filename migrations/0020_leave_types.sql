@@ -0,0 +1,16 @@
+-- leave types are defined by administrators, each with its balance policy and attachment rule,
+-- leave requests, ledger entries and approval rules refer to a type by name
+ALTER TABLE leave_policies RENAME TO leave_types;
+ALTER TABLE leave_types RENAME COLUMN leave_type TO name;
+
+-- `#rrggbb` of badges
+ALTER TABLE leave_types ADD COLUMN color TEXT NOT NULL DEFAULT '#6b7280';
+ALTER TABLE leave_types ADD COLUMN paid INTEGER NOT NULL DEFAULT 1;
+-- requests may start in the past, such as sick leave reported afterwards
+ALTER TABLE leave_types ADD COLUMN backdated INTEGER NOT NULL DEFAULT 0;
+-- inactive types cannot be requested any more, existing requests and balances keep them
+ALTER TABLE leave_types ADD COLUMN active INTEGER NOT NULL DEFAULT 1;
+
+UPDATE leave_types SET color = '#3b82f6' WHERE name = 'Annual';
+UPDATE leave_types SET color = '#ef4444', backdated = 1 WHERE name = 'Sick';
+UPDATE leave_types SET color = '#a855f7' WHERE name = 'Personal';
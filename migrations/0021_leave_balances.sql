@@ -0,0 +1,3 @@
+-- Tracked types are requested within their balance, Unlimited ones such as unpaid leave
+-- have no balance, so they neither accrue days nor record the days used
+ALTER TABLE leave_types ADD COLUMN balance TEXT NOT NULL DEFAULT 'Tracked';
@@ -0,0 +1,19 @@
+// File
+// helpers for reading files chosen by users, the content is sent to server as base64
+(function () {
+  window.readFileAsDataUrl = function (inputId) {
+    return new Promise((resolve, reject) => {
+      const input = document.getElementById(inputId);
+      const file = input && input.files && input.files[0];
+      if (!file) {
+        reject(new Error("no file chosen"));
+        return;
+      }
+
+      const reader = new FileReader();
+      reader.onload = () => resolve(reader.result);
+      reader.onerror = () => reject(reader.error);
+      reader.readAsDataURL(file);
+    });
+  };
+})();
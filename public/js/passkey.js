@@ -0,0 +1,65 @@
+// Passkey
+// helpers for WebAuthn, converts between the JSON of server (base64url strings)
+// and the ArrayBuffers used by navigator.credentials
+(function () {
+  function toBuffer(value) {
+    const base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+    const padded = base64 + "===".slice((base64.length + 3) % 4);
+    return Uint8Array.from(atob(padded), (c) => c.charCodeAt(0)).buffer;
+  }
+
+  function toBase64Url(buffer) {
+    if (!buffer) {
+      return null;
+    }
+    const bytes = new Uint8Array(buffer);
+    let binary = "";
+    bytes.forEach((b) => (binary += String.fromCharCode(b)));
+    return btoa(binary).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+  }
+
+  function toCredentialDescriptors(list) {
+    return (list || []).map((c) => ({ ...c, id: toBuffer(c.id) }));
+  }
+
+  window.passkeyCreate = async function (optionsJson) {
+    const options = JSON.parse(optionsJson).publicKey;
+    options.challenge = toBuffer(options.challenge);
+    options.user.id = toBuffer(options.user.id);
+    options.excludeCredentials = toCredentialDescriptors(options.excludeCredentials);
+
+    const credential = await navigator.credentials.create({ publicKey: options });
+
+    return JSON.stringify({
+      id: credential.id,
+      rawId: toBase64Url(credential.rawId),
+      type: credential.type,
+      extensions: credential.getClientExtensionResults(),
+      response: {
+        attestationObject: toBase64Url(credential.response.attestationObject),
+        clientDataJSON: toBase64Url(credential.response.clientDataJSON),
+      },
+    });
+  };
+
+  window.passkeyGet = async function (optionsJson) {
+    const options = JSON.parse(optionsJson).publicKey;
+    options.challenge = toBuffer(options.challenge);
+    options.allowCredentials = toCredentialDescriptors(options.allowCredentials);
+
+    const credential = await navigator.credentials.get({ publicKey: options });
+
+    return JSON.stringify({
+      id: credential.id,
+      rawId: toBase64Url(credential.rawId),
+      type: credential.type,
+      extensions: credential.getClientExtensionResults(),
+      response: {
+        authenticatorData: toBase64Url(credential.response.authenticatorData),
+        clientDataJSON: toBase64Url(credential.response.clientDataJSON),
+        signature: toBase64Url(credential.response.signature),
+        userHandle: toBase64Url(credential.response.userHandle),
+      },
+    });
+  };
+})();
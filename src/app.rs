@@ -2,8 +2,12 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 
-use crate::components::{DashBoard, Home, Login, NotFound404};
-use crate::models::consts::ADMIN_ROUTE_PREFIX;
+use crate::components::{
+    AcceptInvitation, ApprovalChain, ChangePassword, DashBoard, Departments, Holidays, Home,
+    LeaveCalendar, LeaveLedger, LeaveTypes, Login, NewLeave, NotFound404, Profile, RecycleBin,
+    SessionEvents, UserEdit, UserImport, Users,
+};
+use crate::models::consts::{ADMIN_ROUTE_PREFIX, CHANGE_PASSWORD_ROUTE, INVITATION_ROUTE};
 
 #[component]
 pub fn App() -> impl IntoView {
@@ -13,13 +17,32 @@ pub fn App() -> impl IntoView {
         <Html lang="en" attr:data-theme="light"/>
         <Stylesheet id="leptos" href="/pkg/dvorak_admin.css"/>
         <Link rel="shortcut icon" type_="image/ico" href="/favicon.ico"/>
+        <Script src="/js/passkey.js"/>
+        <Script src="/js/file.js"/>
         <Router>
             <Routes>
                 <Route path=ADMIN_ROUTE_PREFIX view=Home>
                     <Route path="" view=DashBoard/>
+                    <Route path="profile" view=Profile/>
+                    <Route path="leaves/new" view=NewLeave/>
+                    <Route path="leaves/calendar" view=LeaveCalendar/>
+                    <Route path="leaves/balances" view=LeaveLedger/>
+                    <Route path="leaves/types" view=LeaveTypes/>
+                    <Route path="leaves/approval-chain" view=ApprovalChain/>
+                    <Route path="holidays" view=Holidays/>
+                    <Route path="security/events" view=SessionEvents/>
+                    <Route path="users" view=Users/>
+                    <Route path="users/new" view=|| view! { <UserEdit/> }/>
+                    <Route path="users/invite" view=|| view! { <UserEdit invite=true/> }/>
+                    <Route path="users/import" view=UserImport/>
+                    <Route path="users/:id" view=|| view! { <UserEdit/> }/>
+                    <Route path="departments" view=Departments/>
+                    <Route path="recycle-bin" view=RecycleBin/>
                     <Route path="*any" view=NotFound404/>
                 </Route>
                 <Route path="login" view=Login/>
+                <Route path=CHANGE_PASSWORD_ROUTE view=ChangePassword/>
+                <Route path=format!("{}/:token", INVITATION_ROUTE) view=AcceptInvitation/>
             </Routes>
         </Router>
     }
@@ -0,0 +1,292 @@
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use super::current_user::use_has_role;
+use super::leave_types::use_leave_types;
+use super::users::get_roles;
+use crate::models::consts::ADMIN_ROLE;
+use crate::models::{ApprovalRule, Approver, FieldErrors};
+
+#[server]
+async fn get_approval_rules() -> Result<Vec<ApprovalRule>, ServerFnError<String>> {
+    use crate::models::consts::LEAVE_BALANCE_ROLES;
+    use crate::server::{require_any_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_any_role(&LEAVE_BALANCE_ROLES).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    ApprovalRule::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// replace the approval chain, returns the errors of invalid rules, empty if saved
+#[server(name = SaveApprovalRules, prefix = "/api", input = Json)]
+async fn save_approval_rules(
+    rules: Vec<ApprovalRule>,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let errors = ApprovalRule::validate_all(&conn, &rules)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    ApprovalRule::save_all(&conn, &rules).map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+/// the steps leave requests are approved through, in order,
+/// HR sees them and only administrators change them
+#[component]
+pub fn ApprovalChain() -> impl IntoView {
+    let is_admin = use_has_role(ADMIN_ROLE);
+    let (rules, set_rules) = create_signal(Vec::<ApprovalRule>::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let save = create_action(|rules: &Vec<ApprovalRule>| {
+        let rules = rules.clone();
+        async move { save_approval_rules(rules).await }
+    });
+    let saved_rules = create_resource(
+        move || save.version().get(),
+        |_| async move { get_approval_rules().await },
+    );
+    let roles = create_resource(|| (), |_| async move { get_roles().await.unwrap_or_default() });
+    let leave_types = use_leave_types();
+
+    create_effect(move |_| match saved_rules.get() {
+        Some(Ok(list)) => set_rules(list),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+    create_effect(move |_| match save.value().get() {
+        Some(Ok(errors)) => set_error(errors.get("rules").cloned()),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    let update = move |index: usize, f: &dyn Fn(&mut ApprovalRule)| {
+        set_rules.update(|rules| {
+            if let Some(rule) = rules.get_mut(index) {
+                f(rule)
+            }
+        })
+    };
+    let move_up = move |index: usize| set_rules.update(|rules| rules.swap(index - 1, index));
+    let remove = move |index: usize| {
+        set_rules.update(|rules| {
+            rules.remove(index);
+        })
+    };
+
+    view! {
+        <div class="h-full w-full p-4">
+            <div class="card bg-base-100 shadow">
+                <div class="card-body">
+                    <h2 class="card-title">"Approval Chain"</h2>
+                    <p class="text-sm opacity-70">
+                        "Leave requests are approved by every step applying to them, in order. "
+                        "Changes apply to leave requests submitted afterwards."
+                    </p>
+                    {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                    <table class="table table-sm">
+                        <thead>
+                            <tr>
+                                <th>"Step"</th>
+                                <th>"Approver"</th>
+                                <th>"Level / Role"</th>
+                                <th>"Leave Type"</th>
+                                <th>"Over Working Days"</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {move || {
+                                rules
+                                    .get()
+                                    .into_iter()
+                                    .enumerate()
+                                    .map(|(index, rule)| {
+                                        let is_manager = matches!(rule.approver, Approver::Manager(_));
+                                        view! {
+                                            <tr>
+                                                <td>{index + 1}</td>
+                                                <td>
+                                                    <select
+                                                        class="select select-bordered select-sm"
+                                                        disabled=move || !is_admin.get()
+                                                        on:change=move |ev| {
+                                                            let manager = event_target_value(&ev) == "Manager";
+                                                            update(
+                                                                index,
+                                                                &|rule| {
+                                                                    rule.approver = if manager {
+                                                                        Approver::Manager(1)
+                                                                    } else {
+                                                                        Approver::Role(String::new())
+                                                                    };
+                                                                },
+                                                            )
+                                                        }
+                                                    >
+                                                        <option value="Manager" selected=is_manager>
+                                                            "Manager"
+                                                        </option>
+                                                        <option value="Role" selected=!is_manager>
+                                                            "Role"
+                                                        </option>
+                                                    </select>
+                                                </td>
+                                                <td>
+                                                    {match rule.approver.clone() {
+                                                        Approver::Manager(level) => {
+                                                            view! {
+                                                                <input
+                                                                    type="number"
+                                                                    min="1"
+                                                                    class="input input-bordered input-sm w-24"
+                                                                    disabled=move || !is_admin.get()
+                                                                    prop:value=level.to_string()
+                                                                    on:change=move |ev| {
+                                                                        let level = event_target_value(&ev).parse().unwrap_or(1);
+                                                                        update(index, &|rule| rule.approver = Approver::Manager(level))
+                                                                    }
+                                                                />
+                                                            }
+                                                                .into_view()
+                                                        }
+                                                        Approver::Role(role) => {
+                                                            view! {
+                                                                <select
+                                                                    class="select select-bordered select-sm"
+                                                                    disabled=move || !is_admin.get()
+                                                                    on:change=move |ev| {
+                                                                        let role = event_target_value(&ev);
+                                                                        update(index, &|rule| rule.approver = Approver::Role(role.clone()))
+                                                                    }
+                                                                >
+                                                                    <option value="" selected=role.is_empty()>
+                                                                        "choose a role"
+                                                                    </option>
+                                                                    {move || {
+                                                                        let current = role.clone();
+                                                                        roles
+                                                                            .get()
+                                                                            .unwrap_or_default()
+                                                                            .into_iter()
+                                                                            .map(|r| {
+                                                                                let selected = r.name == current;
+                                                                                view! {
+                                                                                    <option value=r.name.clone() selected=selected>
+                                                                                        {r.name}
+                                                                                    </option>
+                                                                                }
+                                                                            })
+                                                                            .collect_view()
+                                                                    }}
+                                                                </select>
+                                                            }
+                                                                .into_view()
+                                                        }
+                                                    }}
+                                                </td>
+                                                <td>
+                                                    <select
+                                                        class="select select-bordered select-sm"
+                                                        disabled=move || !is_admin.get()
+                                                        on:change=move |ev| {
+                                                            let leave_type = Some(event_target_value(&ev)).filter(|value| !value.is_empty());
+                                                            update(index, &|rule| rule.leave_type = leave_type.clone())
+                                                        }
+                                                    >
+                                                        <option value="" selected=rule.leave_type.is_none()>
+                                                            "any"
+                                                        </option>
+                                                        {
+                                                            let current = rule.leave_type.clone();
+                                                            move || {
+                                                                leave_types
+                                                                    .get()
+                                                                    .into_iter()
+                                                                    .map(|leave_type| {
+                                                                        let selected = current.as_deref() == Some(leave_type.name.as_str());
+                                                                        view! {
+                                                                            <option value=leave_type.name.clone() selected=selected>
+                                                                                {leave_type.name}
+                                                                            </option>
+                                                                        }
+                                                                    })
+                                                                    .collect_view()
+                                                            }
+                                                        }
+                                                    </select>
+                                                </td>
+                                                <td>
+                                                    <input
+                                                        type="number"
+                                                        min="0"
+                                                        class="input input-bordered input-sm w-24"
+                                                        disabled=move || !is_admin.get()
+                                                        prop:value=rule.over_days.to_string()
+                                                        on:change=move |ev| {
+                                                            let days = event_target_value(&ev).parse().unwrap_or_default();
+                                                            update(index, &|rule| rule.over_days = days)
+                                                        }
+                                                    />
+                                                </td>
+                                                <td class="space-x-1">
+                                                    <Show when=move || is_admin.get()>
+                                                        <Show when=move || { index > 0 }>
+                                                            <button class="btn btn-ghost btn-xs" on:click=move |_| move_up(index)>
+                                                                "Up"
+                                                            </button>
+                                                        </Show>
+                                                        <button class="btn btn-ghost btn-xs" on:click=move |_| remove(index)>
+                                                            "Remove"
+                                                        </button>
+                                                    </Show>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()
+                            }}
+
+                        </tbody>
+                    </table>
+                    <Show when=move || is_admin.get()>
+                        <div class="card-actions justify-end mt-4">
+                            <button
+                                class="btn btn-ghost"
+                                on:click=move |_| set_rules.update(|rules| rules.push(ApprovalRule::default()))
+                            >
+                                "Add Step"
+                            </button>
+                            <button
+                                class="btn btn-primary"
+                                disabled=save.pending()
+                                on:click=move |_| {
+                                    set_error(None);
+                                    save.dispatch(rules.get_untracked())
+                                }
+                            >
+                                "Save"
+                            </button>
+                        </div>
+                    </Show>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,105 @@
+use leptos::*;
+use leptos_router::*;
+
+#[server(ChangePassword, "/api")]
+pub async fn change_password(
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
+) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROUTE_PREFIX;
+    use crate::models::User;
+    use crate::server::{current_token_for_password_change, AppDataDb};
+    use leptos_actix::{extract, redirect};
+
+    let token = current_token_for_password_change().await?;
+    if new_password != confirm_password {
+        return Err(ServerFnError::from("passwords do not match".to_string()));
+    }
+
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+    User::change_password(
+        &db.lock().unwrap(),
+        &token.id,
+        &current_password,
+        &new_password,
+    )
+    .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    redirect(ADMIN_ROUTE_PREFIX);
+
+    Ok(())
+}
+
+#[component]
+pub fn ChangePassword() -> impl IntoView {
+    let change = create_server_action::<ChangePassword>();
+
+    view! {
+        <main class="flex w-full h-screen place-items-center place-content-center bg-base-200">
+            <div class="card bg-base-100 shadow w-96">
+                <div class="card-body">
+                    <h2 class="card-title">"Change Password"</h2>
+                    <p class="text-sm opacity-70">
+                        "Your password has expired or must be changed before continuing"
+                    </p>
+                    <ActionForm action=change>
+                        <div class="form-control">
+                            <label class="label" for="current_password">
+                                <span class="label-text">"Current password"</span>
+                            </label>
+                            <input
+                                type="password"
+                                class="input input-bordered"
+                                required
+                                id="current_password"
+                                name="current_password"
+                            />
+                        </div>
+                        <div class="form-control">
+                            <label class="label" for="new_password">
+                                <span class="label-text">"New password"</span>
+                            </label>
+                            <input
+                                type="password"
+                                class="input input-bordered"
+                                required
+                                minlength="8"
+                                id="new_password"
+                                name="new_password"
+                            />
+                        </div>
+                        <div class="form-control">
+                            <label class="label" for="confirm_password">
+                                <span class="label-text">"Confirm new password"</span>
+                            </label>
+                            <input
+                                type="password"
+                                class="input input-bordered"
+                                required
+                                minlength="8"
+                                id="confirm_password"
+                                name="confirm_password"
+                            />
+                        </div>
+                        <div class="form-control mt-6">
+                            <button class="btn btn-primary">"Change"</button>
+                        </div>
+                        {move || {
+                            change
+                                .value()
+                                .get()
+                                .and_then(|result| result.err())
+                                .map(|e| {
+                                    view! { <p class="text-error text-sm mt-2">{e.to_string()}</p> }
+                                })
+                        }}
+
+                    </ActionForm>
+                </div>
+            </div>
+        </main>
+    }
+}
@@ -0,0 +1,59 @@
+//! Current User
+//! the logged in user is loaded once by [Home](super::Home) and provided as context,
+//! so that any component under it can read the user reactively
+//!
+//! ```ignore
+//! let is_admin = use_has_role(ADMIN_ROLE);
+//! view! { <Show when=is_admin>"only administrators see this"</Show> }
+//! ```
+
+use leptos::*;
+
+use crate::models::CurrentUser;
+
+#[server]
+pub async fn get_current_user() -> Result<CurrentUser, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    CurrentUser::from_token(&conn, &token).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[derive(Clone, Copy)]
+struct CurrentUserContext(Resource<(), Option<CurrentUser>>);
+
+/// load the current user and provide it to all of the descendants
+pub fn provide_current_user() {
+    let resource = create_resource(|| {}, |_| async move { get_current_user().await.ok() });
+    provide_context(CurrentUserContext(resource));
+}
+
+/// the current user, None while loading or if not logged in
+pub fn use_current_user() -> Signal<Option<CurrentUser>> {
+    let CurrentUserContext(resource) = expect_context::<CurrentUserContext>();
+    Signal::derive(move || resource.get().flatten())
+}
+
+/// whether the current user has `role`, false while loading
+pub fn use_has_role(role: &'static str) -> Signal<bool> {
+    let current_user = use_current_user();
+    Signal::derive(move || {
+        current_user.with(|user| user.as_ref().is_some_and(|user| user.has_role(role)))
+    })
+}
+
+/// a callback loading the current user again, such as after the profile changed
+pub fn use_refresh_current_user() -> Callback<()> {
+    let context = use_context::<CurrentUserContext>();
+    Callback::new(move |_| {
+        if let Some(CurrentUserContext(resource)) = context {
+            resource.refetch();
+        }
+    })
+}
@@ -1,13 +1,26 @@
+use chrono::Datelike;
+use leptos::server_fn::codec::Json;
 use leptos::{html::Canvas, *};
 use leptos_meta::*;
+use leptos_router::*;
 
-use crate::models::{LeaveList, LeaveRequest};
+use super::current_user::use_current_user;
+use super::file::read_file;
+use super::leave_types::LeaveTypeBadge;
+use super::leaves::LeaveBalances;
+use crate::models::{
+    format_days, format_size, leaves_to_ics, ApprovalStatus, ApprovalStep, AttachmentUpload,
+    DayPart, LeaveAttachment, LeaveList, LeaveRequest, LeaveStatus, MAX_LEAVE_COMMENT_LENGTH,
+};
 
 #[component]
 pub fn DashBoard() -> impl IntoView {
     view! {
         <div class="h-full w-full p-4">
             <TotalReview/>
+            <div class="mt-4">
+                <LeaveBalances year=chrono::Local::now().year()/>
+            </div>
             <Visiteds/>
         </div>
     }
@@ -77,27 +90,188 @@ fn TotalReview() -> impl IntoView {
     }
 }
 
+/// leave requests current user can see, all of them for administrators and HR,
+/// otherwise the user's own, those of their reports and those the user approves a step of
+#[server]
+async fn get_leaves() -> Result<LeaveList, ServerFnError<String>> {
+    use crate::server::leave::{fill_staffing, visible_leaves, AppDataLeaveRepository};
+    use crate::server::leave_attachment::fill_attachments;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves): (AppDataDb, AppDataLeaveRepository) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let all = leaves.list().map_err(|e| ServerFnError::from(e.to_string()))?;
+    let conn = db.lock().unwrap();
+    let mut list =
+        visible_leaves(&conn, &all, &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+    fill_staffing(&conn, &mut list, &all).map_err(|e| ServerFnError::from(e.to_string()))?;
+    fill_attachments(&conn, &mut list, &token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(list)
+}
+
+/// content of attachment `id` as a data url, for the requester and approvers of its leave request
 #[server]
-async fn get_leaves() -> Result<LeaveList, ServerFnError> {
-    use crate::models::LeaveRequest;
-    use crate::server::leave;
-    let leaves = LeaveRequest::get_leave_requests().await;
-    Ok(leaves)
+async fn get_leave_attachment(id: String) -> Result<String, ServerFnError<String>> {
+    use crate::models::User;
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_attachment::{can_view, AppDataAttachmentStorage};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves, attachments): (AppDataDb, AppDataLeaveRepository, AppDataAttachmentStorage) =
+        extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let attachment = LeaveAttachment::get(&db.lock().unwrap(), &id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    let leave = leaves
+        .get(attachment.leave_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    let roles =
+        User::roles(&db.lock().unwrap(), &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+    if !can_view(&leave, &token.id, &roles) {
+        return Err(ServerFnError::from("permission denied".to_string()));
+    }
+    attachment
+        .read(&**attachments)
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// attach `upload` to pending or approved leave request `leave_id` of current user,
+/// returns the attachments of the request
+#[server(name = UploadLeaveAttachment, prefix = "/api", input = Json)]
+async fn upload_leave_attachment(
+    leave_id: i64,
+    upload: AttachmentUpload,
+) -> Result<Vec<LeaveAttachment>, ServerFnError<String>> {
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_attachment::{discard, AppDataAttachmentStorage};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves, attachments): (AppDataDb, AppDataLeaveRepository, AppDataAttachmentStorage) =
+        extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let leave = leaves
+        .get(leave_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if leave.user.id != token.id
+        || !matches!(leave.status, LeaveStatus::Pending | LeaveStatus::Approved)
+    {
+        return Err(ServerFnError::from("permission denied".to_string()));
+    }
+
+    //  the file is stored before the database is locked, only to record it
+    let stored = [upload
+        .store(&**attachments)
+        .map_err(|e| ServerFnError::from(e.to_string()))?];
+    let conn = db.lock().unwrap();
+    if let Err(e) = LeaveAttachment::attach(&conn, leave_id, &token.id, &stored) {
+        drop(conn);
+        discard(&**attachments, &stored);
+        return Err(ServerFnError::from(e.to_string()));
+    }
+    LeaveAttachment::list(&conn, leave_id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// remove attachment `id` of a pending leave request of current user,
+/// returns the attachments left
+#[server]
+async fn delete_leave_attachment(
+    id: String,
+) -> Result<Vec<LeaveAttachment>, ServerFnError<String>> {
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_attachment::AppDataAttachmentStorage;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves, attachments): (AppDataDb, AppDataLeaveRepository, AppDataAttachmentStorage) =
+        extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let attachment = LeaveAttachment::get(&db.lock().unwrap(), &id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    let leave = leaves
+        .get(attachment.leave_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if leave.user.id != token.id || leave.status != LeaveStatus::Pending {
+        return Err(ServerFnError::from("permission denied".to_string()));
+    }
+    let conn = db.lock().unwrap();
+    LeaveAttachment::delete(&conn, &**attachments, &id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    LeaveAttachment::list(&conn, leave.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// approve, reject, withdraw or cancel leave request `id` with `comment`
+#[server]
+pub async fn change_leave_status(
+    id: i64,
+    status: LeaveStatus,
+    comment: String,
+) -> Result<(), ServerFnError<String>> {
+    use crate::models::User;
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves): (AppDataDb, AppDataLeaveRepository) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let roles =
+        User::roles(&db.lock().unwrap(), &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+    let actor = User {
+        id: token.id.clone(),
+        username: token.username.clone(),
+    };
+    LeaveRequest::change_status(&**leaves, id, status, &actor, &roles, &comment)
+        .map(|_| ())
+        .map_err(|e| ServerFnError::from(e.to_string()))
 }
 
 #[component]
 fn Visiteds() -> impl IntoView {
+    let change_status = create_action(|input: &(i64, LeaveStatus, String)| {
+        let (id, status, comment) = input.clone();
+        async move { change_leave_status(id, status, comment).await }
+    });
     let leaves = create_resource(
-        || {},
-        |_| async move {
-            get_leaves()
-                .await
-                .map_err(|_| ServerFnError::from("get leave fail".to_string()))
-                .unwrap()
-        },
+        move || change_status.version().get(),
+        |_| async move { get_leaves().await.unwrap_or_default() },
     );
 
+    let error = move || {
+        change_status
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { <p class="text-error text-sm mt-4">{e.to_string()}</p> })
+    };
+
     view! {
+        <div class="flex justify-end gap-2 mt-4">
+            <A href="/admin/leaves/calendar" class="btn btn-ghost btn-sm">
+                "Team Calendar"
+            </A>
+            <A href="/admin/leaves/new" class="btn btn-primary btn-sm">
+                "New Leave Request"
+            </A>
+        </div>
+        {error}
         <div class="overflow-x-auto bg-base-100 rounded-lg mt-4 shadow">
             <table class="table">
                 // <!-- head -->
@@ -106,6 +280,8 @@ fn Visiteds() -> impl IntoView {
                         <th>"Name"</th>
                         <th>"Start Date"</th>
                         <th>"End Date"</th>
+                        <th>"Days"</th>
+                        <th>"Status"</th>
                         <th></th>
                     </tr>
                 </thead>
@@ -120,9 +296,9 @@ fn Visiteds() -> impl IntoView {
                                     view! {
                                         <For
                                             each=move || { list.clone() }
-                                            key=|item| item.id
+                                            key=|item| (item.id, item.status, item.current_step())
                                             children=move |leave: LeaveRequest| {
-                                                view! { <LeaveItem leave=leave/> }
+                                                view! { <LeaveItem leave=leave change_status=change_status/> }
                                             }
                                         />
                                     }
@@ -140,6 +316,8 @@ fn Visiteds() -> impl IntoView {
                         <th>"Name"</th>
                         <th>"Start Date"</th>
                         <th>"End Date"</th>
+                        <th>"Days"</th>
+                        <th>"Status"</th>
                         <th></th>
                     </tr>
                 </tfoot>
@@ -150,9 +328,10 @@ fn Visiteds() -> impl IntoView {
 }
 
 #[component]
-fn LeaveItem(leave: LeaveRequest) -> impl IntoView {
-    use crate::models::LeaveType::*;
-
+fn LeaveItem(
+    leave: LeaveRequest,
+    change_status: Action<(i64, LeaveStatus, String), Result<(), ServerFnError<String>>>,
+) -> impl IntoView {
     let modal_id = format!("leave_detail_{}", leave.id);
     let detail = leave.clone();
 
@@ -171,18 +350,20 @@ fn LeaveItem(leave: LeaveRequest) -> impl IntoView {
                     <div>
                         <div class="font-bold">{leave.user.username}</div>
                         <div class="text-sm opacity-50">
-                            {match leave.leave_type {
-                                Personal => view! { "Personal" },
-                                Sick => view! { "Sick" },
-                                Annual => view! { "Annual" },
-                            }}
-
+                            <LeaveTypeBadge name=leave.leave_type.clone()/>
                         </div>
                     </div>
                 </div>
             </td>
-            <td>{leave.start_date}</td>
-            <td>{leave.end_date}</td>
+            <td>{date_with_part(leave.start_date, leave.start_part)}</td>
+            <td>{date_with_part(leave.end_date, leave.end_part)}</td>
+            <td>{format_days(leave.duration)}</td>
+            <td>
+                <span class=format!(
+                    "badge badge-sm {}",
+                    status_badge(leave.status),
+                )>{leave.status.to_string()}</span>
+            </td>
             <th>
                 <button class="btn btn-ghost btn-xs" onclick=format!("{}.showModal()", modal_id)>
                     details
@@ -190,12 +371,115 @@ fn LeaveItem(leave: LeaveRequest) -> impl IntoView {
             </th>
         </tr>
 
-        <LeaveDetail leave=detail modal_id=modal_id/>
+        <LeaveDetail leave=detail modal_id=modal_id change_status=change_status/>
     }
 }
 
+/// a dialog of leave request details, opened by `{modal_id}.showModal()`
 #[component]
-fn LeaveDetail(leave: LeaveRequest, modal_id: String) -> impl IntoView {
+pub fn LeaveDetail(
+    leave: LeaveRequest,
+    modal_id: String,
+    change_status: Action<(i64, LeaveStatus, String), Result<(), ServerFnError<String>>>,
+) -> impl IntoView {
+    let current_user = use_current_user();
+    let (comment, set_comment) = create_signal(String::new());
+    let (id, status) = (leave.id, leave.status);
+    let detail = leave.clone();
+
+    //  progress of the approval chain, none for requests without a chain
+    let current_step = leave.current_step();
+    let steps = (!leave.approvals.is_empty()).then(|| {
+        let steps = leave
+            .approvals
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                view! {
+                    <li class=format!("step {}", step_class(step, Some(index) == current_step))>
+                        <div class="text-left">
+                            <p>{step.describe()}</p>
+                            <p class="text-sm opacity-70">{step_progress(step)}</p>
+                        </div>
+                    </li>
+                }
+            })
+            .collect_view();
+        view! { <ul class="steps steps-vertical">{steps}</ul> }
+    });
+
+    //  the request as an iCalendar file, downloaded without asking the server again
+    let export = matches!(status, LeaveStatus::Pending | LeaveStatus::Approved).then(|| {
+        let ics = leaves_to_ics(
+            &format!("{}'s leave", leave.user.username),
+            std::slice::from_ref(&leave),
+            true,
+        );
+        view! {
+            <a class="btn btn-ghost" href=ics_data_url(&ics) download=format!("leave-{}.ics", id)>
+                "Export to calendar"
+            </a>
+        }
+    });
+
+    //  a warning of the days the team would be short of members, for pending requests only
+    let staffing = (!leave.staffing.is_empty()).then(|| {
+        let days = leave
+            .staffing
+            .iter()
+            .map(|shortage| {
+                view! {
+                    <li>
+                        {format!(
+                            "{}: {} of {} present, {} also away",
+                            shortage.date,
+                            shortage.present,
+                            shortage.team_size,
+                            shortage.absent.join(", "),
+                        )}
+                    </li>
+                }
+            })
+            .collect_view();
+        view! {
+            <div role="alert" class="alert alert-warning">
+                <div>
+                    <p class="font-bold">"The team would be understaffed"</p>
+                    <ul class="text-sm">{days}</ul>
+                </div>
+            </div>
+        }
+    });
+
+    let attachments = view! {
+        <LeaveAttachments
+            leave_id=id
+            requester_id=leave.user.id.clone()
+            status=status
+            attachments=leave.attachments.clone()
+        />
+    };
+
+    //  the buttons the current user can click, the server checks again
+    let actions = move || {
+        current_user
+            .get()
+            .map(|user| detail.available_statuses(&user.id, &user.roles))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|status| {
+                view! {
+                    <button
+                        class=format!("btn {}", action_button(status))
+                        on:click=move |_| change_status.dispatch((id, status, comment.get_untracked()))
+                    >
+                        {action_label(status)}
+                    </button>
+                }
+            })
+            .collect_view()
+    };
+
     view! {
         <dialog id=modal_id class="modal">
             <div class="modal-box">
@@ -203,15 +487,45 @@ fn LeaveDetail(leave: LeaveRequest, modal_id: String) -> impl IntoView {
                     {format!("{}'s leave request", leave.user.username)}
                 </h3>
                 <div class="flex  flex-col space-y-4 mt-6">
-                    <p>"Type: " {leave.leave_type.to_string()}</p>
-                    <p>"Start date: " {leave.start_date}</p>
-                    <p>"End date: " {leave.end_date}</p>
+                    <p>"Type: " <LeaveTypeBadge name=leave.leave_type.clone() with_pay=true/></p>
+                    <p>"Start date: " {date_with_part(leave.start_date, leave.start_part)}</p>
+                    <p>"End date: " {date_with_part(leave.end_date, leave.end_part)}</p>
+                    <p>"Working days: " {format_days(leave.duration)}</p>
                     <p>"Remark: " {leave.remark}</p>
+                    <p>"Status: " {status.to_string()}</p>
+                    {attachments}
+                    {staffing}
+                    {steps}
+                    {leave
+                        .decided_by
+                        .map(|user| {
+                            view! {
+                                <p>
+                                    {format!(
+                                        "{} by {} at {}",
+                                        status,
+                                        user.username,
+                                        leave.decided_at.unwrap_or_default(),
+                                    )}
+                                </p>
+                                <p>"Comment: " {leave.comment}</p>
+                            }
+                        })}
+                    <Show when=move || !status.next().is_empty()>
+                        <textarea
+                            class="textarea textarea-bordered"
+                            placeholder="Comment"
+                            maxlength=MAX_LEAVE_COMMENT_LENGTH
+                            prop:value=comment
+                            on:input=move |ev| set_comment(event_target_value(&ev))
+                        ></textarea>
+                    </Show>
                 </div>
                 <div class="modal-action">
                     <form method="dialog" class="space-x-2">
-                        <button class="btn btn-secondary">"Reject"</button>
-                        <button class="btn btn-primary">"Approval"</button>
+                        {export}
+                        {actions}
+                        <button class="btn btn-ghost">"Close"</button>
                     </form>
                 </div>
 
@@ -222,3 +536,244 @@ fn LeaveDetail(leave: LeaveRequest, modal_id: String) -> impl IntoView {
         </dialog>
     }
 }
+
+/// files attached to a leave request, viewed or downloaded one at a time, the requester attaches
+/// more while it is pending or approved and removes them while it is pending
+#[component]
+fn LeaveAttachments(
+    leave_id: i64,
+    requester_id: String,
+    status: LeaveStatus,
+    attachments: Vec<LeaveAttachment>,
+) -> impl IntoView {
+    let current_user = use_current_user();
+    let (attachments, set_attachments) = create_signal(attachments);
+    //  the attachment shown and its content as a data url
+    let (shown, set_shown) = create_signal(None::<(LeaveAttachment, String)>);
+    let (error, set_error) = create_signal(None::<String>);
+    //  browsers give the path of the chosen file, the server keeps the last part
+    let (file_name, set_file_name) = create_signal(String::new());
+    let input_id = format!("attachment_file_{}", leave_id);
+
+    let own = Signal::derive(move || {
+        current_user.with(|user| user.as_ref().is_some_and(|user| user.id == requester_id))
+    });
+    let can_upload =
+        move || own.get() && matches!(status, LeaveStatus::Pending | LeaveStatus::Approved);
+    let can_remove = move || own.get() && status == LeaveStatus::Pending;
+
+    let show = move |attachment: LeaveAttachment| {
+        set_error(None);
+        spawn_local(async move {
+            match get_leave_attachment(attachment.id.clone()).await {
+                Ok(data) => set_shown(Some((attachment, data))),
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+    let remove = move |id: String| {
+        set_error(None);
+        spawn_local(async move {
+            match delete_leave_attachment(id.clone()).await {
+                Ok(list) => {
+                    set_attachments(list);
+                    set_shown.update(|shown| {
+                        if shown
+                            .as_ref()
+                            .is_some_and(|(attachment, _)| attachment.id == id)
+                        {
+                            *shown = None;
+                        }
+                    });
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+    let upload = {
+        let input_id = input_id.clone();
+        move |_| {
+            set_error(None);
+            let input_id = input_id.clone();
+            spawn_local(async move {
+                let result = async {
+                    let data = read_file(&input_id).await?;
+                    let file_name = file_name.get_untracked();
+                    upload_leave_attachment(leave_id, AttachmentUpload { file_name, data })
+                        .await
+                        .map_err(|e| e.to_string())
+                }
+                .await;
+                match result {
+                    Ok(list) => set_attachments(list),
+                    Err(e) => set_error(Some(e)),
+                }
+            });
+        }
+    };
+
+    view! {
+        <div>
+            <p>"Attachments:"</p>
+            <ul class="text-sm">
+                <For
+                    each=move || attachments.get()
+                    key=|attachment| attachment.id.clone()
+                    children=move |attachment: LeaveAttachment| {
+                        let id = attachment.id.clone();
+                        let label = format!(
+                            "{} ({}, {})",
+                            attachment.file_name,
+                            format_size(attachment.size),
+                            attachment.created_at,
+                        );
+                        view! {
+                            <li class="flex items-center gap-2">
+                                <span>{label}</span>
+                                <button
+                                    type="button"
+                                    class="btn btn-ghost btn-xs"
+                                    on:click=move |_| show(attachment.clone())
+                                >
+                                    "View"
+                                </button>
+                                <Show when=can_remove>
+                                    {
+                                        let id = id.clone();
+                                        view! {
+                                            <button
+                                                type="button"
+                                                class="btn btn-ghost btn-xs"
+                                                on:click=move |_| remove(id.clone())
+                                            >
+                                                "Remove"
+                                            </button>
+                                        }
+                                    }
+                                </Show>
+                            </li>
+                        }
+                    }
+                />
+            </ul>
+            <Show when=move || attachments.with(|list| list.is_empty())>
+                <p class="text-sm opacity-70">"None"</p>
+            </Show>
+            {move || {
+                shown
+                    .get()
+                    .map(|(attachment, data)| {
+                        let preview = if attachment.content_type.starts_with("image/") {
+                            view! { <img class="max-h-96 mt-2" src=data.clone() alt=attachment.file_name.clone()/> }
+                                .into_view()
+                        } else if attachment.content_type == "application/pdf" {
+                            view! {
+                                <object class="w-full h-96 mt-2" data=data.clone() type="application/pdf"></object>
+                            }
+                                .into_view()
+                        } else {
+                            view! {}.into_view()
+                        };
+                        view! {
+                            {preview}
+                            <a class="link text-sm" href=data download=attachment.file_name.clone()>
+                                {format!("Download {}", attachment.file_name)}
+                            </a>
+                        }
+                    })
+            }}
+            <Show when=can_upload>
+                <div class="flex items-center gap-2 mt-2">
+                    <input
+                        type="file"
+                        class="file-input file-input-bordered file-input-sm"
+                        id=input_id.clone()
+                        on:change=move |ev| set_file_name(event_target_value(&ev))
+                    />
+                    <button type="button" class="btn btn-sm" on:click=upload.clone()>
+                        "Attach"
+                    </button>
+                </div>
+            </Show>
+            {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+        </div>
+    }
+}
+
+/// a data url of iCalendar file `ics`, every byte but unreserved ones percent encoded
+fn ics_data_url(ics: &str) -> String {
+    let encoded: String = ics
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    format!("data:text/calendar;charset=utf-8,{}", encoded)
+}
+
+/// the date, and the part of the day if not the full day
+fn date_with_part(date: chrono::NaiveDate, part: DayPart) -> String {
+    match part {
+        DayPart::Full => date.to_string(),
+        _ => format!("{} ({})", date, part.label()),
+    }
+}
+
+fn status_badge(status: LeaveStatus) -> &'static str {
+    match status {
+        LeaveStatus::Pending => "badge-warning",
+        LeaveStatus::Approved => "badge-success",
+        LeaveStatus::Rejected => "badge-error",
+        LeaveStatus::Cancelled | LeaveStatus::Withdrawn => "badge-ghost",
+    }
+}
+
+fn step_class(step: &ApprovalStep, current: bool) -> &'static str {
+    match step.status {
+        ApprovalStatus::Approved => "step-success",
+        ApprovalStatus::Rejected => "step-error",
+        ApprovalStatus::Pending if current => "step-warning",
+        ApprovalStatus::Pending => "",
+    }
+}
+
+/// who decided the step when, and the comment
+fn step_progress(step: &ApprovalStep) -> String {
+    match &step.decided_by {
+        Some(user) if step.comment.is_empty() => format!(
+            "{} by {} at {}",
+            step.status,
+            user.username,
+            step.decided_at.clone().unwrap_or_default()
+        ),
+        Some(user) => format!(
+            "{} by {} at {}: {}",
+            step.status,
+            user.username,
+            step.decided_at.clone().unwrap_or_default(),
+            step.comment
+        ),
+        None => step.status.to_string(),
+    }
+}
+
+fn action_button(status: LeaveStatus) -> &'static str {
+    match status {
+        LeaveStatus::Approved => "btn-primary",
+        LeaveStatus::Rejected => "btn-secondary",
+        _ => "btn-outline",
+    }
+}
+
+fn action_label(status: LeaveStatus) -> &'static str {
+    match status {
+        LeaveStatus::Approved => "Approve",
+        LeaveStatus::Rejected => "Reject",
+        LeaveStatus::Cancelled => "Cancel Leave",
+        LeaveStatus::Withdrawn => "Withdraw",
+        LeaveStatus::Pending => "Pending",
+    }
+}
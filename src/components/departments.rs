@@ -0,0 +1,357 @@
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use crate::components::current_user::use_has_role;
+use crate::components::users::get_users;
+use crate::models::consts::ADMIN_ROLE;
+use crate::models::{Department, DepartmentForm, User};
+
+#[server]
+pub async fn get_departments() -> Result<Vec<Department>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Department::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// create or update a department, returns the department id
+#[server(name = SaveDepartment, prefix = "/api", input = Json)]
+async fn save_department(form: DepartmentForm) -> Result<String, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Department::save(&conn, &form).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// move a department under `parent_id`, or to the top level if None
+#[server]
+async fn move_department(
+    id: String,
+    parent_id: Option<String>,
+) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Department::move_to(&conn, &id, parent_id.as_deref())
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn delete_department(id: String) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Department::delete(&conn, &id, &token.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// the nearest manager of a user by the reporting line, None if nobody is above the user
+#[server]
+pub async fn get_manager(user_id: String) -> Result<Option<User>, ServerFnError<String>> {
+    use crate::server::department::manager_of;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    manager_of(&conn, &user_id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// departments under `parent_id` and their descendants as nested lists,
+/// every department can be dragged and dropped onto another to become its child
+fn department_tree(
+    departments: &[Department],
+    parent_id: Option<&str>,
+    selected: RwSignal<DepartmentForm>,
+    dragging: RwSignal<Option<String>>,
+    on_move: Callback<(String, Option<String>)>,
+) -> View {
+    departments
+        .iter()
+        .filter(|department| department.parent_id.as_deref() == parent_id)
+        .map(|department| {
+            let id = department.id.clone();
+            let form = DepartmentForm::from(department.clone());
+            let is_selected = {
+                let id = id.clone();
+                move || selected.with(|form| form.id.as_ref() == Some(&id))
+            };
+            let children = department_tree(departments, Some(&id), selected, dragging, on_move);
+            let drag_id = id.clone();
+            let drop_id = id.clone();
+
+            view! {
+                <li>
+                    <a
+                        class:active=is_selected
+                        draggable="true"
+                        on:click=move |_| selected.set(form.clone())
+                        on:dragstart=move |_| dragging.set(Some(drag_id.clone()))
+                        on:dragover=|ev| ev.prevent_default()
+                        on:drop=move |ev| {
+                            ev.prevent_default();
+                            if let Some(id) = dragging.get_untracked() {
+                                if id != drop_id {
+                                    on_move.call((id, Some(drop_id.clone())));
+                                }
+                            }
+                            dragging.set(None);
+                        }
+                    >
+
+                        <span class="font-medium">{department.name.clone()}</span>
+                        <span class="text-xs opacity-60">
+                            {department.manager_name.clone().unwrap_or_default()}
+                        </span>
+                        <span class="badge badge-ghost badge-sm">{department.member_count}</span>
+                    </a>
+                    <ul>{children}</ul>
+                </li>
+            }
+        })
+        .collect_view()
+}
+
+#[component]
+pub fn Departments() -> impl IntoView {
+    let departments = create_resource(
+        || {},
+        |_| async move { get_departments().await.unwrap_or_default() },
+    );
+    let users = create_resource(
+        || {},
+        |_| async move { get_users().await.unwrap_or_default() },
+    );
+    let is_admin = use_has_role(ADMIN_ROLE);
+    let selected = create_rw_signal(DepartmentForm::default());
+    let dragging = create_rw_signal(None::<String>);
+    let (error, set_error) = create_signal(None::<String>);
+
+    let on_move = Callback::new(move |(id, parent_id): (String, Option<String>)| {
+        spawn_local(async move {
+            set_error(move_department(id, parent_id).await.err().map(|e| e.to_string()));
+            departments.refetch();
+        });
+    });
+
+    let handle_save = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let form = selected.get_untracked();
+        spawn_local(async move {
+            match save_department(form).await {
+                Ok(id) => {
+                    set_error(None);
+                    selected.update(|form| form.id = Some(id));
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+            departments.refetch();
+        });
+    };
+
+    let handle_delete = move |_| {
+        let Some(id) = selected.get_untracked().id else {
+            return;
+        };
+        spawn_local(async move {
+            match delete_department(id).await {
+                Ok(_) => {
+                    set_error(None);
+                    selected.set(DepartmentForm::default());
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+            departments.refetch();
+        });
+    };
+
+    view! {
+        <div class="h-full w-full p-4 flex gap-4 items-start">
+            <div class="card bg-base-100 shadow w-1/2">
+                <div class="card-body">
+                    <div class="flex justify-between items-center">
+                        <h2 class="card-title">"Departments"</h2>
+                        <Show when=is_admin>
+                            <button
+                                class="btn btn-primary btn-sm"
+                                on:click=move |_| selected.set(DepartmentForm::default())
+                            >
+                                "New Department"
+                            </button>
+                        </Show>
+                    </div>
+                    <Suspense fallback=move || view! {}>
+                        {move || {
+                            departments
+                                .get()
+                                .map(|list| {
+                                    view! {
+                                        <ul class="menu">
+                                            {department_tree(&list, None, selected, dragging, on_move)}
+                                        </ul>
+                                    }
+                                })
+                        }}
+
+                    </Suspense>
+                    <div
+                        class="border border-dashed rounded p-2 text-center text-sm opacity-60"
+                        on:dragover=|ev| ev.prevent_default()
+                        on:drop=move |ev| {
+                            ev.prevent_default();
+                            if let Some(id) = dragging.get_untracked() {
+                                on_move.call((id, None));
+                            }
+                            dragging.set(None);
+                        }
+                    >
+
+                        "drop here to move to the top level"
+                    </div>
+                </div>
+            </div>
+            <Show when=is_admin>
+                <div class="card bg-base-100 shadow w-1/2">
+                    <form class="card-body" on:submit=handle_save>
+                        <h2 class="card-title">
+                            {move || {
+                                if selected.with(|form| form.id.is_some()) {
+                                    "Edit Department"
+                                } else {
+                                    "New Department"
+                                }
+                            }}
+
+                        </h2>
+                        <div class="form-control">
+                            <label class="label" for="name">
+                                <span class="label-text">"Name"</span>
+                            </label>
+                            <input
+                                type="text"
+                                class="input input-bordered"
+                                id="name"
+                                prop:value=move || selected.with(|form| form.name.clone())
+                                on:input=move |ev| selected.update(|form| form.name = event_target_value(&ev))
+                            />
+                        </div>
+                        <div class="form-control">
+                            <label class="label" for="parent_id">
+                                <span class="label-text">"Parent"</span>
+                            </label>
+                            <select
+                                class="select select-bordered"
+                                id="parent_id"
+                                on:change=move |ev| {
+                                    let value = event_target_value(&ev);
+                                    selected.update(|form| form.parent_id = Some(value).filter(|id| !id.is_empty()))
+                                }
+                            >
+                                <option value="">"None"</option>
+                                {move || {
+                                    departments
+                                        .get()
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|department| {
+                                            let id = department.id.clone();
+                                            view! {
+                                                <option
+                                                    value=department.id
+                                                    selected=move || {
+                                                        selected.with(|form| form.parent_id.as_ref() == Some(&id))
+                                                    }
+                                                >
+                                                    {department.name}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+
+                            </select>
+                        </div>
+                        <div class="form-control">
+                            <label class="label" for="manager_id">
+                                <span class="label-text">"Manager"</span>
+                            </label>
+                            <select
+                                class="select select-bordered"
+                                id="manager_id"
+                                on:change=move |ev| {
+                                    let value = event_target_value(&ev);
+                                    selected.update(|form| form.manager_id = Some(value).filter(|id| !id.is_empty()))
+                                }
+                            >
+                                <option value="">"None"</option>
+                                {move || {
+                                    users
+                                        .get()
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|user| {
+                                            let id = user.id.clone();
+                                            view! {
+                                                <option
+                                                    value=user.id
+                                                    selected=move || {
+                                                        selected.with(|form| form.manager_id.as_ref() == Some(&id))
+                                                    }
+                                                >
+                                                    {user.display_name}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+
+                            </select>
+                        </div>
+                        {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                        <div class="card-actions justify-end mt-4">
+                            <button
+                                type="button"
+                                class="btn btn-ghost"
+                                disabled=move || selected.with(|form| form.id.is_none())
+                                on:click=handle_delete
+                            >
+                                "Delete"
+                            </button>
+                            <button class="btn btn-primary">"Save"</button>
+                        </div>
+                    </form>
+                </div>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,29 @@
+use leptos::*;
+
+use crate::models::FieldErrors;
+
+/// a labeled field of a form, showing the validation error of `name` below
+#[component]
+pub fn Field(
+    label: &'static str,
+    name: &'static str,
+    errors: ReadSignal<FieldErrors>,
+    children: Children,
+) -> impl IntoView {
+    view! {
+        <div class="form-control">
+            <label class="label" for=name>
+                <span class="label-text">{label}</span>
+            </label>
+            {children()}
+            {move || {
+                errors
+                    .with(|errors| errors.get(name).cloned())
+                    .map(|e| {
+                        view! { <span class="text-error text-sm mt-1">{format!("{} {}", label, e)}</span> }
+                    })
+            }}
+
+        </div>
+    }
+}
@@ -0,0 +1,30 @@
+//! File
+//! browser side of file uploads, reads the chosen file through `/js/file.js`
+
+#[cfg(not(feature = "ssr"))]
+mod bindings {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(catch, js_name = readFileAsDataUrl)]
+        pub async fn read_file_as_data_url(input_id: String) -> Result<JsValue, JsValue>;
+    }
+}
+
+/// content of the file chosen in the file input `input_id`, as a data url
+pub async fn read_file(input_id: &str) -> Result<String, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        bindings::read_file_as_data_url(input_id.to_string())
+            .await
+            .map(|data| data.as_string().unwrap_or_default())
+            .map_err(|_| "please choose a file".to_string())
+    }
+
+    #[cfg(feature = "ssr")]
+    {
+        _ = input_id;
+        Err("file is only available in browser".to_string())
+    }
+}
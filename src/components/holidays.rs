@@ -0,0 +1,946 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use crate::components::current_user::use_has_role;
+use crate::components::departments::get_departments;
+use crate::components::field::Field;
+use crate::components::file::read_file;
+use crate::components::users::get_users;
+use crate::models::consts::ADMIN_ROLE;
+use crate::models::{
+    FieldErrors, Holiday, HolidayCalendar, HolidayCalendarForm, HolidayImport, HolidayRule,
+    DEFAULT_CALENDAR_ID,
+};
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+#[server]
+async fn get_holiday_calendars() -> Result<Vec<HolidayCalendar>, ServerFnError<String>> {
+    use crate::models::consts::LEAVE_BALANCE_ROLES;
+    use crate::server::{require_any_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_any_role(&LEAVE_BALANCE_ROLES).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayCalendar::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// create or update a calendar, returns the errors of invalid fields, empty if saved
+#[server(name = SaveHolidayCalendar, prefix = "/api", input = Json)]
+async fn save_holiday_calendar(
+    form: HolidayCalendarForm,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let errors = form
+        .validate(&conn)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    HolidayCalendar::save(&conn, &form).map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+#[server]
+async fn delete_holiday_calendar(id: i64) -> Result<(), ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayCalendar::delete(&conn, id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// assign calendar `id` to a department, None to follow its parent department
+#[server]
+async fn assign_department_calendar(
+    department_id: String,
+    id: Option<i64>,
+) -> Result<(), ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayCalendar::assign_department(&conn, &department_id, id)
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// assign calendar `id` to a user, None to follow their department
+#[server]
+async fn assign_user_calendar(user_id: String, id: Option<i64>) -> Result<(), ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayCalendar::assign_user(&conn, &user_id, id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn get_holidays(calendar_id: i64, year: i32) -> Result<Vec<Holiday>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Holiday::list(&conn, calendar_id, year).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// add or rename a holiday, returns the errors of invalid fields, empty if saved
+#[server(name = SaveHoliday, prefix = "/api", input = Json)]
+async fn save_holiday(
+    calendar_id: i64,
+    holiday: Holiday,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let errors = holiday.validate();
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    let conn = db.lock().unwrap();
+    holiday
+        .save(&conn, calendar_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+#[server]
+async fn delete_holiday(calendar_id: i64, date: NaiveDate) -> Result<(), ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Holiday::delete(&conn, calendar_id, date).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn get_holiday_rules(calendar_id: i64) -> Result<Vec<HolidayRule>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayRule::list(&conn, calendar_id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// add or rename a yearly holiday, returns the errors of invalid fields, empty if saved
+#[server(name = SaveHolidayRule, prefix = "/api", input = Json)]
+async fn save_holiday_rule(
+    calendar_id: i64,
+    rule: HolidayRule,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let errors = rule.validate();
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    let conn = db.lock().unwrap();
+    rule.save(&conn, calendar_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+#[server]
+async fn delete_holiday_rule(id: i64) -> Result<(), ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    HolidayRule::delete(&conn, id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// import the holidays of an iCalendar file as data url into calendar `calendar_id`
+#[server(name = ImportHolidays, prefix = "/api", input = Json)]
+async fn import_holidays(
+    calendar_id: i64,
+    data: String,
+) -> Result<HolidayImport, ServerFnError<String>> {
+    use crate::server::calendar::import_ics;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    import_ics(&conn, calendar_id, &data).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// holiday calendars of regions, the holidays of a year and the yearly ones are not counted
+/// as working days by leave requests of the departments and users assigned,
+/// only administrators change them
+#[component]
+pub fn Holidays() -> impl IntoView {
+    let is_admin = use_has_role(ADMIN_ROLE);
+    let (calendar_id, set_calendar_id) = create_signal(DEFAULT_CALENDAR_ID);
+    let (year, set_year) = create_signal(chrono::Local::now().year());
+    let (calendar_form, set_calendar_form) = create_signal(HolidayCalendarForm::default());
+    let (calendar_errors, set_calendar_errors) = create_signal(FieldErrors::new());
+    let (date, set_date) = create_signal(None::<NaiveDate>);
+    let (name, set_name) = create_signal(String::new());
+    let (working, set_working) = create_signal(false);
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (rule, set_rule) = create_signal(HolidayRule {
+        month: 1,
+        day: 1,
+        ..Default::default()
+    });
+    let (rule_errors, set_rule_errors) = create_signal(FieldErrors::new());
+    let (message, set_message) = create_signal(None::<Result<String, String>>);
+
+    let save_calendar = create_action(|form: &HolidayCalendarForm| {
+        let form = form.clone();
+        async move { save_holiday_calendar(form).await }
+    });
+    let delete_calendar = create_action(|id: &i64| {
+        let id = *id;
+        async move { delete_holiday_calendar(id).await }
+    });
+    let assign_department = create_action(|input: &(String, Option<i64>)| {
+        let (department_id, id) = input.clone();
+        async move { assign_department_calendar(department_id, id).await }
+    });
+    let assign_user = create_action(|input: &(String, Option<i64>)| {
+        let (user_id, id) = input.clone();
+        async move { assign_user_calendar(user_id, id).await }
+    });
+    let save = create_action(|input: &(i64, Holiday)| {
+        let (calendar_id, holiday) = input.clone();
+        async move { save_holiday(calendar_id, holiday).await }
+    });
+    let delete = create_action(|input: &(i64, NaiveDate)| {
+        let (calendar_id, date) = *input;
+        async move { delete_holiday(calendar_id, date).await }
+    });
+    let save_rule = create_action(|input: &(i64, HolidayRule)| {
+        let (calendar_id, rule) = input.clone();
+        async move { save_holiday_rule(calendar_id, rule).await }
+    });
+    let delete_rule = create_action(|id: &i64| {
+        let id = *id;
+        async move { delete_holiday_rule(id).await }
+    });
+    let (imported, set_imported) = create_signal(0);
+
+    let calendars = create_resource(
+        move || {
+            (
+                save_calendar.version().get(),
+                delete_calendar.version().get(),
+                assign_department.version().get(),
+                assign_user.version().get(),
+            )
+        },
+        |_| async move { get_holiday_calendars().await },
+    );
+    let holidays = create_resource(
+        move || {
+            (
+                calendar_id.get(),
+                year.get(),
+                save.version().get(),
+                delete.version().get(),
+                imported.get(),
+            )
+        },
+        |(calendar_id, year, _, _, _)| async move { get_holidays(calendar_id, year).await },
+    );
+    let rules = create_resource(
+        move || {
+            (
+                calendar_id.get(),
+                save_rule.version().get(),
+                delete_rule.version().get(),
+                imported.get(),
+            )
+        },
+        |(calendar_id, _, _, _)| async move { get_holiday_rules(calendar_id).await },
+    );
+    let departments = create_resource(
+        move || is_admin.get(),
+        |is_admin| async move {
+            if is_admin {
+                get_departments().await.unwrap_or_default()
+            } else {
+                vec![]
+            }
+        },
+    );
+    let users = create_resource(
+        move || is_admin.get(),
+        |is_admin| async move {
+            if is_admin {
+                get_users().await.unwrap_or_default()
+            } else {
+                vec![]
+            }
+        },
+    );
+
+    let current_calendar = move || {
+        calendars
+            .get()
+            .and_then(|result| result.ok())
+            .and_then(|list| list.into_iter().find(|calendar| calendar.id == calendar_id.get()))
+    };
+    //  the form edits the calendar picked, until a new one is started
+    create_effect(move |_| {
+        if let Some(calendar) = current_calendar() {
+            set_calendar_form(HolidayCalendarForm {
+                id: Some(calendar.id),
+                name: calendar.name,
+                weekend: calendar.weekend,
+            });
+            set_calendar_errors(FieldErrors::new());
+        }
+    });
+
+    let report = move |result: Option<Result<(), ServerFnError<String>>>| {
+        if let Some(Err(e)) = result {
+            set_message(Some(Err(e.to_string())));
+        }
+    };
+    create_effect(move |_| match save_calendar.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => {
+            set_calendar_errors(FieldErrors::new());
+            set_message(Some(Ok("Calendar saved".to_string())));
+        }
+        Some(Ok(errors)) => set_calendar_errors(errors),
+        Some(Err(e)) => set_message(Some(Err(e.to_string()))),
+        None => {}
+    });
+    create_effect(move |_| match delete_calendar.value().get() {
+        Some(Ok(())) => set_calendar_id(DEFAULT_CALENDAR_ID),
+        result => report(result),
+    });
+    create_effect(move |_| report(assign_department.value().get()));
+    create_effect(move |_| report(assign_user.value().get()));
+    create_effect(move |_| report(delete.value().get()));
+    create_effect(move |_| report(delete_rule.value().get()));
+    create_effect(move |_| match save.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => {
+            set_errors(FieldErrors::new());
+            set_name(String::new());
+            set_working(false);
+        }
+        Some(Ok(errors)) => set_errors(errors),
+        Some(Err(e)) => set_message(Some(Err(e.to_string()))),
+        None => {}
+    });
+    create_effect(move |_| match save_rule.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => {
+            set_rule_errors(FieldErrors::new());
+            set_rule.update(|rule| rule.name.clear());
+        }
+        Some(Ok(errors)) => set_rule_errors(errors),
+        Some(Err(e)) => set_message(Some(Err(e.to_string()))),
+        None => {}
+    });
+
+    let handle_save_calendar = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_message(None);
+        save_calendar.dispatch(calendar_form.get_untracked());
+    };
+    let handle_save = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_message(None);
+        let Some(date) = date.get_untracked() else {
+            set_errors(FieldErrors::from([(
+                "date".to_string(),
+                "must be a date".to_string(),
+            )]));
+            return;
+        };
+        save.dispatch((
+            calendar_id.get_untracked(),
+            Holiday {
+                date,
+                name: name.get_untracked(),
+                working: working.get_untracked(),
+            },
+        ));
+    };
+    let handle_save_rule = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_message(None);
+        save_rule.dispatch((calendar_id.get_untracked(), rule.get_untracked()));
+    };
+    let handle_import = move |_| {
+        set_message(None);
+        let calendar_id = calendar_id.get_untracked();
+        spawn_local(async move {
+            let result = async {
+                let data = read_file("ics_file").await?;
+                import_holidays(calendar_id, data)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+
+            match result {
+                Ok(import) => {
+                    set_imported.update(|count| *count += 1);
+                    set_message(Some(Ok(format!(
+                        "{} holidays and {} yearly holidays imported",
+                        import.holidays, import.rules
+                    ))));
+                }
+                Err(e) => set_message(Some(Err(e))),
+            }
+        });
+    };
+
+    view! {
+        <div class="h-full w-full p-4 space-y-4">
+            <div class="flex flex-wrap justify-between items-center gap-2">
+                <h2 class="text-lg font-bold">"Holidays"</h2>
+                <div class="flex flex-wrap gap-2">
+                    <select
+                        class="select select-bordered select-sm"
+                        on:change=move |ev| {
+                            if let Ok(id) = event_target_value(&ev).parse() {
+                                set_calendar_id(id)
+                            }
+                        }
+                    >
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                calendars
+                                    .get()
+                                    .and_then(|result| result.ok())
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|calendar| {
+                                                view! {
+                                                    <option
+                                                        value=calendar.id.to_string()
+                                                        selected=move || calendar_id.get() == calendar.id
+                                                    >
+                                                        {calendar.name}
+                                                    </option>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </select>
+                    <div class="join">
+                        <button class="btn btn-sm join-item" on:click=move |_| set_year.update(|year| *year -= 1)>
+                            "«"
+                        </button>
+                        <span class="btn btn-sm join-item no-animation">{year}</span>
+                        <button class="btn btn-sm join-item" on:click=move |_| set_year.update(|year| *year += 1)>
+                            "»"
+                        </button>
+                    </div>
+                </div>
+            </div>
+            {move || {
+                message()
+                    .map(|message| match message {
+                        Ok(message) => view! { <p class="text-success text-sm">{message}</p> },
+                        Err(e) => view! { <p class="text-error text-sm">{e}</p> },
+                    })
+            }}
+            <div class="flex flex-wrap gap-4 items-start">
+                <div class="overflow-x-auto bg-base-100 rounded-lg shadow flex-1">
+                    <table class="table">
+                        <thead>
+                            <tr>
+                                <th>"Date"</th>
+                                <th>"Weekday"</th>
+                                <th>"Name"</th>
+                                <th></th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    holidays
+                                        .get()
+                                        .map(|result| match result {
+                                            Ok(list) => {
+                                                list.into_iter()
+                                                    .map(|holiday| {
+                                                        let (date, working) = (holiday.date, holiday.working);
+                                                        view! {
+                                                            <tr>
+                                                                <td>{date.to_string()}</td>
+                                                                <td>{date.format("%A").to_string()}</td>
+                                                                <td>{holiday.name}</td>
+                                                                <td>
+                                                                    <Show when=move || working>
+                                                                        <span class="badge badge-outline">"working day"</span>
+                                                                    </Show>
+                                                                </td>
+                                                                <td>
+                                                                    <Show when=move || is_admin.get()>
+                                                                        <button
+                                                                            class="btn btn-ghost btn-xs"
+                                                                            on:click=move |_| {
+                                                                                delete.dispatch((calendar_id.get_untracked(), date))
+                                                                            }
+                                                                        >
+                                                                            "Delete"
+                                                                        </button>
+                                                                    </Show>
+                                                                </td>
+                                                            </tr>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            }
+                                            Err(e) => view! { <tr><td class="text-error">{e.to_string()}</td></tr> }.into_view(),
+                                        })
+                                }}
+
+                            </Suspense>
+                        </tbody>
+                    </table>
+                </div>
+                <Show when=move || is_admin.get()>
+                    <div class="card bg-base-100 shadow">
+                        <form class="card-body" on:submit=handle_save>
+                            <h2 class="card-title">"Add Holiday"</h2>
+                            <Field label="Date" name="date" errors=errors>
+                                <input
+                                    type="date"
+                                    class="input input-bordered"
+                                    id="date"
+                                    on:input=move |ev| set_date(event_target_value(&ev).parse().ok())
+                                />
+                            </Field>
+                            <Field label="Name" name="name" errors=errors>
+                                <input
+                                    type="text"
+                                    class="input input-bordered"
+                                    id="name"
+                                    prop:value=name
+                                    on:input=move |ev| set_name(event_target_value(&ev))
+                                />
+                            </Field>
+                            <label class="label cursor-pointer gap-2" title="a weekend day worked in exchange for a holiday">
+                                <input
+                                    type="checkbox"
+                                    class="checkbox checkbox-sm"
+                                    prop:checked=working
+                                    on:change=move |ev| set_working(event_target_checked(&ev))
+                                />
+                                <span class="label-text">"Working day"</span>
+                            </label>
+                            <div class="card-actions justify-end mt-4">
+                                <button class="btn btn-primary" type="submit" disabled=save.pending()>
+                                    "Save"
+                                </button>
+                            </div>
+                            <div class="divider">"or"</div>
+                            <div class="flex gap-2 items-center">
+                                <input
+                                    type="file"
+                                    accept=".ics,text/calendar"
+                                    class="file-input file-input-bordered file-input-sm"
+                                    id="ics_file"
+                                />
+                                <button type="button" class="btn btn-sm" on:click=handle_import>
+                                    "Import .ics"
+                                </button>
+                            </div>
+                        </form>
+                    </div>
+                </Show>
+            </div>
+            <div class="flex flex-wrap gap-4 items-start">
+                <div class="overflow-x-auto bg-base-100 rounded-lg shadow flex-1">
+                    <table class="table">
+                        <thead>
+                            <tr>
+                                <th>"Every Year On"</th>
+                                <th>"Name"</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    rules
+                                        .get()
+                                        .map(|result| match result {
+                                            Ok(list) => {
+                                                list.into_iter()
+                                                    .map(|rule| {
+                                                        let id = rule.id;
+                                                        view! {
+                                                            <tr>
+                                                                <td>{month_day(rule.month, rule.day)}</td>
+                                                                <td>{rule.name}</td>
+                                                                <td>
+                                                                    <Show when=move || is_admin.get()>
+                                                                        <button
+                                                                            class="btn btn-ghost btn-xs"
+                                                                            on:click=move |_| delete_rule.dispatch(id)
+                                                                        >
+                                                                            "Delete"
+                                                                        </button>
+                                                                    </Show>
+                                                                </td>
+                                                            </tr>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            }
+                                            Err(e) => view! { <tr><td class="text-error">{e.to_string()}</td></tr> }.into_view(),
+                                        })
+                                }}
+
+                            </Suspense>
+                        </tbody>
+                    </table>
+                </div>
+                <Show when=move || is_admin.get()>
+                    <div class="card bg-base-100 shadow">
+                        <form class="card-body" on:submit=handle_save_rule>
+                            <h2 class="card-title">"Add Yearly Holiday"</h2>
+                            <Field label="Month" name="month" errors=rule_errors>
+                                <select
+                                    class="select select-bordered"
+                                    id="month"
+                                    on:change=move |ev| {
+                                        let month = event_target_value(&ev).parse().unwrap_or(1);
+                                        set_rule.update(|rule| rule.month = month)
+                                    }
+                                >
+                                    {(1..=12u32)
+                                        .map(|month| {
+                                            view! {
+                                                <option
+                                                    value=month.to_string()
+                                                    selected=move || rule.with(|rule| rule.month == month)
+                                                >
+                                                    {month_name(month)}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </Field>
+                            <Field label="Day" name="day" errors=rule_errors>
+                                <input
+                                    type="number"
+                                    min="1"
+                                    max="31"
+                                    class="input input-bordered"
+                                    id="day"
+                                    prop:value=move || rule.with(|rule| rule.day.to_string())
+                                    on:input=move |ev| {
+                                        let day = event_target_value(&ev).parse().unwrap_or_default();
+                                        set_rule.update(|rule| rule.day = day)
+                                    }
+                                />
+                            </Field>
+                            <Field label="Name" name="name" errors=rule_errors>
+                                <input
+                                    type="text"
+                                    class="input input-bordered"
+                                    id="rule_name"
+                                    prop:value=move || rule.with(|rule| rule.name.clone())
+                                    on:input=move |ev| set_rule.update(|rule| rule.name = event_target_value(&ev))
+                                />
+                            </Field>
+                            <div class="card-actions justify-end mt-4">
+                                <button class="btn btn-primary" type="submit" disabled=save_rule.pending()>
+                                    "Save"
+                                </button>
+                            </div>
+                        </form>
+                    </div>
+                </Show>
+            </div>
+            <div class="flex flex-wrap gap-4 items-start">
+                <div class="card bg-base-100 shadow flex-1">
+                    <form class="card-body" on:submit=handle_save_calendar>
+                        <h2 class="card-title">
+                            {move || {
+                                if calendar_form.with(|form| form.id.is_some()) {
+                                    "Calendar"
+                                } else {
+                                    "New Calendar"
+                                }
+                            }}
+
+                        </h2>
+                        <Field label="Name" name="name" errors=calendar_errors>
+                            <input
+                                type="text"
+                                class="input input-bordered"
+                                id="calendar_name"
+                                disabled=move || !is_admin.get()
+                                prop:value=move || calendar_form.with(|form| form.name.clone())
+                                on:input=move |ev| set_calendar_form.update(|form| form.name = event_target_value(&ev))
+                            />
+                        </Field>
+                        <Field label="Weekend" name="weekend" errors=calendar_errors>
+                            <div class="flex flex-wrap gap-4" title="none for the weekend of the server settings">
+                                {WEEKDAYS
+                                    .into_iter()
+                                    .map(|day| {
+                                        view! {
+                                            <label class="label cursor-pointer gap-2">
+                                                <input
+                                                    type="checkbox"
+                                                    class="checkbox checkbox-sm"
+                                                    disabled=move || !is_admin.get()
+                                                    prop:checked=move || calendar_form.with(|form| form.weekend.contains(&day))
+                                                    on:change=move |ev| {
+                                                        let checked = event_target_checked(&ev);
+                                                        set_calendar_form
+                                                            .update(|form| {
+                                                                form.weekend.retain(|d| *d != day);
+                                                                if checked {
+                                                                    form.weekend.push(day);
+                                                                }
+                                                            })
+                                                    }
+                                                />
+
+                                                <span class="label-text">{day.to_string()}</span>
+                                            </label>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </div>
+                        </Field>
+                        <Show when=move || is_admin.get()>
+                            <div class="card-actions justify-end mt-4">
+                                <Show when=move || calendar_id.get() != DEFAULT_CALENDAR_ID>
+                                    <button
+                                        type="button"
+                                        class="btn btn-ghost"
+                                        on:click=move |_| delete_calendar.dispatch(calendar_id.get_untracked())
+                                    >
+                                        "Delete"
+                                    </button>
+                                </Show>
+                                <button
+                                    type="button"
+                                    class="btn btn-ghost"
+                                    on:click=move |_| {
+                                        set_calendar_errors(FieldErrors::new());
+                                        set_calendar_form(HolidayCalendarForm::default())
+                                    }
+                                >
+                                    "New Calendar"
+                                </button>
+                                <button class="btn btn-primary" type="submit" disabled=save_calendar.pending()>
+                                    "Save"
+                                </button>
+                            </div>
+                        </Show>
+                    </form>
+                </div>
+                <div class="card bg-base-100 shadow flex-1">
+                    <div class="card-body">
+                        <h2 class="card-title">"Assigned To"</h2>
+                        <p class="text-sm opacity-70">
+                            "Users follow their own calendar, else the one of their department or its parent, "
+                            "else the default calendar."
+                        </p>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                current_calendar()
+                                    .map(|calendar| {
+                                        let assigned_departments = calendar
+                                            .departments
+                                            .into_iter()
+                                            .map(|(id, name)| {
+                                                view! {
+                                                    <li class="flex justify-between items-center">
+                                                        <span>{name}</span>
+                                                        <Show when=move || is_admin.get()>
+                                                            <button
+                                                                class="btn btn-ghost btn-xs"
+                                                                on:click=move |_| assign_department.dispatch((id.clone(), None))
+                                                            >
+                                                                "Remove"
+                                                            </button>
+                                                        </Show>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view();
+                                        let assigned_users = calendar
+                                            .users
+                                            .into_iter()
+                                            .map(|user| {
+                                                view! {
+                                                    <li class="flex justify-between items-center">
+                                                        <span>{user.username}</span>
+                                                        <Show when=move || is_admin.get()>
+                                                            <button
+                                                                class="btn btn-ghost btn-xs"
+                                                                on:click=move |_| assign_user.dispatch((user.id.clone(), None))
+                                                            >
+                                                                "Remove"
+                                                            </button>
+                                                        </Show>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view();
+                                        view! {
+                                            <h3 class="font-bold">"Departments"</h3>
+                                            <ul class="space-y-1">{assigned_departments}</ul>
+                                            <h3 class="font-bold">"Users"</h3>
+                                            <ul class="space-y-1">{assigned_users}</ul>
+                                        }
+                                    })
+                            }}
+
+                        </Suspense>
+                        <Show when=move || is_admin.get()>
+                            <div class="flex flex-wrap gap-2 mt-4">
+                                <select
+                                    class="select select-bordered select-sm"
+                                    on:change=move |ev| {
+                                        let id = event_target_value(&ev);
+                                        if !id.is_empty() {
+                                            assign_department.dispatch((id, Some(calendar_id.get_untracked())))
+                                        }
+                                    }
+                                >
+                                    <option value="" selected=true>
+                                        "assign a department"
+                                    </option>
+                                    <Suspense fallback=move || view! {}>
+                                        {move || {
+                                            departments
+                                                .get()
+                                                .map(|list| {
+                                                    list.into_iter()
+                                                        .map(|department| {
+                                                            view! { <option value=department.id>{department.name}</option> }
+                                                        })
+                                                        .collect_view()
+                                                })
+                                        }}
+
+                                    </Suspense>
+                                </select>
+                                <select
+                                    class="select select-bordered select-sm"
+                                    on:change=move |ev| {
+                                        let id = event_target_value(&ev);
+                                        if !id.is_empty() {
+                                            assign_user.dispatch((id, Some(calendar_id.get_untracked())))
+                                        }
+                                    }
+                                >
+                                    <option value="" selected=true>
+                                        "assign a user"
+                                    </option>
+                                    <Suspense fallback=move || view! {}>
+                                        {move || {
+                                            users
+                                                .get()
+                                                .map(|list| {
+                                                    list.into_iter()
+                                                        .map(|user| view! { <option value=user.id>{user.username}</option> })
+                                                        .collect_view()
+                                                })
+                                        }}
+
+                                    </Suspense>
+                                </select>
+                            </div>
+                        </Show>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+fn month_name(month: u32) -> String {
+    NaiveDate::from_ymd_opt(2000, month, 1)
+        .map(|date| date.format("%B").to_string())
+        .unwrap_or_default()
+}
+
+/// a day of every year such as `January 1`
+fn month_day(month: u32, day: u32) -> String {
+    format!("{} {}", month_name(month), day)
+}
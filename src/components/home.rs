@@ -1,15 +1,22 @@
+use crate::components::current_user::{provide_current_user, use_current_user};
 use crate::components::icons::*;
-use crate::models::consts::ADMIN_ROUTE_PREFIX;
-use crate::models::{Menu as MenuModel, MenuList};
+use crate::components::leave_types::provide_leave_types;
+use crate::models::consts::{ADMIN_ROUTE_PREFIX, DEFAULT_AVATAR};
+use crate::models::{avatar_url, IdleStatus, Menu as MenuModel, MenuList};
 use leptos::*;
 use leptos_router::*;
+use std::{cell::Cell, time::Duration};
 
 #[component]
 pub fn Home() -> impl IntoView {
+    provide_current_user();
+    provide_leave_types();
+
     view! {
         <main class="flex relative min-h-screen bg-base-200 bg-admin">
             <Header/>
             <MenuList/>
+            <IdleWatcher/>
             <div class="h-screen overflow-y-scroll flex-grow pt-16 flex flex-col">
                 <Outlet/>
                 <div class="flex-grow flex flex-col justify-end">
@@ -59,39 +66,160 @@ fn Footer() -> impl IntoView {
 
 #[server]
 async fn logout() -> Result<(), ServerFnError<String>> {
-    use crate::server::LOGIN_COOKIE_NAME;
-    use actix_web::{
-        cookie::{Cookie, SameSite},
-        http::header,
-        http::header::HeaderValue,
-    };
-    use leptos_actix::{redirect, ResponseOptions};
-
-    let response = expect_context::<ResponseOptions>();
-
-    //  clean login cookie
-    let cookie = Cookie::build(LOGIN_COOKIE_NAME, "")
-        .secure(true)
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .path("/")
-        .finish();
-
-    if let Ok(cookie) = HeaderValue::from_str(&cookie.to_string()) {
-        response.insert_header(header::SET_COOKIE, cookie);
-    } else {
-        return Err(ServerFnError::from(
-            "server cannot clear cookies".to_string(),
-        ));
-    }
+    use crate::server::sign_out;
+    use leptos_actix::redirect;
 
+    sign_out().await?;
     redirect("/login");
 
     return Ok(());
 }
 
+/// idle status of current session, checking it does not count as activity
+#[server(GetIdleStatus, "/api", "Url", "idle_status")]
+async fn get_idle_status() -> Result<IdleStatus, ServerFnError<String>> {
+    use crate::server::session::idle_remaining;
+    use crate::server::{current_token, AppDataDb, SETTINGS};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let remaining = idle_remaining(&db.lock().unwrap(), &token.session_id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    Ok(IdleStatus {
+        timeout: if remaining.is_some() {
+            SETTINGS.idle_timeout
+        } else {
+            0
+        },
+        warning: SETTINGS.idle_warning,
+        remaining: remaining.unwrap_or(0),
+    })
+}
+
+/// the request itself refreshes the activity of session, and the login cookie is issued again
+#[server]
+async fn keep_alive() -> Result<(), ServerFnError<String>> {
+    use crate::server::refresh_login_cookie;
+
+    refresh_login_cookie().await
+}
+
+/// counts down the idle timeout of current session,
+/// warns before it expires and logs out when expired
+#[component]
+fn IdleWatcher() -> impl IntoView {
+    let (status, set_status) = create_signal(None::<IdleStatus>);
+    let (expired, set_expired) = create_signal(false);
+
+    let handle_expire = move || {
+        set_expired(true);
+        spawn_local(async move {
+            _ = logout().await;
+        });
+    };
+
+    //  server is the only truth, other tabs may keep the session active
+    let sync = move || {
+        spawn_local(async move {
+            match get_idle_status().await {
+                Ok(current) => set_status(Some(current)),
+                Err(_) => handle_expire(),
+            }
+        });
+    };
+
+    create_effect(move |_| {
+        sync();
+
+        let ticks = Cell::new(0u32);
+        let handle = set_interval_with_handle(
+            move || {
+                ticks.set(ticks.get() + 1);
+                if ticks.get() % 30 == 0 {
+                    sync();
+                }
+                set_status.update(|status| {
+                    if let Some(status) = status {
+                        status.remaining = (status.remaining - 1).max(0);
+                    }
+                });
+            },
+            Duration::from_secs(1),
+        );
+        if let Ok(handle) = handle {
+            on_cleanup(move || handle.clear());
+        }
+    });
+
+    create_effect(move |_| {
+        if let Some(status) = status() {
+            if status.timeout > 0 && status.remaining == 0 && !expired.get_untracked() {
+                set_status(None);
+                sync();
+            }
+        }
+    });
+
+    let warning = move || {
+        status()
+            .map(|status| status.timeout > 0 && status.remaining <= status.warning)
+            .unwrap_or(false)
+    };
+
+    let handle_stay = move |_| {
+        spawn_local(async move {
+            _ = keep_alive().await;
+            sync();
+        });
+    };
+
+    let handle_logout = move |_| handle_expire();
+
+    view! {
+        <div class="modal" class:modal-open=warning>
+            <div class="modal-box">
+                <h3 class="font-bold text-lg">"Are you still there?"</h3>
+                <p class="py-4">
+                    "You will be signed out in "
+                    {move || status().map(|status| status.remaining).unwrap_or(0)}
+                    " seconds because of inactivity."
+                </p>
+                <div class="modal-action">
+                    <button class="btn btn-secondary" on:click=handle_logout>
+                        "Log out"
+                    </button>
+                    <button class="btn btn-primary" on:click=handle_stay>
+                        "Stay signed in"
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
+
 #[component]
 fn Header() -> impl IntoView {
+    let current_user = use_current_user();
+    let display_name = move || {
+        current_user.with(|user| {
+            user.as_ref()
+                .map(|user| user.display_name.clone())
+                .unwrap_or_default()
+        })
+    };
+    let avatar = move || {
+        current_user.with(|user| {
+            user.as_ref()
+                .map(|user| avatar_url(&user.avatar, 64))
+                .unwrap_or(DEFAULT_AVATAR.to_string())
+        })
+    };
+
     let handle_logout = move |_| {
         spawn_local(async move {
             logout().await.expect("logout failed");
@@ -110,7 +238,9 @@ fn Header() -> impl IntoView {
                         <span class="indicator-item badge badge-primary mt-1">99+</span>
                         <div class="avatar" tabindex="0" role="button">
                             <div class="w-12 rounded-full ring ring-base-100 ring-offset-base-100 ring-offset-2">
-                                <img class="h-full" src="/images/avatar.png" alt="Avatar"/>
+                                <Transition fallback=move || view! {}>
+                                    <img class="h-full" src=avatar alt="Avatar"/>
+                                </Transition>
                             </div>
                         </div>
                     </div>
@@ -122,7 +252,9 @@ fn Header() -> impl IntoView {
                         <li>
                             <div class="flex">
                                 <Person/>
-                                <a href="javascript:;">"Dvorak Profile"</a>
+                                <A href=format!("{}/profile", ADMIN_ROUTE_PREFIX)>
+                                    <Transition fallback=move || view! {}>{display_name}</Transition>
+                                </A>
                             </div>
 
                         </li>
@@ -158,6 +290,13 @@ async fn get_menu() -> Result<MenuList, ServerFnError> {
 #[component]
 fn MenuList() -> impl IntoView {
     let list = create_resource(|| {}, |_| async move { get_menu().await.unwrap() });
+    let current_user = use_current_user();
+    let permitted = move |menu: &MenuModel| match &menu.role {
+        Some(role) => {
+            current_user.with(|user| user.as_ref().is_some_and(|user| user.has_role(role)))
+        }
+        None => true,
+    };
 
     view! {
         <nav class="h-screen overflow-y-scroll pt-16 w-72 border border-r-gray-500">
@@ -171,6 +310,7 @@ fn MenuList() -> impl IntoView {
                                 Some(menu_list) => {
                                     menu_list
                                         .into_iter()
+                                        .filter(permitted)
                                         .map(|menu| {
                                             view! {
                                                 <li key=menu.id>
@@ -0,0 +1,286 @@
+use leptos::server_fn::codec::Json;
+use leptos::*;
+use leptos_router::*;
+
+use crate::components::field::Field;
+use crate::models::consts::{LANGUAGES, TIMEZONES};
+use crate::models::{AcceptInvitationForm, FieldErrors, Invitation, UserForm};
+
+/// create a pending user and mail the invitation link,
+/// returns the errors of invalid fields, empty if invited,
+/// the user stays invited if the mail cannot be sent, and the mail can be resent
+#[server(name = InviteUser, prefix = "/api", input = Json)]
+pub async fn invite_user(form: UserForm) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::models::UserStatus;
+    use crate::server::invitation::{invite, InvitationError};
+    use crate::server::mailer::AppDataMailer;
+    use crate::server::user::random_password;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    let (db, mailer): (AppDataDb, AppDataMailer) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    //  the invitee sets the password on accepting
+    let form = UserForm {
+        id: None,
+        status: UserStatus::Pending,
+        password: random_password(),
+        must_change_password: false,
+        ..form
+    };
+    let mail = {
+        let conn = db.lock().unwrap();
+        let errors = form
+            .validate(&conn)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        if !errors.is_empty() {
+            return Ok(errors);
+        }
+
+        //  the user is created together with the invitation or not at all
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        let id = form
+            .save(&tx)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        let mail = invite(&tx, &id, &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+        tx.commit()
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        mail
+    };
+
+    mailer.send(&mail).map_err(|_| {
+        ServerFnError::from(format!(
+            "{}, the user is invited and the mail can be resent",
+            InvitationError::Mail
+        ))
+    })?;
+    Ok(FieldErrors::new())
+}
+
+/// mail a new invitation link to the pending user `id`, the links sent before are invalid then
+#[server]
+pub async fn resend_invitation(id: String) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::invitation::{invite, InvitationError};
+    use crate::server::mailer::AppDataMailer;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    let (db, mailer): (AppDataDb, AppDataMailer) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let mail = invite(&db.lock().unwrap(), &id, &token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    mailer
+        .send(&mail)
+        .map_err(|_| ServerFnError::from(InvitationError::Mail.to_string()))
+}
+
+/// the invitation of a link token, no login required
+#[server]
+async fn get_invitation(token: String) -> Result<Invitation, ServerFnError<String>> {
+    use crate::server::AppDataDb;
+    use leptos_actix::extract;
+
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Invitation::get(&conn, &token).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// accept the invitation of a link token, no login required,
+/// returns the errors of invalid fields, empty if accepted
+#[server(name = AcceptInvitationLink, prefix = "/api", input = Json)]
+async fn accept_invitation(
+    token: String,
+    form: AcceptInvitationForm,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::AppDataDb;
+    use leptos_actix::extract;
+
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    form.accept(&conn, &token)
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// page of an invitation link at `/invitation/:token`,
+/// the invitee sets the password and profile to activate the account
+#[component]
+pub fn AcceptInvitation() -> impl IntoView {
+    let params = use_params_map();
+    let token = move || params.with(|params| params.get("token").cloned().unwrap_or_default());
+
+    let (form, set_form) = create_signal(AcceptInvitationForm {
+        timezone: TIMEZONES[0].to_string(),
+        language: LANGUAGES[0].0.to_string(),
+        ..Default::default()
+    });
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let invitation = create_resource(token, get_invitation);
+    create_effect(move |_| {
+        if let Some(Ok(invitation)) = invitation.get() {
+            set_form.update(|form| form.display_name = invitation.display_name);
+        }
+    });
+
+    let accept = create_action(move |form: &AcceptInvitationForm| {
+        accept_invitation(token(), form.clone())
+    });
+    create_effect(move |_| match accept.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => use_navigate()("/login", Default::default()),
+        Some(Ok(errors)) => set_errors(errors),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    let handle_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_error(None);
+        accept.dispatch(form.get_untracked());
+    };
+
+    view! {
+        <main class="flex w-full min-h-screen place-items-center place-content-center bg-base-200">
+            <div class="card bg-base-100 shadow w-96">
+                <Suspense fallback=move || view! {}>
+                    {move || {
+                        invitation
+                            .get()
+                            .map(|result| match result {
+                                Err(e) => {
+                                    view! {
+                                        <div class="card-body">
+                                            <h2 class="card-title">"Invitation"</h2>
+                                            <p class="text-error">{e.to_string()}</p>
+                                            <p class="text-sm opacity-70">
+                                                "Please ask your administrator for a new invitation"
+                                            </p>
+                                        </div>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(invitation) => {
+                                    view! {
+                                        <form class="card-body" on:submit=handle_submit>
+                                            <h2 class="card-title">"Welcome, " {invitation.username}</h2>
+                                            <p class="text-sm opacity-70">
+                                                {format!(
+                                                    "Set a password for {} to activate your account, the link expires at {} (UTC)",
+                                                    invitation.email,
+                                                    invitation.expires_at,
+                                                )}
+                                            </p>
+                                            <Field label="Display Name" name="display_name" errors=errors>
+                                                <input
+                                                    type="text"
+                                                    class="input input-bordered"
+                                                    id="display_name"
+                                                    prop:value=move || form.with(|form| form.display_name.clone())
+                                                    on:input=move |ev| {
+                                                        set_form.update(|form| form.display_name = event_target_value(&ev))
+                                                    }
+                                                />
+                                            </Field>
+                                            <Field label="Password" name="password" errors=errors>
+                                                <input
+                                                    type="password"
+                                                    class="input input-bordered"
+                                                    id="password"
+                                                    autocomplete="new-password"
+                                                    on:input=move |ev| {
+                                                        set_form.update(|form| form.password = event_target_value(&ev))
+                                                    }
+                                                />
+                                            </Field>
+                                            <Field label="Confirm Password" name="confirm_password" errors=errors>
+                                                <input
+                                                    type="password"
+                                                    class="input input-bordered"
+                                                    id="confirm_password"
+                                                    autocomplete="new-password"
+                                                    on:input=move |ev| {
+                                                        set_form
+                                                            .update(|form| form.confirm_password = event_target_value(&ev))
+                                                    }
+                                                />
+                                            </Field>
+                                            <Field label="Timezone" name="timezone" errors=errors>
+                                                <select
+                                                    class="select select-bordered"
+                                                    id="timezone"
+                                                    on:change=move |ev| {
+                                                        set_form.update(|form| form.timezone = event_target_value(&ev))
+                                                    }
+                                                >
+                                                    {TIMEZONES
+                                                        .iter()
+                                                        .map(|timezone| {
+                                                            view! {
+                                                                <option
+                                                                    value=*timezone
+                                                                    selected=move || form.with(|form| form.timezone == *timezone)
+                                                                >
+                                                                    {*timezone}
+                                                                </option>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </select>
+                                            </Field>
+                                            <Field label="Language" name="language" errors=errors>
+                                                <select
+                                                    class="select select-bordered"
+                                                    id="language"
+                                                    on:change=move |ev| {
+                                                        set_form.update(|form| form.language = event_target_value(&ev))
+                                                    }
+                                                >
+                                                    {LANGUAGES
+                                                        .iter()
+                                                        .map(|(code, name)| {
+                                                            view! {
+                                                                <option
+                                                                    value=*code
+                                                                    selected=move || form.with(|form| form.language == *code)
+                                                                >
+                                                                    {*name}
+                                                                </option>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </select>
+                                            </Field>
+                                            {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                                            <div class="form-control mt-6">
+                                                <button class="btn btn-primary" disabled=accept.pending()>
+                                                    "Activate"
+                                                </button>
+                                            </div>
+                                        </form>
+                                    }
+                                        .into_view()
+                                }
+                            })
+                    }}
+
+                </Suspense>
+            </div>
+        </main>
+    }
+}
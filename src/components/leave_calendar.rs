@@ -0,0 +1,497 @@
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use leptos::*;
+
+use super::current_user::use_current_user;
+use super::dashboard::{change_leave_status, LeaveDetail};
+use super::departments::get_departments;
+use super::leave_types::{leave_type_style, use_leave_types, LeaveTypeBadge};
+use crate::models::{has_approver_role, LeaveFeed, LeaveList, LeaveStatus};
+
+/// approved and pending leave requests between `start` and `end` of the members of department
+/// `department_id` and its sub departments, the department of current user if None,
+/// only approvers see other departments and the remarks of leave requests of others
+#[server]
+async fn get_leave_calendar(
+    department_id: Option<String>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<LeaveList, ServerFnError<String>> {
+    use crate::server::department::{department_of, members_of};
+    use crate::server::leave::{fill_staffing, is_approver, AppDataLeaveRepository};
+    use crate::server::leave_attachment::fill_attachments;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves): (AppDataDb, AppDataLeaveRepository) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let list = leaves.list().map_err(|e| ServerFnError::from(e.to_string()))?;
+    let conn = db.lock().unwrap();
+    let is_approver =
+        is_approver(&conn, &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+    let own_department =
+        department_of(&conn, &token.id).map_err(|e| ServerFnError::from(e.to_string()))?;
+    let department_id = department_id.or(own_department.clone());
+    if !is_approver && department_id != own_department {
+        return Err(ServerFnError::from("permission denied".to_string()));
+    }
+    let members = match &department_id {
+        Some(id) => members_of(&conn, id).map_err(|e| ServerFnError::from(e.to_string()))?,
+        None => vec![token.id.clone()],
+    };
+
+    let mut shown: LeaveList = list
+        .iter()
+        .filter(|leave| {
+            members.contains(&leave.user.id)
+                && matches!(leave.status, LeaveStatus::Pending | LeaveStatus::Approved)
+                && leave.start_date <= end
+                && leave.end_date >= start
+        })
+        .cloned()
+        .map(|mut leave| {
+            let decides = leave.approvals.iter().any(|step| {
+                step.approver_user
+                    .as_ref()
+                    .is_some_and(|user| user.id == token.id)
+            });
+            if !is_approver && !decides && leave.user.id != token.id {
+                leave.remark.clear();
+                leave.comment.clear();
+                leave.approvals.iter_mut().for_each(|step| step.comment.clear());
+            }
+            leave
+        })
+        .collect();
+    fill_staffing(&conn, &mut shown, &list).map_err(|e| ServerFnError::from(e.to_string()))?;
+    fill_attachments(&conn, &mut shown, &token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(shown)
+}
+
+/// iCalendar feeds of current user
+#[server]
+async fn get_leave_feeds() -> Result<Vec<LeaveFeed>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    LeaveFeed::list(&conn, &token.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// create or reset the feed of current user of the leaves of a team, department `department_id`
+/// or their own department if None, or of their own leaves if not `team`,
+/// returns the url of the feed
+#[server]
+async fn create_leave_feed(
+    team: bool,
+    department_id: Option<String>,
+) -> Result<String, ServerFnError<String>> {
+    use crate::server::department::department_of;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let department_id = match (team, department_id) {
+        (false, _) => None,
+        (true, Some(id)) => Some(id),
+        (true, None) => Some(
+            department_of(&conn, &token.id)
+                .map_err(|e| ServerFnError::from(e.to_string()))?
+                .ok_or_else(|| ServerFnError::from("you are not in any department".to_string()))?,
+        ),
+    };
+    LeaveFeed::create(&conn, &token.id, department_id.as_deref())
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn delete_leave_feed(id: String) -> Result<(), ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    LeaveFeed::delete(&conn, &token.id, &id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CalendarView {
+    Month,
+    Week,
+}
+
+/// approved and pending leaves of a team by month or week, colored by leave type,
+/// clicking a leave opens its details
+#[component]
+pub fn LeaveCalendar() -> impl IntoView {
+    let current_user = use_current_user();
+    let leave_types = use_leave_types();
+    let is_approver = Signal::derive(move || {
+        current_user.with(|user| user.as_ref().is_some_and(|user| has_approver_role(&user.roles)))
+    });
+    let (calendar_view, set_calendar_view) = create_signal(CalendarView::Month);
+    let (anchor, set_anchor) = create_signal(chrono::Local::now().date_naive());
+    let (department_id, set_department_id) = create_signal(None::<String>);
+
+    let change_status = create_action(|input: &(i64, LeaveStatus, String)| {
+        let (id, status, comment) = input.clone();
+        async move { change_leave_status(id, status, comment).await }
+    });
+    let range = move || visible_range(calendar_view.get(), anchor.get());
+    let leaves = create_resource(
+        move || (department_id.get(), range(), change_status.version().get()),
+        |(department_id, (start, end), _)| async move {
+            get_leave_calendar(department_id, start, end).await
+        },
+    );
+    let departments = create_resource(
+        move || is_approver.get(),
+        |is_approver| async move {
+            if is_approver {
+                get_departments().await.unwrap_or_default()
+            } else {
+                vec![]
+            }
+        },
+    );
+
+    let title = move || match calendar_view.get() {
+        CalendarView::Month => anchor.get().format("%B %Y").to_string(),
+        CalendarView::Week => {
+            let (start, end) = range();
+            format!("{} – {}", start, end)
+        }
+    };
+    let shift = move |forward: bool| {
+        set_anchor.update(|anchor| *anchor = shifted(calendar_view.get_untracked(), *anchor, forward))
+    };
+    let error = move || {
+        change_status
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { <p class="text-error text-sm">{e.to_string()}</p> })
+    };
+
+    view! {
+        <div class="h-full w-full p-4 space-y-4">
+            <div class="flex flex-wrap justify-between items-center gap-2">
+                <h2 class="text-lg font-bold">{title}</h2>
+                <div class="flex flex-wrap gap-2">
+                    <Show when=move || is_approver.get()>
+                        <select
+                            class="select select-bordered select-sm"
+                            on:change=move |ev| {
+                                let value = event_target_value(&ev);
+                                set_department_id((!value.is_empty()).then_some(value))
+                            }
+                        >
+                            <option value="">"My team"</option>
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    departments
+                                        .get()
+                                        .map(|list| {
+                                            list.into_iter()
+                                                .map(|department| {
+                                                    view! { <option value=department.id>{department.name}</option> }
+                                                })
+                                                .collect_view()
+                                        })
+                                }}
+
+                            </Suspense>
+                        </select>
+                    </Show>
+                    <div class="join">
+                        <button
+                            class="btn btn-sm join-item"
+                            class:btn-active=move || calendar_view.get() == CalendarView::Month
+                            on:click=move |_| set_calendar_view(CalendarView::Month)
+                        >
+                            "Month"
+                        </button>
+                        <button
+                            class="btn btn-sm join-item"
+                            class:btn-active=move || calendar_view.get() == CalendarView::Week
+                            on:click=move |_| set_calendar_view(CalendarView::Week)
+                        >
+                            "Week"
+                        </button>
+                    </div>
+                    <div class="join">
+                        <button class="btn btn-sm join-item" on:click=move |_| shift(false)>
+                            "«"
+                        </button>
+                        <button
+                            class="btn btn-sm join-item"
+                            on:click=move |_| set_anchor(chrono::Local::now().date_naive())
+                        >
+                            "Today"
+                        </button>
+                        <button class="btn btn-sm join-item" on:click=move |_| shift(true)>
+                            "»"
+                        </button>
+                    </div>
+                </div>
+            </div>
+            <div class="flex flex-wrap gap-2 text-sm">
+                {move || {
+                    leave_types
+                        .get()
+                        .into_iter()
+                        .filter(|leave_type| leave_type.active)
+                        .map(|leave_type| view! { <LeaveTypeBadge name=leave_type.name/> })
+                        .collect_view()
+                }}
+                <span class="badge badge-outline">"Pending"</span>
+            </div>
+            {error}
+            <LeaveFeeds department_id=department_id/>
+            <Suspense fallback=move || view! {}>
+                {move || {
+                    leaves
+                        .get()
+                        .map(|result| match result {
+                            Ok(list) => {
+                                let (start, end) = range();
+                                view! {
+                                    <CalendarGrid
+                                        list=list.clone()
+                                        start=start
+                                        end=end
+                                        month=(calendar_view.get() == CalendarView::Month)
+                                            .then(|| anchor.get().month())
+                                    />
+                                    {list
+                                        .into_iter()
+                                        .map(|leave| {
+                                            let modal_id = calendar_modal_id(leave.id);
+                                            view! { <LeaveDetail leave=leave modal_id=modal_id change_status=change_status/> }
+                                        })
+                                        .collect_view()}
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p class="text-error text-sm">{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Suspense>
+        </div>
+    }
+}
+
+/// links of iCalendar feeds of approved leaves to subscribe to in calendar apps,
+/// of current user's own leaves or of the team shown, `department_id`
+#[component]
+fn LeaveFeeds(department_id: ReadSignal<Option<String>>) -> impl IntoView {
+    let (url, set_url) = create_signal(None::<String>);
+    let create = create_action(|input: &(bool, Option<String>)| {
+        let (team, department_id) = input.clone();
+        async move { create_leave_feed(team, department_id).await }
+    });
+    let delete = create_action(|id: &String| {
+        let id = id.clone();
+        async move { delete_leave_feed(id).await }
+    });
+    let feeds = create_resource(
+        move || (create.version().get(), delete.version().get()),
+        |_| async move { get_leave_feeds().await.unwrap_or_default() },
+    );
+    create_effect(move |_| {
+        if let Some(Ok(url)) = create.value().get() {
+            set_url(Some(url));
+        }
+    });
+    let error = move || {
+        let create_error = create.value().get().and_then(|result| result.err());
+        let delete_error = delete.value().get().and_then(|result| result.err());
+        create_error
+            .or(delete_error)
+            .map(|e| view! { <p class="text-error text-sm">{e.to_string()}</p> })
+    };
+
+    view! {
+        <details class="collapse collapse-arrow bg-base-100 shadow">
+            <summary class="collapse-title font-bold">"Subscribe in calendar apps"</summary>
+            <div class="collapse-content space-y-2">
+                <p class="text-sm opacity-70">
+                    "Approved leaves are published at secret links, anyone having a link sees them. "
+                    "A link is shown once, create it again to replace it."
+                </p>
+                {error}
+                {move || {
+                    url.get()
+                        .map(|url| {
+                            view! {
+                                <input
+                                    type="text"
+                                    class="input input-bordered input-sm w-full"
+                                    readonly
+                                    prop:value=url
+                                    onfocus="this.select()"
+                                />
+                            }
+                        })
+                }}
+                <ul class="space-y-1">
+                    <Suspense fallback=move || view! {}>
+                        {move || {
+                            feeds
+                                .get()
+                                .map(|list| {
+                                    list.into_iter()
+                                        .map(|feed| {
+                                            let name = if feed.department_id.is_some() {
+                                                format!("{} leaves", feed.department)
+                                            } else {
+                                                "My leaves".to_string()
+                                            };
+                                            let id = feed.id.clone();
+                                            view! {
+                                                <li class="flex justify-between items-center">
+                                                    <span>
+                                                        {name} " "
+                                                        <span class="text-sm opacity-70">
+                                                            {format!("created at {}", feed.created_at)}
+                                                        </span>
+                                                    </span>
+                                                    <button
+                                                        class="btn btn-ghost btn-xs"
+                                                        on:click=move |_| delete.dispatch(id.clone())
+                                                    >
+                                                        "Delete"
+                                                    </button>
+                                                </li>
+                                            }
+                                        })
+                                        .collect_view()
+                                })
+                        }}
+
+                    </Suspense>
+                </ul>
+                <div class="flex flex-wrap gap-2">
+                    <button
+                        class="btn btn-sm"
+                        disabled=create.pending()
+                        on:click=move |_| create.dispatch((false, None))
+                    >
+                        "Link of my leaves"
+                    </button>
+                    <button
+                        class="btn btn-sm"
+                        disabled=create.pending()
+                        on:click=move |_| create.dispatch((true, department_id.get_untracked()))
+                    >
+                        "Link of this team"
+                    </button>
+                </div>
+            </div>
+        </details>
+    }
+}
+
+/// days from `start` to `end` by weeks, days out of `month` dimmed if given
+#[component]
+fn CalendarGrid(list: LeaveList, start: NaiveDate, end: NaiveDate, month: Option<u32>) -> impl IntoView {
+    let leave_types = use_leave_types();
+    let today = chrono::Local::now().date_naive();
+    let days: Vec<NaiveDate> = start.iter_days().take_while(|day| *day <= end).collect();
+
+    view! {
+        <div class="grid grid-cols-7 gap-px bg-base-300 rounded-lg overflow-hidden shadow">
+            {["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+                .into_iter()
+                .map(|name| view! { <div class="bg-base-200 p-2 text-center text-sm font-bold">{name}</div> })
+                .collect_view()}
+            {days
+                .into_iter()
+                .map(|day| {
+                    let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+                    let outside = month.is_some_and(|month| day.month() != month);
+                    let leaves = list
+                        .iter()
+                        .filter(|leave| leave.start_date <= day && leave.end_date >= day)
+                        .map(|leave| {
+                            let name = leave.leave_type.clone();
+                            let pending = leave.status == LeaveStatus::Pending;
+                            let style = move || leave_types.with(|list| leave_type_style(list, &name, pending));
+                            view! {
+                                <button
+                                    class="badge badge-sm w-full justify-start truncate cursor-pointer"
+                                    class:badge-outline=pending
+                                    style=style
+                                    title=format!("{} {} ({})", leave.user.username, leave.leave_type, leave.status)
+                                    onclick=format!("{}.showModal()", calendar_modal_id(leave.id))
+                                >
+                                    {leave.user.username.clone()}
+                                </button>
+                            }
+                        })
+                        .collect_view();
+                    view! {
+                        <div
+                            class="min-h-24 p-1 space-y-1"
+                            class:bg-base-100=!weekend
+                            class:bg-base-200=weekend
+                            class:opacity-50=outside
+                        >
+                            <div class="text-right text-sm" class:font-bold=day == today>
+                                {day.day()}
+                            </div>
+                            {leaves}
+                        </div>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+/// the first and the last day shown around `anchor`, whole weeks from Monday to Sunday
+fn visible_range(calendar_view: CalendarView, anchor: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let (first, last) = match calendar_view {
+        CalendarView::Month => {
+            let first = anchor.with_day(1).unwrap_or(anchor);
+            let last = (first + Months::new(1)).pred_opt().unwrap_or(first);
+            (first, last)
+        }
+        CalendarView::Week => (anchor, anchor),
+    };
+    let start = first - Duration::days(first.weekday().num_days_from_monday() as i64);
+    let end = last + Duration::days(6 - last.weekday().num_days_from_monday() as i64);
+    (start, end)
+}
+
+/// `anchor` moved a month or a week forward or backward
+fn shifted(calendar_view: CalendarView, anchor: NaiveDate, forward: bool) -> NaiveDate {
+    match (calendar_view, forward) {
+        (CalendarView::Month, true) => anchor + Months::new(1),
+        (CalendarView::Month, false) => anchor - Months::new(1),
+        (CalendarView::Week, true) => anchor + Duration::days(7),
+        (CalendarView::Week, false) => anchor - Duration::days(7),
+    }
+}
+
+fn calendar_modal_id(id: i64) -> String {
+    format!("calendar_leave_{}", id)
+}
@@ -0,0 +1,367 @@
+use chrono::Datelike;
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use crate::components::field::Field;
+use crate::components::leave_types::{use_leave_types, LeaveTypeBadge};
+use crate::models::{
+    format_days, FieldErrors, LeaveAdjustmentForm, LedgerEntry, UserLeaveBalances,
+};
+
+/// leave balances of every user in `year`
+#[server]
+async fn get_users_leave_balances(
+    year: i32,
+) -> Result<Vec<UserLeaveBalances>, ServerFnError<String>> {
+    use crate::models::consts::LEAVE_BALANCE_ROLES;
+    use crate::models::{User, UserDetail};
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_ledger::leave_balances;
+    use crate::server::{require_any_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_any_role(&LEAVE_BALANCE_ROLES).await?;
+    let (db, leaves): (AppDataDb, AppDataLeaveRepository) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let leaves = leaves.list().map_err(|e| ServerFnError::from(e.to_string()))?;
+    let conn = db.lock().unwrap();
+    let users = UserDetail::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))?;
+    users
+        .into_iter()
+        .map(|user| {
+            let balances = leave_balances(&conn, &leaves, &user.id, year)
+                .map_err(|e| ServerFnError::from(e.to_string()))?;
+            Ok(UserLeaveBalances {
+                user: User {
+                    id: user.id,
+                    username: user.username,
+                },
+                balances,
+            })
+        })
+        .collect()
+}
+
+/// ledger entries of user `user_id` in `year`
+#[server]
+async fn get_leave_ledger(
+    user_id: String,
+    year: i32,
+) -> Result<Vec<LedgerEntry>, ServerFnError<String>> {
+    use crate::models::consts::LEAVE_BALANCE_ROLES;
+    use crate::server::{require_any_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_any_role(&LEAVE_BALANCE_ROLES).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    LedgerEntry::list(&conn, &user_id, year).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// give or take days of a leave balance, returns the errors of invalid fields, empty if saved
+#[server(name = AdjustLeaveBalance, prefix = "/api", input = Json)]
+async fn adjust_leave_balance(
+    form: LeaveAdjustmentForm,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::models::consts::LEAVE_BALANCE_ROLES;
+    use crate::server::{require_any_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_any_role(&LEAVE_BALANCE_ROLES).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let errors = form
+        .validate(&conn)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    form.save(&conn, &token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+/// leave balances of everyone, the ledger of a chosen user and manual adjustments
+#[component]
+pub fn LeaveLedger() -> impl IntoView {
+    let (year, set_year) = create_signal(chrono::Local::now().year());
+    let (selected, set_selected) = create_signal(None::<UserLeaveBalances>);
+    let (form, set_form) = create_signal(LeaveAdjustmentForm::default());
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let leave_types = use_leave_types();
+    let active_types = move || {
+        leave_types
+            .get()
+            .into_iter()
+            .filter(|leave_type| leave_type.active)
+            .map(|leave_type| leave_type.name)
+            .collect::<Vec<_>>()
+    };
+    //  the first active type is chosen once leave types are loaded
+    create_effect(move |_| {
+        if let Some(first) = active_types().into_iter().next() {
+            if form.with_untracked(|form| form.leave_type.is_empty()) {
+                set_form.update(|form| form.leave_type = first);
+            }
+        }
+    });
+
+    let adjust = create_action(move |form: &LeaveAdjustmentForm| {
+        let form = form.clone();
+        async move { adjust_leave_balance(form).await }
+    });
+    let users = create_resource(
+        move || (year.get(), adjust.version().get()),
+        |(year, _)| async move { get_users_leave_balances(year).await },
+    );
+    let ledger = create_resource(
+        move || {
+            (
+                selected.with(|selected| selected.as_ref().map(|selected| selected.user.id.clone())),
+                year.get(),
+                adjust.version().get(),
+            )
+        },
+        |(user_id, year, _)| async move {
+            match user_id {
+                Some(user_id) => get_leave_ledger(user_id, year).await.unwrap_or_default(),
+                None => vec![],
+            }
+        },
+    );
+    create_effect(move |_| match adjust.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => {
+            set_errors(FieldErrors::new());
+            set_form.update(|form| {
+                form.days = 0.0;
+                form.reason.clear();
+            });
+        }
+        Some(Ok(errors)) => set_errors(errors),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    let select_user = move |user: UserLeaveBalances| {
+        set_form.update(|form| form.user_id = user.user.id.clone());
+        set_errors(FieldErrors::new());
+        set_selected(Some(user));
+    };
+    let handle_adjust = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_error(None);
+        let mut form = form.get_untracked();
+        form.year = year.get_untracked();
+        adjust.dispatch(form);
+    };
+
+    view! {
+        <div class="h-full w-full p-4 space-y-4">
+            <div class="flex justify-between items-center">
+                <h2 class="text-lg font-bold">"Leave Balances"</h2>
+                <div class="join">
+                    <button class="btn btn-sm join-item" on:click=move |_| set_year.update(|year| *year -= 1)>
+                        "«"
+                    </button>
+                    <span class="btn btn-sm join-item no-animation">{year}</span>
+                    <button class="btn btn-sm join-item" on:click=move |_| set_year.update(|year| *year += 1)>
+                        "»"
+                    </button>
+                </div>
+            </div>
+            {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+            <div class="overflow-x-auto bg-base-100 rounded-lg shadow">
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"User"</th>
+                            {move || {
+                                active_types()
+                                    .into_iter()
+                                    .map(|leave_type| view! { <th>{format!("{} remaining", leave_type)}</th> })
+                                    .collect_view()
+                            }}
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                users
+                                    .get()
+                                    .map(|result| match result {
+                                        Ok(list) => {
+                                            list.into_iter()
+                                                .map(|user| {
+                                                    let id = user.user.id.clone();
+                                                    let row = user.clone();
+                                                    view! {
+                                                        <tr
+                                                            class="hover cursor-pointer"
+                                                            class:bg-base-200=move || {
+                                                                selected.with(|selected| {
+                                                                    selected.as_ref().is_some_and(|selected| selected.user.id == id)
+                                                                })
+                                                            }
+                                                            on:click=move |_| select_user(row.clone())
+                                                        >
+                                                            <td>{user.user.username}</td>
+                                                            {active_types()
+                                                                .into_iter()
+                                                                .map(|leave_type| {
+                                                                    let balance = user
+                                                                        .balances
+                                                                        .iter()
+                                                                        .find(|balance| balance.leave_type == leave_type)
+                                                                        .cloned()
+                                                                        .unwrap_or_default();
+                                                                    view! {
+                                                                        <td>
+                                                                            {format_days(balance.remaining())} " / "
+                                                                            {format_days(balance.accrued + balance.adjusted)}
+                                                                        </td>
+                                                                    }
+                                                                })
+                                                                .collect_view()}
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect_view()
+                                        }
+                                        Err(e) => view! { <tr><td class="text-error">{e.to_string()}</td></tr> }.into_view(),
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+            </div>
+            {move || {
+                selected
+                    .get()
+                    .map(|user| {
+                        view! {
+                            <div class="flex flex-wrap gap-4 items-start">
+                                <div class="card bg-base-100 shadow flex-1">
+                                    <div class="card-body">
+                                        <h2 class="card-title">
+                                            {format!("Ledger of {} in {}", user.user.username, year.get_untracked())}
+                                        </h2>
+                                        <table class="table table-sm">
+                                            <thead>
+                                                <tr>
+                                                    <th>"Date"</th>
+                                                    <th>"Type"</th>
+                                                    <th>"Kind"</th>
+                                                    <th>"Days"</th>
+                                                    <th>"Reason"</th>
+                                                    <th>"By"</th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                <Suspense fallback=move || view! {}>
+                                                    {move || {
+                                                        ledger
+                                                            .get()
+                                                            .map(|list| {
+                                                                list.into_iter()
+                                                                    .map(|entry| {
+                                                                        view! {
+                                                                            <tr>
+                                                                                <td>{entry.created_at}</td>
+                                                                                <td>
+                                                                                    <LeaveTypeBadge name=entry.leave_type/>
+                                                                                </td>
+                                                                                <td>{entry.kind.to_string()}</td>
+                                                                                <td class:text-error=entry.days < 0.0>
+                                                                                    {format_days(entry.days)}
+                                                                                </td>
+                                                                                <td>{entry.reason}</td>
+                                                                                <td>{entry.created_by.unwrap_or("system".to_string())}</td>
+                                                                            </tr>
+                                                                        }
+                                                                    })
+                                                                    .collect_view()
+                                                            })
+                                                    }}
+
+                                                </Suspense>
+                                            </tbody>
+                                        </table>
+                                    </div>
+                                </div>
+                                <div class="card bg-base-100 shadow">
+                                    <form class="card-body" on:submit=handle_adjust>
+                                        <h2 class="card-title">"Adjust Balance"</h2>
+                                        <Field label="Leave Type" name="leave_type" errors=errors>
+                                            <select
+                                                class="select select-bordered"
+                                                id="leave_type"
+                                                on:change=move |ev| {
+                                                    let value = event_target_value(&ev);
+                                                    set_form.update(|form| form.leave_type = value)
+                                                }
+                                            >
+                                                {move || {
+                                                    active_types()
+                                                        .into_iter()
+                                                        .map(|leave_type| {
+                                                            let name = leave_type.clone();
+                                                            view! {
+                                                                <option
+                                                                    value=leave_type.clone()
+                                                                    selected=move || form.with(|form| form.leave_type == name)
+                                                                >
+                                                                    {leave_type}
+                                                                </option>
+                                                            }
+                                                        })
+                                                        .collect_view()
+                                                }}
+                                            </select>
+                                        </Field>
+                                        <Field label="Days" name="days" errors=errors>
+                                            <input
+                                                type="number"
+                                                step="0.5"
+                                                class="input input-bordered"
+                                                id="days"
+                                                prop:value=move || form.with(|form| form.days.to_string())
+                                                on:input=move |ev| {
+                                                    let value = event_target_value(&ev);
+                                                    set_form.update(|form| form.days = value.parse().unwrap_or_default())
+                                                }
+                                            />
+                                        </Field>
+                                        <Field label="Reason" name="reason" errors=errors>
+                                            <input
+                                                type="text"
+                                                class="input input-bordered"
+                                                id="reason"
+                                                prop:value=move || form.with(|form| form.reason.clone())
+                                                on:input=move |ev| set_form.update(|form| form.reason = event_target_value(&ev))
+                                            />
+                                        </Field>
+                                        <div class="card-actions justify-end mt-4">
+                                            <button class="btn btn-primary" type="submit" disabled=adjust.pending()>
+                                                "Adjust"
+                                            </button>
+                                        </div>
+                                    </form>
+                                </div>
+                            </div>
+                        }
+                    })
+            }}
+        </div>
+    }
+}
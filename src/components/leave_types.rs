@@ -0,0 +1,401 @@
+//! Leave Types
+//! leave types are loaded once by [Home](super::Home) and provided as context,
+//! so that any component renders a leave request by the name, color and pay of its type
+//!
+//! ```ignore
+//! view! { <LeaveTypeBadge name=leave.leave_type.clone()/> }
+//! ```
+
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use crate::components::current_user::use_has_role;
+use crate::models::consts::ADMIN_ROLE;
+use crate::models::{Accrual, Balance, FieldErrors, LeaveType};
+
+/// the color of a leave type just created
+const DEFAULT_COLOR: &str = "#6b7280";
+
+/// all of leave types, inactive ones included for existing requests
+#[server]
+async fn get_leave_types() -> Result<Vec<LeaveType>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    LeaveType::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// create a leave type if `create`, otherwise change one,
+/// returns the errors of invalid fields, empty if saved
+#[server(name = SaveLeaveType, prefix = "/api", input = Json)]
+async fn save_leave_type(
+    leave_type: LeaveType,
+    create: bool,
+) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let mut leave_type = leave_type;
+    leave_type.name = leave_type.name.trim().to_string();
+    leave_type.policy.leave_type = leave_type.name.clone();
+
+    let conn = db.lock().unwrap();
+    let errors = leave_type
+        .validate(&conn, create)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+    leave_type
+        .save(&conn, create)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(errors)
+}
+
+#[derive(Clone, Copy)]
+struct LeaveTypesContext(Resource<(), Vec<LeaveType>>);
+
+/// load leave types and provide them to all of the descendants
+pub fn provide_leave_types() {
+    let resource = create_resource(
+        || {},
+        |_| async move { get_leave_types().await.unwrap_or_default() },
+    );
+    provide_context(LeaveTypesContext(resource));
+}
+
+/// all of leave types in the order created, empty while loading
+pub fn use_leave_types() -> Signal<Vec<LeaveType>> {
+    let LeaveTypesContext(resource) = expect_context::<LeaveTypesContext>();
+    Signal::derive(move || resource.get().unwrap_or_default())
+}
+
+/// a callback loading leave types again, such as after one changed
+pub fn use_refresh_leave_types() -> Callback<()> {
+    let context = use_context::<LeaveTypesContext>();
+    Callback::new(move |_| {
+        if let Some(LeaveTypesContext(resource)) = context {
+            resource.refetch();
+        }
+    })
+}
+
+/// inline style of a badge of leave type `name` of `leave_types`, empty if unknown
+pub fn leave_type_style(leave_types: &[LeaveType], name: &str, outline: bool) -> String {
+    leave_types
+        .iter()
+        .find(|leave_type| leave_type.name == name)
+        .map(|leave_type| leave_type.badge_style(outline))
+        .unwrap_or_default()
+}
+
+/// the name of a leave type in its color, and whether it is unpaid if `with_pay`
+#[component]
+pub fn LeaveTypeBadge(
+    #[prop(into)] name: String,
+    #[prop(optional)] with_pay: bool,
+) -> impl IntoView {
+    let leave_types = use_leave_types();
+    let style = {
+        let name = name.clone();
+        move || leave_types.with(|list| leave_type_style(list, &name, false))
+    };
+    let unpaid = {
+        let name = name.clone();
+        move || {
+            with_pay
+                && leave_types.with(|list| {
+                    list.iter()
+                        .any(|leave_type| leave_type.name == name && !leave_type.paid)
+                })
+        }
+    };
+
+    view! {
+        <span class="badge" style=style>
+            {name}
+            {move || unpaid().then_some(" (unpaid)")}
+        </span>
+    }
+}
+
+/// leave types with their balance policies and attachment rules, only administrators change them
+#[component]
+pub fn LeaveTypes() -> impl IntoView {
+    let is_admin = use_has_role(ADMIN_ROLE);
+    let leave_types = use_leave_types();
+    let refresh = use_refresh_leave_types();
+    let save = create_action(|input: &(LeaveType, bool)| {
+        let (leave_type, create) = input.clone();
+        async move { save_leave_type(leave_type, create).await }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(errors)) = save.value().get() {
+            if errors.is_empty() {
+                refresh.call(());
+            }
+        }
+    });
+    let message = move || match save.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => None,
+        Some(Ok(errors)) => Some(
+            errors
+                .into_iter()
+                .map(|(field, e)| format!("{} {}", field, e))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Some(Err(e)) => Some(e.to_string()),
+        None => None,
+    };
+
+    view! {
+        <div class="h-full w-full p-4 space-y-4">
+            <h2 class="text-lg font-bold">"Leave Types"</h2>
+            {move || message().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+            <div class="overflow-x-auto bg-base-100 rounded-lg shadow">
+                <table class="table table-sm">
+                    <thead>
+                        <tr>
+                            <th>"Name"</th>
+                            <th>"Color"</th>
+                            <th>"Paid"</th>
+                            <th>"Backdated"</th>
+                            <th>"Active"</th>
+                            <th>"Balance"</th>
+                            <th>"Accrual"</th>
+                            <th>"Days per Year"</th>
+                            <th>"Max Carry-over"</th>
+                            <th>"Carry-over Expires (months)"</th>
+                            <th>"Attachment over (days)"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            leave_types
+                                .get()
+                                .into_iter()
+                                .map(|leave_type| {
+                                    view! { <LeaveTypeRow leave_type=leave_type create=false is_admin=is_admin save=save/> }
+                                })
+                                .collect_view()
+                        }}
+                        <Show when=move || is_admin.get()>
+                            {move || {
+                                //  a blank row again after every save
+                                save.version().get();
+                                let leave_type = LeaveType {
+                                    color: DEFAULT_COLOR.to_string(),
+                                    paid: true,
+                                    active: true,
+                                    ..Default::default()
+                                };
+                                view! { <LeaveTypeRow leave_type=leave_type create=true is_admin=is_admin save=save/> }
+                            }}
+                        </Show>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn LeaveTypeRow(
+    leave_type: LeaveType,
+    create: bool,
+    is_admin: Signal<bool>,
+    save: Action<(LeaveType, bool), Result<FieldErrors, ServerFnError<String>>>,
+) -> impl IntoView {
+    let (leave_type, set_leave_type) = create_signal(leave_type);
+    let disabled = move || !is_admin.get();
+
+    view! {
+        <tr>
+            <td>
+                {if create {
+                    view! {
+                        <input
+                            type="text"
+                            class="input input-bordered input-sm w-32"
+                            placeholder="New type"
+                            prop:value=move || leave_type.with(|leave_type| leave_type.name.clone())
+                            on:input=move |ev| set_leave_type.update(|leave_type| leave_type.name = event_target_value(&ev))
+                        />
+                    }
+                        .into_view()
+                } else {
+                    view! { <LeaveTypeBadge name=leave_type.get_untracked().name/> }.into_view()
+                }}
+            </td>
+            <td>
+                <input
+                    type="color"
+                    class="w-10 h-8 cursor-pointer"
+                    disabled=disabled
+                    prop:value=move || leave_type.with(|leave_type| leave_type.color.clone())
+                    on:input=move |ev| set_leave_type.update(|leave_type| leave_type.color = event_target_value(&ev))
+                />
+            </td>
+            <td>
+                <input
+                    type="checkbox"
+                    class="checkbox checkbox-sm"
+                    disabled=disabled
+                    prop:checked=move || leave_type.with(|leave_type| leave_type.paid)
+                    on:change=move |ev| set_leave_type.update(|leave_type| leave_type.paid = event_target_checked(&ev))
+                />
+            </td>
+            <td>
+                <input
+                    type="checkbox"
+                    class="checkbox checkbox-sm"
+                    disabled=disabled
+                    prop:checked=move || leave_type.with(|leave_type| leave_type.backdated)
+                    on:change=move |ev| {
+                        set_leave_type.update(|leave_type| leave_type.backdated = event_target_checked(&ev))
+                    }
+                />
+            </td>
+            <td>
+                <input
+                    type="checkbox"
+                    class="checkbox checkbox-sm"
+                    disabled=disabled
+                    prop:checked=move || leave_type.with(|leave_type| leave_type.active)
+                    on:change=move |ev| set_leave_type.update(|leave_type| leave_type.active = event_target_checked(&ev))
+                />
+            </td>
+            <td>
+                <select
+                    class="select select-bordered select-sm"
+                    disabled=disabled
+                    on:change=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type.update(|leave_type| leave_type.policy.balance = value.parse().unwrap_or_default())
+                    }
+                >
+                    {[Balance::Tracked, Balance::Unlimited]
+                        .into_iter()
+                        .map(|balance| {
+                            view! {
+                                <option
+                                    value=balance.to_string()
+                                    selected=move || leave_type.with(|leave_type| leave_type.policy.balance == balance)
+                                >
+                                    {balance.to_string()}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+            </td>
+            <td>
+                <select
+                    class="select select-bordered select-sm"
+                    disabled=disabled
+                    on:change=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type.update(|leave_type| leave_type.policy.accrual = value.parse().unwrap_or_default())
+                    }
+                >
+                    {[Accrual::Yearly, Accrual::Monthly]
+                        .into_iter()
+                        .map(|accrual| {
+                            view! {
+                                <option
+                                    value=accrual.to_string()
+                                    selected=move || leave_type.with(|leave_type| leave_type.policy.accrual == accrual)
+                                >
+                                    {accrual.to_string()}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+            </td>
+            <td>
+                <input
+                    type="number"
+                    step="0.5"
+                    class="input input-bordered input-sm w-24"
+                    disabled=disabled
+                    prop:value=move || leave_type.with(|leave_type| leave_type.policy.days_per_year.to_string())
+                    on:input=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type.update(|leave_type| leave_type.policy.days_per_year = value.parse().unwrap_or_default())
+                    }
+                />
+            </td>
+            <td>
+                <input
+                    type="number"
+                    step="0.5"
+                    class="input input-bordered input-sm w-24"
+                    disabled=disabled
+                    prop:value=move || leave_type.with(|leave_type| leave_type.policy.max_carry_over.to_string())
+                    on:input=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type.update(|leave_type| leave_type.policy.max_carry_over = value.parse().unwrap_or_default())
+                    }
+                />
+            </td>
+            <td>
+                <input
+                    type="number"
+                    class="input input-bordered input-sm w-24"
+                    disabled=disabled
+                    prop:value=move || leave_type.with(|leave_type| leave_type.policy.carry_over_expiry_months.to_string())
+                    on:input=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type
+                            .update(|leave_type| {
+                                leave_type.policy.carry_over_expiry_months = value.parse().unwrap_or_default()
+                            })
+                    }
+                />
+            </td>
+            <td>
+                <input
+                    type="number"
+                    step="0.5"
+                    class="input input-bordered input-sm w-24"
+                    placeholder="never"
+                    disabled=disabled
+                    prop:value=move || {
+                        leave_type
+                            .with(|leave_type| {
+                                leave_type.policy.attachment_over_days.map(|days| days.to_string()).unwrap_or_default()
+                            })
+                    }
+                    on:input=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_leave_type.update(|leave_type| leave_type.policy.attachment_over_days = value.parse().ok())
+                    }
+                />
+            </td>
+            <td>
+                <Show when=move || is_admin.get()>
+                    <button
+                        class="btn btn-ghost btn-xs"
+                        on:click=move |_| save.dispatch((leave_type.get_untracked(), create))
+                    >
+                        {if create { "Create" } else { "Save" }}
+                    </button>
+                </Show>
+            </td>
+        </tr>
+    }
+}
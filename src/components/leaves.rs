@@ -0,0 +1,413 @@
+use chrono::Datelike;
+use leptos::server_fn::codec::Json;
+use leptos::*;
+use leptos_router::*;
+
+use crate::components::field::Field;
+use crate::components::file::read_file;
+use crate::components::leave_types::{use_leave_types, LeaveTypeBadge};
+use crate::models::{
+    format_days, AttachmentUpload, DayPart, FieldErrors, LeaveBalance, LeaveForm, WorkingCalendar,
+};
+
+/// leave balances of current user in `year`
+#[server]
+async fn get_leave_balances(year: i32) -> Result<Vec<LeaveBalance>, ServerFnError<String>> {
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_ledger::leave_balances;
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves): (AppDataDb, AppDataLeaveRepository) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let leaves = leaves
+        .list_of_user(&token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    let conn = db.lock().unwrap();
+    leave_balances(&conn, &leaves, &token.id, year).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// weekend and holidays of `year` by the calendar of current user,
+/// counting working days while the form is filled
+#[server]
+async fn get_working_calendar(year: i32) -> Result<WorkingCalendar, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use chrono::NaiveDate;
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let (Some(start), Some(end)) = (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year, 12, 31),
+    ) else {
+        return Err(ServerFnError::from("invalid year".to_string()));
+    };
+    let conn = db.lock().unwrap();
+    WorkingCalendar::of_user(&conn, &token.id, start, end)
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// submit a leave request of current user, returns the errors of invalid fields, empty if submitted
+#[server(name = SubmitLeave, prefix = "/api", input = Json)]
+async fn submit_leave(form: LeaveForm) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::models::{LeaveAttachment, User};
+    use crate::server::approval::resolve_chain;
+    use crate::server::leave::AppDataLeaveRepository;
+    use crate::server::leave_attachment::{discard, AppDataAttachmentStorage};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (db, leaves, attachments): (AppDataDb, AppDataLeaveRepository, AppDataAttachmentStorage) =
+        extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let today = chrono::Local::now().date_naive();
+    let existing = leaves
+        .list_of_user(&token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    //  the repository may lock the database too, so the lock is released before creating
+    let (duration, approvals) = {
+        let conn = db.lock().unwrap();
+        let errors = form
+            .validate(&conn, &existing, &token.id, today)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        if !errors.is_empty() {
+            return Ok(errors);
+        }
+        let duration = form
+            .working_days(&conn, &token.id)
+            .map_err(|e| ServerFnError::from(e.to_string()))?
+            .unwrap_or_default();
+        let approvals = resolve_chain(&conn, &token.id, &form.leave_type, duration)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        (duration, approvals)
+    };
+
+    //  files are stored before the request is created, and removed again if anything fails
+    let mut stored = vec![];
+    for upload in form.attachments.iter() {
+        match upload.store(&**attachments) {
+            Ok(file) => stored.push(file),
+            Err(e) => {
+                discard(&**attachments, &stored);
+                return Err(ServerFnError::from(e.to_string()));
+            }
+        }
+    }
+
+    let user = User {
+        id: token.id.clone(),
+        username: token.username.clone(),
+    };
+    let id = match leaves.create(&user, &form, duration, &approvals) {
+        Ok(id) => id,
+        Err(e) => {
+            discard(&**attachments, &stored);
+            return Err(ServerFnError::from(e.to_string()));
+        }
+    };
+
+    //  bound first, the lock is released before the repository deletes the request
+    let attached = LeaveAttachment::attach(&db.lock().unwrap(), id, &token.id, &stored);
+    if let Err(e) = attached {
+        _ = leaves.delete(id);
+        discard(&**attachments, &stored);
+        return Err(ServerFnError::from(e.to_string()));
+    }
+    Ok(FieldErrors::new())
+}
+
+/// a new leave request of current user, showing the working days and the remaining balance
+#[component]
+pub fn NewLeave() -> impl IntoView {
+    let (form, set_form) = create_signal(LeaveForm::default());
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let leave_types = use_leave_types();
+    let active_types = move || {
+        leave_types
+            .get()
+            .into_iter()
+            .filter(|leave_type| leave_type.active)
+            .collect::<Vec<_>>()
+    };
+    //  the first active type is chosen once leave types are loaded
+    create_effect(move |_| {
+        if let Some(first) = active_types().into_iter().next() {
+            if form.with_untracked(|form| form.leave_type.is_empty()) {
+                set_form.update(|form| form.leave_type = first.name);
+            }
+        }
+    });
+
+    //  balances of the year the leave starts in, this year before a date is picked
+    let year = move || {
+        form.with(|form| form.start_date)
+            .map(|date| date.year())
+            .unwrap_or_else(|| chrono::Local::now().year())
+    };
+    let calendar = create_resource(year, |year| async move {
+        get_working_calendar(year).await.unwrap_or_default()
+    });
+    let working_days = move || {
+        calendar
+            .get()
+            .and_then(|calendar| form.with(|form| form.duration(&calendar)))
+    };
+    let several_days = move || form.with(|form| form.start_date != form.end_date);
+
+    let submit = create_action(move |form: &LeaveForm| {
+        let form = form.clone();
+        async move { submit_leave(form).await }
+    });
+    create_effect(move |_| match submit.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => use_navigate()("/admin", Default::default()),
+        Some(Ok(errors)) => set_errors(errors),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    //  name of the file chosen to attach, read only when submitted
+    let (attachment, set_attachment) = create_signal(None::<String>);
+
+    let handle_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_error(None);
+        let mut form = form.get_untracked();
+        spawn_local(async move {
+            if let Some(file_name) = attachment.get_untracked() {
+                match read_file("attachment_file").await {
+                    Ok(data) => form.attachments = vec![AttachmentUpload { file_name, data }],
+                    Err(e) => {
+                        set_error(Some(e));
+                        return;
+                    }
+                }
+            }
+            submit.dispatch(form);
+        });
+    };
+
+    view! {
+        <div class="h-full w-full p-4 flex flex-wrap gap-4 items-start">
+            <div class="card bg-base-100 shadow max-w-2xl flex-1">
+                <form class="card-body" on:submit=handle_submit>
+                    <h2 class="card-title">"New Leave Request"</h2>
+                    <Field label="Leave Type" name="leave_type" errors=errors>
+                        <select
+                            class="select select-bordered"
+                            id="leave_type"
+                            on:change=move |ev| {
+                                let value = event_target_value(&ev);
+                                set_form.update(|form| form.leave_type = value)
+                            }
+                        >
+                            {move || {
+                                active_types()
+                                    .into_iter()
+                                    .map(|leave_type| {
+                                        let name = leave_type.name.clone();
+                                        view! {
+                                            <option
+                                                value=leave_type.name.clone()
+                                                selected=move || form.with(|form| form.leave_type == name)
+                                            >
+                                                {leave_type.name}
+                                                {(!leave_type.paid).then_some(" (unpaid)")}
+                                            </option>
+                                        }
+                                    })
+                                    .collect_view()
+                            }}
+                        </select>
+                    </Field>
+                    <div class="flex gap-4">
+                        <Field label="Start Date" name="start_date" errors=errors>
+                            <input
+                                type="date"
+                                class="input input-bordered"
+                                id="start_date"
+                                prop:value=move || form.with(|form| date_value(form.start_date))
+                                on:input=move |ev| {
+                                    set_form.update(|form| form.start_date = event_target_value(&ev).parse().ok())
+                                }
+                            />
+                        </Field>
+                        <Field label="End Date" name="end_date" errors=errors>
+                            <input
+                                type="date"
+                                class="input input-bordered"
+                                id="end_date"
+                                prop:value=move || form.with(|form| date_value(form.end_date))
+                                on:input=move |ev| {
+                                    set_form.update(|form| form.end_date = event_target_value(&ev).parse().ok())
+                                }
+                            />
+                        </Field>
+                    </div>
+                    <div class="flex gap-4">
+                        <Field label="Start Day Part" name="start_part" errors=errors>
+                            <DayPartSelect
+                                id="start_part"
+                                value=Signal::derive(move || form.with(|form| form.start_part))
+                                on_change=move |part| set_form.update(|form| form.start_part = part)
+                            />
+                        </Field>
+                        <Show when=several_days>
+                            <Field label="End Day Part" name="end_part" errors=errors>
+                                <DayPartSelect
+                                    id="end_part"
+                                    value=Signal::derive(move || form.with(|form| form.end_part))
+                                    on_change=move |part| set_form.update(|form| form.end_part = part)
+                                />
+                            </Field>
+                        </Show>
+                    </div>
+                    <p class="text-sm opacity-70">
+                        {move || match working_days() {
+                            Some(days) => format!("{} working days", format_days(days)),
+                            None => "pick the start and end dates".to_string(),
+                        }}
+                    </p>
+                    <Field label="Remark" name="remark" errors=errors>
+                        <textarea
+                            class="textarea textarea-bordered"
+                            id="remark"
+                            prop:value=move || form.with(|form| form.remark.clone())
+                            on:input=move |ev| set_form.update(|form| form.remark = event_target_value(&ev))
+                        ></textarea>
+                    </Field>
+                    <Field label="Attachment" name="attachments" errors=errors>
+                        <input
+                            type="file"
+                            class="file-input file-input-bordered"
+                            id="attachment_file"
+                            on:change=move |ev| {
+                                let value = event_target_value(&ev);
+                                set_attachment(Some(value).filter(|value| !value.is_empty()))
+                            }
+                        />
+                    </Field>
+                    {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                    <div class="card-actions justify-end mt-4">
+                        <A href="/admin" class="btn btn-ghost">
+                            "Cancel"
+                        </A>
+                        <button class="btn btn-primary" type="submit" disabled=submit.pending()>
+                            "Submit"
+                        </button>
+                    </div>
+                </form>
+            </div>
+            <LeaveBalances
+                year=Signal::derive(year)
+                selected=Signal::derive(move || Some(form.with(|form| form.leave_type.clone())))
+            />
+        </div>
+    }
+}
+
+/// a choice of the part of a day
+#[component]
+fn DayPartSelect(
+    id: &'static str,
+    #[prop(into)] value: Signal<DayPart>,
+    #[prop(into)] on_change: Callback<DayPart>,
+) -> impl IntoView {
+    view! {
+        <select
+            class="select select-bordered"
+            id=id
+            on:change=move |ev| on_change.call(event_target_value(&ev).parse().unwrap_or_default())
+        >
+            {DayPart::ALL
+                .into_iter()
+                .map(|part| {
+                    view! {
+                        <option value=part.to_string() selected=move || value.get() == part>
+                            {part.label()}
+                        </option>
+                    }
+                })
+                .collect_view()}
+        </select>
+    }
+}
+
+/// `YYYY-MM-DD` of a date input, empty if no date
+fn date_value(date: Option<chrono::NaiveDate>) -> String {
+    date.map(|date| date.to_string()).unwrap_or_default()
+}
+
+/// leave balances of current user in `year`, highlighting the `selected` leave type
+#[component]
+pub fn LeaveBalances(
+    #[prop(into)] year: MaybeSignal<i32>,
+    #[prop(optional, into)] selected: MaybeSignal<Option<String>>,
+) -> impl IntoView {
+    let balances = create_resource(
+        move || year.get(),
+        |year| async move { get_leave_balances(year).await.unwrap_or_default() },
+    );
+
+    view! {
+        <div class="card bg-base-100 shadow">
+            <div class="card-body">
+                <h2 class="card-title">{move || format!("Leave Balance of {}", year.get())}</h2>
+                <table class="table table-sm">
+                    <thead>
+                        <tr>
+                            <th>"Type"</th>
+                            <th>"Accrued"</th>
+                            <th>"Adjusted"</th>
+                            <th>"Used"</th>
+                            <th>"Expired"</th>
+                            <th>"Pending"</th>
+                            <th>"Remaining"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                balances
+                                    .get()
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|balance| {
+                                                let leave_type = balance.leave_type.clone();
+                                                view! {
+                                                    <tr class:font-bold=move || {
+                                                        selected.with(|selected| selected.as_ref() == Some(&leave_type))
+                                                    }>
+                                                        <td>
+                                                            <LeaveTypeBadge name=balance.leave_type.clone()/>
+                                                        </td>
+                                                        <td>{format_days(balance.accrued)}</td>
+                                                        <td>{format_days(balance.adjusted)}</td>
+                                                        <td>{format_days(balance.used)}</td>
+                                                        <td>{format_days(balance.expired)}</td>
+                                                        <td>{format_days(balance.pending)}</td>
+                                                        <td>{format_days(balance.remaining())}</td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
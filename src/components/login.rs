@@ -1,57 +1,190 @@
 use leptos::*;
 use leptos_router::*;
 
+use crate::components::passkey::get_credential;
+use crate::models::{Captcha, PasskeyLoginOptions};
+
 #[server(UserLogin, "/api")]
-pub async fn user_login(username: String, password: String) -> Result<(), ServerFnError<String>> {
-    use crate::models::{User, UserError};
-    use crate::server::{AppDataCipher, LOGIN_COOKIE_NAME};
-    use actix_web::{
-        cookie::{
-            time::Duration,
-            Cookie, SameSite,
-        },
-        http::header,
-        http::header::HeaderValue,
+pub async fn user_login(
+    username: String,
+    password: String,
+    captcha_id: Option<String>,
+    captcha_answer: Option<String>,
+) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::{ADMIN_ROUTE_PREFIX, CHANGE_PASSWORD_ROUTE};
+    use crate::models::User;
+    use crate::server::captcha::{
+        captcha_required, clear_login_failures, record_login_failure, AppDataCaptcha,
     };
-    use base64::prelude::*;
-    use leptos_actix::{extract, redirect, ResponseOptions};
+    use crate::server::session::ClientInfo;
+    use crate::server::{sign_in, AppDataDb};
+    use actix_web::HttpRequest;
+    use leptos_actix::{extract, redirect};
 
-    let cipher: AppDataCipher = extract()
+    let (req, db, captcha): (HttpRequest, AppDataDb, AppDataCaptcha) = extract()
         .await
-        .map_err(|_| ServerFnError::from("extract fail").to_string())?;
-    let mut cipher = cipher.lock().unwrap();
-
-    let user = User::login(username.to_owned(), password.to_owned()).map_err(|e| match e {
-        UserError::NotExist => ServerFnError::from("user not exist".to_string()),
-    })?;
-    let jsoned_user = user.to_json();
-
-    if let Ok(encrypted_user) = cipher.encrypt(&jsoned_user.as_bytes().to_vec()) {
-        let encrypted_user = BASE64_STANDARD.encode(encrypted_user);
-        let response = expect_context::<ResponseOptions>();
-
-        let cookie = Cookie::build(LOGIN_COOKIE_NAME, encrypted_user)
-            .max_age(Duration::WEEK)
-            .secure(true)
-            .http_only(true)
-            .same_site(SameSite::Strict)
-            .path("/")
-            .finish();
-        if let Ok(cookie) = HeaderValue::from_str(&cookie.to_string()) {
-            response.insert_header(header::SET_COOKIE, cookie);
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+    let ip = ClientInfo::from_request(&req).ip_text();
+
+    let required = captcha_required(&db.lock().unwrap(), &ip, &username)
+        .map_err(|_| ServerFnError::from("check login failures fail".to_string()))?;
+    if required {
+        let passed = match (captcha_id, captcha_answer) {
+            (Some(id), Some(answer)) => captcha.lock().unwrap().verify(&id, &answer),
+            _ => false,
+        };
+        if !passed {
+            return Err(ServerFnError::from("incorrect captcha".to_string()));
+        }
+    }
+
+    //  bound first, the lock of the scrutinee would live through the arms
+    let result = User::login(&db.lock().unwrap(), username.to_owned(), password.to_owned());
+    let user = match result {
+        Ok(user) => user,
+        Err(e) => {
+            _ = record_login_failure(&db.lock().unwrap(), &ip, &username);
+            return Err(ServerFnError::from(e.to_string()));
         }
+    };
+    _ = clear_login_failures(&db.lock().unwrap(), &ip, &username);
+
+    let password_expired = User::password_expired(&db.lock().unwrap(), &user.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
 
-        redirect("/admin");
+    sign_in(user, false).await?;
+    redirect(if password_expired {
+        CHANGE_PASSWORD_ROUTE
+    } else {
+        ADMIN_ROUTE_PREFIX
+    });
+
+    return Ok(());
+}
 
-        return Ok(());
+/// a CAPTCHA for the next login, None if not required for `username` and current ip
+#[server(GetCaptcha, "/api")]
+pub async fn get_captcha(username: String) -> Result<Option<Captcha>, ServerFnError<String>> {
+    use crate::server::captcha::{captcha_required, AppDataCaptcha};
+    use crate::server::session::ClientInfo;
+    use crate::server::AppDataDb;
+    use actix_web::HttpRequest;
+    use leptos_actix::extract;
+
+    let (req, db, captcha): (HttpRequest, AppDataDb, AppDataCaptcha) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+    let ip = ClientInfo::from_request(&req).ip_text();
+
+    let required = captcha_required(&db.lock().unwrap(), &ip, &username)
+        .map_err(|_| ServerFnError::from("check login failures fail".to_string()))?;
+    if !required {
+        return Ok(None);
     }
 
-    return Err(ServerFnError::from("user not exist".to_string()));
+    Ok(Some(captcha.lock().unwrap().issue()))
+}
+
+/// start signing in with passkey, returns the options for browser authenticator
+#[server(StartPasskeyLogin, "/api")]
+pub async fn start_passkey_login(
+    username: String,
+) -> Result<PasskeyLoginOptions, ServerFnError<String>> {
+    use crate::server::passkey::{self, AppDataPasskeyState, AppDataWebauthn};
+    use crate::server::AppDataDb;
+    use leptos_actix::extract;
+
+    let (webauthn, state, db): (AppDataWebauthn, AppDataPasskeyState, AppDataDb) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    passkey::start_authentication(
+        &webauthn,
+        &mut state.lock().unwrap(),
+        &db.lock().unwrap(),
+        &username,
+    )
+    .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// verify the credential signed by browser authenticator and log in
+#[server(FinishPasskeyLogin, "/api")]
+pub async fn finish_passkey_login(
+    ceremony_id: String,
+    credential: String,
+) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::{ADMIN_ROUTE_PREFIX, CHANGE_PASSWORD_ROUTE};
+    use crate::models::User;
+    use crate::server::passkey::{self, AppDataPasskeyState, AppDataWebauthn};
+    use crate::server::{sign_in, AppDataDb};
+    use leptos_actix::{extract, redirect};
+
+    let (webauthn, state, db): (AppDataWebauthn, AppDataPasskeyState, AppDataDb) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let credential = serde_json::from_str(&credential)
+        .map_err(|_| ServerFnError::from("invalid passkey credential".to_string()))?;
+    let user = passkey::finish_authentication(
+        &webauthn,
+        &mut state.lock().unwrap(),
+        &db.lock().unwrap(),
+        &ceremony_id,
+        &credential,
+    )
+    .map_err(|e| ServerFnError::from(e.to_string()))?;
+    let password_expired = User::password_expired(&db.lock().unwrap(), &user.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    sign_in(user, true).await?;
+    redirect(if password_expired {
+        CHANGE_PASSWORD_ROUTE
+    } else {
+        ADMIN_ROUTE_PREFIX
+    });
+
+    Ok(())
 }
 
 #[component]
 pub fn Login() -> impl IntoView {
     let login = create_server_action::<UserLogin>();
+    let (username, set_username) = create_signal(String::new());
+    let (passkey_error, set_passkey_error) = create_signal(None::<String>);
+
+    //  asks again after every login attempt, failures may make a CAPTCHA required
+    let captcha = create_resource(
+        move || login.version().get(),
+        move |_| async move {
+            get_captcha(username.get_untracked())
+                .await
+                .ok()
+                .flatten()
+        },
+    );
+
+    let handle_passkey_login = move |_| {
+        let username = username.get_untracked();
+        if username.is_empty() {
+            set_passkey_error(Some("Please input username first".to_string()));
+            return;
+        }
+
+        spawn_local(async move {
+            let result = async {
+                let login = start_passkey_login(username)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let credential = get_credential(login.options).await?;
+                finish_passkey_login(login.ceremony_id, credential)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+
+            set_passkey_error(result.err());
+        });
+    };
 
     view! {
         <main class="flex w-full h-screen">
@@ -82,6 +215,8 @@ pub fn Login() -> impl IntoView {
                                     required
                                     id="username"
                                     name="username"
+                                    on:input=move |ev| set_username(event_target_value(&ev))
+                                    on:change=move |_| captcha.refetch()
                                 />
                             </div>
                             <div class="form-control">
@@ -96,10 +231,70 @@ pub fn Login() -> impl IntoView {
                                     name="password"
                                 />
                             </div>
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    captcha
+                                        .get()
+                                        .flatten()
+                                        .map(|captcha| {
+                                            view! {
+                                                <div class="form-control">
+                                                    <label class="label" for="captcha_answer">
+                                                        <span class="label-text">"Captcha"</span>
+                                                    </label>
+                                                    <div class="flex gap-2 items-center">
+                                                        <img
+                                                            class="h-10 rounded bg-base-200"
+                                                            src=captcha.image
+                                                            alt="Captcha"
+                                                        />
+                                                        <input
+                                                            type="text"
+                                                            inputmode="numeric"
+                                                            autocomplete="off"
+                                                            class="input input-bordered w-full"
+                                                            required
+                                                            id="captcha_answer"
+                                                            name="captcha_answer"
+                                                        />
+                                                    </div>
+                                                    <input
+                                                        type="hidden"
+                                                        name="captcha_id"
+                                                        value=captcha.id
+                                                    />
+                                                </div>
+                                            }
+                                        })
+                                }}
+
+                            </Suspense>
                             <div class="form-control mt-6">
                                 <button class="btn btn-primary">"Login"</button>
                             </div>
+                            {move || {
+                                login
+                                    .value()
+                                    .get()
+                                    .and_then(|result| result.err())
+                                    .map(|e| {
+                                        view! { <p class="text-error text-sm mt-2">{e.to_string()}</p> }
+                                    })
+                            }}
+
                         </ActionForm>
+                        <div class="form-control">
+                            <button class="btn btn-outline" on:click=handle_passkey_login>
+                                "Sign in with passkey"
+                            </button>
+                            {move || {
+                                passkey_error()
+                                    .map(|e| {
+                                        view! { <p class="text-error text-sm mt-2">{e}</p> }
+                                    })
+                            }}
+
+                        </div>
 
                         <div class="card-actions justify-end">
                             <button class="btn btn-link" onclick="forgot_modal.showModal()">
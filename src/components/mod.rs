@@ -2,9 +2,41 @@ mod login;
 mod home;
 mod dashboard;
 mod not_found_404;
+mod change_password;
+mod approval_rules;
+pub mod current_user;
+mod departments;
+mod field;
+mod file;
+mod holidays;
+mod invitation;
+mod leave_calendar;
+mod leave_ledger;
+pub mod leave_types;
+mod leaves;
+mod passkey;
+mod profile;
+mod recycle_bin;
+mod security;
+mod user_csv;
+mod users;
 pub mod icons;
 
 pub use home::Home;
 pub use login::Login;
 pub use dashboard::DashBoard;
-pub use not_found_404::NotFound404;
\ No newline at end of file
+pub use not_found_404::NotFound404;
+pub use change_password::ChangePassword;
+pub use approval_rules::ApprovalChain;
+pub use departments::Departments;
+pub use holidays::Holidays;
+pub use invitation::AcceptInvitation;
+pub use leave_calendar::LeaveCalendar;
+pub use leave_ledger::LeaveLedger;
+pub use leave_types::LeaveTypes;
+pub use leaves::NewLeave;
+pub use profile::Profile;
+pub use recycle_bin::RecycleBin;
+pub use security::SessionEvents;
+pub use user_csv::UserImport;
+pub use users::{UserEdit, Users};
@@ -0,0 +1,53 @@
+//! Passkey
+//! browser side of WebAuthn, calls `navigator.credentials` through `/js/passkey.js`
+//!
+//! options and credentials are passed as JSON strings in both directions,
+//! the same shape webauthn-rs serializes and deserializes on server
+
+#[cfg(not(feature = "ssr"))]
+mod bindings {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(catch, js_name = passkeyCreate)]
+        pub async fn passkey_create(options: String) -> Result<JsValue, JsValue>;
+
+        #[wasm_bindgen(catch, js_name = passkeyGet)]
+        pub async fn passkey_get(options: String) -> Result<JsValue, JsValue>;
+    }
+}
+
+/// create a new credential by the authenticator of browser
+pub async fn create_credential(options: String) -> Result<String, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        bindings::passkey_create(options)
+            .await
+            .map(|credential| credential.as_string().unwrap_or_default())
+            .map_err(|_| "create passkey canceled".to_string())
+    }
+
+    #[cfg(feature = "ssr")]
+    {
+        _ = options;
+        Err("passkey is only available in browser".to_string())
+    }
+}
+
+/// sign the challenge by one of existing credentials
+pub async fn get_credential(options: String) -> Result<String, String> {
+    #[cfg(not(feature = "ssr"))]
+    {
+        bindings::passkey_get(options)
+            .await
+            .map(|credential| credential.as_string().unwrap_or_default())
+            .map_err(|_| "sign in with passkey canceled".to_string())
+    }
+
+    #[cfg(feature = "ssr")]
+    {
+        _ = options;
+        Err("passkey is only available in browser".to_string())
+    }
+}
@@ -0,0 +1,416 @@
+use leptos::server_fn::codec::Json;
+use leptos::*;
+
+use crate::components::current_user::use_refresh_current_user;
+use crate::components::field::Field;
+use crate::components::file::read_file;
+use crate::components::passkey::create_credential;
+use crate::models::consts::{LANGUAGES, TIMEZONES};
+use crate::models::{avatar_url, FieldErrors, PasskeyInfo, ProfileForm, UserProfile};
+
+#[server]
+pub async fn get_profile() -> Result<UserProfile, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    UserProfile::get(&conn, &token.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// update the profile of current user, returns the errors of invalid fields, empty if saved
+#[server(name = SaveProfile, prefix = "/api", input = Json)]
+async fn save_profile(form: ProfileForm) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let errors = form
+        .validate(&conn, &token.id)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+    if errors.is_empty() {
+        form.save(&conn, &token.id)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+    }
+
+    Ok(errors)
+}
+
+/// replace the avatar of current user by `data`, an image as data url,
+/// returns the new avatar url
+#[server(name = UploadAvatar, prefix = "/api", input = Json)]
+async fn upload_avatar(data: String) -> Result<String, ServerFnError<String>> {
+    use crate::server::avatar::{set_avatar, write_avatar};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    //  decoding and resizing take a while, the database is locked only to set the avatar
+    let avatar = write_avatar(&token.id, &data).map_err(|e| ServerFnError::from(e.to_string()))?;
+    let conn = db.lock().unwrap();
+    set_avatar(&conn, &token.id, &avatar).map_err(|e| ServerFnError::from(e.to_string()))?;
+    Ok(avatar)
+}
+
+#[server]
+async fn get_passkeys() -> Result<Vec<PasskeyInfo>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    PasskeyInfo::list(&conn, &token.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// start registering a passkey of current user, returns the options for browser authenticator
+#[server]
+async fn start_passkey_registration() -> Result<String, ServerFnError<String>> {
+    use crate::server::passkey::{self, AppDataPasskeyState, AppDataWebauthn};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (webauthn, state, db): (AppDataWebauthn, AppDataPasskeyState, AppDataDb) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let options = passkey::start_registration(
+        &webauthn,
+        &mut state.lock().unwrap(),
+        &db.lock().unwrap(),
+        &token.to_user(),
+    )
+    .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    serde_json::to_string(&options)
+        .map_err(|_| ServerFnError::from("serialize passkey options fail".to_string()))
+}
+
+#[server]
+async fn finish_passkey_registration(
+    name: String,
+    credential: String,
+) -> Result<(), ServerFnError<String>> {
+    use crate::server::passkey::{self, AppDataPasskeyState, AppDataWebauthn};
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let (webauthn, state, db): (AppDataWebauthn, AppDataPasskeyState, AppDataDb) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let credential = serde_json::from_str(&credential)
+        .map_err(|_| ServerFnError::from("invalid passkey credential".to_string()))?;
+    let name = if name.trim().is_empty() {
+        "Passkey".to_string()
+    } else {
+        name.trim().to_string()
+    };
+
+    passkey::finish_registration(
+        &webauthn,
+        &mut state.lock().unwrap(),
+        &db.lock().unwrap(),
+        &token.to_user(),
+        &name,
+        &credential,
+    )
+    .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn delete_passkey(id: i64) -> Result<(), ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    PasskeyInfo::delete(&conn, &token.id, id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[component]
+pub fn Profile() -> impl IntoView {
+    view! {
+        <div class="h-full w-full p-4 space-y-4">
+            <ProfileCard/>
+            <Passkeys/>
+        </div>
+    }
+}
+
+#[component]
+fn ProfileCard() -> impl IntoView {
+    let (form, set_form) = create_signal(ProfileForm::default());
+    let (avatar, set_avatar) = create_signal(String::new());
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (message, set_message) = create_signal(None::<Result<String, String>>);
+    let refresh_current_user = use_refresh_current_user();
+
+    let profile = create_resource(|| {}, |_| async move { get_profile().await });
+    create_effect(move |_| match profile.get() {
+        Some(Ok(profile)) => {
+            set_avatar(profile.avatar.clone());
+            set_form(profile.into());
+        }
+        Some(Err(e)) => set_message(Some(Err(e.to_string()))),
+        None => {}
+    });
+
+    let handle_save = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let form = form.get_untracked();
+        spawn_local(async move {
+            match save_profile(form).await {
+                Ok(errors) => {
+                    if errors.is_empty() {
+                        set_message(Some(Ok("Profile saved".to_string())));
+                        refresh_current_user.call(());
+                    } else {
+                        set_message(None);
+                    }
+                    set_errors(errors);
+                }
+                Err(e) => set_message(Some(Err(e.to_string()))),
+            }
+        });
+    };
+
+    let handle_upload = move |_| {
+        spawn_local(async move {
+            let result = async {
+                let data = read_file("avatar_file").await?;
+                upload_avatar(data).await.map_err(|e| e.to_string())
+            }
+            .await;
+
+            match result {
+                Ok(url) => {
+                    set_avatar(url);
+                    set_message(Some(Ok("Avatar uploaded".to_string())));
+                    refresh_current_user.call(());
+                }
+                Err(e) => set_message(Some(Err(e))),
+            }
+        });
+    };
+
+    view! {
+        <div class="card bg-base-100 shadow">
+            <form class="card-body" on:submit=handle_save>
+                <h2 class="card-title">"Profile"</h2>
+                <div class="flex items-center gap-4">
+                    <div class="avatar">
+                        <div class="w-24 rounded-full">
+                            <img src=move || avatar.with(|avatar| avatar_url(avatar, 256)) alt="Avatar"/>
+                        </div>
+                    </div>
+                    <input
+                        type="file"
+                        accept="image/png,image/jpeg,image/gif,image/webp"
+                        class="file-input file-input-bordered file-input-sm"
+                        id="avatar_file"
+                    />
+                    <button type="button" class="btn btn-sm" on:click=handle_upload>
+                        "Upload avatar"
+                    </button>
+                </div>
+                <Field label="Display Name" name="display_name" errors=errors>
+                    <input
+                        type="text"
+                        class="input input-bordered"
+                        id="display_name"
+                        prop:value=move || form.with(|form| form.display_name.clone())
+                        on:input=move |ev| {
+                            set_form.update(|form| form.display_name = event_target_value(&ev))
+                        }
+                    />
+                </Field>
+                <Field label="Email" name="email" errors=errors>
+                    <input
+                        type="email"
+                        class="input input-bordered"
+                        id="email"
+                        prop:value=move || form.with(|form| form.email.clone())
+                        on:input=move |ev| set_form.update(|form| form.email = event_target_value(&ev))
+                    />
+                </Field>
+                <Field label="Timezone" name="timezone" errors=errors>
+                    <select
+                        class="select select-bordered"
+                        id="timezone"
+                        on:change=move |ev| set_form.update(|form| form.timezone = event_target_value(&ev))
+                    >
+                        {TIMEZONES
+                            .iter()
+                            .map(|timezone| {
+                                view! {
+                                    <option
+                                        value=*timezone
+                                        selected=move || form.with(|form| form.timezone == *timezone)
+                                    >
+                                        {*timezone}
+                                    </option>
+                                }
+                            })
+                            .collect_view()}
+                    </select>
+                </Field>
+                <Field label="Language" name="language" errors=errors>
+                    <select
+                        class="select select-bordered"
+                        id="language"
+                        on:change=move |ev| set_form.update(|form| form.language = event_target_value(&ev))
+                    >
+                        {LANGUAGES
+                            .iter()
+                            .map(|(code, name)| {
+                                view! {
+                                    <option
+                                        value=*code
+                                        selected=move || form.with(|form| form.language == *code)
+                                    >
+                                        {*name}
+                                    </option>
+                                }
+                            })
+                            .collect_view()}
+                    </select>
+                </Field>
+                {move || {
+                    message()
+                        .map(|message| match message {
+                            Ok(message) => view! { <p class="text-success text-sm">{message}</p> },
+                            Err(e) => view! { <p class="text-error text-sm">{e}</p> },
+                        })
+                }}
+
+                <div class="card-actions justify-end">
+                    <button class="btn btn-primary">"Save"</button>
+                </div>
+            </form>
+        </div>
+    }
+}
+
+#[component]
+fn Passkeys() -> impl IntoView {
+    let passkeys = create_resource(|| {}, |_| async move { get_passkeys().await.unwrap_or_default() });
+    let (name, set_name) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let handle_add = move |_| {
+        let name = name.get_untracked();
+        spawn_local(async move {
+            let result = async {
+                let options = start_passkey_registration()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let credential = create_credential(options).await?;
+                finish_passkey_registration(name, credential)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+
+            if result.is_ok() {
+                set_name(String::new());
+                passkeys.refetch();
+            }
+            set_error(result.err());
+        });
+    };
+
+    let handle_delete = move |id: i64| {
+        spawn_local(async move {
+            if let Err(e) = delete_passkey(id).await {
+                set_error(Some(e.to_string()));
+            }
+            passkeys.refetch();
+        });
+    };
+
+    view! {
+        <div class="card bg-base-100 shadow">
+            <div class="card-body">
+                <h2 class="card-title">"Passkeys"</h2>
+                <p class="text-sm opacity-70">
+                    "Sign in with your fingerprint, face or security key instead of password"
+                </p>
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Name"</th>
+                            <th>"Created At"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                passkeys
+                                    .get()
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|passkey| {
+                                                let id = passkey.id;
+                                                view! {
+                                                    <tr>
+                                                        <td>{passkey.name}</td>
+                                                        <td>{passkey.created_at}</td>
+                                                        <td>
+                                                            <button
+                                                                class="btn btn-ghost btn-xs"
+                                                                on:click=move |_| handle_delete(id)
+                                                            >
+                                                                "remove"
+                                                            </button>
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+                <div class="card-actions items-center">
+                    <input
+                        type="text"
+                        class="input input-bordered input-sm"
+                        placeholder="Passkey name"
+                        prop:value=name
+                        on:input=move |ev| set_name(event_target_value(&ev))
+                    />
+                    <button class="btn btn-primary btn-sm" on:click=handle_add>
+                        "Add passkey"
+                    </button>
+                </div>
+                {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+            </div>
+        </div>
+    }
+}
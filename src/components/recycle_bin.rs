@@ -0,0 +1,144 @@
+use leptos::*;
+
+use crate::models::{DeletedRecord, RecordKind};
+
+#[server]
+async fn get_deleted_records() -> Result<Vec<DeletedRecord>, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    DeletedRecord::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn restore_record(kind: RecordKind, id: String) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    DeletedRecord::restore(&conn, kind, &id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn purge_record(kind: RecordKind, id: String) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::leave_attachment::AppDataAttachmentStorage;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let (db, attachments): (AppDataDb, AppDataAttachmentStorage) = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    DeletedRecord::purge(&conn, &**attachments, kind, &id)
+        .map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// deleted users and departments, which can be restored or purged for ever
+#[component]
+pub fn RecycleBin() -> impl IntoView {
+    let records = create_resource(
+        || {},
+        |_| async move { get_deleted_records().await.unwrap_or_default() },
+    );
+    let (error, set_error) = create_signal(None::<String>);
+
+    let handle_restore = move |kind: RecordKind, id: String| {
+        spawn_local(async move {
+            set_error(restore_record(kind, id).await.err().map(|e| e.to_string()));
+            records.refetch();
+        });
+    };
+    let handle_purge = move |kind: RecordKind, id: String| {
+        let confirmed = window()
+            .confirm_with_message("Purge the record for ever? It cannot be restored then.")
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        spawn_local(async move {
+            set_error(purge_record(kind, id).await.err().map(|e| e.to_string()));
+            records.refetch();
+        });
+    };
+
+    view! {
+        <div class="h-full w-full p-4">
+            <h2 class="text-lg font-bold mb-4">"Recycle Bin"</h2>
+            {move || error().map(|e| view! { <p class="text-error text-sm mb-2">{e}</p> })}
+            <div class="overflow-x-auto bg-base-100 rounded-lg shadow">
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Kind"</th>
+                            <th>"Name"</th>
+                            <th>"Deleted At"</th>
+                            <th>"Deleted By"</th>
+                            <th>"Purged At"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                records
+                                    .get()
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|record| {
+                                                let kind = record.kind;
+                                                let (restore_id, purge_id) = (record.id.clone(), record.id.clone());
+                                                view! {
+                                                    <tr>
+                                                        <td>
+                                                            <span class="badge badge-ghost badge-sm">
+                                                                {kind.to_string()}
+                                                            </span>
+                                                        </td>
+                                                        <td>{record.name}</td>
+                                                        <td>{record.deleted_at}</td>
+                                                        <td>{record.deleted_by}</td>
+                                                        <td>{record.purge_at.unwrap_or("never".to_string())}</td>
+                                                        <td class="flex gap-2">
+                                                            <button
+                                                                class="btn btn-ghost btn-xs"
+                                                                on:click=move |_| handle_restore(kind, restore_id.clone())
+                                                            >
+                                                                "Restore"
+                                                            </button>
+                                                            <button
+                                                                class="btn btn-ghost btn-xs text-error"
+                                                                on:click=move |_| handle_purge(kind, purge_id.clone())
+                                                            >
+                                                                "Purge"
+                                                            </button>
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,72 @@
+use leptos::*;
+
+use crate::models::SessionEvent;
+
+#[server]
+async fn get_session_events() -> Result<Vec<SessionEvent>, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    SessionEvent::latest(&conn, 200).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[component]
+pub fn SessionEvents() -> impl IntoView {
+    let events = create_resource(
+        || {},
+        |_| async move { get_session_events().await.unwrap_or_default() },
+    );
+
+    view! {
+        <div class="h-full w-full p-4">
+            <div class="overflow-x-auto bg-base-100 rounded-lg shadow">
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Time"</th>
+                            <th>"User"</th>
+                            <th>"Event"</th>
+                            <th>"Detail"</th>
+                            <th>"IP"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                events
+                                    .get()
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|event| {
+                                                view! {
+                                                    <tr key=event.id>
+                                                        <td>{event.created_at}</td>
+                                                        <td>{event.username}</td>
+                                                        <td>
+                                                            <span class="badge badge-warning">
+                                                                {event.kind.to_string()}
+                                                            </span>
+                                                        </td>
+                                                        <td>{event.detail}</td>
+                                                        <td>{event.ip}</td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
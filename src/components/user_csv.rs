@@ -0,0 +1,330 @@
+use leptos::server_fn::codec::{GetUrl, Json, StreamingText, TextStream};
+use leptos::*;
+use leptos_router::*;
+
+use crate::components::file::read_file;
+use crate::models::{ImportMapping, ImportReport, USER_CSV_FIELDS};
+
+/// all of users as CSV, streamed in chunks, can be downloaded by a link
+#[server(
+    name = ExportUsers,
+    prefix = "/api",
+    endpoint = "users.csv",
+    input = GetUrl,
+    output = StreamingText
+)]
+pub async fn export_users() -> Result<TextStream<String>, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::models::UserDetail;
+    use crate::server::user_csv::{export_header, export_rows};
+    use crate::server::{require_role, AppDataDb};
+    use futures::StreamExt;
+    use leptos_actix::extract;
+
+    /// users read from database at once
+    const CHUNK_SIZE: usize = 500;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    //  the username of the last exported user, None after all exported
+    let chunks = futures::stream::unfold(Some(String::new()), move |after| {
+        let db = db.clone();
+        async move {
+            let after = after?;
+            let users = UserDetail::list_after(&db.lock().unwrap(), &after, CHUNK_SIZE);
+            match users {
+                Ok(users) if users.is_empty() => None,
+                Ok(users) => {
+                    let next = users.last().map(|user| user.username.clone());
+                    let rows = export_rows(&users).map_err(|e| ServerFnError::from(e.to_string()));
+                    Some((rows, next))
+                }
+                Err(e) => Some((Err(ServerFnError::from(e.to_string())), None)),
+            }
+        }
+    });
+
+    Ok(TextStream::new(
+        futures::stream::once(async { Ok(export_header()) }).chain(chunks),
+    ))
+}
+
+/// columns in the header line of the CSV file `data`, which is a data url
+#[server(name = ReadImportHeaders, prefix = "/api", input = Json)]
+async fn read_import_headers(data: String) -> Result<Vec<String>, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::require_role;
+    use crate::server::user_csv::read_headers;
+
+    require_role(ADMIN_ROLE).await?;
+    read_headers(&data).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// validate every row of the CSV file `data`, and create the users of valid rows if `commit`
+#[server(name = ImportUsers, prefix = "/api", input = Json)]
+async fn import_users(
+    data: String,
+    mapping: ImportMapping,
+    commit: bool,
+) -> Result<ImportReport, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::user_csv::import;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let mut conn = db.lock().unwrap();
+    import(&mut conn, &data, &mapping, commit).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Upload,
+    Mapping,
+    Preview,
+    Done,
+}
+
+/// wizard of importing users from a CSV file: upload, map columns, preview by a dry run, commit
+#[component]
+pub fn UserImport() -> impl IntoView {
+    let (step, set_step) = create_signal(Step::Upload);
+    let (data, set_data) = create_signal(String::new());
+    let (headers, set_headers) = create_signal(Vec::<String>::new());
+    let (mapping, set_mapping) = create_signal(ImportMapping::new());
+    let (report, set_report) = create_signal(ImportReport::default());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let handle_upload = move |_| {
+        spawn_local(async move {
+            let result = async {
+                let data = read_file("csv_file").await?;
+                let headers = read_import_headers(data.clone())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>((data, headers))
+            }
+            .await;
+
+            match result {
+                Ok((data, headers)) => {
+                    //  columns named like fields are mapped at once
+                    let mapping = USER_CSV_FIELDS
+                        .iter()
+                        .filter_map(|field| {
+                            headers
+                                .iter()
+                                .position(|header| header.eq_ignore_ascii_case(field))
+                                .map(|column| (field.to_string(), column))
+                        })
+                        .collect();
+                    set_data(data);
+                    set_headers(headers);
+                    set_mapping(mapping);
+                    set_error(None);
+                    set_step(Step::Mapping);
+                }
+                Err(e) => set_error(Some(e)),
+            }
+        });
+    };
+
+    let run_import = move |commit: bool| {
+        let (data, mapping) = (data.get_untracked(), mapping.get_untracked());
+        spawn_local(async move {
+            match import_users(data, mapping, commit).await {
+                Ok(report) => {
+                    set_report(report);
+                    set_error(None);
+                    set_step(if commit { Step::Done } else { Step::Preview });
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let invalid_count = move || {
+        report.with(|report| report.rows.iter().filter(|row| !row.errors.is_empty()).count())
+    };
+
+    view! {
+        <div class="h-full w-full p-4">
+            <div class="card bg-base-100 shadow">
+                <div class="card-body">
+                    <h2 class="card-title">"Import Users"</h2>
+                    <ul class="steps mb-4">
+                        <li class="step step-primary">"Upload"</li>
+                        <li class="step" class:step-primary=move || step() != Step::Upload>
+                            "Map Columns"
+                        </li>
+                        <li
+                            class="step"
+                            class:step-primary=move || matches!(step(), Step::Preview | Step::Done)
+                        >
+                            "Preview"
+                        </li>
+                        <li class="step" class:step-primary=move || step() == Step::Done>
+                            "Done"
+                        </li>
+                    </ul>
+                    {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                    <Show when=move || step() == Step::Upload>
+                        <p class="text-sm opacity-70">
+                            "A CSV file with a header line, roles are separated by ';', "
+                            "users without password must change the random one at first login"
+                        </p>
+                        <div class="flex gap-2 items-center">
+                            <input
+                                type="file"
+                                accept=".csv,text/csv"
+                                class="file-input file-input-bordered file-input-sm"
+                                id="csv_file"
+                            />
+                            <button class="btn btn-primary btn-sm" on:click=handle_upload>
+                                "Upload"
+                            </button>
+                        </div>
+                    </Show>
+                    <Show when=move || step() == Step::Mapping>
+                        <div class="grid grid-cols-2 gap-2 max-w-xl">
+                            {USER_CSV_FIELDS
+                                .into_iter()
+                                .map(|field| {
+                                    view! {
+                                        <label class="label" for=field>
+                                            <span class="label-text">{field}</span>
+                                        </label>
+                                        <select
+                                            class="select select-bordered select-sm"
+                                            id=field
+                                            on:change=move |ev| {
+                                                let column = event_target_value(&ev).parse::<usize>().ok();
+                                                set_mapping
+                                                    .update(|mapping| {
+                                                        match column {
+                                                            Some(column) => mapping.insert(field.to_string(), column),
+                                                            None => mapping.remove(field),
+                                                        };
+                                                    })
+                                            }
+                                        >
+                                            <option value="">"(not imported)"</option>
+                                            {move || {
+                                                headers()
+                                                    .into_iter()
+                                                    .enumerate()
+                                                    .map(|(column, header)| {
+                                                        view! {
+                                                            <option
+                                                                value=column
+                                                                selected=move || {
+                                                                    mapping.with(|mapping| mapping.get(field) == Some(&column))
+                                                                }
+                                                            >
+                                                                {header}
+                                                            </option>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            }}
+
+                                        </select>
+                                    }
+                                })
+                                .collect_view()}
+                        </div>
+                        <div class="card-actions justify-end">
+                            <button class="btn btn-primary btn-sm" on:click=move |_| run_import(false)>
+                                "Preview"
+                            </button>
+                        </div>
+                    </Show>
+                    <Show when=move || matches!(step(), Step::Preview | Step::Done)>
+                        <p>
+                            {move || {
+                                if step() == Step::Done {
+                                    format!("{} users created", report.with(|report| report.created))
+                                } else {
+                                    format!(
+                                        "{} rows, {} invalid rows will be skipped",
+                                        report.with(|report| report.rows.len()),
+                                        invalid_count(),
+                                    )
+                                }
+                            }}
+
+                        </p>
+                        <div class="overflow-x-auto max-h-96">
+                            <table class="table table-sm">
+                                <thead>
+                                    <tr>
+                                        <th>"Line"</th>
+                                        <th>"Username"</th>
+                                        <th>"Email"</th>
+                                        <th>"Display Name"</th>
+                                        <th>"Errors"</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {move || {
+                                        report()
+                                            .rows
+                                            .into_iter()
+                                            .map(|row| {
+                                                let valid = row.errors.is_empty();
+                                                view! {
+                                                    <tr class:text-error=!valid>
+                                                        <td>{row.line}</td>
+                                                        <td>{row.username}</td>
+                                                        <td>{row.email}</td>
+                                                        <td>{row.display_name}</td>
+                                                        <td>
+                                                            {row
+                                                                .errors
+                                                                .into_iter()
+                                                                .map(|(field, e)| format!("{} {}", field, e))
+                                                                .collect::<Vec<_>>()
+                                                                .join(", ")}
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    }}
+
+                                </tbody>
+                            </table>
+                        </div>
+                        <div class="card-actions justify-end">
+                            <Show
+                                when=move || step() == Step::Preview
+                                fallback=move || {
+                                    view! {
+                                        <A href="/admin/users" class="btn btn-primary btn-sm">
+                                            "Back to users"
+                                        </A>
+                                    }
+                                }
+                            >
+
+                                <button class="btn btn-ghost btn-sm" on:click=move |_| set_step(Step::Mapping)>
+                                    "Back"
+                                </button>
+                                <button class="btn btn-primary btn-sm" on:click=move |_| run_import(true)>
+                                    "Import"
+                                </button>
+                            </Show>
+                        </div>
+                    </Show>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,569 @@
+use leptos::server_fn::codec::Json;
+use leptos::*;
+use leptos_router::*;
+
+use crate::components::departments::get_departments;
+use crate::components::field::Field;
+use crate::components::invitation::{invite_user, resend_invitation};
+use crate::models::consts::{DEFAULT_AVATAR, EMPLOYEE_ROLE};
+use crate::models::{avatar_url, FieldErrors, Role, UserDetail, UserForm, UserStatus};
+
+#[server]
+pub async fn get_users() -> Result<Vec<UserDetail>, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    UserDetail::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+async fn get_user(id: String) -> Result<UserDetail, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    UserDetail::get(&conn, &id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[server]
+pub async fn get_roles() -> Result<Vec<Role>, ServerFnError<String>> {
+    use crate::server::{current_token, AppDataDb};
+    use leptos_actix::extract;
+
+    current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    Role::list(&conn).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// create or update a user, returns the errors of invalid fields, empty if saved
+#[server(name = SaveUser, prefix = "/api", input = Json)]
+async fn save_user(form: UserForm) -> Result<FieldErrors, ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    let mut errors = form
+        .validate(&conn)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    //  administrators cannot lock themselves out
+    if form.id.as_deref() == Some(token.id.as_str()) {
+        if form.status != UserStatus::Active {
+            errors.insert("status".into(), "cannot be changed for yourself".into());
+        }
+        if !form.roles.iter().any(|role| role == ADMIN_ROLE) {
+            errors.insert("roles".into(), "must keep admin for yourself".into());
+        }
+    }
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    form.save(&conn)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    Ok(errors)
+}
+
+#[server]
+async fn set_user_status(id: String, status: UserStatus) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::models::User;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    if token.id == id {
+        return Err(ServerFnError::from("cannot disable yourself".to_string()));
+    }
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    User::set_status(&conn, &id, status).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+/// move a user into the recycle bin
+#[server]
+async fn delete_user(id: String) -> Result<(), ServerFnError<String>> {
+    use crate::models::consts::ADMIN_ROLE;
+    use crate::models::User;
+    use crate::server::{require_role, AppDataDb};
+    use leptos_actix::extract;
+
+    let token = require_role(ADMIN_ROLE).await?;
+    if token.id == id {
+        return Err(ServerFnError::from("cannot delete yourself".to_string()));
+    }
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    User::delete(&conn, &id, &token.id).map_err(|e| ServerFnError::from(e.to_string()))
+}
+
+#[component]
+pub fn Users() -> impl IntoView {
+    let users = create_resource(
+        || {},
+        |_| async move { get_users().await.unwrap_or_default() },
+    );
+    let (error, set_error) = create_signal(None::<String>);
+
+    let handle_status = move |id: String, status: UserStatus| {
+        spawn_local(async move {
+            set_error(set_user_status(id, status).await.err().map(|e| e.to_string()));
+            users.refetch();
+        });
+    };
+    let handle_delete = move |id: String| {
+        spawn_local(async move {
+            set_error(delete_user(id).await.err().map(|e| e.to_string()));
+            users.refetch();
+        });
+    };
+    let handle_resend = move |id: String| {
+        spawn_local(async move {
+            set_error(resend_invitation(id).await.err().map(|e| e.to_string()));
+        });
+    };
+
+    view! {
+        <div class="h-full w-full p-4">
+            <div class="flex justify-between items-center mb-4">
+                <h2 class="text-lg font-bold">"Users"</h2>
+                <div class="flex gap-2">
+                    <a href="/api/users.csv" download="users.csv" rel="external" class="btn btn-sm">
+                        "Export"
+                    </a>
+                    <A href="/admin/users/import" class="btn btn-sm">
+                        "Import"
+                    </A>
+                    <A href="/admin/users/invite" class="btn btn-sm">
+                        "Invite"
+                    </A>
+                    <A href="/admin/users/new" class="btn btn-primary btn-sm">
+                        "New User"
+                    </A>
+                </div>
+            </div>
+            {move || error().map(|e| view! { <p class="text-error text-sm mb-2">{e}</p> })}
+            <div class="overflow-x-auto bg-base-100 rounded-lg shadow">
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"User"</th>
+                            <th>"Email"</th>
+                            <th>"Department"</th>
+                            <th>"Roles"</th>
+                            <th>"Status"</th>
+                            <th>"Password Expires At"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Suspense fallback=move || view! {}>
+                            {move || {
+                                users
+                                    .get()
+                                    .map(|list| {
+                                        list.into_iter()
+                                            .map(|user| {
+                                                let id = user.id.clone();
+                                                let delete_id = user.id.clone();
+                                                let status = user.status;
+                                                let expires_at = if user.must_change_password {
+                                                    "at next login".to_string()
+                                                } else {
+                                                    user.password_expires_at
+                                                        .unwrap_or("never".to_string())
+                                                };
+                                                view! {
+                                                    <tr>
+                                                        <td>
+                                                            <div class="flex items-center gap-3">
+                                                                <div class="avatar">
+                                                                    <div class="w-10 rounded-full">
+                                                                        <img src=avatar_url(&user.avatar, 64) alt="Avatar"/>
+                                                                    </div>
+                                                                </div>
+                                                                <div>
+                                                                    <div class="font-bold">{user.display_name}</div>
+                                                                    <div class="text-sm opacity-50">
+                                                                        {user.username}
+                                                                    </div>
+                                                                </div>
+                                                            </div>
+                                                        </td>
+                                                        <td>{user.email}</td>
+                                                        <td>{user.department}</td>
+                                                        <td>
+                                                            {user
+                                                                .roles
+                                                                .into_iter()
+                                                                .map(|role| {
+                                                                    view! {
+                                                                        <span class="badge badge-ghost badge-sm mr-1">
+                                                                            {role}
+                                                                        </span>
+                                                                    }
+                                                                })
+                                                                .collect_view()}
+                                                        </td>
+                                                        <td>
+                                                            <span class=match status {
+                                                                UserStatus::Active => "badge badge-success badge-sm",
+                                                                UserStatus::Pending => "badge badge-warning badge-sm",
+                                                                UserStatus::Disabled => "badge badge-error badge-sm",
+                                                            }>{status.to_string()}</span>
+                                                        </td>
+                                                        <td>{expires_at}</td>
+                                                        <td class="flex gap-2">
+                                                            <A
+                                                                href=format!("/admin/users/{}", user.id)
+                                                                class="btn btn-ghost btn-xs"
+                                                            >
+                                                                "Edit"
+                                                            </A>
+                                                            {match status {
+                                                                UserStatus::Pending => {
+                                                                    view! {
+                                                                        <button
+                                                                            class="btn btn-ghost btn-xs"
+                                                                            on:click=move |_| handle_resend(id.clone())
+                                                                        >
+                                                                            "Resend invitation"
+                                                                        </button>
+                                                                    }
+                                                                }
+                                                                _ => {
+                                                                    let active = status == UserStatus::Active;
+                                                                    view! {
+                                                                        <button
+                                                                            class="btn btn-ghost btn-xs"
+                                                                            on:click=move |_| {
+                                                                                handle_status(
+                                                                                    id.clone(),
+                                                                                    if active {
+                                                                                        UserStatus::Disabled
+                                                                                    } else {
+                                                                                        UserStatus::Active
+                                                                                    },
+                                                                                )
+                                                                            }
+                                                                        >
+
+                                                                            {if active { "Disable" } else { "Enable" }}
+                                                                        </button>
+                                                                    }
+                                                                }
+                                                            }}
+                                                            <button
+                                                                class="btn btn-ghost btn-xs text-error"
+                                                                on:click=move |_| handle_delete(delete_id.clone())
+                                                            >
+                                                                "Delete"
+                                                            </button>
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    })
+                            }}
+
+                        </Suspense>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+/// page of creating a user at `/admin/users/new`, or editing a user at `/admin/users/:id`,
+/// or inviting a user at `/admin/users/invite` if `invite`, who sets the password later
+#[component]
+pub fn UserEdit(#[prop(optional)] invite: bool) -> impl IntoView {
+    let params = use_params_map();
+    let id = move || params.with(|params| params.get("id").cloned());
+
+    let (form, set_form) = create_signal(UserForm::default());
+    let (errors, set_errors) = create_signal(FieldErrors::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    let user = create_resource(id, |id| async move {
+        match id {
+            Some(id) => get_user(id).await.map(UserForm::from),
+            None => Ok(UserForm {
+                roles: vec![EMPLOYEE_ROLE.to_string()],
+                avatar: DEFAULT_AVATAR.to_string(),
+                must_change_password: true,
+                ..Default::default()
+            }),
+        }
+    });
+    let roles = create_resource(
+        || {},
+        |_| async move { get_roles().await.unwrap_or_default() },
+    );
+    let departments = create_resource(
+        || {},
+        |_| async move { get_departments().await.unwrap_or_default() },
+    );
+    create_effect(move |_| match user.get() {
+        Some(Ok(user)) => set_form(user),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    let save = create_action(move |form: &UserForm| {
+        let form = form.clone();
+        async move {
+            if invite {
+                invite_user(form).await
+            } else {
+                save_user(form).await
+            }
+        }
+    });
+    create_effect(move |_| match save.value().get() {
+        Some(Ok(errors)) if errors.is_empty() => use_navigate()("/admin/users", Default::default()),
+        Some(Ok(errors)) => set_errors(errors),
+        Some(Err(e)) => set_error(Some(e.to_string())),
+        None => {}
+    });
+
+    let handle_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        set_error(None);
+        save.dispatch(form.get_untracked());
+    };
+
+    view! {
+        <div class="h-full w-full p-4">
+            <div class="card bg-base-100 shadow max-w-2xl">
+                <form class="card-body" on:submit=handle_submit>
+                    <h2 class="card-title">
+                        {move || {
+                            if invite {
+                                "Invite User"
+                            } else if id().is_some() {
+                                "Edit User"
+                            } else {
+                                "New User"
+                            }
+                        }}
+                    </h2>
+                    <Field label="Username" name="username" errors=errors>
+                        <input
+                            type="text"
+                            class="input input-bordered"
+                            id="username"
+                            prop:value=move || form.with(|form| form.username.clone())
+                            on:input=move |ev| set_form.update(|form| form.username = event_target_value(&ev))
+                        />
+                    </Field>
+                    <Field label="Display Name" name="display_name" errors=errors>
+                        <input
+                            type="text"
+                            class="input input-bordered"
+                            id="display_name"
+                            prop:value=move || form.with(|form| form.display_name.clone())
+                            on:input=move |ev| {
+                                set_form.update(|form| form.display_name = event_target_value(&ev))
+                            }
+                        />
+                    </Field>
+                    <Field label="Email" name="email" errors=errors>
+                        <input
+                            type="email"
+                            class="input input-bordered"
+                            id="email"
+                            prop:value=move || form.with(|form| form.email.clone())
+                            on:input=move |ev| set_form.update(|form| form.email = event_target_value(&ev))
+                        />
+                    </Field>
+                    <Field label="Department" name="department_id" errors=errors>
+                        <select
+                            class="select select-bordered"
+                            id="department_id"
+                            on:change=move |ev| {
+                                let value = event_target_value(&ev);
+                                set_form
+                                    .update(|form| {
+                                        form.department_id = Some(value).filter(|id| !id.is_empty())
+                                    })
+                            }
+                        >
+                            <option value="">"None"</option>
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    departments
+                                        .get()
+                                        .map(|list| {
+                                            list.into_iter()
+                                                .map(|department| {
+                                                    let id = department.id.clone();
+                                                    view! {
+                                                        <option
+                                                            value=department.id
+                                                            selected=move || {
+                                                                form.with(|form| form.department_id.as_ref() == Some(&id))
+                                                            }
+                                                        >
+                                                            {department.name}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()
+                                        })
+                                }}
+
+                            </Suspense>
+                        </select>
+                    </Field>
+                    <Field label="Avatar" name="avatar" errors=errors>
+                        <input
+                            type="text"
+                            class="input input-bordered"
+                            id="avatar"
+                            prop:value=move || form.with(|form| form.avatar.clone())
+                            on:input=move |ev| set_form.update(|form| form.avatar = event_target_value(&ev))
+                        />
+                    </Field>
+                    <Field label="Roles" name="roles" errors=errors>
+                        <div class="flex flex-wrap gap-4">
+                            <Suspense fallback=move || view! {}>
+                                {move || {
+                                    roles
+                                        .get()
+                                        .map(|list| {
+                                            list.into_iter()
+                                                .map(|role| {
+                                                    let name = role.name.clone();
+                                                    let checked = {
+                                                        let name = name.clone();
+                                                        move || form.with(|form| form.roles.contains(&name))
+                                                    };
+                                                    view! {
+                                                        <label class="label cursor-pointer gap-2" title=role.description>
+                                                            <input
+                                                                type="checkbox"
+                                                                class="checkbox checkbox-sm"
+                                                                prop:checked=checked
+                                                                on:change=move |ev| {
+                                                                    let checked = event_target_checked(&ev);
+                                                                    set_form
+                                                                        .update(|form| {
+                                                                            form.roles.retain(|r| r != &name);
+                                                                            if checked {
+                                                                                form.roles.push(name.clone());
+                                                                            }
+                                                                        })
+                                                                }
+                                                            />
+
+                                                            <span class="label-text">{role.name}</span>
+                                                        </label>
+                                                    }
+                                                })
+                                                .collect_view()
+                                        })
+                                }}
+
+                            </Suspense>
+                        </div>
+                    </Field>
+                    <Show when=move || !invite>
+                        <Field label="Status" name="status" errors=errors>
+                            <select
+                                class="select select-bordered"
+                                id="status"
+                                on:change=move |ev| {
+                                    let status = event_target_value(&ev).parse().unwrap_or_default();
+                                    set_form.update(|form| form.status = status)
+                                }
+                            >
+                                {move || {
+                                    //  only accepting an invitation makes a user no longer pending
+                                    let pending = form.with(|form| form.status == UserStatus::Pending);
+                                    [UserStatus::Active, UserStatus::Disabled, UserStatus::Pending]
+                                        .into_iter()
+                                        .filter(move |status| pending || *status != UserStatus::Pending)
+                                        .map(|status| {
+                                            view! {
+                                                <option
+                                                    value=status.to_string()
+                                                    selected=move || form.with(|form| form.status == status)
+                                                >
+                                                    {status.to_string()}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </select>
+                        </Field>
+                        <Field label="Password" name="password" errors=errors>
+                            <input
+                                type="password"
+                                class="input input-bordered"
+                                id="password"
+                                autocomplete="new-password"
+                                placeholder=move || if id().is_some() { "unchanged if empty" } else { "" }
+                                prop:value=move || form.with(|form| form.password.clone())
+                                on:input=move |ev| set_form.update(|form| form.password = event_target_value(&ev))
+                            />
+                        </Field>
+                        <label class="label cursor-pointer justify-start gap-2">
+                            <input
+                                type="checkbox"
+                                class="checkbox checkbox-sm"
+                                prop:checked=move || form.with(|form| form.must_change_password)
+                                on:change=move |ev| {
+                                    set_form.update(|form| form.must_change_password = event_target_checked(&ev))
+                                }
+                            />
+
+                            <span class="label-text">"Must change password at next login"</span>
+                        </label>
+                    </Show>
+                    {move || error().map(|e| view! { <p class="text-error text-sm">{e}</p> })}
+                    <div class="card-actions justify-end mt-4">
+                        <A href="/admin/users" class="btn btn-ghost">
+                            "Cancel"
+                        </A>
+                        <button class="btn btn-primary" disabled=save.pending()>
+                            {if invite { "Send Invitation" } else { "Save" }}
+                        </button>
+                    </div>
+                </form>
+            </div>
+        </div>
+    }
+}
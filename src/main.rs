@@ -8,7 +8,18 @@ use actix_files::Files;
 use actix_web::*;
 use leptos::*;
 use leptos_actix::{generate_route_list, LeptosRoutes};
-use server::{new_app_data_cipher, Authentication};
+use models::consts::AVATAR_ROUTE;
+use server::captcha::new_app_data_captcha;
+use server::leave::new_app_data_leave_repository;
+use server::leave_attachment::new_app_data_attachment_storage;
+use server::leave_feed::leave_feed_service;
+use server::leave_ledger::accrue_periodically;
+use server::mailer::new_app_data_mailer;
+use server::passkey::{new_app_data_passkey_state, new_app_data_webauthn};
+use server::recycle_bin::purge_expired_periodically;
+use server::scim::scim_service;
+use server::session::{new_app_data_geoip, purge_sessions_periodically};
+use server::{new_app_data_cipher, new_app_data_db, Authentication, SETTINGS};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -20,6 +31,20 @@ async fn main() -> std::io::Result<()> {
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(|| view! { <App/> });
 
+    //  shared by all workers
+    let db = new_app_data_db();
+    let webauthn = new_app_data_webauthn();
+    let passkey_state = new_app_data_passkey_state();
+    let geoip = new_app_data_geoip();
+    let captcha = new_app_data_captcha();
+    let mailer = new_app_data_mailer();
+    let leaves = new_app_data_leave_repository(db.clone());
+    let attachments = new_app_data_attachment_storage();
+    std::fs::create_dir_all(&SETTINGS.avatar_dir)?;
+    rt::spawn(purge_expired_periodically(db.clone(), attachments.clone()));
+    rt::spawn(accrue_periodically(db.clone()));
+    rt::spawn(purge_sessions_periodically(db.clone()));
+
     HttpServer::new(move || {
         let leptos_options = &conf.leptos_options;
         let site_root = &leptos_options.site_root;
@@ -32,6 +57,17 @@ async fn main() -> std::io::Result<()> {
                 || view! { <App/> },
             )
             .app_data(new_app_data_cipher())
+            .app_data(db.clone())
+            .app_data(webauthn.clone())
+            .app_data(passkey_state.clone())
+            .app_data(geoip.clone())
+            .app_data(captcha.clone())
+            .app_data(mailer.clone())
+            .app_data(leaves.clone())
+            .app_data(attachments.clone())
+            .service(scim_service())
+            .service(leave_feed_service())
+            .service(Files::new(AVATAR_ROUTE, &SETTINGS.avatar_dir))
             .service(Files::new("/", site_root))
             .wrap(middleware::Compress::default())
             .wrap(Authentication)
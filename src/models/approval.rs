@@ -0,0 +1,115 @@
+use super::consts::ADMIN_ROLE;
+use super::User;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// who approves a step of a leave request
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum Approver {
+    /// the manager at this level of the reporting line of the requester,
+    /// 1 is the direct manager, 2 the manager above, and so on
+    Manager(u32),
+    /// any user having the role
+    Role(String),
+}
+
+impl Default for Approver {
+    fn default() -> Self {
+        Approver::Manager(1)
+    }
+}
+
+impl Display for Approver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Approver::Manager(1) => write!(f, "direct manager"),
+            Approver::Manager(level) => write!(f, "manager of level {}", level),
+            Approver::Role(role) => write!(f, "{}", role),
+        }
+    }
+}
+
+/// a step of the approval chain, applying to leave requests of `leave_type`
+/// (any type if None) longer than `over_days` working days
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ApprovalRule {
+    pub approver: Approver,
+    /// name of the leave type
+    pub leave_type: Option<String>,
+    pub over_days: i64,
+}
+
+impl ApprovalRule {
+    pub fn applies_to(&self, leave_type: &str, working_days: f64) -> bool {
+        (self.leave_type.is_none() || self.leave_type.as_deref() == Some(leave_type))
+            && working_days > self.over_days as f64
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ApprovalStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl Display for ApprovalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalStatus::Pending => write!(f, "Pending"),
+            ApprovalStatus::Approved => write!(f, "Approved"),
+            ApprovalStatus::Rejected => write!(f, "Rejected"),
+        }
+    }
+}
+
+impl FromStr for ApprovalStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(ApprovalStatus::Pending),
+            "Approved" => Ok(ApprovalStatus::Approved),
+            "Rejected" => Ok(ApprovalStatus::Rejected),
+            _ => Err(()),
+        }
+    }
+}
+
+/// a step of the approval chain of a leave request, resolved when the request is submitted
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ApprovalStep {
+    pub approver: Approver,
+    /// the manager approving a [Approver::Manager] step, None for role steps
+    pub approver_user: Option<User>,
+    pub status: ApprovalStatus,
+    pub decided_by: Option<User>,
+    pub comment: String,
+    pub decided_at: Option<String>,
+}
+
+impl ApprovalStep {
+    /// whether user `user_id` having `roles` decides this step,
+    /// administrators decide any step, such as when a manager is absent
+    pub fn can_decide(&self, user_id: &str, roles: &[String]) -> bool {
+        let has_role = |role: &str| roles.iter().any(|r| r == role);
+        has_role(ADMIN_ROLE)
+            || match &self.approver {
+                Approver::Manager(_) => self
+                    .approver_user
+                    .as_ref()
+                    .is_some_and(|user| user.id == user_id),
+                Approver::Role(role) => has_role(role),
+            }
+    }
+
+    /// who is expected to decide, for display
+    pub fn describe(&self) -> String {
+        match &self.approver_user {
+            Some(user) => format!("{} ({})", user.username, self.approver),
+            None => self.approver.to_string(),
+        }
+    }
+}
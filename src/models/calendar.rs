@@ -0,0 +1,236 @@
+use super::{DayPart, LeaveRequest, LeaveStatus, User};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// the calendar of users and departments without one assigned
+pub const DEFAULT_CALENDAR_ID: i64 = 1;
+
+/// holidays and weekend of a region, assigned to departments and users
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HolidayCalendar {
+    pub id: i64,
+    pub name: String,
+    /// empty for the weekend of the server settings
+    pub weekend: Vec<Weekday>,
+    /// ids and names of the departments assigned
+    pub departments: Vec<(String, String)>,
+    /// users assigned, besides the members of the departments
+    pub users: Vec<User>,
+}
+
+/// form of creating a calendar (`id` is None) or editing a calendar
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HolidayCalendarForm {
+    pub id: Option<i64>,
+    pub name: String,
+    pub weekend: Vec<Weekday>,
+}
+
+/// a day off for everyone of a calendar, or a day worked in exchange for a holiday if `working`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+    pub working: bool,
+}
+
+/// a holiday on the same day every year
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HolidayRule {
+    pub id: i64,
+    pub month: u32,
+    pub day: u32,
+    pub name: String,
+}
+
+/// holidays and rules imported from an iCalendar file
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HolidayImport {
+    pub holidays: usize,
+    pub rules: usize,
+}
+
+/// weekends and holidays, the days leave requests do not count
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct WorkingCalendar {
+    pub weekend: Vec<Weekday>,
+    /// names of holidays by date
+    pub holidays: BTreeMap<NaiveDate, String>,
+    /// names of days worked in exchange for holidays by date, working even on the weekend
+    pub working_weekend_days: BTreeMap<NaiveDate, String>,
+}
+
+impl WorkingCalendar {
+    pub fn is_working_day(&self, day: NaiveDate) -> bool {
+        self.working_weekend_days.contains_key(&day)
+            || (!self.weekend.contains(&day.weekday()) && !self.holidays.contains_key(&day))
+    }
+
+    /// working days from `start` to `end` inclusive, a half day counts 0.5,
+    /// a leave of one day is taken in `start_part`
+    pub fn duration(
+        &self,
+        start: NaiveDate,
+        start_part: DayPart,
+        end: NaiveDate,
+        end_part: DayPart,
+    ) -> f64 {
+        start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .filter(|day| self.is_working_day(*day))
+            .map(|day| {
+                let part = if day == start {
+                    start_part
+                } else if day == end {
+                    end_part
+                } else {
+                    DayPart::Full
+                };
+                if part == DayPart::Full {
+                    1.0
+                } else {
+                    0.5
+                }
+            })
+            .sum()
+    }
+}
+
+/// an iCalendar feed of approved leaves a user subscribes to in calendar apps
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaveFeed {
+    pub id: String,
+    /// None for the feed of the user's own leaves
+    pub department_id: Option<String>,
+    /// name of the department, empty for the user's own leaves
+    pub department: String,
+    pub created_at: String,
+}
+
+/// an iCalendar file named `name` with an all-day event for every leave request of `leaves`,
+/// remarks are written only if `with_remarks`
+pub fn leaves_to_ics(name: &str, leaves: &[LeaveRequest], with_remarks: bool) -> String {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Dvorak Admin//Leaves//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_ics(name)),
+    ];
+    for leave in leaves {
+        let mut summary = format!("{} - {} leave", leave.user.username, leave.leave_type);
+        if leave.start_date == leave.end_date {
+            if leave.start_part != DayPart::Full {
+                summary.push_str(&format!(" ({})", leave.start_part.label()));
+            }
+        } else {
+            if leave.start_part == DayPart::Afternoon {
+                summary.push_str(" (from the afternoon)");
+            }
+            if leave.end_part == DayPart::Morning {
+                summary.push_str(" (until the morning)");
+            }
+        }
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:leave-{}@dvorak-admin", leave.id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!(
+            "DTSTART;VALUE=DATE:{}",
+            leave.start_date.format("%Y%m%d")
+        ));
+        //  the end of an all-day event is exclusive
+        lines.push(format!(
+            "DTEND;VALUE=DATE:{}",
+            (leave.end_date + Duration::days(1)).format("%Y%m%d")
+        ));
+        lines.push(format!("SUMMARY:{}", escape_ics(&summary)));
+        if with_remarks && !leave.remark.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_ics(&leave.remark)));
+        }
+        lines.push(format!(
+            "STATUS:{}",
+            if leave.status == LeaveStatus::Approved {
+                "CONFIRMED"
+            } else {
+                "TENTATIVE"
+            }
+        ));
+        lines.push("TRANSP:OPAQUE".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_ics(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+fn escape_ics(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// `line` folded into lines of at most 75 octets, continued by a leading space
+fn fold_ics(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_to_ics_escapes_text() {
+        let leave = LeaveRequest {
+            id: 7,
+            user: User {
+                id: "alice".to_string(),
+                username: "smith, alice".to_string(),
+            },
+            leave_type: "Annual".to_string(),
+            start_date: NaiveDate::from_ymd_opt(2030, 6, 3).unwrap(),
+            start_part: DayPart::Full,
+            end_date: NaiveDate::from_ymd_opt(2030, 6, 4).unwrap(),
+            end_part: DayPart::Full,
+            duration: 2.0,
+            remark: "trip; back\\home\nsoon".to_string(),
+            status: LeaveStatus::Approved,
+            decided_by: None,
+            comment: String::new(),
+            decided_at: None,
+            approvals: vec![],
+            staffing: vec![],
+            attachments: vec![],
+        };
+
+        let ics = leaves_to_ics("Team, Leaves", &[leave.clone()], true);
+        assert!(ics.contains("X-WR-CALNAME:Team\\, Leaves\r\n"));
+        assert!(ics.contains("SUMMARY:smith\\, alice - Annual leave\r\n"));
+        assert!(ics.contains("DESCRIPTION:trip\\; back\\\\home\\nsoon\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20300605\r\n"));
+
+        let ics = leaves_to_ics("Team", &[leave], false);
+        assert!(!ics.contains("DESCRIPTION"));
+    }
+}
@@ -1 +1,52 @@
 pub const ADMIN_ROUTE_PREFIX: &'static str = "/admin";
+pub const CHANGE_PASSWORD_ROUTE: &'static str = "/change-password";
+/// invitees accept invitations here without logging in
+pub const INVITATION_ROUTE: &'static str = "/invitation";
+/// checking the idle status does not count as activity of the session
+pub const IDLE_STATUS_PATH: &'static str = "/api/idle_status";
+/// role of who manages users and settings
+pub const ADMIN_ROLE: &'static str = "admin";
+/// role of who approves leaves of the team
+pub const MANAGER_ROLE: &'static str = "manager";
+/// role of who manages leaves of all employees
+pub const HR_ROLE: &'static str = "hr";
+/// roles of who approve or reject leave requests of others
+pub const LEAVE_APPROVER_ROLES: [&'static str; 3] = [ADMIN_ROLE, MANAGER_ROLE, HR_ROLE];
+/// roles of who adjust leave balances of everyone
+pub const LEAVE_BALANCE_ROLES: [&'static str; 2] = [ADMIN_ROLE, HR_ROLE];
+/// role given to users created without any role
+pub const EMPLOYEE_ROLE: &'static str = "employee";
+/// iCalendar feeds of approved leaves are served here, by secret tokens instead of sessions
+pub const LEAVE_FEED_ROUTE: &'static str = "/feeds";
+/// uploaded avatars are served here
+pub const AVATAR_ROUTE: &'static str = "/avatars";
+pub const DEFAULT_AVATAR: &'static str = "/images/avatar.png";
+/// uploaded avatars are resized to all of these sizes (px)
+pub const AVATAR_SIZES: [u32; 3] = [32, 64, 256];
+/// timezones can be chosen in profile
+pub const TIMEZONES: &[&'static str] = &[
+    "UTC",
+    "America/New_York",
+    "America/Chicago",
+    "America/Denver",
+    "America/Los_Angeles",
+    "America/Sao_Paulo",
+    "Europe/London",
+    "Europe/Paris",
+    "Europe/Berlin",
+    "Europe/Moscow",
+    "Asia/Dubai",
+    "Asia/Kolkata",
+    "Asia/Singapore",
+    "Asia/Shanghai",
+    "Asia/Tokyo",
+    "Australia/Sydney",
+];
+/// languages can be chosen in profile, by code and name
+pub const LANGUAGES: &[(&'static str, &'static str)] = &[
+    ("en", "English"),
+    ("zh", "中文"),
+    ("de", "Deutsch"),
+    ("fr", "Français"),
+    ("ja", "日本語"),
+];
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// a department or org unit, departments form a tree by `parent_id`
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Department {
+    pub id: String,
+    pub name: String,
+    /// None for top level departments
+    pub parent_id: Option<String>,
+    pub manager_id: Option<String>,
+    /// display name of the manager
+    pub manager_name: Option<String>,
+    pub member_count: i64,
+}
+
+/// form of creating a department (`id` is None) or editing a department
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct DepartmentForm {
+    pub id: Option<String>,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub manager_id: Option<String>,
+}
+
+impl From<Department> for DepartmentForm {
+    fn from(department: Department) -> Self {
+        Self {
+            id: Some(department.id),
+            name: department.name,
+            parent_id: department.parent_id,
+            manager_id: department.manager_id,
+        }
+    }
+}
@@ -0,0 +1,609 @@
+use super::consts::{ADMIN_ROLE, HR_ROLE, LEAVE_APPROVER_ROLES};
+use super::{ApprovalStatus, ApprovalStep, Approver, User, WorkingCalendar};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// the longest comment on a leave request decision
+pub const MAX_LEAVE_COMMENT_LENGTH: usize = 500;
+
+pub enum LeaveError {
+    NotExist,
+    /// the status cannot change from the first to the second
+    InvalidTransition(LeaveStatus, LeaveStatus),
+    PermissionDenied,
+    /// the approval step was decided by someone else meanwhile
+    AlreadyDecided,
+    /// another leave request of the same user from the first to the second date overlaps
+    Overlap(NaiveDate, NaiveDate),
+    CommentTooLong,
+    Storage,
+}
+
+impl fmt::Display for LeaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaveError::NotExist => write!(f, "leave request not exist"),
+            LeaveError::InvalidTransition(from, to) => {
+                write!(f, "{} leave request cannot be {}", from, to)
+            }
+            LeaveError::PermissionDenied => write!(f, "permission denied"),
+            LeaveError::AlreadyDecided => write!(f, "leave request was decided already"),
+            LeaveError::Overlap(start, end) => write!(
+                f,
+                "leave request overlaps another leave of the same user from {} to {}",
+                start, end
+            ),
+            LeaveError::CommentTooLong => write!(
+                f,
+                "comment must have at most {} characters",
+                MAX_LEAVE_COMMENT_LENGTH
+            ),
+            LeaveError::Storage => write!(f, "leave storage fail"),
+        }
+    }
+}
+
+/// a kind of leave defined by administrators, referred to by name everywhere else
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LeaveType {
+    /// unique, cannot change once created
+    pub name: String,
+    /// `#rrggbb` of badges
+    pub color: String,
+    pub paid: bool,
+    /// requests may start in the past, such as sick leave reported afterwards
+    pub backdated: bool,
+    /// inactive types cannot be requested any more, existing requests and balances keep them
+    pub active: bool,
+    pub policy: LeavePolicy,
+}
+
+impl LeaveType {
+    /// inline style of a badge of this type, filled with its color or only outlined,
+    /// with dark or light text by the brightness of the color
+    pub fn badge_style(&self, outline: bool) -> String {
+        if outline {
+            return format!(
+                "border-color: {0}; color: {0}; background-color: transparent",
+                self.color
+            );
+        }
+        let channel = |index: usize| {
+            self.color
+                .get(index..index + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .unwrap_or_default() as u32
+        };
+        //  perceived brightness, from 0 to 255
+        let brightness = (channel(1) * 299 + channel(3) * 587 + channel(5) * 114) / 1000;
+        let text = if brightness > 150 {
+            "#1f2937"
+        } else {
+            "#ffffff"
+        };
+        format!(
+            "border-color: {0}; background-color: {0}; color: {1}",
+            self.color, text
+        )
+    }
+}
+
+/// status of a leave request
+///
+/// - Pending: submitted, waiting for an approver
+/// - Approved / Rejected: decided by an approver
+/// - Withdrawn: taken back by the requester before decided
+/// - Cancelled: called off after approved
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LeaveStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+    Cancelled,
+    Withdrawn,
+}
+
+impl Display for LeaveStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaveStatus::Pending => write!(f, "Pending"),
+            LeaveStatus::Approved => write!(f, "Approved"),
+            LeaveStatus::Rejected => write!(f, "Rejected"),
+            LeaveStatus::Cancelled => write!(f, "Cancelled"),
+            LeaveStatus::Withdrawn => write!(f, "Withdrawn"),
+        }
+    }
+}
+
+impl FromStr for LeaveStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(LeaveStatus::Pending),
+            "Approved" => Ok(LeaveStatus::Approved),
+            "Rejected" => Ok(LeaveStatus::Rejected),
+            "Cancelled" => Ok(LeaveStatus::Cancelled),
+            "Withdrawn" => Ok(LeaveStatus::Withdrawn),
+            _ => Err(()),
+        }
+    }
+}
+
+impl LeaveStatus {
+    /// statuses this one can change to, Rejected, Cancelled and Withdrawn are final
+    pub fn next(self) -> &'static [LeaveStatus] {
+        match self {
+            LeaveStatus::Pending => &[
+                LeaveStatus::Approved,
+                LeaveStatus::Rejected,
+                LeaveStatus::Withdrawn,
+            ],
+            LeaveStatus::Approved => &[LeaveStatus::Cancelled],
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaveRequest {
+    pub id: i64,
+    pub user: User,
+    /// name of the [LeaveType]
+    pub leave_type: String,
+    pub start_date: NaiveDate,
+    pub start_part: DayPart,
+    /// inclusive
+    pub end_date: NaiveDate,
+    pub end_part: DayPart,
+    /// working days, computed when submitted
+    pub duration: f64,
+    pub remark: String,
+    pub status: LeaveStatus,
+    /// who changed the status last, None while pending
+    pub decided_by: Option<User>,
+    /// comment of the last status change
+    pub comment: String,
+    pub decided_at: Option<String>,
+    /// steps of the approval chain in order, empty for requests any approver decides
+    pub approvals: Vec<ApprovalStep>,
+    /// days the team of the requester would be understaffed, found when pending requests
+    /// are listed and never stored
+    #[serde(default)]
+    pub staffing: Vec<StaffingShortage>,
+    /// files attached by the requester, filled when listed for those who may see them
+    #[serde(default)]
+    pub attachments: Vec<LeaveAttachment>,
+}
+
+/// a working day too few members of a team are present
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StaffingShortage {
+    pub date: NaiveDate,
+    /// members present, none of them on leave even for half a day
+    pub present: usize,
+    pub team_size: usize,
+    /// usernames of the other members on pending or approved leave
+    pub absent: Vec<String>,
+}
+
+/// a file attached to a leave request, such as a sick note
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaveAttachment {
+    pub id: String,
+    pub leave_id: i64,
+    pub file_name: String,
+    pub content_type: String,
+    /// in bytes
+    pub size: i64,
+    pub created_at: String,
+}
+
+/// a file to be attached, read in the browser as a data url
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct AttachmentUpload {
+    pub file_name: String,
+    pub data: String,
+}
+
+impl LeaveRequest {
+    /// whether this leave request and the days from `start` to `end` share a half day
+    pub fn overlaps(
+        &self,
+        start: NaiveDate,
+        start_part: DayPart,
+        end: NaiveDate,
+        end_part: DayPart,
+    ) -> bool {
+        let (first, last) = half_days(
+            self.start_date,
+            self.start_part,
+            self.end_date,
+            self.end_part,
+        );
+        let (other_first, other_last) = half_days(start, start_part, end, end_part);
+        first <= other_last && other_first <= last
+    }
+
+    /// index of the first step still waiting for a decision
+    pub fn current_step(&self) -> Option<usize> {
+        if self.status != LeaveStatus::Pending {
+            return None;
+        }
+        self.approvals
+            .iter()
+            .position(|step| step.status == ApprovalStatus::Pending)
+    }
+
+    /// whether user `user_id` is the approver of any step of the approval chain
+    pub fn in_chain(&self, user_id: &str) -> bool {
+        self.approvals.iter().any(|step| {
+            step.approver_user
+                .as_ref()
+                .is_some_and(|user| user.id == user_id)
+        })
+    }
+
+    /// whether user `user_id` having `roles` approves any step of the approval chain,
+    /// as the manager of the step or having the role of the step
+    pub fn is_chain_approver(&self, user_id: &str, roles: &[String]) -> bool {
+        self.in_chain(user_id)
+            || self.approvals.iter().any(|step| match &step.approver {
+                Approver::Role(role) => roles.contains(role),
+                Approver::Manager(_) => false,
+            })
+    }
+
+    /// whether user `user_id` having `roles` decides the current step,
+    /// any approver decides requests without an approval chain
+    pub fn can_decide(&self, user_id: &str, roles: &[String]) -> bool {
+        if self.user.id == user_id || self.status != LeaveStatus::Pending {
+            return false;
+        }
+        match self.current_step() {
+            Some(step) => self.approvals[step].can_decide(user_id, roles),
+            None => self.approvals.is_empty() && has_approver_role(roles),
+        }
+    }
+
+    /// statuses user `user_id` having `roles` can change this leave request to,
+    /// approvers decide the current step of leave requests of others,
+    /// requesters withdraw or cancel their own, and the approvers of its chain,
+    /// administrators and HR cancel it too
+    ///
+    /// approving a step other than the last only advances the chain,
+    /// the request stays pending until all of the steps are approved
+    pub fn available_statuses(&self, user_id: &str, roles: &[String]) -> Vec<LeaveStatus> {
+        let own = self.user.id == user_id;
+        self.status
+            .next()
+            .iter()
+            .copied()
+            .filter(|status| match status {
+                LeaveStatus::Approved | LeaveStatus::Rejected => self.can_decide(user_id, roles),
+                LeaveStatus::Withdrawn => own,
+                LeaveStatus::Cancelled => {
+                    own || self.is_chain_approver(user_id, roles)
+                        || roles
+                            .iter()
+                            .any(|role| role == ADMIN_ROLE || role == HR_ROLE)
+                }
+                LeaveStatus::Pending => false,
+            })
+            .collect()
+    }
+}
+
+/// whether `roles` include a role deciding leave requests
+pub fn has_approver_role(roles: &[String]) -> bool {
+    roles
+        .iter()
+        .any(|role| LEAVE_APPROVER_ROLES.contains(&role.as_str()))
+}
+
+pub type LeaveList = Vec<LeaveRequest>;
+
+/// a leave request to be created, whose user is the one submitting it
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LeaveForm {
+    /// name of an active [LeaveType]
+    pub leave_type: String,
+    /// None until picked
+    pub start_date: Option<NaiveDate>,
+    /// [DayPart::Afternoon] to start at noon, the part taken if the leave lasts one day
+    pub start_part: DayPart,
+    pub end_date: Option<NaiveDate>,
+    /// [DayPart::Morning] to end at noon, ignored if the leave lasts one day
+    pub end_part: DayPart,
+    pub remark: String,
+    /// files sent with the request, required by the policy of some leave types
+    #[serde(default)]
+    pub attachments: Vec<AttachmentUpload>,
+}
+
+impl LeaveForm {
+    /// working days by `calendar`, None if any date is missing or the range is reversed
+    pub fn duration(&self, calendar: &WorkingCalendar) -> Option<f64> {
+        let (start, end) = (self.start_date?, self.end_date?);
+        (start <= end).then(|| calendar.duration(start, self.start_part, end, self.end_part()))
+    }
+
+    /// the part of the last day, the same as the start for a leave of one day
+    pub fn end_part(&self) -> DayPart {
+        if self.start_date == self.end_date {
+            self.start_part
+        } else {
+            self.end_part
+        }
+    }
+}
+
+/// the part of a day taken by a leave request
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DayPart {
+    #[default]
+    Full,
+    Morning,
+    Afternoon,
+}
+
+impl DayPart {
+    pub const ALL: [DayPart; 3] = [DayPart::Full, DayPart::Morning, DayPart::Afternoon];
+
+    /// for display
+    pub fn label(self) -> &'static str {
+        match self {
+            DayPart::Full => "full day",
+            DayPart::Morning => "morning",
+            DayPart::Afternoon => "afternoon",
+        }
+    }
+}
+
+impl Display for DayPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DayPart::Full => write!(f, "Full"),
+            DayPart::Morning => write!(f, "Morning"),
+            DayPart::Afternoon => write!(f, "Afternoon"),
+        }
+    }
+}
+
+impl FromStr for DayPart {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Full" => Ok(DayPart::Full),
+            "Morning" => Ok(DayPart::Morning),
+            "Afternoon" => Ok(DayPart::Afternoon),
+            _ => Err(()),
+        }
+    }
+}
+
+/// the first and the last half day from `start` to `end`, numbered from the common era,
+/// a leave of one day is taken in `start_part`
+fn half_days(
+    start: NaiveDate,
+    start_part: DayPart,
+    end: NaiveDate,
+    end_part: DayPart,
+) -> (i64, i64) {
+    let (start_half, end_half) = (
+        start.num_days_from_ce() as i64 * 2,
+        end.num_days_from_ce() as i64 * 2,
+    );
+    let end_part = if start == end { start_part } else { end_part };
+    (
+        start_half + i64::from(start_part == DayPart::Afternoon),
+        end_half + i64::from(end_part != DayPart::Morning),
+    )
+}
+
+/// how the days of a leave type are granted
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum Accrual {
+    /// all of the days on the first day of the year
+    #[default]
+    Yearly,
+    /// a twelfth of the days on the first day of every month
+    Monthly,
+}
+
+impl Display for Accrual {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Accrual::Yearly => write!(f, "Yearly"),
+            Accrual::Monthly => write!(f, "Monthly"),
+        }
+    }
+}
+
+impl FromStr for Accrual {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Yearly" => Ok(Accrual::Yearly),
+            "Monthly" => Ok(Accrual::Monthly),
+            _ => Err(()),
+        }
+    }
+}
+
+/// whether the days of a leave type are counted against a balance
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum Balance {
+    /// requested within the days accrued, and the days used are recorded in the ledger
+    #[default]
+    Tracked,
+    /// requested without a balance, such as unpaid leave, nothing is recorded in the ledger
+    Unlimited,
+}
+
+impl Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Balance::Tracked => write!(f, "Tracked"),
+            Balance::Unlimited => write!(f, "Unlimited"),
+        }
+    }
+}
+
+impl FromStr for Balance {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Tracked" => Ok(Balance::Tracked),
+            "Unlimited" => Ok(Balance::Unlimited),
+            _ => Err(()),
+        }
+    }
+}
+
+/// balance policy and attachment rule of a leave type
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LeavePolicy {
+    /// name of the leave type
+    pub leave_type: String,
+    pub balance: Balance,
+    pub accrual: Accrual,
+    pub days_per_year: f64,
+    /// unused days up to this are carried over to the next year, the rest expire
+    pub max_carry_over: f64,
+    /// carried over days unused this many months into the year expire, 0 means never
+    pub carry_over_expiry_months: u32,
+    /// requests longer than this many working days must have an attachment, None means never
+    pub attachment_over_days: Option<f64>,
+}
+
+/// kind of a leave ledger entry
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LedgerKind {
+    /// days granted by the policy
+    #[default]
+    Accrual,
+    /// days taken by an approved leave request, given back if it is cancelled
+    Usage,
+    /// days given or taken manually by HR
+    Adjustment,
+    /// unused days brought from the last year
+    CarryOver,
+    /// unused days lost at year end or when carried over days expire
+    Expiry,
+}
+
+impl Display for LedgerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerKind::Accrual => write!(f, "Accrual"),
+            LedgerKind::Usage => write!(f, "Usage"),
+            LedgerKind::Adjustment => write!(f, "Adjustment"),
+            LedgerKind::CarryOver => write!(f, "CarryOver"),
+            LedgerKind::Expiry => write!(f, "Expiry"),
+        }
+    }
+}
+
+impl FromStr for LedgerKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Accrual" => Ok(LedgerKind::Accrual),
+            "Usage" => Ok(LedgerKind::Usage),
+            "Adjustment" => Ok(LedgerKind::Adjustment),
+            "CarryOver" => Ok(LedgerKind::CarryOver),
+            "Expiry" => Ok(LedgerKind::Expiry),
+            _ => Err(()),
+        }
+    }
+}
+
+/// a change of a leave balance, days are negative when taken
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub leave_type: String,
+    pub year: i32,
+    pub kind: LedgerKind,
+    pub days: f64,
+    pub reason: String,
+    /// username of who recorded it, None if recorded automatically
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+/// a manual adjustment of a leave balance by HR
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LeaveAdjustmentForm {
+    pub user_id: String,
+    pub leave_type: String,
+    pub year: i32,
+    /// positive to give days, negative to take
+    pub days: f64,
+    pub reason: String,
+}
+
+/// days of a leave type a user has taken and can still take in a year, summed from the ledger
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LeaveBalance {
+    pub leave_type: String,
+    pub year: i32,
+    /// accrued and carried over days
+    pub accrued: f64,
+    /// days of approved leave requests
+    pub used: f64,
+    /// days of manual adjustments, negative if taken
+    pub adjusted: f64,
+    pub expired: f64,
+    /// working days of leave requests waiting for approval
+    pub pending: f64,
+}
+
+impl LeaveBalance {
+    /// pending days are reserved, so that requests cannot exceed the balance together
+    pub fn remaining(&self) -> f64 {
+        self.accrued + self.adjusted - self.used - self.expired - self.pending
+    }
+}
+
+/// leave balances of a user
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UserLeaveBalances {
+    pub user: User,
+    pub balances: Vec<LeaveBalance>,
+}
+
+/// days rounded to 2 decimal places, such as monthly accruals
+pub fn round_days(days: f64) -> f64 {
+    (days * 100.0).round() / 100.0
+}
+
+/// days for display, without decimal places if whole
+pub fn format_days(days: f64) -> String {
+    let days = round_days(days);
+    if days.fract() == 0.0 {
+        format!("{}", days as i64)
+    } else {
+        format!("{}", days)
+    }
+}
+
+/// `bytes` for display, in MB with at most one decimal place from 1 MB, otherwise in KB
+pub fn format_size(bytes: i64) -> String {
+    const KB: i64 = 1024;
+    const MB: i64 = 1024 * KB;
+    if bytes >= MB {
+        let mb = (bytes as f64 / MB as f64 * 10.0).round() / 10.0;
+        format!("{} MB", mb)
+    } else {
+        format!("{} KB", (bytes + KB - 1) / KB)
+    }
+}
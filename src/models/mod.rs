@@ -1,33 +1,100 @@
 pub mod consts;
+mod approval;
+mod calendar;
+mod department;
+mod leave;
+mod recycle_bin;
+mod user;
+
+pub use approval::*;
+pub use calendar::*;
+pub use department::*;
+pub use leave::*;
+pub use recycle_bin::*;
+pub use user::*;
 
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
+use std::str::FromStr;
 
-/// representing the user information
+/// a passkey registered by user, without the credential itself
 #[derive(Serialize, Deserialize, Clone)]
-pub struct User {
-    pub id: String,
-    pub username: String,
+pub struct PasskeyInfo {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
 }
 
-impl User {
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).expect("serialize User fail")
-    }
+/// options of signing in with passkey, the ceremony id is sent back when finished
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PasskeyLoginOptions {
+    pub ceremony_id: String,
+    /// JSON options for `navigator.credentials.get`
+    pub options: String,
 }
 
-pub enum UserError {
-    NotExist,
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SessionEventKind {
+    NewCountry,
+    NewDevice,
+    ImpossibleTravel,
+    FingerprintMismatch,
 }
 
-impl fmt::Display for UserError {
+impl Display for SessionEventKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            UserError::NotExist => write!(f, "user not exist"),
+            SessionEventKind::NewCountry => write!(f, "NewCountry"),
+            SessionEventKind::NewDevice => write!(f, "NewDevice"),
+            SessionEventKind::ImpossibleTravel => write!(f, "ImpossibleTravel"),
+            SessionEventKind::FingerprintMismatch => write!(f, "FingerprintMismatch"),
         }
     }
 }
 
+impl FromStr for SessionEventKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "NewCountry" => Ok(SessionEventKind::NewCountry),
+            "NewDevice" => Ok(SessionEventKind::NewDevice),
+            "ImpossibleTravel" => Ok(SessionEventKind::ImpossibleTravel),
+            "FingerprintMismatch" => Ok(SessionEventKind::FingerprintMismatch),
+            _ => Err(()),
+        }
+    }
+}
+
+/// an anomaly detected on login or during a session, recorded for administrators
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionEvent {
+    pub id: i64,
+    pub username: String,
+    pub kind: SessionEventKind,
+    pub detail: String,
+    pub ip: String,
+    pub created_at: String,
+}
+
+/// CAPTCHA challenge shown on login form, the answer is kept on server
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Captcha {
+    pub id: String,
+    /// data uri of the rendered image
+    pub image: String,
+}
+
+/// idle timeout of current session, all in seconds
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct IdleStatus {
+    /// 0 if sessions never time out
+    pub timeout: i64,
+    /// how long before the user is warned
+    pub warning: i64,
+    pub remaining: i64,
+}
+
 pub type MenuList = Vec<Menu>;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -35,6 +102,8 @@ pub struct Menu {
     pub id: usize,
     pub title: String,
     pub icon: String,
+    /// only shown to users of this role, None for everyone
+    pub role: Option<String>,
     pub sub_menu: Vec<SubMenu>,
 }
 
@@ -44,32 +113,3 @@ pub struct SubMenu {
     pub title: String,
     pub link: String,
 }
-
-#[derive(Serialize, Deserialize, Clone)]
-pub enum LeaveType {
-    Personal,
-    Sick,
-    Annual,
-}
-
-impl Display for LeaveType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            LeaveType::Annual => write!(f, "Annual"),
-            LeaveType::Sick => write!(f, "Sick"),
-            LeaveType::Personal => write!(f, "Personal"),
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct LeaveRequest {
-    pub id: usize,
-    pub user: User,
-    pub leave_type: LeaveType,
-    pub start_date: String,
-    pub end_date: String,
-    pub remark: String,
-}
-
-pub type LeaveList = Vec<LeaveRequest>;
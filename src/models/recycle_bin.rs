@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// kinds of records which are moved into the recycle bin when deleted
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum RecordKind {
+    User,
+    Department,
+}
+
+impl Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordKind::User => write!(f, "User"),
+            RecordKind::Department => write!(f, "Department"),
+        }
+    }
+}
+
+impl FromStr for RecordKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "User" => Ok(RecordKind::User),
+            "Department" => Ok(RecordKind::Department),
+            _ => Err(()),
+        }
+    }
+}
+
+/// a soft deleted record in the recycle bin
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeletedRecord {
+    pub kind: RecordKind,
+    pub id: String,
+    pub name: String,
+    pub deleted_at: String,
+    /// username of who deleted the record, or `scim` if deleted by the identity provider
+    pub deleted_by: String,
+    /// None if deleted records are kept forever
+    pub purge_at: Option<String>,
+}
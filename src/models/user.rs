@@ -0,0 +1,239 @@
+use super::consts::AVATAR_SIZES;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// representing the user information
+#[derive(Serialize, Deserialize, Clone)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+}
+
+impl User {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialize User fail")
+    }
+}
+
+pub enum UserError {
+    NotExist,
+    WrongPassword,
+    WeakPassword,
+    Storage,
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserError::NotExist => write!(f, "user not exist"),
+            UserError::WrongPassword => write!(f, "wrong password"),
+            UserError::WeakPassword => write!(
+                f,
+                "password must have at least 8 characters and differ from the current one"
+            ),
+            UserError::Storage => write!(f, "user storage fail"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum UserStatus {
+    #[default]
+    Active,
+    Disabled,
+    /// invited but not accepted yet
+    Pending,
+}
+
+impl Display for UserStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserStatus::Active => write!(f, "Active"),
+            UserStatus::Disabled => write!(f, "Disabled"),
+            UserStatus::Pending => write!(f, "Pending"),
+        }
+    }
+}
+
+impl FromStr for UserStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Active" => Ok(UserStatus::Active),
+            "Disabled" => Ok(UserStatus::Disabled),
+            "Pending" => Ok(UserStatus::Pending),
+            _ => Err(()),
+        }
+    }
+}
+
+/// full information of a user, for administrators
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UserDetail {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    pub department_id: Option<String>,
+    /// name of the department, empty if not in any department
+    pub department: String,
+    pub roles: Vec<String>,
+    pub status: UserStatus,
+    pub avatar: String,
+    pub must_change_password: bool,
+    /// None if passwords never expire
+    pub password_expires_at: Option<String>,
+}
+
+/// form of creating a user (`id` is None) or editing a user
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UserForm {
+    pub id: Option<String>,
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    pub department_id: Option<String>,
+    pub roles: Vec<String>,
+    pub status: UserStatus,
+    pub avatar: String,
+    /// required when creating, empty means unchanged when editing
+    pub password: String,
+    pub must_change_password: bool,
+}
+
+impl From<UserDetail> for UserForm {
+    fn from(user: UserDetail) -> Self {
+        Self {
+            id: Some(user.id),
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+            department_id: user.department_id,
+            roles: user.roles,
+            status: user.status,
+            avatar: user.avatar,
+            password: String::new(),
+            must_change_password: user.must_change_password,
+        }
+    }
+}
+
+/// profile of the logged in user, edited by the user themself
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UserProfile {
+    pub id: String,
+    pub username: String,
+    pub display_name: String,
+    pub email: String,
+    pub timezone: String,
+    pub language: String,
+    pub avatar: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ProfileForm {
+    pub display_name: String,
+    pub email: String,
+    pub timezone: String,
+    pub language: String,
+}
+
+impl From<UserProfile> for ProfileForm {
+    fn from(profile: UserProfile) -> Self {
+        Self {
+            display_name: profile.display_name,
+            email: profile.email,
+            timezone: profile.timezone,
+            language: profile.language,
+        }
+    }
+}
+
+/// the logged in user with roles and preferences, provided as context to components
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CurrentUser {
+    pub id: String,
+    pub username: String,
+    pub display_name: String,
+    pub avatar: String,
+    pub roles: Vec<String>,
+    pub timezone: String,
+    pub language: String,
+}
+
+impl CurrentUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// url of `avatar` in `size`, one of [AVATAR_SIZES],
+/// uploaded avatars keep a `{size}` placeholder in their url, others have only one size
+pub fn avatar_url(avatar: &str, size: u32) -> String {
+    debug_assert!(AVATAR_SIZES.contains(&size));
+    avatar.replace("{size}", &size.to_string())
+}
+
+/// an invitation shown to the invitee, who sets password and profile to accept it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Invitation {
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    pub expires_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct AcceptInvitationForm {
+    pub display_name: String,
+    pub password: String,
+    pub confirm_password: String,
+    pub timezone: String,
+    pub language: String,
+}
+
+/// columns of the user CSV, both exported and importable
+pub const USER_CSV_FIELDS: [&'static str; 7] = [
+    "username",
+    "email",
+    "display_name",
+    "department",
+    "roles",
+    "status",
+    "password",
+];
+
+/// which column of the CSV file is imported as a field of [USER_CSV_FIELDS],
+/// keyed by field name, unmapped fields are left empty
+pub type ImportMapping = BTreeMap<String, usize>;
+
+/// a row of the CSV file to import, `line` counts from 1 including the header
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportRow {
+    pub line: usize,
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    /// empty if the row is valid
+    pub errors: FieldErrors,
+}
+
+/// result of importing, or of a dry run which changes nothing
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+    /// number of users created, always 0 for a dry run
+    pub created: usize,
+}
+
+/// validation errors of a form, keyed by field name
+pub type FieldErrors = BTreeMap<String, String>;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+}
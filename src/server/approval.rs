@@ -0,0 +1,240 @@
+//! Approval
+//! leave requests are approved through a chain of steps, made of the [ApprovalRule]s
+//! applying to the leave type and the working days, in the order of rules
+//!
+//! - a [Approver::Manager] step is decided by the manager at that level of the reporting line
+//! - a [Approver::Role] step is decided by any user having the role
+//!
+//! the chain is resolved when the request is submitted, so that later changes of rules
+//! or departments do not affect submitted requests,
+//! steps without a manager or repeating the approver of a previous step are left out,
+//! and requests no rule resolves for are approved by HR
+
+use super::department::{reporting_line, DepartmentError};
+use super::user::Role;
+use crate::models::consts::HR_ROLE;
+use crate::models::{ApprovalRule, ApprovalStep, Approver, FieldErrors, LeaveError, LeaveType};
+use rusqlite::{params, Connection};
+
+/// the highest manager level of a rule
+const MAX_MANAGER_LEVEL: u32 = 5;
+/// the most steps of a chain
+const MAX_RULES: usize = 10;
+
+impl From<DepartmentError> for LeaveError {
+    fn from(e: DepartmentError) -> Self {
+        match e {
+            DepartmentError::NotExist => LeaveError::NotExist,
+            _ => LeaveError::Storage,
+        }
+    }
+}
+
+impl ApprovalRule {
+    /// all of the rules in order
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, LeaveError> {
+        let mut stmt = conn.prepare(
+            "SELECT approver, manager_level, role, leave_type, over_days
+             FROM approval_rules ORDER BY position",
+        )?;
+        let list = stmt
+            .query_map([], |row| {
+                let approver: String = row.get(0)?;
+                Ok(ApprovalRule {
+                    approver: match approver.as_str() {
+                        "Role" => Approver::Role(row.get(2)?),
+                        _ => Approver::Manager(row.get(1)?),
+                    },
+                    leave_type: row.get(3)?,
+                    over_days: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// errors of every invalid rule under `rules`, empty if all of them are valid
+    pub fn validate_all(conn: &Connection, rules: &[Self]) -> Result<FieldErrors, LeaveError> {
+        let mut errors = FieldErrors::new();
+        if rules.len() > MAX_RULES {
+            errors.insert(
+                "rules".into(),
+                format!("must have at most {} steps", MAX_RULES),
+            );
+            return Ok(errors);
+        }
+
+        let known_roles: Vec<String> = Role::list(conn)
+            .map_err(|_| LeaveError::Storage)?
+            .into_iter()
+            .map(|role| role.name)
+            .collect();
+        let known_types: Vec<String> = LeaveType::list(conn)?
+            .into_iter()
+            .map(|leave_type| leave_type.name)
+            .collect();
+        for (index, rule) in rules.iter().enumerate() {
+            let error = match &rule.approver {
+                Approver::Manager(level) if !(1..=MAX_MANAGER_LEVEL).contains(level) => Some(
+                    format!("manager level must be between 1 and {}", MAX_MANAGER_LEVEL),
+                ),
+                Approver::Role(role) if !known_roles.contains(role) => {
+                    Some(format!("role {} not exist", role))
+                }
+                _ if !(0..=366).contains(&rule.over_days) => {
+                    Some("days must be between 0 and 366".to_string())
+                }
+                _ => rule
+                    .leave_type
+                    .as_ref()
+                    .filter(|leave_type| !known_types.contains(leave_type))
+                    .map(|leave_type| format!("leave type {} not exist", leave_type)),
+            };
+            if let Some(error) = error {
+                errors.insert("rules".into(), format!("step {}: {}", index + 1, error));
+                break;
+            }
+        }
+
+        Ok(errors)
+    }
+
+    /// replace all of the rules, which must be validated first,
+    /// changes apply to leave requests submitted afterwards
+    pub fn save_all(conn: &Connection, rules: &[Self]) -> Result<(), LeaveError> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM approval_rules", [])?;
+        for (index, rule) in rules.iter().enumerate() {
+            let (approver, manager_level, role) = match &rule.approver {
+                Approver::Manager(level) => ("Manager", *level, ""),
+                Approver::Role(role) => ("Role", 1, role.as_str()),
+            };
+            tx.execute(
+                "INSERT INTO approval_rules
+                 (position, approver, manager_level, role, leave_type, over_days)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    index + 1,
+                    approver,
+                    manager_level,
+                    role,
+                    rule.leave_type,
+                    rule.over_days,
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+}
+
+/// the approval chain of a leave request of user `user_id`
+/// of `leave_type` for `working_days`
+pub fn resolve_chain(
+    conn: &Connection,
+    user_id: &str,
+    leave_type: &str,
+    working_days: f64,
+) -> Result<Vec<ApprovalStep>, LeaveError> {
+    let managers = reporting_line(conn, user_id)?;
+
+    let mut steps: Vec<ApprovalStep> = vec![];
+    for rule in ApprovalRule::list(conn)? {
+        if !rule.applies_to(leave_type, working_days) {
+            continue;
+        }
+        let step = match &rule.approver {
+            Approver::Manager(level) => {
+                let Some(manager) = level.checked_sub(1).and_then(|i| managers.get(i as usize))
+                else {
+                    continue;
+                };
+                let repeated = steps.iter().any(|step| {
+                    step.approver_user
+                        .as_ref()
+                        .is_some_and(|user| user.id == manager.id)
+                });
+                if repeated {
+                    continue;
+                }
+                ApprovalStep {
+                    approver: rule.approver.clone(),
+                    approver_user: Some(manager.clone()),
+                    ..Default::default()
+                }
+            }
+            Approver::Role(_) => {
+                if steps.iter().any(|step| step.approver == rule.approver) {
+                    continue;
+                }
+                ApprovalStep {
+                    approver: rule.approver.clone(),
+                    ..Default::default()
+                }
+            }
+        };
+        steps.push(step);
+    }
+
+    if steps.is_empty() {
+        steps.push(ApprovalStep {
+            approver: Approver::Role(HR_ROLE.to_string()),
+            ..Default::default()
+        });
+    }
+
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Department, DepartmentForm};
+    use crate::server::new_test_db;
+
+    fn approvers(steps: &[ApprovalStep]) -> Vec<String> {
+        steps
+            .iter()
+            .map(|step| match (&step.approver, &step.approver_user) {
+                (_, Some(user)) => user.id.clone(),
+                (Approver::Role(role), None) => role.clone(),
+                (Approver::Manager(level), None) => level.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolve_chain_without_a_manager_level() {
+        let conn = new_test_db();
+        for id in ["boss", "alice", "bob"] {
+            conn.execute(
+                "INSERT INTO users (id, username, password_hash, password_set_at)
+                 VALUES (?1, ?1, '', 0)",
+                params![id],
+            )
+            .unwrap();
+        }
+        //  a department without a parent, no manager at level 2
+        let form = DepartmentForm {
+            name: "Team".to_string(),
+            manager_id: Some("boss".to_string()),
+            ..Default::default()
+        };
+        let department_id = Department::save(&conn, &form).ok().unwrap();
+        conn.execute(
+            "UPDATE users SET department_id = ?1 WHERE id = 'alice'",
+            params![department_id],
+        )
+        .unwrap();
+
+        let chain = resolve_chain(&conn, "alice", "Annual", 10.0).ok().unwrap();
+        assert!(approvers(&chain) == vec!["boss"]);
+        let chain = resolve_chain(&conn, "alice", "Sick", 10.0).ok().unwrap();
+        assert!(approvers(&chain) == vec!["boss", HR_ROLE]);
+        //  no manager at all falls back to HR
+        let chain = resolve_chain(&conn, "bob", "Annual", 10.0).ok().unwrap();
+        assert!(approvers(&chain) == vec![HR_ROLE]);
+    }
+}
@@ -28,15 +28,26 @@ use std::{
     rc::Rc,
 };
 
-use crate::server::AppDataCipher;
+use crate::models::consts::{
+    ADMIN_ROUTE_PREFIX, AVATAR_ROUTE, CHANGE_PASSWORD_ROUTE, IDLE_STATUS_PATH, INVITATION_ROUTE,
+    LEAVE_FEED_ROUTE,
+};
+use crate::models::User;
+use crate::server::session::{
+    check_session, create_session, revoke_session, AppDataGeoIp, ClientInfo, SESSION_LIFETIME,
+};
+use crate::server::{AppDataCipher, AppDataDb};
 use actix_web::{
     body::EitherBody,
+    cookie::{time::Duration, Cookie, SameSite},
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    http::header::{self, ACCEPT},
-    Error, HttpMessage, HttpResponse,
+    http::header::{self, HeaderValue, ACCEPT},
+    Error, HttpMessage, HttpRequest, HttpResponse,
 };
 use base64::prelude::*;
 use futures_util::future::{FutureExt, LocalBoxFuture};
+use leptos::{expect_context, ServerFnError};
+use leptos_actix::{extract, ResponseOptions};
 
 pub struct Authentication;
 
@@ -78,20 +89,42 @@ where
     fn call(&self, mut req: ServiceRequest) -> Self::Future {
         let service = Rc::clone(&self.service);
         async move {
-            if is_not_login_page(&req) {
-                let cipher = req.extract::<AppDataCipher>().await.unwrap();
-                if let Some(authenticate_token) = is_logged_in(&req, cipher) {
-                    req.extensions_mut()
-                        .insert::<RequestAuthenticationToken>(Rc::new(authenticate_token));
-                } else {
-                    let (request, _) = req.into_parts();
-                    let resp = HttpResponse::Found()
-                        .insert_header((header::LOCATION, "/login"))
-                        .finish()
-                        .map_into_right_body();
-
-                    return Ok(ServiceResponse::new(request, resp));
+            //  static files need no session, nor the database every page loads them
+            if is_asset(&req) {
+                return service
+                    .call(req)
+                    .await
+                    .map(ServiceResponse::map_into_left_body);
+            }
+
+            let cipher = req.extract::<AppDataCipher>().await.unwrap();
+            let db = req.extract::<AppDataDb>().await.unwrap();
+            let geoip = req.extract::<AppDataGeoIp>().await.unwrap();
+
+            //  a token whose session is revoked or anomalous is treated as not logged in
+            let authenticate_token = is_logged_in(&req, cipher)
+                .filter(|token| {
+                    let client = ClientInfo::from_request(req.request());
+                    let activity = req.path() != IDLE_STATUS_PATH;
+                    check_session(&db.lock().unwrap(), &geoip, token, &client, activity).is_ok()
+                })
+                .and_then(|mut token| {
+                    token.password_expired =
+                        User::password_expired(&db.lock().unwrap(), &token.id).ok()?;
+                    Some(token)
+                });
+
+            if let Some(authenticate_token) = authenticate_token {
+                //  nothing under admin is reachable before the expired password changed
+                if authenticate_token.password_expired && is_admin_page(&req) {
+                    return Ok(redirect_to(req, CHANGE_PASSWORD_ROUTE));
                 }
+
+                //  server functions also need to know who is logged in
+                req.extensions_mut()
+                    .insert::<RequestAuthenticationToken>(Rc::new(authenticate_token));
+            } else if is_not_login_page(&req) {
+                return Ok(redirect_to(req, "/login"));
             }
 
             service
@@ -106,7 +139,12 @@ where
 fn is_not_login_page(req: &ServiceRequest) -> bool {
     if let Some(accept) = req.headers().get(ACCEPT) {
         if let Ok(value) = accept.to_str() {
-            if value.find("text/html").is_some() && req.path() != "/login" {
+            if value.find("text/html").is_some()
+                && req.path() != "/login"
+                && !req.path().starts_with(INVITATION_ROUTE)
+                //  calendar apps fetch feeds by their token, never logged in
+                && !req.path().starts_with(LEAVE_FEED_ROUTE)
+            {
                 return true;
             }
         }
@@ -115,6 +153,34 @@ fn is_not_login_page(req: &ServiceRequest) -> bool {
     false
 }
 
+fn redirect_to<B>(req: ServiceRequest, location: &str) -> ServiceResponse<EitherBody<B>> {
+    let (request, _) = req.into_parts();
+    let resp = HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .finish()
+        .map_into_right_body();
+
+    ServiceResponse::new(request, resp)
+}
+
+fn is_asset(req: &ServiceRequest) -> bool {
+    let path = req.path();
+    path == "/favicon.ico"
+        || ["/pkg/", "/images/", "/js/", AVATAR_ROUTE]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+}
+
+fn is_admin_page(req: &ServiceRequest) -> bool {
+    req.path().starts_with(ADMIN_ROUTE_PREFIX)
+        && req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(|accept| accept.contains("text/html"))
+            .unwrap_or(false)
+}
+
 fn is_logged_in(req: &ServiceRequest, cipher: AppDataCipher) -> Option<AuthenticationToken> {
     if let Some(cookie) = req.cookie(LOGIN_COOKIE_NAME) {
         let mut cipher = cipher.lock().unwrap();
@@ -129,3 +195,129 @@ fn is_logged_in(req: &ServiceRequest, cipher: AppDataCipher) -> Option<Authentic
 
 /// only used in cookie name
 pub const LOGIN_COOKIE_NAME: &'static str = "LOGIN";
+
+/// create a session of `user` logged in by `passkey` or by password,
+/// and issue the login cookie to the response of current server function
+pub async fn sign_in(user: User, passkey: bool) -> Result<(), ServerFnError<String>> {
+    let (req, cipher, db, geoip): (HttpRequest, AppDataCipher, AppDataDb, AppDataGeoIp) =
+        extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let client = ClientInfo::from_request(&req);
+    let session_id = create_session(&db.lock().unwrap(), &geoip, &user, &client, passkey)
+        .map_err(|e| ServerFnError::from(e.to_string()))?;
+
+    set_login_cookie(&AuthenticationToken::new(user, session_id), &cipher)
+}
+
+/// issue the login cookie of current user again, so that it lives longer
+pub async fn refresh_login_cookie() -> Result<(), ServerFnError<String>> {
+    let token = current_token().await?;
+    let cipher: AppDataCipher = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    set_login_cookie(&token, &cipher)
+}
+
+/// revoke the session of current user and clean the login cookie
+pub async fn sign_out() -> Result<(), ServerFnError<String>> {
+    if let Ok(token) = current_token().await {
+        let db: AppDataDb = extract()
+            .await
+            .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+        revoke_session(&db.lock().unwrap(), &token.session_id)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+    }
+
+    let cookie = Cookie::build(LOGIN_COOKIE_NAME, "")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .path("/")
+        .finish();
+
+    set_cookie(cookie)
+}
+
+fn set_login_cookie(
+    token: &AuthenticationToken,
+    cipher: &AppDataCipher,
+) -> Result<(), ServerFnError<String>> {
+    let mut cipher = cipher.lock().unwrap();
+    let encrypted_token = cipher
+        .encrypt(&token.to_json().as_bytes().to_vec())
+        .map_err(|_| ServerFnError::from("server cannot encrypt token".to_string()))?;
+    let encrypted_token = BASE64_STANDARD.encode(encrypted_token);
+
+    let cookie = Cookie::build(LOGIN_COOKIE_NAME, encrypted_token)
+        .max_age(Duration::seconds(SESSION_LIFETIME))
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .path("/")
+        .finish();
+
+    set_cookie(cookie)
+}
+
+fn set_cookie(cookie: Cookie) -> Result<(), ServerFnError<String>> {
+    let cookie = HeaderValue::from_str(&cookie.to_string())
+        .map_err(|_| ServerFnError::from("server cannot set cookies".to_string()))?;
+
+    let response = expect_context::<ResponseOptions>();
+    response.insert_header(header::SET_COOKIE, cookie);
+
+    Ok(())
+}
+
+/// get the [AuthenticationToken] of current user in server functions,
+/// fails if the password of current user is expired
+pub async fn current_token() -> Result<AuthenticationToken, ServerFnError<String>> {
+    let token = current_token_for_password_change().await?;
+    if token.password_expired {
+        return Err(ServerFnError::from("password expired".to_string()));
+    }
+
+    Ok(token)
+}
+
+/// get the [AuthenticationToken] of current user in server functions,
+/// even if the password is expired, only for changing password
+pub async fn current_token_for_password_change(
+) -> Result<AuthenticationToken, ServerFnError<String>> {
+    let authenticated = extract::<Authenticated>()
+        .await
+        .map_err(|_| ServerFnError::from("not logged in".to_string()))?;
+
+    Ok(AuthenticationToken::clone(&authenticated))
+}
+
+/// get the [AuthenticationToken] of current user in server functions,
+/// fails unless current user has `role`
+pub async fn require_role(role: &str) -> Result<AuthenticationToken, ServerFnError<String>> {
+    require_any_role(&[role]).await
+}
+
+/// get the [AuthenticationToken] of current user in server functions,
+/// fails unless current user has one of `roles`
+pub async fn require_any_role(
+    roles: &[&str],
+) -> Result<AuthenticationToken, ServerFnError<String>> {
+    let token = current_token().await?;
+    let db: AppDataDb = extract()
+        .await
+        .map_err(|_| ServerFnError::from("extract fail".to_string()))?;
+
+    let conn = db.lock().unwrap();
+    for role in roles {
+        let has_role = User::has_role(&conn, &token.id, role)
+            .map_err(|e| ServerFnError::from(e.to_string()))?;
+        if has_role {
+            return Ok(token);
+        }
+    }
+
+    Err(ServerFnError::from("permission denied".to_string()))
+}
@@ -3,6 +3,7 @@ use actix_web::{
     http::StatusCode,
     FromRequest, HttpMessage, HttpResponse, ResponseError,
 };
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::ops::Deref;
 use std::rc::Rc;
@@ -78,22 +79,43 @@ pub type RequestAuthenticationToken = Rc<AuthenticationToken>;
 ///     None
 /// }
 /// ```
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AuthenticationToken {
     /// current user id
     pub id: String,
     /// current username
     pub username: String,
+    /// id of the session created when logged in
+    pub session_id: String,
+    /// password must be changed before anything else, set by middleware on every request
+    #[serde(skip)]
+    pub password_expired: bool,
 }
 
 impl AuthenticationToken {
+    pub fn new(user: User, session_id: String) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            session_id,
+            password_expired: false,
+        }
+    }
+
     /// build AuthenticationToken from cookie
     /// if cookie is invalid or empty, it returns None
     pub fn from_json(json: &str) -> Option<Self> {
-        let user: User = serde_json::from_str(json).ok()?;
+        serde_json::from_str(json).ok()
+    }
 
-        Some(Self {
-            id: user.id,
-            username: user.username,
-        })
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialize AuthenticationToken fail")
+    }
+
+    pub fn to_user(&self) -> User {
+        User {
+            id: self.id.clone(),
+            username: self.username.clone(),
+        }
     }
 }
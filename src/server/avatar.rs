@@ -0,0 +1,142 @@
+//! Avatar
+//! uploaded avatars are cropped to a square at the center and resized to all of [AVATAR_SIZES],
+//! the files are kept in [SETTINGS] `avatar_dir` and served at [AVATAR_ROUTE]
+//!
+//! every upload is saved as a new version, so browsers never show a cached old avatar
+//!
+//! images are decoded and resized by [write_avatar] without the database,
+//! only [set_avatar] needs the connection
+
+use super::SETTINGS;
+use crate::models::consts::{AVATAR_ROUTE, AVATAR_SIZES};
+use base64::prelude::*;
+use image::{imageops::FilterType, ImageFormat, ImageReader, Limits};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{fmt, fs, io::Cursor, path::PathBuf};
+
+/// the largest upload accepted, in bytes
+const MAX_AVATAR_BYTES: usize = 5 * 1024 * 1024;
+/// the largest width and height of an uploaded image, in px
+const MAX_AVATAR_DIMENSION: u32 = 8192;
+
+pub enum AvatarError {
+    TooLarge,
+    InvalidImage,
+    NotExist,
+    Storage,
+}
+
+impl fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AvatarError::TooLarge => write!(f, "avatar must be smaller than 5 MB"),
+            AvatarError::InvalidImage => write!(f, "avatar is not a supported image"),
+            AvatarError::NotExist => write!(f, "user not exist"),
+            AvatarError::Storage => write!(f, "avatar storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AvatarError {
+    fn from(_: rusqlite::Error) -> Self {
+        AvatarError::Storage
+    }
+}
+
+impl From<std::io::Error> for AvatarError {
+    fn from(_: std::io::Error) -> Self {
+        AvatarError::Storage
+    }
+}
+
+/// write the files of `data`, an image as base64 or data url, as a new avatar of `user_id`,
+/// returns the new avatar url to be set by [set_avatar]
+pub fn write_avatar(user_id: &str, data: &str) -> Result<String, AvatarError> {
+    let data = data.split_once(',').map(|(_, data)| data).unwrap_or(data);
+    if data.len() > MAX_AVATAR_BYTES / 3 * 4 + 4 {
+        return Err(AvatarError::TooLarge);
+    }
+    let bytes = BASE64_STANDARD
+        .decode(data.trim())
+        .map_err(|_| AvatarError::InvalidImage)?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_AVATAR_DIMENSION);
+    limits.max_image_height = Some(MAX_AVATAR_DIMENSION);
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|_| AvatarError::InvalidImage)?;
+    reader.limits(limits);
+    let image = reader.decode().map_err(|_| AvatarError::InvalidImage)?;
+
+    let side = image.width().min(image.height());
+    let square = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+
+    fs::create_dir_all(&SETTINGS.avatar_dir)?;
+    let version = chrono::Utc::now().timestamp_millis();
+    let stem = format!("{}-{}", user_id, version);
+    for size in AVATAR_SIZES {
+        square
+            .resize_exact(size, size, FilterType::Lanczos3)
+            .save_with_format(file_path(&stem, size), ImageFormat::Png)
+            .map_err(|_| AvatarError::Storage)?;
+    }
+
+    Ok(format!("{}/{}-{{size}}.png", AVATAR_ROUTE, stem))
+}
+
+/// set `avatar` written by [write_avatar] as the avatar of `user_id`,
+/// the files of the previous one are removed, and of `avatar` if it cannot be set
+pub fn set_avatar(conn: &Connection, user_id: &str, avatar: &str) -> Result<(), AvatarError> {
+    let result = conn
+        .query_row(
+            "SELECT avatar FROM users WHERE id = ?1",
+            params![user_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(AvatarError::from)
+        .and_then(|previous| previous.ok_or(AvatarError::NotExist))
+        .and_then(|previous| {
+            conn.execute(
+                "UPDATE users SET avatar = ?1 WHERE id = ?2",
+                params![avatar, user_id],
+            )?;
+            Ok(previous)
+        });
+
+    match result {
+        Ok(previous) => {
+            remove_files(&previous);
+            Ok(())
+        }
+        Err(e) => {
+            remove_files(avatar);
+            Err(e)
+        }
+    }
+}
+
+fn file_path(stem: &str, size: u32) -> PathBuf {
+    PathBuf::from(&SETTINGS.avatar_dir).join(format!("{}-{}.png", stem, size))
+}
+
+/// remove the files of an uploaded avatar, nothing to do for others
+pub(super) fn remove_files(avatar: &str) {
+    let Some(stem) = avatar
+        .strip_prefix(AVATAR_ROUTE)
+        .and_then(|name| name.strip_prefix('/'))
+        .and_then(|name| name.strip_suffix("-{size}.png"))
+    else {
+        return;
+    };
+
+    for size in AVATAR_SIZES {
+        _ = fs::remove_file(file_path(stem, size));
+    }
+}
@@ -0,0 +1,694 @@
+//! Calendar
+//! the working calendar counts the working days of leave requests,
+//! every day is a working day except the weekend and the holidays of a [HolidayCalendar]
+//!
+//! - administrators manage a calendar for every region, with holidays on dates, holidays on the
+//! same day every year by [HolidayRule]s, and weekend days worked in exchange for holidays
+//! - holidays are added one by one or imported from iCalendar files, yearly events become rules
+//! - a user follows the calendar assigned to them, else the one of their department or its
+//! nearest ancestor, else the default calendar
+//! - a calendar without a weekend of its own uses the weekend of [SETTINGS] `weekend`
+
+use super::department::{ancestors, department_of, DepartmentError};
+use super::SETTINGS;
+use crate::models::{
+    FieldErrors, Holiday, HolidayCalendar, HolidayCalendarForm, HolidayImport, HolidayRule,
+    LeaveError, User, WorkingCalendar, DEFAULT_CALENDAR_ID,
+};
+use base64::prelude::*;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// the longest name of a holiday or a calendar
+const MAX_HOLIDAY_NAME_LENGTH: usize = 64;
+/// the most days an event of an iCalendar file may last
+const MAX_EVENT_DAYS: i64 = 31;
+/// the most holidays and rules an iCalendar file may have
+const MAX_IMPORT_HOLIDAYS: usize = 1000;
+
+pub enum CalendarError {
+    NotExist,
+    /// the default calendar cannot be deleted
+    DefaultCalendar,
+    InvalidFile,
+    TooManyHolidays,
+    Storage,
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarError::NotExist => write!(f, "holiday calendar not exist"),
+            CalendarError::DefaultCalendar => write!(f, "default calendar cannot be deleted"),
+            CalendarError::InvalidFile => write!(f, "file is not a valid iCalendar file"),
+            CalendarError::TooManyHolidays => write!(
+                f,
+                "at most {} holidays can be imported at once",
+                MAX_IMPORT_HOLIDAYS
+            ),
+            CalendarError::Storage => write!(f, "holiday storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for CalendarError {
+    fn from(_: rusqlite::Error) -> Self {
+        CalendarError::Storage
+    }
+}
+
+impl From<DepartmentError> for CalendarError {
+    fn from(e: DepartmentError) -> Self {
+        match e {
+            DepartmentError::NotExist => CalendarError::NotExist,
+            _ => CalendarError::Storage,
+        }
+    }
+}
+
+impl From<CalendarError> for LeaveError {
+    fn from(_: CalendarError) -> Self {
+        LeaveError::Storage
+    }
+}
+
+impl HolidayCalendar {
+    /// all calendars with the departments and users assigned, the default calendar first
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, CalendarError> {
+        let mut stmt =
+            conn.prepare("SELECT id, name, weekend FROM holiday_calendars ORDER BY id")?;
+        let mut list = stmt
+            .query_map([], |row| {
+                Ok(HolidayCalendar {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    weekend: parse_weekend(&row.get::<_, String>(2)?),
+                    ..Default::default()
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT holiday_calendar_id, id, name FROM departments
+             WHERE holiday_calendar_id IS NOT NULL AND deleted_at IS NULL ORDER BY name",
+        )?;
+        let departments = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, (row.get(1)?, row.get(2)?)))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut stmt = conn.prepare(
+            "SELECT holiday_calendar_id, id, username FROM users
+             WHERE holiday_calendar_id IS NOT NULL AND deleted_at IS NULL ORDER BY username",
+        )?;
+        let users = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    User {
+                        id: row.get(1)?,
+                        username: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for calendar in list.iter_mut() {
+            calendar.departments = departments
+                .iter()
+                .filter(|(id, _)| *id == calendar.id)
+                .map(|(_, department)| department.clone())
+                .collect();
+            calendar.users = users
+                .iter()
+                .filter(|(id, _)| *id == calendar.id)
+                .map(|(_, user)| user.clone())
+                .collect();
+        }
+
+        Ok(list)
+    }
+
+    /// create or update a calendar, returns the calendar id
+    pub fn save(conn: &Connection, form: &HolidayCalendarForm) -> Result<i64, CalendarError> {
+        let weekend = form
+            .weekend
+            .iter()
+            .map(|day| day.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        match form.id {
+            Some(id) => {
+                let updated = conn.execute(
+                    "UPDATE holiday_calendars SET name = ?1, weekend = ?2 WHERE id = ?3",
+                    params![form.name.trim(), weekend, id],
+                )?;
+                if updated == 0 {
+                    return Err(CalendarError::NotExist);
+                }
+                Ok(id)
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO holiday_calendars (name, weekend) VALUES (?1, ?2)",
+                    params![form.name.trim(), weekend],
+                )?;
+                Ok(conn.last_insert_rowid())
+            }
+        }
+    }
+
+    /// delete a calendar with its holidays, departments and users assigned follow the default one
+    pub fn delete(conn: &Connection, id: i64) -> Result<(), CalendarError> {
+        if id == DEFAULT_CALENDAR_ID {
+            return Err(CalendarError::DefaultCalendar);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM holidays WHERE calendar_id = ?1", params![id])?;
+        tx.execute(
+            "DELETE FROM holiday_rules WHERE calendar_id = ?1",
+            params![id],
+        )?;
+        tx.execute(
+            "UPDATE departments SET holiday_calendar_id = NULL WHERE holiday_calendar_id = ?1",
+            params![id],
+        )?;
+        tx.execute(
+            "UPDATE users SET holiday_calendar_id = NULL WHERE holiday_calendar_id = ?1",
+            params![id],
+        )?;
+        tx.execute("DELETE FROM holiday_calendars WHERE id = ?1", params![id])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    pub fn exists(conn: &Connection, id: i64) -> Result<bool, CalendarError> {
+        let exists = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM holiday_calendars WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// assign calendar `id` to a department, None to follow the parent department
+    pub fn assign_department(
+        conn: &Connection,
+        department_id: &str,
+        id: Option<i64>,
+    ) -> Result<(), CalendarError> {
+        if let Some(id) = id {
+            if !Self::exists(conn, id)? {
+                return Err(CalendarError::NotExist);
+            }
+        }
+        let updated = conn.execute(
+            "UPDATE departments SET holiday_calendar_id = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![id, department_id],
+        )?;
+        if updated == 0 {
+            return Err(CalendarError::NotExist);
+        }
+        Ok(())
+    }
+
+    /// assign calendar `id` to a user, None to follow their department
+    pub fn assign_user(
+        conn: &Connection,
+        user_id: &str,
+        id: Option<i64>,
+    ) -> Result<(), CalendarError> {
+        if let Some(id) = id {
+            if !Self::exists(conn, id)? {
+                return Err(CalendarError::NotExist);
+            }
+        }
+        let updated = conn.execute(
+            "UPDATE users SET holiday_calendar_id = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![id, user_id],
+        )?;
+        if updated == 0 {
+            return Err(CalendarError::NotExist);
+        }
+        Ok(())
+    }
+}
+
+impl HolidayCalendarForm {
+    /// errors of every invalid field, empty if the form is valid
+    pub fn validate(&self, conn: &Connection) -> Result<FieldErrors, CalendarError> {
+        let mut errors = FieldErrors::new();
+        let name = self.name.trim();
+        let length = name.chars().count();
+        if length == 0 || length > MAX_HOLIDAY_NAME_LENGTH {
+            errors.insert(
+                "name".into(),
+                format!("must have 1 to {} characters", MAX_HOLIDAY_NAME_LENGTH),
+            );
+        } else {
+            let taken: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM holiday_calendars WHERE name = ?1 AND id IS NOT ?2)",
+                params![name, self.id],
+                |row| row.get(0),
+            )?;
+            if taken {
+                errors.insert("name".into(), "is taken by another calendar".into());
+            }
+        }
+        if self.weekend.len() == 7 {
+            errors.insert("weekend".into(), "must leave a working day".into());
+        }
+        Ok(errors)
+    }
+}
+
+impl Holiday {
+    /// holidays of calendar `calendar_id` in `year`, the earliest first
+    pub fn list(
+        conn: &Connection,
+        calendar_id: i64,
+        year: i32,
+    ) -> Result<Vec<Self>, CalendarError> {
+        let mut stmt = conn.prepare(
+            "SELECT date, name, working FROM holidays
+             WHERE calendar_id = ?1 AND strftime('%Y', date) = ?2 ORDER BY date",
+        )?;
+        let list = stmt
+            .query_map(params![calendar_id, format!("{:04}", year)], |row| {
+                Ok(Holiday {
+                    date: row.get(0)?,
+                    name: row.get(1)?,
+                    working: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// errors of every invalid field, empty if the holiday is valid
+    pub fn validate(&self) -> FieldErrors {
+        validate_name(&self.name)
+    }
+
+    /// add the holiday to calendar `calendar_id` or replace the one of the same date,
+    /// submitted leave requests keep their working days
+    pub fn save(&self, conn: &Connection, calendar_id: i64) -> Result<(), CalendarError> {
+        if !HolidayCalendar::exists(conn, calendar_id)? {
+            return Err(CalendarError::NotExist);
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO holidays (calendar_id, date, name, working) VALUES (?1, ?2, ?3, ?4)",
+            params![calendar_id, self.date, self.name.trim(), self.working],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(
+        conn: &Connection,
+        calendar_id: i64,
+        date: NaiveDate,
+    ) -> Result<(), CalendarError> {
+        conn.execute(
+            "DELETE FROM holidays WHERE calendar_id = ?1 AND date = ?2",
+            params![calendar_id, date],
+        )?;
+        Ok(())
+    }
+}
+
+impl HolidayRule {
+    /// rules of calendar `calendar_id` in the order of the year
+    pub fn list(conn: &Connection, calendar_id: i64) -> Result<Vec<Self>, CalendarError> {
+        let mut stmt = conn.prepare(
+            "SELECT id, month, day, name FROM holiday_rules WHERE calendar_id = ?1 ORDER BY month, day",
+        )?;
+        let list = stmt
+            .query_map(params![calendar_id], |row| {
+                Ok(HolidayRule {
+                    id: row.get(0)?,
+                    month: row.get(1)?,
+                    day: row.get(2)?,
+                    name: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// errors of every invalid field, empty if the rule is valid
+    pub fn validate(&self) -> FieldErrors {
+        let mut errors = validate_name(&self.name);
+        //  a leap year, so that February 29 is a valid day
+        if NaiveDate::from_ymd_opt(2000, self.month, self.day).is_none() {
+            errors.insert("day".into(), "must be a day of the month".into());
+        }
+        errors
+    }
+
+    /// add the rule to calendar `calendar_id` or rename the one of the same day
+    pub fn save(&self, conn: &Connection, calendar_id: i64) -> Result<(), CalendarError> {
+        if !HolidayCalendar::exists(conn, calendar_id)? {
+            return Err(CalendarError::NotExist);
+        }
+        conn.execute(
+            "INSERT INTO holiday_rules (calendar_id, month, day, name) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (calendar_id, month, day) DO UPDATE SET name = excluded.name",
+            params![calendar_id, self.month, self.day, self.name.trim()],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, id: i64) -> Result<(), CalendarError> {
+        conn.execute("DELETE FROM holiday_rules WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+impl WorkingCalendar {
+    /// the weekend and the holidays of calendar `calendar_id` from `start` to `end`,
+    /// holidays on dates take precedence over rules
+    pub fn load(
+        conn: &Connection,
+        calendar_id: i64,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Self, CalendarError> {
+        let weekend = conn
+            .query_row(
+                "SELECT weekend FROM holiday_calendars WHERE id = ?1",
+                params![calendar_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .ok_or(CalendarError::NotExist)?;
+        let weekend = parse_weekend(&weekend);
+
+        let mut stmt = conn.prepare(
+            "SELECT date, name, working FROM holidays
+             WHERE calendar_id = ?1 AND date BETWEEN ?2 AND ?3 ORDER BY date",
+        )?;
+        let dated = stmt
+            .query_map(params![calendar_id, start, end], |row| {
+                Ok(Holiday {
+                    date: row.get(0)?,
+                    name: row.get(1)?,
+                    working: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut holidays = BTreeMap::new();
+        let mut working_weekend_days = BTreeMap::new();
+        for holiday in dated {
+            if holiday.working {
+                working_weekend_days.insert(holiday.date, holiday.name);
+            } else {
+                holidays.insert(holiday.date, holiday.name);
+            }
+        }
+        for rule in HolidayRule::list(conn, calendar_id)? {
+            for year in start.year()..=end.year() {
+                let Some(date) = NaiveDate::from_ymd_opt(year, rule.month, rule.day) else {
+                    continue;
+                };
+                if date < start || date > end || working_weekend_days.contains_key(&date) {
+                    continue;
+                }
+                holidays.entry(date).or_insert_with(|| rule.name.clone());
+            }
+        }
+
+        Ok(WorkingCalendar {
+            weekend: if weekend.is_empty() {
+                SETTINGS.weekend.clone()
+            } else {
+                weekend
+            },
+            holidays,
+            working_weekend_days,
+        })
+    }
+
+    /// the working calendar of `user_id` from `start` to `end`
+    pub fn of_user(
+        conn: &Connection,
+        user_id: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Self, CalendarError> {
+        let calendar_id = calendar_of(conn, user_id)?;
+        Self::load(conn, calendar_id, start, end)
+    }
+}
+
+/// the calendar of `user_id`: their own, else the one of their department or its nearest
+/// ancestor, else the default calendar
+pub fn calendar_of(conn: &Connection, user_id: &str) -> Result<i64, CalendarError> {
+    let own = conn
+        .query_row(
+            "SELECT holiday_calendar_id FROM users WHERE id = ?1 AND deleted_at IS NULL",
+            params![user_id],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .optional()?
+        .flatten();
+    if let Some(id) = own {
+        return Ok(id);
+    }
+
+    let Some(department_id) = department_of(conn, user_id)? else {
+        return Ok(DEFAULT_CALENDAR_ID);
+    };
+    for id in ancestors(conn, &department_id)? {
+        let assigned = conn
+            .query_row(
+                "SELECT holiday_calendar_id FROM departments WHERE id = ?1 AND deleted_at IS NULL",
+                params![id],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .optional()?
+            .flatten();
+        if let Some(calendar_id) = assigned {
+            return Ok(calendar_id);
+        }
+    }
+
+    Ok(DEFAULT_CALENDAR_ID)
+}
+
+/// import the holidays of `data`, an iCalendar file as text or data url, into calendar
+/// `calendar_id`, events repeated yearly on a single day become rules
+pub fn import_ics(
+    conn: &Connection,
+    calendar_id: i64,
+    data: &str,
+) -> Result<HolidayImport, CalendarError> {
+    if !HolidayCalendar::exists(conn, calendar_id)? {
+        return Err(CalendarError::NotExist);
+    }
+    let text = decode(data)?;
+    let (holidays, rules) = parse_ics(&text)?;
+    if holidays.len() + rules.len() > MAX_IMPORT_HOLIDAYS {
+        return Err(CalendarError::TooManyHolidays);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for holiday in &holidays {
+        holiday.save(&tx, calendar_id)?;
+    }
+    for rule in &rules {
+        rule.save(&tx, calendar_id)?;
+    }
+    tx.commit()?;
+
+    Ok(HolidayImport {
+        holidays: holidays.len(),
+        rules: rules.len(),
+    })
+}
+
+/// holidays and rules of the events of an iCalendar file, names cut to the longest allowed
+fn parse_ics(text: &str) -> Result<(Vec<Holiday>, Vec<HolidayRule>), CalendarError> {
+    //  long lines are folded by a line break followed by a space or a tab
+    let unfolded = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    if !unfolded.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Err(CalendarError::InvalidFile);
+    }
+
+    let mut holidays = vec![];
+    let mut rules = vec![];
+    let mut event: Option<BTreeMap<String, String>> = None;
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        //  parameters such as `;VALUE=DATE` follow the property name
+        let name = name.split(';').next().unwrap_or_default().to_uppercase();
+        match (name.as_str(), value) {
+            ("BEGIN", "VEVENT") => event = Some(BTreeMap::new()),
+            ("END", "VEVENT") => {
+                let Some(properties) = event.take() else {
+                    return Err(CalendarError::InvalidFile);
+                };
+                let start = properties
+                    .get("DTSTART")
+                    .and_then(|value| parse_ics_date(value))
+                    .ok_or(CalendarError::InvalidFile)?;
+                //  the end date is exclusive, a missing one means a single day
+                let last = properties
+                    .get("DTEND")
+                    .and_then(|value| parse_ics_date(value))
+                    .and_then(|end| end.pred_opt())
+                    .unwrap_or(start)
+                    .max(start);
+                if (last - start).num_days() >= MAX_EVENT_DAYS {
+                    return Err(CalendarError::InvalidFile);
+                }
+                let name: String = properties
+                    .get("SUMMARY")
+                    .map(|summary| unescape(summary))
+                    .filter(|summary| !summary.trim().is_empty())
+                    .unwrap_or_else(|| "Holiday".to_string())
+                    .trim()
+                    .chars()
+                    .take(MAX_HOLIDAY_NAME_LENGTH)
+                    .collect();
+                let yearly = properties
+                    .get("RRULE")
+                    .is_some_and(|rule| rule.split(';').any(|part| part == "FREQ=YEARLY"));
+
+                if yearly && start == last {
+                    rules.push(HolidayRule {
+                        id: 0,
+                        month: start.month(),
+                        day: start.day(),
+                        name,
+                    });
+                } else {
+                    let mut day = start;
+                    while day <= last {
+                        holidays.push(Holiday {
+                            date: day,
+                            name: name.clone(),
+                            working: false,
+                        });
+                        day += Duration::days(1);
+                    }
+                }
+                if holidays.len() + rules.len() > MAX_IMPORT_HOLIDAYS {
+                    return Err(CalendarError::TooManyHolidays);
+                }
+            }
+            _ => {
+                if let Some(properties) = event.as_mut() {
+                    properties.entry(name).or_insert_with(|| value.to_string());
+                }
+            }
+        }
+    }
+
+    Ok((holidays, rules))
+}
+
+/// the date of an iCalendar date or date-time such as `20250101` or `20250101T000000Z`
+fn parse_ics_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// text of `data`, which is a data url or the text itself
+fn decode(data: &str) -> Result<String, CalendarError> {
+    let Some((_, encoded)) = data
+        .strip_prefix("data:")
+        .and_then(|data| data.split_once(","))
+    else {
+        return Ok(data.to_string());
+    };
+
+    let bytes = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|_| CalendarError::InvalidFile)?;
+    String::from_utf8(bytes).map_err(|_| CalendarError::InvalidFile)
+}
+
+/// days of a weekend saved like `Sat,Sun`
+fn parse_weekend(value: &str) -> Vec<Weekday> {
+    value
+        .split(',')
+        .filter_map(|day| day.trim().parse().ok())
+        .collect()
+}
+
+fn validate_name(name: &str) -> FieldErrors {
+    let mut errors = FieldErrors::new();
+    let length = name.trim().chars().count();
+    if length == 0 || length > MAX_HOLIDAY_NAME_LENGTH {
+        errors.insert(
+            "name".into(),
+            format!("must have 1 to {} characters", MAX_HOLIDAY_NAME_LENGTH),
+        );
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DayPart;
+    use crate::server::new_test_db;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn duration_of_working_days_only() {
+        let conn = new_test_db();
+        conn.execute(
+            "UPDATE holiday_calendars SET weekend = 'Sat,Sun' WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        //  Wednesday off, and Saturday worked in exchange
+        conn.execute(
+            "INSERT INTO holidays (calendar_id, date, name, working)
+             VALUES (1, ?1, 'Holiday', 0), (1, ?2, 'Exchange', 1)",
+            params![date("2030-06-05"), date("2030-06-08")],
+        )
+        .unwrap();
+        let calendar = WorkingCalendar::load(&conn, 1, date("2030-06-03"), date("2030-06-16"))
+            .ok()
+            .unwrap();
+        let duration = |start: &str, start_part, end: &str, end_part| {
+            calendar.duration(date(start), start_part, date(end), end_part)
+        };
+        let (full, morning, afternoon) = (DayPart::Full, DayPart::Morning, DayPart::Afternoon);
+
+        //  Monday to Friday
+        assert!(duration("2030-06-03", full, "2030-06-07", full) == 4.0);
+        assert!(duration("2030-06-15", full, "2030-06-16", full) == 0.0);
+        assert!(duration("2030-06-08", full, "2030-06-09", full) == 1.0);
+        assert!(duration("2030-06-10", morning, "2030-06-10", morning) == 0.5);
+        //  from Monday afternoon until Friday morning
+        assert!(duration("2030-06-10", afternoon, "2030-06-14", morning) == 4.0);
+        //  half days on a holiday are not taken
+        assert!(duration("2030-06-05", afternoon, "2030-06-06", full) == 1.0);
+    }
+}
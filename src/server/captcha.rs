@@ -0,0 +1,286 @@
+//! Captcha
+//! self hosted arithmetic CAPTCHA, no external service is required
+//!
+//! - failures of login are counted per ip and per username,
+//! a CAPTCHA is required after the configured number of failures
+//! - the question is rendered by server as a distorted PNG image with noise,
+//! the answer is kept in [AppDataCaptcha] and can be verified only once
+
+use super::SETTINGS;
+use crate::models::Captcha;
+use actix_web::web::Data;
+use base64::prelude::*;
+use image::{ImageFormat, Rgb, RgbImage};
+use rand::Rng;
+use rusqlite::{params, Connection};
+use std::{collections::HashMap, f64::consts::TAU, io::Cursor, sync::Mutex};
+
+/// seconds a CAPTCHA is valid after issued
+const CAPTCHA_TTL: i64 = 300;
+
+/// app data of issued CAPTCHAs
+/// used in actix app_data, must be shared by all workers
+pub type AppDataCaptcha = Data<Mutex<CaptchaStore>>;
+
+pub fn new_app_data_captcha() -> AppDataCaptcha {
+    Data::new(Mutex::new(CaptchaStore::default()))
+}
+
+/// answers and expiry of issued CAPTCHAs, keyed by CAPTCHA id
+#[derive(Default)]
+pub struct CaptchaStore {
+    pending: HashMap<String, (i64, i64)>,
+}
+
+impl CaptchaStore {
+    pub fn issue(&mut self) -> Captcha {
+        let now = chrono::Utc::now().timestamp();
+        self.pending.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let mut rng = rand::thread_rng();
+        let (question, answer) = match rng.gen_range(0..3) {
+            0 => {
+                let (a, b) = (rng.gen_range(1..20), rng.gen_range(1..20));
+                (format!("{}+{}", a, b), a + b)
+            }
+            1 => {
+                let (a, b) = (rng.gen_range(10..30), rng.gen_range(1..10));
+                (format!("{}-{}", a, b), a - b)
+            }
+            _ => {
+                let (a, b) = (rng.gen_range(2..10), rng.gen_range(2..10));
+                (format!("{}x{}", a, b), a * b)
+            }
+        };
+
+        let id = BASE64_URL_SAFE_NO_PAD.encode(rng.gen::<[u8; 16]>());
+        self.pending.insert(id.clone(), (answer, now + CAPTCHA_TTL));
+
+        Captcha {
+            id,
+            image: format!(
+                "data:image/png;base64,{}",
+                BASE64_STANDARD.encode(render(&question))
+            ),
+        }
+    }
+
+    /// every CAPTCHA can be verified only once, whatever the answer is right or not
+    pub fn verify(&mut self, id: &str, answer: &str) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        match self.pending.remove(id) {
+            Some((expected, expires_at)) => {
+                expires_at > now && answer.trim().parse::<i64>() == Ok(expected)
+            }
+            None => false,
+        }
+    }
+}
+
+/// whether the next login of `username` from `ip` needs a CAPTCHA
+pub fn captcha_required(conn: &Connection, ip: &str, username: &str) -> rusqlite::Result<bool> {
+    let since = chrono::Utc::now().timestamp() - SETTINGS.login_failure_window;
+    let count: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(count), 0) FROM login_failures
+         WHERE key IN (?1, ?2) AND last_failed_at >= ?3",
+        params![ip_key(ip), username_key(username), since],
+        |row| row.get(0),
+    )?;
+
+    Ok(count >= SETTINGS.captcha_after_failures)
+}
+
+pub fn record_login_failure(conn: &Connection, ip: &str, username: &str) -> rusqlite::Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    let since = now - SETTINGS.login_failure_window;
+
+    for key in [ip_key(ip), username_key(username)] {
+        conn.execute(
+            "INSERT INTO login_failures (key, count, last_failed_at) VALUES (?1, 1, ?2)
+             ON CONFLICT (key) DO UPDATE SET
+                count = CASE WHEN last_failed_at >= ?3 THEN count + 1 ELSE 1 END,
+                last_failed_at = ?2",
+            params![key, now, since],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn clear_login_failures(conn: &Connection, ip: &str, username: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM login_failures WHERE key IN (?1, ?2)",
+        params![ip_key(ip), username_key(username)],
+    )?;
+    Ok(())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+fn username_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+const GLYPH_WIDTH: f64 = 12.0;
+const GLYPH_HEIGHT: f64 = 22.0;
+const GLYPH_ADVANCE: f64 = 24.0;
+
+/// strokes of a glyph in a box of [GLYPH_WIDTH] x [GLYPH_HEIGHT], digits are seven-segment
+fn glyph(c: char) -> Vec<[(f64, f64); 2]> {
+    let (w, h, m) = (GLYPH_WIDTH, GLYPH_HEIGHT, GLYPH_HEIGHT / 2.0);
+    let a = [(0.0, 0.0), (w, 0.0)];
+    let b = [(w, 0.0), (w, m)];
+    let c_ = [(w, m), (w, h)];
+    let d = [(0.0, h), (w, h)];
+    let e = [(0.0, m), (0.0, h)];
+    let f = [(0.0, 0.0), (0.0, m)];
+    let g = [(0.0, m), (w, m)];
+
+    match c {
+        '0' => vec![a, b, c_, d, e, f],
+        '1' => vec![b, c_],
+        '2' => vec![a, b, g, e, d],
+        '3' => vec![a, b, g, c_, d],
+        '4' => vec![f, g, b, c_],
+        '5' => vec![a, f, g, c_, d],
+        '6' => vec![a, f, g, e, d, c_],
+        '7' => vec![a, b, c_],
+        '8' => vec![a, b, c_, d, e, f, g],
+        '9' => vec![a, b, c_, d, f, g],
+        '+' => vec![g, [(w / 2.0, m - 6.0), (w / 2.0, m + 6.0)]],
+        '-' => vec![g],
+        'x' => vec![
+            [(1.0, m - 6.0), (w - 1.0, m + 6.0)],
+            [(w - 1.0, m - 6.0), (1.0, m + 6.0)],
+        ],
+        _ => vec![],
+    }
+}
+
+/// render `question` as a PNG image, every glyph is moved, rotated and jittered randomly,
+/// then the image is waved and covered by noise lines and speckles,
+/// so that the question is not in the markup and its strokes are no longer straight
+fn render(question: &str) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let width = (16.0 + GLYPH_ADVANCE * question.len() as f64) as u32;
+    let height = 40;
+    let mut canvas = RgbImage::from_fn(width, height, |_, _| {
+        let v = rng.gen_range(225..=255);
+        Rgb([v, v, v])
+    });
+
+    for (index, c) in question.chars().enumerate() {
+        let x = 10.0 + GLYPH_ADVANCE * index as f64 + rng.gen_range(-2.0..2.0);
+        let y = 9.0 + rng.gen_range(-3.0..3.0);
+        let (sin, cos) = rng.gen_range(-0.3..0.3f64).sin_cos();
+        let color = Rgb([
+            rng.gen_range(0..120),
+            rng.gen_range(0..120),
+            rng.gen_range(0..120),
+        ]);
+        let radius = rng.gen_range(1.0..1.6);
+        for stroke in glyph(c) {
+            let [from, to] = stroke.map(|(px, py)| {
+                let dx = px + rng.gen_range(-1.5..1.5) - GLYPH_WIDTH / 2.0;
+                let dy = py + rng.gen_range(-1.5..1.5) - GLYPH_HEIGHT / 2.0;
+                (
+                    x + GLYPH_WIDTH / 2.0 + dx * cos - dy * sin,
+                    y + GLYPH_HEIGHT / 2.0 + dx * sin + dy * cos,
+                )
+            });
+            draw_line(&mut canvas, from, to, radius, color);
+        }
+    }
+
+    for _ in 0..6 {
+        let from = (
+            rng.gen_range(0.0..width as f64),
+            rng.gen_range(0.0..height as f64),
+        );
+        let to = (
+            rng.gen_range(0.0..width as f64),
+            rng.gen_range(0.0..height as f64),
+        );
+        let v = rng.gen_range(60..180);
+        draw_line(&mut canvas, from, to, 0.6, Rgb([v, v, v]));
+    }
+
+    //  every column is moved up or down along a wave
+    let amplitude = rng.gen_range(1.5..3.0);
+    let period = rng.gen_range(18.0..30.0);
+    let phase = rng.gen_range(0.0..TAU);
+    let mut image = RgbImage::from_fn(width, height, |x, y| {
+        let shift = amplitude * (x as f64 / period * TAU + phase).sin();
+        let source = (y as f64 + shift).round().clamp(0.0, (height - 1) as f64);
+        *canvas.get_pixel(x, source as u32)
+    });
+
+    for _ in 0..width * height / 12 {
+        let v = if rng.gen() {
+            rng.gen_range(0..100)
+        } else {
+            255
+        };
+        image.put_pixel(
+            rng.gen_range(0..width),
+            rng.gen_range(0..height),
+            Rgb([v, v, v]),
+        );
+    }
+
+    let mut png = Cursor::new(vec![]);
+    //  encoding into memory never fails
+    _ = image.write_to(&mut png, ImageFormat::Png);
+    png.into_inner()
+}
+
+/// paint a stroke of `radius` from `from` to `to`, by disks along the line
+fn draw_line(image: &mut RgbImage, from: (f64, f64), to: (f64, f64), radius: f64, color: Rgb<u8>) {
+    let length = (to.0 - from.0).hypot(to.1 - from.1);
+    let steps = (length * 2.0).ceil().max(1.0) as usize;
+    let r = radius.ceil() as i64;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        let (cx, cy) = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let (px, py) = (cx.round() as i64 + dx, cy.round() as i64 + dy);
+                let inside = ((px as f64 - cx).powi(2) + (py as f64 - cy).powi(2)).sqrt() <= radius;
+                if inside
+                    && (0..image.width() as i64).contains(&px)
+                    && (0..image.height() as i64).contains(&py)
+                {
+                    image.put_pixel(px as u32, py as u32, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_is_verified_only_once() {
+        let mut store = CaptchaStore::default();
+        let captcha = store.issue();
+        assert!(captcha.image.starts_with("data:image/png;base64,"));
+        let answer = store.pending[&captcha.id].0.to_string();
+
+        assert!(store.verify(&captcha.id, &answer));
+        assert!(!store.verify(&captcha.id, &answer));
+    }
+
+    #[test]
+    fn wrong_answer_uses_up_the_captcha() {
+        let mut store = CaptchaStore::default();
+        let captcha = store.issue();
+        let answer = store.pending[&captcha.id].0;
+
+        assert!(!store.verify(&captcha.id, &(answer + 1).to_string()));
+        assert!(!store.verify(&captcha.id, &answer.to_string()));
+    }
+}
@@ -0,0 +1,68 @@
+//! Database
+//! sqlite connection shared by server functions and middleware
+//!
+//! migrations in `migrations/` are applied in order when the connection opened,
+//! the applied version is kept in sqlite `user_version`
+
+use super::SETTINGS;
+use crate::models::User;
+use actix_web::web::Data;
+use rusqlite::Connection;
+use std::sync::Mutex;
+
+/// app data database
+/// used in actix app_data
+pub type AppDataDb = Data<Mutex<Connection>>;
+
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../migrations/0001_passkeys.sql"),
+    include_str!("../../migrations/0002_sessions.sql"),
+    include_str!("../../migrations/0003_login_failures.sql"),
+    include_str!("../../migrations/0004_users.sql"),
+    include_str!("../../migrations/0005_session_activity.sql"),
+    include_str!("../../migrations/0006_user_profiles.sql"),
+    include_str!("../../migrations/0007_departments.sql"),
+    include_str!("../../migrations/0008_user_preferences.sql"),
+    include_str!("../../migrations/0009_invitations.sql"),
+    include_str!("../../migrations/0010_soft_delete.sql"),
+    include_str!("../../migrations/0011_scim.sql"),
+    include_str!("../../migrations/0012_leave_requests.sql"),
+    include_str!("../../migrations/0013_leave_status.sql"),
+    include_str!("../../migrations/0014_leave_ledger.sql"),
+    include_str!("../../migrations/0015_approval_chains.sql"),
+    include_str!("../../migrations/0016_leave_durations.sql"),
+    include_str!("../../migrations/0017_holiday_calendars.sql"),
+    include_str!("../../migrations/0018_leave_feeds.sql"),
+    include_str!("../../migrations/0019_leave_attachments.sql"),
+    include_str!("../../migrations/0020_leave_types.sql"),
+    include_str!("../../migrations/0021_leave_balances.sql"),
+];
+
+pub fn new_app_data_db() -> AppDataDb {
+    let conn = Connection::open(&SETTINGS.database_path).expect("open database fail");
+    migrate(&conn).expect("migrate database fail");
+    User::seed(&conn).expect("seed database fail");
+
+    Data::new(Mutex::new(conn))
+}
+
+/// an in memory database migrated and seeded like [new_app_data_db], for tests
+#[cfg(test)]
+pub fn new_test_db() -> Connection {
+    let conn = Connection::open_in_memory().expect("open database fail");
+    migrate(&conn).expect("migrate database fail");
+    User::seed(&conn).expect("seed database fail");
+
+    conn
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        conn.execute_batch(sql)?;
+        conn.pragma_update(None, "user_version", index as i64 + 1)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,402 @@
+//! Department
+//! departments form a tree, every department may have a manager and users are members of
+//! at most one department
+//!
+//! the reporting line of a user is made of the managers of the department and its ancestors,
+//! [manager_of] and [reporting_line] are used by other modules such as leave approval,
+//! [members_of] by the team leave calendar, and [ancestors] by holiday calendars
+
+use crate::models::{Department, DepartmentForm, User, UserStatus};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fmt;
+
+pub enum DepartmentError {
+    NotExist,
+    InvalidName,
+    ManagerNotExist,
+    Cycle,
+    NotEmpty,
+    Storage,
+}
+
+impl fmt::Display for DepartmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DepartmentError::NotExist => write!(f, "department not exist"),
+            DepartmentError::InvalidName => write!(f, "department name must have 1 to 64 characters"),
+            DepartmentError::ManagerNotExist => write!(f, "manager not exist"),
+            DepartmentError::Cycle => write!(f, "department cannot be moved under itself"),
+            DepartmentError::NotEmpty => write!(f, "department has sub departments or members"),
+            DepartmentError::Storage => write!(f, "department storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DepartmentError {
+    fn from(_: rusqlite::Error) -> Self {
+        DepartmentError::Storage
+    }
+}
+
+impl Department {
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, DepartmentError> {
+        let mut stmt = conn.prepare(
+            "SELECT departments.id, departments.name, departments.parent_id,
+                departments.manager_id, managers.display_name,
+                (SELECT COUNT(*) FROM users
+                    WHERE users.department_id = departments.id AND users.deleted_at IS NULL)
+             FROM departments LEFT JOIN users AS managers
+                ON managers.id = departments.manager_id AND managers.deleted_at IS NULL
+             WHERE departments.deleted_at IS NULL
+             ORDER BY departments.name",
+        )?;
+        let list = stmt
+            .query_map([], |row| {
+                Ok(Department {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    manager_id: row.get(3)?,
+                    manager_name: row.get(4)?,
+                    member_count: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    pub fn exists(conn: &Connection, id: &str) -> Result<bool, DepartmentError> {
+        let exists = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM departments WHERE id = ?1 AND deleted_at IS NULL)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// create or update a department, returns the department id
+    pub fn save(conn: &Connection, form: &DepartmentForm) -> Result<String, DepartmentError> {
+        let name = form.name.trim();
+        if name.is_empty() || name.chars().count() > 64 {
+            return Err(DepartmentError::InvalidName);
+        }
+        if let Some(manager_id) = &form.manager_id {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM users WHERE id = ?1 AND deleted_at IS NULL)",
+                params![manager_id],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(DepartmentError::ManagerNotExist);
+            }
+        }
+
+        match &form.id {
+            Some(id) => {
+                //  nothing is changed if it cannot be moved, such as under its sub department
+                let tx = conn.unchecked_transaction()?;
+                let updated = tx.execute(
+                    "UPDATE departments SET name = ?1, manager_id = ?2
+                     WHERE id = ?3 AND deleted_at IS NULL",
+                    params![name, form.manager_id, id],
+                )?;
+                if updated == 0 {
+                    return Err(DepartmentError::NotExist);
+                }
+                Department::move_to(&tx, id, form.parent_id.as_deref())?;
+                tx.commit()?;
+                Ok(id.clone())
+            }
+            None => {
+                if let Some(parent_id) = &form.parent_id {
+                    if !Department::exists(conn, parent_id)? {
+                        return Err(DepartmentError::NotExist);
+                    }
+                }
+                let id = uuid::Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO departments (id, name, parent_id, manager_id) VALUES (?1, ?2, ?3, ?4)",
+                    params![id, name, form.parent_id, form.manager_id],
+                )?;
+                Ok(id)
+            }
+        }
+    }
+
+    /// move a department under `parent_id`, or to the top level if None
+    pub fn move_to(
+        conn: &Connection,
+        id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<(), DepartmentError> {
+        if let Some(parent_id) = parent_id {
+            if !Department::exists(conn, parent_id)? {
+                return Err(DepartmentError::NotExist);
+            }
+            if ancestors(conn, parent_id)?.iter().any(|ancestor| ancestor == id) {
+                return Err(DepartmentError::Cycle);
+            }
+        }
+
+        let updated = conn.execute(
+            "UPDATE departments SET parent_id = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![parent_id, id],
+        )?;
+        if updated == 0 {
+            return Err(DepartmentError::NotExist);
+        }
+
+        Ok(())
+    }
+
+    /// move a department into the recycle bin,
+    /// only a department without sub departments and members can be deleted
+    pub fn delete(conn: &Connection, id: &str, deleted_by: &str) -> Result<(), DepartmentError> {
+        let in_use: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM departments WHERE parent_id = ?1 AND deleted_at IS NULL)
+                OR EXISTS (SELECT 1 FROM users WHERE department_id = ?1 AND deleted_at IS NULL)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if in_use {
+            return Err(DepartmentError::NotEmpty);
+        }
+
+        let updated = conn.execute(
+            "UPDATE departments SET deleted_at = ?1, deleted_by = ?2
+             WHERE id = ?3 AND deleted_at IS NULL",
+            params![chrono::Utc::now().timestamp(), deleted_by, id],
+        )?;
+        if updated == 0 {
+            return Err(DepartmentError::NotExist);
+        }
+
+        Ok(())
+    }
+
+    /// bring a department back from the recycle bin,
+    /// to the top level if the parent department was deleted meanwhile
+    pub fn restore(conn: &Connection, id: &str) -> Result<(), DepartmentError> {
+        let updated = conn.execute(
+            "UPDATE departments SET deleted_at = NULL, deleted_by = NULL,
+                parent_id = (SELECT parents.id FROM departments AS parents
+                    WHERE parents.id = departments.parent_id AND parents.deleted_at IS NULL)
+             WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
+        if updated == 0 {
+            return Err(DepartmentError::NotExist);
+        }
+
+        Ok(())
+    }
+
+    /// remove a department in the recycle bin for ever,
+    /// deleted members and sub departments no longer refer to it
+    pub fn purge(conn: &Connection, id: &str) -> Result<(), DepartmentError> {
+        let deleted = conn.execute(
+            "DELETE FROM departments WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
+        if deleted == 0 {
+            return Err(DepartmentError::NotExist);
+        }
+        conn.execute(
+            "UPDATE users SET department_id = NULL WHERE department_id = ?1",
+            params![id],
+        )?;
+        conn.execute(
+            "UPDATE departments SET parent_id = NULL WHERE parent_id = ?1",
+            params![id],
+        )?;
+
+        Ok(())
+    }
+}
+
+/// `id` and all of its ancestors, from `id` up to the top level
+pub fn ancestors(conn: &Connection, id: &str) -> Result<Vec<String>, DepartmentError> {
+    let mut list = vec![id.to_string()];
+    let mut current = id.to_string();
+
+    while let Some(parent_id) = conn
+        .query_row(
+            "SELECT parent_id FROM departments WHERE id = ?1",
+            params![current],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten()
+    {
+        //  stop on a broken tree rather than looping forever
+        if list.contains(&parent_id) {
+            break;
+        }
+        list.push(parent_id.clone());
+        current = parent_id;
+    }
+
+    Ok(list)
+}
+
+/// managers above `user_id`, the nearest first,
+/// a manager of their own department reports to the manager of the parent department
+pub fn reporting_line(conn: &Connection, user_id: &str) -> Result<Vec<User>, DepartmentError> {
+    let Some(department_id) = department_of(conn, user_id)? else {
+        return Ok(vec![]);
+    };
+
+    let mut managers: Vec<User> = vec![];
+    for id in ancestors(conn, &department_id)? {
+        let manager = conn
+            .query_row(
+                "SELECT users.id, users.username FROM departments
+                 JOIN users ON users.id = departments.manager_id
+                 WHERE departments.id = ?1 AND users.status = ?2 AND users.deleted_at IS NULL",
+                params![id, UserStatus::Active.to_string()],
+                |row| {
+                    Ok(User {
+                        id: row.get(0)?,
+                        username: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        if let Some(manager) = manager {
+            if manager.id != user_id && managers.iter().all(|m| m.id != manager.id) {
+                managers.push(manager);
+            }
+        }
+    }
+
+    Ok(managers)
+}
+
+/// the department of `user_id`, None if the user is not a member of any
+pub fn department_of(conn: &Connection, user_id: &str) -> Result<Option<String>, DepartmentError> {
+    conn.query_row(
+        "SELECT department_id FROM users WHERE id = ?1 AND deleted_at IS NULL",
+        params![user_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(DepartmentError::NotExist)
+}
+
+/// ids of the members of department `id` and of all of its sub departments
+pub fn members_of(conn: &Connection, id: &str) -> Result<Vec<String>, DepartmentError> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE tree(id) AS (
+             SELECT id FROM departments WHERE id = ?1 AND deleted_at IS NULL
+             UNION
+             SELECT departments.id FROM departments JOIN tree ON departments.parent_id = tree.id
+             WHERE departments.deleted_at IS NULL
+         )
+         SELECT id FROM users WHERE department_id IN tree AND deleted_at IS NULL",
+    )?;
+    let list = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(list)
+}
+
+/// ids of the members of the departments managed by `manager_id` and of their sub departments,
+/// not including the manager
+pub fn reports_of(conn: &Connection, manager_id: &str) -> Result<Vec<String>, DepartmentError> {
+    let mut stmt =
+        conn.prepare("SELECT id FROM departments WHERE manager_id = ?1 AND deleted_at IS NULL")?;
+    let departments = stmt
+        .query_map(params![manager_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    let mut reports: Vec<String> = vec![];
+    for id in departments {
+        for member in members_of(conn, &id)? {
+            if member != manager_id && !reports.contains(&member) {
+                reports.push(member);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+/// the nearest manager of `user_id`, None if nobody is above the user
+pub fn manager_of(conn: &Connection, user_id: &str) -> Result<Option<User>, DepartmentError> {
+    Ok(reporting_line(conn, user_id)?.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::new_test_db;
+
+    fn create(conn: &Connection, name: &str, parent_id: Option<&str>) -> String {
+        let form = DepartmentForm {
+            name: name.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            ..Default::default()
+        };
+        Department::save(conn, &form).ok().unwrap()
+    }
+
+    #[test]
+    fn save_nothing_if_moved_under_itself() {
+        let conn = new_test_db();
+        let parent = create(&conn, "Engineering", None);
+        let child = create(&conn, "Platform", Some(&parent));
+
+        let form = DepartmentForm {
+            id: Some(parent.clone()),
+            name: "Renamed".to_string(),
+            parent_id: Some(child),
+            manager_id: Some("123456".to_string()),
+        };
+        assert!(matches!(
+            Department::save(&conn, &form),
+            Err(DepartmentError::Cycle)
+        ));
+
+        let (name, manager_id): (String, Option<String>) = conn
+            .query_row(
+                "SELECT name, manager_id FROM departments WHERE id = ?1",
+                params![parent],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(name == "Engineering" && manager_id.is_none());
+    }
+
+    #[test]
+    fn reports_of_sub_departments_without_the_manager() {
+        let conn = new_test_db();
+        let parent = create(&conn, "Engineering", None);
+        let child = create(&conn, "Platform", Some(&parent));
+        let other = create(&conn, "Sales", None);
+        conn.execute(
+            "UPDATE departments SET manager_id = '123456' WHERE id = ?1",
+            params![parent],
+        )
+        .unwrap();
+        for (id, department_id) in [
+            ("123456", &parent),
+            ("a", &parent),
+            ("b", &child),
+            ("c", &other),
+        ] {
+            conn.execute(
+                "INSERT INTO users (id, username, password_hash, password_set_at, department_id)
+                 VALUES (?1, ?1, '', 0, ?2)
+                 ON CONFLICT (id) DO UPDATE SET department_id = ?2",
+                params![id, department_id],
+            )
+            .unwrap();
+        }
+
+        let mut reports = reports_of(&conn, "123456").ok().unwrap();
+        reports.sort();
+        assert!(reports == vec!["a".to_string(), "b".to_string()]);
+        assert!(reports_of(&conn, "a").ok().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+//! Invitation
+//! administrators invite users by email instead of setting passwords for them
+//!
+//! - inviting creates a [UserStatus::Pending] user, who cannot log in,
+//! and mails a link to the invitee through [Mailer],
+//! the mail is sent after the database is released, as mailers may be slow
+//! - the link carries the invitation id and expiry signed by HMAC-SHA256,
+//! the key is generated once and kept in the `secrets` table
+//! - accepting sets the password and profile, and activates the user,
+//! inviting the same user again invalidates the links sent before
+
+use super::mailer::Mail;
+use super::user::{format_timestamp, hash_password, validate_display_name, validate_preferences};
+use super::SETTINGS;
+use crate::models::consts::INVITATION_ROUTE;
+use crate::models::{AcceptInvitationForm, FieldErrors, Invitation, UserStatus};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::Sha256;
+use std::fmt;
+
+const SIGNING_KEY_NAME: &str = "invitation_signing_key";
+
+pub enum InvitationError {
+    Invalid,
+    Expired,
+    NotPending,
+    Mail,
+    Storage,
+}
+
+impl fmt::Display for InvitationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvitationError::Invalid => write!(f, "invitation link is invalid"),
+            InvitationError::Expired => write!(f, "invitation link is expired"),
+            InvitationError::NotPending => write!(f, "user is not waiting for an invitation"),
+            InvitationError::Mail => write!(f, "send invitation mail fail"),
+            InvitationError::Storage => write!(f, "invitation storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for InvitationError {
+    fn from(_: rusqlite::Error) -> Self {
+        InvitationError::Storage
+    }
+}
+
+/// invite the pending user `user_id`, returns the mail of the link to be sent,
+/// links of the previous invitations of the user are no longer valid
+pub fn invite(conn: &Connection, user_id: &str, invited_by: &str) -> Result<Mail, InvitationError> {
+    let (email, display_name, status) = conn
+        .query_row(
+            "SELECT email, display_name, status FROM users WHERE id = ?1 AND deleted_at IS NULL",
+            params![user_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )
+        .optional()?
+        .ok_or(InvitationError::NotPending)?;
+    if status.parse::<UserStatus>() != Ok(UserStatus::Pending) {
+        return Err(InvitationError::NotPending);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let id = uuid::Uuid::new_v4().to_string();
+    let expires_at = now + SETTINGS.invitation_ttl_hours * 60 * 60;
+    conn.execute(
+        "DELETE FROM invitations WHERE user_id = ?1 AND accepted_at IS NULL",
+        params![user_id],
+    )?;
+    conn.execute(
+        "INSERT INTO invitations (id, user_id, expires_at, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, user_id, expires_at, invited_by, now],
+    )?;
+
+    let link = format!(
+        "{}{}/{}",
+        SETTINGS.public_url.trim_end_matches('/'),
+        INVITATION_ROUTE,
+        sign(conn, &id, expires_at)?
+    );
+
+    Ok(Mail {
+        to: email,
+        subject: "You are invited to Dvorak Admin".to_string(),
+        body: format!(
+            "Hi {},\r\n\r\nplease set your password to activate your account:\r\n{}\r\n\r\nthe link expires at {} (UTC).",
+            display_name,
+            link,
+            format_timestamp(expires_at)
+        ),
+    })
+}
+
+/// the user has a link neither accepted nor expired yet
+pub fn is_invited(conn: &Connection, user_id: &str) -> Result<bool, InvitationError> {
+    let invited = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM invitations
+            WHERE user_id = ?1 AND accepted_at IS NULL AND expires_at > ?2)",
+        params![user_id, chrono::Utc::now().timestamp()],
+        |row| row.get(0),
+    )?;
+    Ok(invited)
+}
+
+/// invalidate the links of user `user_id` not accepted yet
+pub fn cancel(conn: &Connection, user_id: &str) -> Result<(), InvitationError> {
+    conn.execute(
+        "DELETE FROM invitations WHERE user_id = ?1 AND accepted_at IS NULL",
+        params![user_id],
+    )?;
+    Ok(())
+}
+
+impl Invitation {
+    /// the invitation of a link `token`, fails if the link is invalid, expired or used
+    pub fn get(conn: &Connection, token: &str) -> Result<Self, InvitationError> {
+        let (_, user_id, expires_at) = verify(conn, token)?;
+
+        conn.query_row(
+            "SELECT username, email, display_name FROM users WHERE id = ?1 AND deleted_at IS NULL",
+            params![user_id],
+            |row| {
+                Ok(Invitation {
+                    username: row.get(0)?,
+                    email: row.get(1)?,
+                    display_name: row.get(2)?,
+                    expires_at: format_timestamp(expires_at),
+                })
+            },
+        )
+        .optional()?
+        .ok_or(InvitationError::Invalid)
+    }
+}
+
+impl AcceptInvitationForm {
+    /// errors of every invalid field, empty if the form is valid
+    pub fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+
+        validate_display_name(&self.display_name, &mut errors);
+        validate_preferences(&self.timezone, &self.language, &mut errors);
+        if self.password.chars().count() < 8 {
+            errors.insert("password".into(), "must have at least 8 characters".into());
+        } else if self.password != self.confirm_password {
+            errors.insert("confirm_password".into(), "does not match".into());
+        }
+
+        errors
+    }
+
+    /// set the password and profile of the invitee and activate the user,
+    /// returns the errors of invalid fields, empty if accepted
+    pub fn accept(&self, conn: &Connection, token: &str) -> Result<FieldErrors, InvitationError> {
+        let (id, user_id, _) = verify(conn, token)?;
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Ok(errors);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let tx = conn.unchecked_transaction()?;
+        let updated = tx.execute(
+            "UPDATE users SET display_name = ?1, timezone = ?2, language = ?3, password_hash = ?4,
+                password_set_at = ?5, must_change_password = 0, status = ?6
+             WHERE id = ?7 AND status = ?8 AND deleted_at IS NULL",
+            params![
+                self.display_name.trim(),
+                self.timezone,
+                self.language,
+                hash_password(&self.password).map_err(|_| InvitationError::Storage)?,
+                now,
+                UserStatus::Active.to_string(),
+                user_id,
+                UserStatus::Pending.to_string(),
+            ],
+        )?;
+        //  the user was activated, disabled or deleted since invited
+        if updated == 0 {
+            return Err(InvitationError::NotPending);
+        }
+        tx.execute(
+            "UPDATE invitations SET accepted_at = ?1 WHERE id = ?2",
+            params![now, id],
+        )?;
+        tx.commit()?;
+
+        Ok(errors)
+    }
+}
+
+/// the link token of invitation `id`, `{id}.{expires_at}.{signature}`
+fn sign(conn: &Connection, id: &str, expires_at: i64) -> Result<String, InvitationError> {
+    let payload = format!("{}.{}", id, expires_at);
+    let mut mac = new_mac(conn)?;
+    mac.update(payload.as_bytes());
+    let signature = BASE64_URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    Ok(format!("{}.{}", payload, signature))
+}
+
+/// invitation id, user id and expiry of a link token,
+/// which must be signed, not expired, not accepted and not replaced by a later invitation
+fn verify(conn: &Connection, token: &str) -> Result<(String, String, i64), InvitationError> {
+    let (payload, signature) = token.rsplit_once('.').ok_or(InvitationError::Invalid)?;
+    let signature = BASE64_URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| InvitationError::Invalid)?;
+    let mut mac = new_mac(conn)?;
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| InvitationError::Invalid)?;
+
+    let (id, expires_at) = payload.split_once('.').ok_or(InvitationError::Invalid)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| InvitationError::Invalid)?;
+    if expires_at <= chrono::Utc::now().timestamp() {
+        return Err(InvitationError::Expired);
+    }
+
+    let user_id = conn
+        .query_row(
+            "SELECT user_id FROM invitations WHERE id = ?1 AND accepted_at IS NULL",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(InvitationError::Invalid)?;
+
+    Ok((id.to_string(), user_id, expires_at))
+}
+
+fn new_mac(conn: &Connection) -> Result<Hmac<Sha256>, InvitationError> {
+    let key: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT value FROM secrets WHERE name = ?1",
+            params![SIGNING_KEY_NAME],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let key = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+            conn.execute(
+                "INSERT INTO secrets (name, value) VALUES (?1, ?2)",
+                params![SIGNING_KEY_NAME, key],
+            )?;
+            key
+        }
+    };
+
+    Hmac::<Sha256>::new_from_slice(&key).map_err(|_| InvitationError::Storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::mailer::{FileMailer, Mailer};
+    use crate::server::new_test_db;
+    use std::fs;
+
+    const USER_ID: &str = "invitee";
+
+    fn new_pending_user(conn: &Connection) {
+        conn.execute(
+            "INSERT INTO users (id, username, email, display_name, password_hash, password_set_at, status)
+             VALUES (?1, ?1, 'invitee@example.com', 'Invitee', '', 0, ?2)",
+            params![USER_ID, UserStatus::Pending.to_string()],
+        )
+        .unwrap();
+    }
+
+    /// send `mail` by a [FileMailer] and read the link token back from the written `.eml`
+    fn send_and_read_token(mail: &Mail) -> String {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let mailer = FileMailer::new(dir.to_str().unwrap());
+        assert!(mailer.send(mail).is_ok());
+
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].file_name().to_string_lossy().ends_with(".eml"));
+        let content = fs::read_to_string(entries[0].path()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(content.contains("To: invitee@example.com\r\n"));
+        let link = content
+            .lines()
+            .find(|line| line.contains(INVITATION_ROUTE))
+            .expect("mail has no invitation link");
+        link.rsplit('/').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn mailed_link_is_verified() {
+        let conn = new_test_db();
+        new_pending_user(&conn);
+
+        let token = send_and_read_token(&invite(&conn, USER_ID, "123456").ok().unwrap());
+        assert!(matches!(verify(&conn, &token), Ok((_, user_id, _)) if user_id == USER_ID));
+        assert!(matches!(
+            verify(&conn, &format!("{}x", token)),
+            Err(InvitationError::Invalid)
+        ));
+    }
+
+    #[test]
+    fn expired_link_is_rejected() {
+        let conn = new_test_db();
+        new_pending_user(&conn);
+
+        let token = send_and_read_token(&invite(&conn, USER_ID, "123456").ok().unwrap());
+        let (id, _) = token.split_once('.').unwrap();
+        let expired = sign(&conn, id, chrono::Utc::now().timestamp() - 1)
+            .ok()
+            .unwrap();
+        assert!(matches!(
+            verify(&conn, &expired),
+            Err(InvitationError::Expired)
+        ));
+    }
+
+    #[test]
+    fn replaced_link_is_rejected() {
+        let conn = new_test_db();
+        new_pending_user(&conn);
+
+        let first = send_and_read_token(&invite(&conn, USER_ID, "123456").ok().unwrap());
+        let second = send_and_read_token(&invite(&conn, USER_ID, "123456").ok().unwrap());
+        assert!(matches!(
+            verify(&conn, &first),
+            Err(InvitationError::Invalid)
+        ));
+        assert!(matches!(verify(&conn, &second), Ok((_, user_id, _)) if user_id == USER_ID));
+    }
+}
@@ -1,33 +1,552 @@
-use crate::models::{LeaveList, LeaveRequest, LeaveType, User};
+//! Leave
+//! leave requests are kept through the [LeaveRepository] trait,
+//! so that the storage can be replaced without touching server functions
+//!
+//! - [SqliteLeaveRepository] keeps leave requests in the shared sqlite database, the default
+//! - [MemoryLeaveRepository] keeps them in memory only, for tests and demos,
+//! selected by [SETTINGS] `leave_storage`
+//!
+//! the status of a leave request follows [LeaveStatus::next],
+//! and who can change it to what follows [LeaveRequest::available_statuses],
+//! a pending request is approved once every step of its approval chain is approved,
+//! see [super::approval]
+//!
+//! to keep leave requests in another database such as Postgres, please implement [LeaveRepository]
+
+mod memory;
+mod sqlite;
+
+pub use memory::*;
+pub use sqlite::*;
+
+use super::department::{department_of, members_of, reports_of};
+use super::leave_attachment::{AttachmentError, MAX_ATTACHMENTS};
+use super::leave_ledger::{leave_balances, record_leave_usage};
+use super::{AppDataDb, LeaveStorage, SETTINGS};
+use crate::models::consts::{ADMIN_ROLE, HR_ROLE};
+use crate::models::{
+    format_days, has_approver_role, ApprovalStatus, ApprovalStep, Balance, DayPart, FieldErrors,
+    LeaveError, LeaveForm, LeaveList, LeaveRequest, LeaveStatus, LeaveType, StaffingShortage, User,
+    UserError, WorkingCalendar, MAX_LEAVE_COMMENT_LENGTH,
+};
+use actix_web::web::Data;
+use chrono::{Datelike, NaiveDate};
+use rusqlite::Connection;
+use std::sync::Arc;
+
+pub trait LeaveRepository: Send + Sync {
+    /// all of leave requests of users not deleted, the latest starting first
+    fn list(&self) -> Result<LeaveList, LeaveError>;
+
+    /// leave requests of user `user_id` if not deleted, the latest starting first
+    fn list_of_user(&self, user_id: &str) -> Result<LeaveList, LeaveError>;
+
+    fn get(&self, id: i64) -> Result<LeaveRequest, LeaveError>;
+
+    /// create a leave request of `user` taking `duration` working days,
+    /// to be decided by the steps of `approvals`, the form must be validated first, returns the id
+    fn create(
+        &self,
+        user: &User,
+        form: &LeaveForm,
+        duration: f64,
+        approvals: &[ApprovalStep],
+    ) -> Result<i64, LeaveError>;
+
+    /// remove leave request `id` and its approval chain, such as when what it was submitted with
+    /// cannot be saved right after created
+    fn delete(&self, id: i64) -> Result<(), LeaveError>;
+
+    /// decide leave request `id` in one transaction, nothing changes if any part fails,
+    /// `step` decides a step of the approval chain, failing with [LeaveError::AlreadyDecided]
+    /// if the step is no longer pending, `status` changes the status from the first to the
+    /// second, failing with [LeaveError::InvalidTransition] if the status is no longer the first,
+    /// such as decided by someone else meanwhile,
+    /// `on_decided` records what follows the decided request in the same transaction,
+    /// such as the days used in the leave ledger, returns the decided request
+    fn decide(
+        &self,
+        id: i64,
+        step: Option<(usize, ApprovalStatus)>,
+        status: Option<(LeaveStatus, LeaveStatus)>,
+        decided_by: &User,
+        comment: &str,
+        on_decided: &OnDecided,
+    ) -> Result<LeaveRequest, LeaveError>;
+}
+
+/// called by [LeaveRepository::decide] with the shared database in the transaction of the decision
+pub type OnDecided = dyn Fn(&Connection, &LeaveRequest) -> Result<(), LeaveError>;
+
+/// app data leave repository
+/// used in actix app_data
+pub type AppDataLeaveRepository = Data<dyn LeaveRepository>;
+
+pub fn new_app_data_leave_repository(db: AppDataDb) -> AppDataLeaveRepository {
+    let repository: Arc<dyn LeaveRepository> = match SETTINGS.leave_storage {
+        LeaveStorage::Sqlite => Arc::new(SqliteLeaveRepository::new(db)),
+        LeaveStorage::Memory => Arc::new(MemoryLeaveRepository::new(db)),
+    };
+    Data::from(repository)
+}
+
+/// leave requests of `list` user `user_id` sees, all of them for administrators and HR,
+/// otherwise their own, those of their reports, and those whose chain they approve a step of
+pub fn visible_leaves(
+    conn: &Connection,
+    list: &LeaveList,
+    user_id: &str,
+) -> Result<LeaveList, LeaveError> {
+    let roles = User::roles(conn, user_id).map_err(|_| LeaveError::Storage)?;
+    if roles
+        .iter()
+        .any(|role| role == ADMIN_ROLE || role == HR_ROLE)
+    {
+        return Ok(list.clone());
+    }
+    let reports = reports_of(conn, user_id)?;
+
+    Ok(list
+        .iter()
+        .filter(|leave| {
+            leave.user.id == user_id
+                || reports.contains(&leave.user.id)
+                || leave.is_chain_approver(user_id, &roles)
+        })
+        .cloned()
+        .collect())
+}
+
+/// whether user `user_id` approves or rejects leave requests of others
+pub fn is_approver(conn: &Connection, user_id: &str) -> Result<bool, UserError> {
+    Ok(has_approver_role(&User::roles(conn, user_id)?))
+}
 
 impl LeaveRequest {
-    pub async fn get_leave_requests() -> LeaveList {
-        async {
-            vec![
-                LeaveRequest {
-                    id: 0,
-                    user: User {
-                        id: "001".to_string(),
-                        username: "Hart Hagerty".to_string(),
-                    },
-                    leave_type: LeaveType::Personal,
-                    start_date: "2024-03-02".to_string(),
-                    end_date: "2024-03-03".to_string(),
-                    remark: "for something reason".to_string(),
-                },
-                LeaveRequest {
-                    id: 1,
-                    user: User {
-                        id: "002".to_string(),
-                        username: "Brice Swyre".to_string(),
-                    },
-                    leave_type: LeaveType::Sick,
-                    start_date: "2024-03-02".to_string(),
-                    end_date: "2024-03-03".to_string(),
-                    remark: "for something reason".to_string(),
-                },
-            ]
-        }
-        .await
+    /// change the status of leave request `id` to `to` on behalf of `actor` having `roles`,
+    /// approving or rejecting decides the current step of the approval chain,
+    /// and approving the last step approves the request,
+    /// the days used or given back are recorded into the leave ledger along
+    pub fn change_status(
+        repository: &dyn LeaveRepository,
+        id: i64,
+        to: LeaveStatus,
+        actor: &User,
+        roles: &[String],
+        comment: &str,
+    ) -> Result<LeaveRequest, LeaveError> {
+        let comment = comment.trim();
+        if comment.chars().count() > MAX_LEAVE_COMMENT_LENGTH {
+            return Err(LeaveError::CommentTooLong);
+        }
+
+        let leave = repository.get(id)?;
+        if !leave.status.next().contains(&to) {
+            return Err(LeaveError::InvalidTransition(leave.status, to));
+        }
+        if !leave.available_statuses(&actor.id, roles).contains(&to) {
+            return Err(LeaveError::PermissionDenied);
+        }
+        //  the same days cannot be approved twice, such as for requests submitted meanwhile
+        if to == LeaveStatus::Approved {
+            let overlapped = repository
+                .list_of_user(&leave.user.id)?
+                .into_iter()
+                .find(|other| {
+                    other.id != id
+                        && other.status == LeaveStatus::Approved
+                        && other.overlaps(
+                            leave.start_date,
+                            leave.start_part,
+                            leave.end_date,
+                            leave.end_part,
+                        )
+                });
+            if let Some(other) = overlapped {
+                return Err(LeaveError::Overlap(other.start_date, other.end_date));
+            }
+        }
+
+        //  rejecting any step or approving the last one decides the request along with the step
+        let status = Some((leave.status, to));
+        match (to, leave.current_step()) {
+            (LeaveStatus::Approved, Some(step)) => {
+                let last = step + 1 == leave.approvals.len();
+                repository.decide(
+                    id,
+                    Some((step, ApprovalStatus::Approved)),
+                    if last { status } else { None },
+                    actor,
+                    comment,
+                    &record_leave_usage,
+                )
+            }
+            (LeaveStatus::Rejected, Some(step)) => repository.decide(
+                id,
+                Some((step, ApprovalStatus::Rejected)),
+                status,
+                actor,
+                comment,
+                &record_leave_usage,
+            ),
+            _ => repository.decide(id, None, status, actor, comment, &record_leave_usage),
+        }
+    }
+
+    /// working days of this leave request the team of its requester would have fewer members
+    /// present than [SETTINGS] `min_staffing` percent, by the pending and approved requests
+    /// of `leaves`, days no one else is absent are never short
+    pub fn staffing_shortages(
+        &self,
+        conn: &Connection,
+        leaves: &LeaveList,
+    ) -> Result<Vec<StaffingShortage>, LeaveError> {
+        if SETTINGS.min_staffing == 0 {
+            return Ok(vec![]);
+        }
+        let Some(department_id) = department_of(conn, &self.user.id)? else {
+            return Ok(vec![]);
+        };
+        let members = members_of(conn, &department_id)?;
+        let calendar =
+            WorkingCalendar::of_user(conn, &self.user.id, self.start_date, self.end_date)?;
+
+        let mut shortages = vec![];
+        for date in self
+            .start_date
+            .iter_days()
+            .take_while(|day| *day <= self.end_date)
+        {
+            if !calendar.is_working_day(date) {
+                continue;
+            }
+            let mut absent: Vec<String> = leaves
+                .iter()
+                .filter(|leave| {
+                    leave.user.id != self.user.id
+                        && members.contains(&leave.user.id)
+                        && matches!(leave.status, LeaveStatus::Pending | LeaveStatus::Approved)
+                        && leave.overlaps(date, DayPart::Full, date, DayPart::Full)
+                })
+                .map(|leave| leave.user.username.clone())
+                .collect();
+            absent.sort();
+            absent.dedup();
+            let present = members.len().saturating_sub(absent.len() + 1);
+            if !absent.is_empty() && present * 100 < members.len() * SETTINGS.min_staffing as usize
+            {
+                shortages.push(StaffingShortage {
+                    date,
+                    present,
+                    team_size: members.len(),
+                    absent,
+                });
+            }
+        }
+
+        Ok(shortages)
+    }
+}
+
+/// find the staffing shortages of the pending requests of `list` for their approvers,
+/// counting the leaves of everyone in `leaves`
+pub fn fill_staffing(
+    conn: &Connection,
+    list: &mut LeaveList,
+    leaves: &LeaveList,
+) -> Result<(), LeaveError> {
+    for leave in list.iter_mut() {
+        if leave.status == LeaveStatus::Pending {
+            leave.staffing = leave.staffing_shortages(conn, leaves)?;
+        }
+    }
+    Ok(())
+}
+
+impl LeaveForm {
+    /// errors of every invalid field of a leave request submitted by user `user_id`,
+    /// whose existing leave requests are `leaves`, empty if the form is valid
+    pub fn validate(
+        &self,
+        conn: &Connection,
+        leaves: &LeaveList,
+        user_id: &str,
+        today: NaiveDate,
+    ) -> Result<FieldErrors, LeaveError> {
+        let mut errors = FieldErrors::new();
+
+        let leave_type =
+            LeaveType::find(conn, &self.leave_type)?.filter(|leave_type| leave_type.active);
+        if leave_type.is_none() {
+            errors.insert("leave_type".into(), "must be an active leave type".into());
+        }
+        if self.remark.trim().chars().count() > MAX_LEAVE_COMMENT_LENGTH {
+            errors.insert(
+                "remark".into(),
+                format!("must have at most {} characters", MAX_LEAVE_COMMENT_LENGTH),
+            );
+        }
+
+        if self.start_date.is_none() {
+            errors.insert("start_date".into(), "must be a date".into());
+        }
+        if self.end_date.is_none() {
+            errors.insert("end_date".into(), "must be a date".into());
+        }
+        let (Some(start), Some(end)) = (self.start_date, self.end_date) else {
+            return Ok(errors);
+        };
+
+        if end < start {
+            errors.insert(
+                "end_date".into(),
+                "must not be before the start date".into(),
+            );
+            return Ok(errors);
+        }
+        //  balances are yearly, so a request cannot span two years
+        if end.year() != start.year() {
+            errors.insert(
+                "end_date".into(),
+                "must be in the same year as the start date".into(),
+            );
+            return Ok(errors);
+        }
+        if start != end {
+            if self.start_part == DayPart::Morning {
+                errors.insert(
+                    "start_part".into(),
+                    "must be the full day or the afternoon for a leave of several days".into(),
+                );
+            }
+            if self.end_part == DayPart::Afternoon {
+                errors.insert(
+                    "end_part".into(),
+                    "must be the full day or the morning for a leave of several days".into(),
+                );
+            }
+        }
+        //  such as sickness, often reported afterwards
+        let backdated = leave_type
+            .as_ref()
+            .is_some_and(|leave_type| leave_type.backdated);
+        if start < today && !backdated {
+            errors.insert(
+                "start_date".into(),
+                format!(
+                    "cannot be in the past for {} leave",
+                    self.leave_type.to_lowercase()
+                ),
+            );
+        }
+        let days = self.working_days(conn, user_id)?.unwrap_or_default();
+        if days == 0.0 {
+            errors.insert("end_date".into(), "must include a working day".into());
+        }
+
+        let overlapped = leaves.iter().find(|leave| {
+            matches!(leave.status, LeaveStatus::Pending | LeaveStatus::Approved)
+                && leave.overlaps(start, self.start_part, end, self.end_part())
+        });
+        if let Some(leave) = overlapped {
+            errors.insert(
+                "start_date".into(),
+                format!(
+                    "overlaps your {} leave from {} to {}",
+                    leave.status.to_string().to_lowercase(),
+                    leave.start_date,
+                    leave.end_date
+                ),
+            );
+        }
+
+        for upload in self.attachments.iter() {
+            if let Err(e) = upload.decode() {
+                errors.insert("attachments".into(), e.to_string());
+            }
+        }
+        if self.attachments.len() > MAX_ATTACHMENTS {
+            errors.insert(
+                "attachments".into(),
+                AttachmentError::TooManyAttachments.to_string(),
+            );
+        }
+        let attachment_over_days = leave_type
+            .as_ref()
+            .and_then(|leave_type| leave_type.policy.attachment_over_days);
+        if let Some(over) = attachment_over_days {
+            if days > over && self.attachments.is_empty() {
+                errors.insert(
+                    "attachments".into(),
+                    format!(
+                        "must have a file for {} leave over {} working days",
+                        self.leave_type.to_lowercase(),
+                        format_days(over)
+                    ),
+                );
+            }
+        }
+
+        let tracked = leave_type
+            .as_ref()
+            .is_some_and(|leave_type| leave_type.policy.balance == Balance::Tracked);
+        if !tracked {
+            return Ok(errors);
+        }
+        let remaining = leave_balances(conn, leaves, user_id, start.year())?
+            .into_iter()
+            .find(|balance| balance.leave_type == self.leave_type)
+            .map(|balance| balance.remaining())
+            .unwrap_or_default();
+        if days > remaining {
+            errors.insert(
+                "leave_type".into(),
+                format!(
+                    "has only {} days left in {}, but {} working days are requested",
+                    format_days(remaining.max(0.0)),
+                    start.year(),
+                    format_days(days)
+                ),
+            );
+        }
+
+        Ok(errors)
+    }
+
+    /// working days by the working calendar of `user_id`,
+    /// None if any date is missing or the range is reversed
+    pub fn working_days(
+        &self,
+        conn: &Connection,
+        user_id: &str,
+    ) -> Result<Option<f64>, LeaveError> {
+        let (Some(start), Some(end)) = (self.start_date, self.end_date) else {
+            return Ok(None);
+        };
+        let calendar = WorkingCalendar::of_user(conn, user_id, start, end)?;
+        Ok(self.duration(&calendar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::new_test_db;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    fn form(leave_type: &str, start: &str, end: &str) -> LeaveForm {
+        LeaveForm {
+            leave_type: leave_type.to_string(),
+            start_date: Some(date(start)),
+            end_date: Some(date(end)),
+            ..Default::default()
+        }
+    }
+
+    fn leave(status: LeaveStatus, start: &str, end: &str) -> LeaveRequest {
+        LeaveRequest {
+            id: 1,
+            user: User {
+                id: "123456".to_string(),
+                username: "123456".to_string(),
+            },
+            leave_type: "Annual".to_string(),
+            start_date: date(start),
+            start_part: DayPart::Full,
+            end_date: date(end),
+            end_part: DayPart::Full,
+            duration: 2.0,
+            remark: String::new(),
+            status,
+            decided_by: None,
+            comment: String::new(),
+            decided_at: None,
+            approvals: vec![],
+            staffing: vec![],
+            attachments: vec![],
+        }
+    }
+
+    #[test]
+    fn validate_overlap_with_pending_or_approved_leaves() {
+        let conn = new_test_db();
+        let today = date("2030-06-01");
+        let form = form("Annual", "2030-06-04", "2030-06-05");
+
+        let leaves = vec![leave(LeaveStatus::Approved, "2030-06-03", "2030-06-04")];
+        let errors = form.validate(&conn, &leaves, "123456", today).ok().unwrap();
+        assert!(errors
+            .get("start_date")
+            .is_some_and(|error| error.starts_with("overlaps your approved leave")));
+
+        let leaves = vec![leave(LeaveStatus::Withdrawn, "2030-06-03", "2030-06-04")];
+        let errors = form.validate(&conn, &leaves, "123456", today).ok().unwrap();
+        assert!(!errors.contains_key("start_date"));
+    }
+
+    #[test]
+    fn validate_backdating_by_leave_type() {
+        let conn = new_test_db();
+        let today = date("2030-06-10");
+
+        let errors = form("Annual", "2030-06-03", "2030-06-04")
+            .validate(&conn, &LeaveList::new(), "123456", today)
+            .ok()
+            .unwrap();
+        assert!(errors
+            .get("start_date")
+            .is_some_and(|error| error.starts_with("cannot be in the past")));
+
+        //  sick leave is reported afterwards
+        let errors = form("Sick", "2030-06-03", "2030-06-04")
+            .validate(&conn, &LeaveList::new(), "123456", today)
+            .ok()
+            .unwrap();
+        assert!(!errors.contains_key("start_date"));
+    }
+
+    #[test]
+    fn balance_of_tracked_types_only() {
+        let conn = new_test_db();
+        let today = date("2030-06-01");
+        let form = form("Annual", "2030-06-03", "2030-06-04");
+        let used = |conn: &Connection| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM leave_ledger WHERE kind = 'Usage'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        //  nothing accrued in 2030 yet
+        let errors = form
+            .validate(&conn, &LeaveList::new(), "123456", today)
+            .ok()
+            .unwrap();
+        assert!(errors
+            .get("leave_type")
+            .is_some_and(|error| error.contains("days left")));
+        let tracked = leave(LeaveStatus::Approved, "2030-06-03", "2030-06-04");
+        assert!(record_leave_usage(&conn, &tracked).is_ok());
+        assert!(used(&conn) == 1);
+
+        conn.execute(
+            "UPDATE leave_types SET balance = 'Unlimited' WHERE name = 'Annual'",
+            [],
+        )
+        .unwrap();
+        let errors = form
+            .validate(&conn, &LeaveList::new(), "123456", today)
+            .ok()
+            .unwrap();
+        assert!(!errors.contains_key("leave_type"));
+        let mut unlimited = leave(LeaveStatus::Approved, "2030-06-10", "2030-06-11");
+        unlimited.id = 2;
+        assert!(record_leave_usage(&conn, &unlimited).is_ok());
+        assert!(used(&conn) == 1);
     }
 }
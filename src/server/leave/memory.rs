@@ -0,0 +1,412 @@
+use super::{LeaveRepository, OnDecided};
+use crate::models::{
+    ApprovalStatus, ApprovalStep, LeaveError, LeaveForm, LeaveList, LeaveRequest, LeaveStatus, User,
+};
+use crate::server::user::format_timestamp;
+use crate::server::AppDataDb;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// leave requests in memory, lost when the server stops,
+/// what is recorded along a decision, such as the leave ledger, is kept in the shared database
+pub struct MemoryLeaveRepository {
+    leaves: Mutex<LeaveList>,
+    db: AppDataDb,
+}
+
+impl MemoryLeaveRepository {
+    pub fn new(db: AppDataDb) -> Self {
+        Self {
+            leaves: Mutex::new(vec![]),
+            db,
+        }
+    }
+
+    /// latest starting first, like the sqlite repository
+    fn sorted(mut list: LeaveList) -> LeaveList {
+        list.sort_by(|a, b| (b.start_date, b.id).cmp(&(a.start_date, a.id)));
+        list
+    }
+
+    /// users in the recycle bin, whose leave requests are not listed
+    fn deleted_users(&self) -> Result<HashSet<String>, LeaveError> {
+        let conn = self.db.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM users WHERE deleted_at IS NOT NULL")?;
+        let deleted = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(deleted)
+    }
+}
+
+impl LeaveRepository for MemoryLeaveRepository {
+    fn list(&self) -> Result<LeaveList, LeaveError> {
+        let deleted = self.deleted_users()?;
+        let leaves = self.leaves.lock().unwrap();
+        Ok(Self::sorted(
+            leaves
+                .iter()
+                .filter(|leave| !deleted.contains(&leave.user.id))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    fn list_of_user(&self, user_id: &str) -> Result<LeaveList, LeaveError> {
+        if self.deleted_users()?.contains(user_id) {
+            return Ok(vec![]);
+        }
+        let leaves = self.leaves.lock().unwrap();
+        Ok(Self::sorted(
+            leaves
+                .iter()
+                .filter(|leave| leave.user.id == user_id)
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    fn get(&self, id: i64) -> Result<LeaveRequest, LeaveError> {
+        let leaves = self.leaves.lock().unwrap();
+        leaves
+            .iter()
+            .find(|leave| leave.id == id)
+            .cloned()
+            .ok_or(LeaveError::NotExist)
+    }
+
+    fn create(
+        &self,
+        user: &User,
+        form: &LeaveForm,
+        duration: f64,
+        approvals: &[ApprovalStep],
+    ) -> Result<i64, LeaveError> {
+        let (Some(start_date), Some(end_date)) = (form.start_date, form.end_date) else {
+            return Err(LeaveError::Storage);
+        };
+        let mut leaves = self.leaves.lock().unwrap();
+        let id = leaves.iter().map(|leave| leave.id).max().unwrap_or(0) + 1;
+        leaves.push(LeaveRequest {
+            id,
+            user: user.clone(),
+            leave_type: form.leave_type.clone(),
+            start_date,
+            start_part: form.start_part,
+            end_date,
+            end_part: form.end_part(),
+            duration,
+            remark: form.remark.trim().to_string(),
+            status: LeaveStatus::Pending,
+            decided_by: None,
+            comment: String::new(),
+            decided_at: None,
+            approvals: approvals.to_vec(),
+            staffing: vec![],
+            attachments: vec![],
+        });
+        Ok(id)
+    }
+
+    fn delete(&self, id: i64) -> Result<(), LeaveError> {
+        let mut leaves = self.leaves.lock().unwrap();
+        let count = leaves.len();
+        leaves.retain(|leave| leave.id != id);
+        if leaves.len() == count {
+            return Err(LeaveError::NotExist);
+        }
+        Ok(())
+    }
+
+    fn decide(
+        &self,
+        id: i64,
+        step: Option<(usize, ApprovalStatus)>,
+        status: Option<(LeaveStatus, LeaveStatus)>,
+        decided_by: &User,
+        comment: &str,
+        on_decided: &OnDecided,
+    ) -> Result<LeaveRequest, LeaveError> {
+        let mut leaves = self.leaves.lock().unwrap();
+        let leave = leaves
+            .iter_mut()
+            .find(|leave| leave.id == id)
+            .ok_or(LeaveError::NotExist)?;
+        //  checked before changing anything, so that nothing changes if either fails
+        if let Some((step, _)) = step {
+            if leave
+                .approvals
+                .get(step)
+                .map_or(true, |approval| approval.status != ApprovalStatus::Pending)
+            {
+                return Err(LeaveError::AlreadyDecided);
+            }
+        }
+        if let Some((from, to)) = status {
+            if leave.status != from {
+                return Err(LeaveError::InvalidTransition(from, to));
+            }
+        }
+
+        //  changed in a copy kept only once `on_decided` is recorded
+        let mut decided = leave.clone();
+        let decided_at = Some(format_timestamp(chrono::Utc::now().timestamp()));
+        if let Some((step, status)) = step {
+            let approval = &mut decided.approvals[step];
+            approval.status = status;
+            approval.decided_by = Some(decided_by.clone());
+            approval.comment = comment.to_string();
+            approval.decided_at = decided_at.clone();
+        }
+        if let Some((_, to)) = status {
+            decided.status = to;
+            decided.decided_by = Some(decided_by.clone());
+            decided.comment = comment.to_string();
+            decided.decided_at = decided_at;
+        }
+
+        let conn = self.db.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        on_decided(&tx, &decided)?;
+        tx.commit()?;
+
+        *leave = decided.clone();
+        Ok(decided)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::new_test_db;
+    use actix_web::web::Data;
+    use chrono::NaiveDate;
+    use rusqlite::Connection;
+
+    fn new_repository() -> MemoryLeaveRepository {
+        MemoryLeaveRepository::new(Data::new(Mutex::new(new_test_db())))
+    }
+
+    fn record_nothing(_: &Connection, _: &LeaveRequest) -> Result<(), LeaveError> {
+        Ok(())
+    }
+
+    fn user(id: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: id.to_string(),
+        }
+    }
+
+    fn form(start: (i32, u32, u32), end: (i32, u32, u32)) -> LeaveForm {
+        LeaveForm {
+            leave_type: "Annual".to_string(),
+            start_date: NaiveDate::from_ymd_opt(start.0, start.1, start.2),
+            end_date: NaiveDate::from_ymd_opt(end.0, end.1, end.2),
+            ..Default::default()
+        }
+    }
+
+    fn ids(list: &LeaveList) -> Vec<i64> {
+        list.iter().map(|leave| leave.id).collect()
+    }
+
+    #[test]
+    fn list_latest_starting_first() {
+        let repository = new_repository();
+        let (alice, bob) = (user("alice"), user("bob"));
+        let first = repository
+            .create(&alice, &form((2024, 3, 1), (2024, 3, 2)), 2.0, &[])
+            .unwrap_or_default();
+        let second = repository
+            .create(&bob, &form((2024, 5, 1), (2024, 5, 1)), 1.0, &[])
+            .unwrap_or_default();
+        let third = repository
+            .create(&alice, &form((2024, 3, 1), (2024, 3, 1)), 1.0, &[])
+            .unwrap_or_default();
+
+        assert!(ids(&repository.list().unwrap_or_default()) == vec![second, third, first]);
+        assert!(ids(&repository.list_of_user("alice").unwrap_or_default()) == vec![third, first]);
+
+        let leave = repository.get(first).ok().unwrap();
+        assert!(leave.status == LeaveStatus::Pending);
+        assert!(leave.user.id == "alice" && leave.duration == 2.0);
+        assert!(matches!(repository.get(42), Err(LeaveError::NotExist)));
+        assert!(matches!(
+            repository.create(&alice, &LeaveForm::default(), 1.0, &[]),
+            Err(LeaveError::Storage)
+        ));
+    }
+
+    #[test]
+    fn decide_pending_steps_only() {
+        let repository = new_repository();
+        let manager = user("manager");
+        let id = repository
+            .create(
+                &user("alice"),
+                &form((2024, 3, 1), (2024, 3, 1)),
+                1.0,
+                &[ApprovalStep::default(), ApprovalStep::default()],
+            )
+            .unwrap_or_default();
+
+        let approve = |step| Some((step, ApprovalStatus::Approved));
+        assert!(repository
+            .decide(id, approve(0), None, &manager, "ok", &record_nothing)
+            .is_ok());
+        let leave = repository.get(id).ok().unwrap();
+        assert!(leave.approvals[0].status == ApprovalStatus::Approved);
+        assert!(leave.approvals[0].comment == "ok");
+        assert!(leave.status == LeaveStatus::Pending);
+        assert!(leave.current_step() == Some(1));
+
+        assert!(matches!(
+            repository.decide(id, approve(0), None, &manager, "", &record_nothing),
+            Err(LeaveError::AlreadyDecided)
+        ));
+        assert!(matches!(
+            repository.decide(id, approve(2), None, &manager, "", &record_nothing),
+            Err(LeaveError::AlreadyDecided)
+        ));
+        assert!(matches!(
+            repository.decide(42, approve(0), None, &manager, "", &record_nothing),
+            Err(LeaveError::NotExist)
+        ));
+    }
+
+    #[test]
+    fn decide_from_the_current_status_only() {
+        let repository = new_repository();
+        let manager = user("manager");
+        let id = repository
+            .create(
+                &user("alice"),
+                &form((2024, 3, 1), (2024, 3, 1)),
+                1.0,
+                &[ApprovalStep::default()],
+            )
+            .unwrap_or_default();
+
+        let approved = Some((LeaveStatus::Pending, LeaveStatus::Approved));
+        assert!(repository
+            .decide(
+                id,
+                Some((0, ApprovalStatus::Approved)),
+                approved,
+                &manager,
+                "ok",
+                &record_nothing
+            )
+            .is_ok());
+        let leave = repository.get(id).ok().unwrap();
+        assert!(leave.status == LeaveStatus::Approved);
+        assert!(leave.approvals[0].status == ApprovalStatus::Approved);
+        assert!(leave.decided_by.is_some_and(|user| user.id == "manager"));
+
+        //  such as approved by someone else meanwhile
+        let rejected = Some((LeaveStatus::Pending, LeaveStatus::Rejected));
+        assert!(matches!(
+            repository.decide(id, None, rejected, &manager, "", &record_nothing),
+            Err(LeaveError::InvalidTransition(
+                LeaveStatus::Pending,
+                LeaveStatus::Rejected
+            ))
+        ));
+        assert!(repository.get(id).ok().unwrap().status == LeaveStatus::Approved);
+    }
+
+    #[test]
+    fn decide_nothing_if_the_status_changed_meanwhile() {
+        let repository = new_repository();
+        let manager = user("manager");
+        let id = repository
+            .create(
+                &user("alice"),
+                &form((2024, 3, 1), (2024, 3, 1)),
+                1.0,
+                &[ApprovalStep::default()],
+            )
+            .unwrap_or_default();
+        let withdrawn = Some((LeaveStatus::Pending, LeaveStatus::Withdrawn));
+        assert!(repository
+            .decide(id, None, withdrawn, &user("alice"), "", &record_nothing)
+            .is_ok());
+
+        let rejected = Some((LeaveStatus::Pending, LeaveStatus::Rejected));
+        assert!(matches!(
+            repository.decide(
+                id,
+                Some((0, ApprovalStatus::Rejected)),
+                rejected,
+                &manager,
+                "",
+                &record_nothing
+            ),
+            Err(LeaveError::InvalidTransition(_, _))
+        ));
+        let leave = repository.get(id).ok().unwrap();
+        assert!(leave.status == LeaveStatus::Withdrawn);
+        assert!(leave.approvals[0].status == ApprovalStatus::Pending);
+    }
+
+    #[test]
+    fn decide_nothing_if_not_recorded() {
+        let repository = new_repository();
+        let id = repository
+            .create(
+                &user("alice"),
+                &form((2024, 3, 1), (2024, 3, 1)),
+                1.0,
+                &[ApprovalStep::default()],
+            )
+            .unwrap_or_default();
+
+        let approved = Some((LeaveStatus::Pending, LeaveStatus::Approved));
+        assert!(matches!(
+            repository.decide(
+                id,
+                Some((0, ApprovalStatus::Approved)),
+                approved,
+                &user("manager"),
+                "",
+                &|_, _| Err(LeaveError::Storage),
+            ),
+            Err(LeaveError::Storage)
+        ));
+        let leave = repository.get(id).ok().unwrap();
+        assert!(leave.status == LeaveStatus::Pending);
+        assert!(leave.approvals[0].status == ApprovalStatus::Pending);
+    }
+
+    #[test]
+    fn list_no_leaves_of_deleted_users() {
+        let repository = new_repository();
+        for id in ["alice", "bob"] {
+            repository
+                .db
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT INTO users (id, username, password_hash, password_set_at)
+                     VALUES (?1, ?1, '', 0)",
+                    [id],
+                )
+                .unwrap();
+        }
+        let (alice, bob) = (user("alice"), user("bob"));
+        repository
+            .create(&alice, &form((2024, 3, 1), (2024, 3, 1)), 1.0, &[])
+            .unwrap_or_default();
+        let kept = repository
+            .create(&bob, &form((2024, 3, 1), (2024, 3, 1)), 1.0, &[])
+            .unwrap_or_default();
+
+        assert!(User::delete(&repository.db.lock().unwrap(), "alice", "123456").is_ok());
+        assert!(ids(&repository.list().unwrap_or_default()) == vec![kept]);
+        assert!(repository
+            .list_of_user("alice")
+            .unwrap_or_default()
+            .is_empty());
+    }
+}
@@ -0,0 +1,366 @@
+use super::{LeaveRepository, OnDecided};
+use crate::models::{
+    ApprovalStatus, ApprovalStep, Approver, LeaveError, LeaveForm, LeaveList, LeaveRequest,
+    LeaveStatus, User,
+};
+use crate::server::user::format_timestamp;
+use crate::server::AppDataDb;
+use rusqlite::{params, Connection, OptionalExtension, Params, Row};
+use std::collections::HashMap;
+
+impl From<rusqlite::Error> for LeaveError {
+    fn from(_: rusqlite::Error) -> Self {
+        LeaveError::Storage
+    }
+}
+
+const LEAVE_COLUMNS: &str = "leave_requests.id, leave_requests.user_id, users.username,
+    leave_requests.leave_type, leave_requests.start_date, leave_requests.end_date,
+    leave_requests.remark, leave_requests.status, leave_requests.decided_by, deciders.username,
+    leave_requests.comment, leave_requests.decided_at, leave_requests.start_part,
+    leave_requests.end_part, leave_requests.duration
+    FROM leave_requests JOIN users ON users.id = leave_requests.user_id
+    LEFT JOIN users AS deciders ON deciders.id = leave_requests.decided_by";
+
+const APPROVAL_COLUMNS: &str = "leave_approvals.leave_id, leave_approvals.approver,
+    leave_approvals.manager_level, leave_approvals.role, leave_approvals.approver_id,
+    approvers.username, leave_approvals.status, leave_approvals.decided_by, deciders.username,
+    leave_approvals.comment, leave_approvals.decided_at
+    FROM leave_approvals
+    LEFT JOIN users AS approvers ON approvers.id = leave_approvals.approver_id
+    LEFT JOIN users AS deciders ON deciders.id = leave_approvals.decided_by";
+
+/// leave requests in the shared sqlite database
+pub struct SqliteLeaveRepository {
+    db: AppDataDb,
+}
+
+impl SqliteLeaveRepository {
+    pub fn new(db: AppDataDb) -> Self {
+        Self { db }
+    }
+}
+
+impl LeaveRepository for SqliteLeaveRepository {
+    fn list(&self) -> Result<LeaveList, LeaveError> {
+        let conn = self.db.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE users.deleted_at IS NULL
+             ORDER BY leave_requests.start_date DESC, leave_requests.id DESC",
+            LEAVE_COLUMNS
+        ))?;
+        let mut list = stmt
+            .query_map([], leave_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut approvals = load_approvals(&conn, "1 = 1", [])?;
+        for leave in list.iter_mut() {
+            leave.approvals = approvals.remove(&leave.id).unwrap_or_default();
+        }
+        Ok(list)
+    }
+
+    fn list_of_user(&self, user_id: &str) -> Result<LeaveList, LeaveError> {
+        let conn = self.db.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE leave_requests.user_id = ?1 AND users.deleted_at IS NULL
+             ORDER BY leave_requests.start_date DESC, leave_requests.id DESC",
+            LEAVE_COLUMNS
+        ))?;
+        let mut list = stmt
+            .query_map(params![user_id], leave_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut approvals = load_approvals(
+            &conn,
+            "leave_approvals.leave_id IN (SELECT id FROM leave_requests WHERE user_id = ?1)",
+            params![user_id],
+        )?;
+        for leave in list.iter_mut() {
+            leave.approvals = approvals.remove(&leave.id).unwrap_or_default();
+        }
+        Ok(list)
+    }
+
+    fn get(&self, id: i64) -> Result<LeaveRequest, LeaveError> {
+        get_leave(&self.db.lock().unwrap(), id)
+    }
+
+    fn create(
+        &self,
+        user: &User,
+        form: &LeaveForm,
+        duration: f64,
+        approvals: &[ApprovalStep],
+    ) -> Result<i64, LeaveError> {
+        let (Some(start_date), Some(end_date)) = (form.start_date, form.end_date) else {
+            return Err(LeaveError::Storage);
+        };
+        let conn = self.db.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO leave_requests
+             (user_id, leave_type, start_date, start_part, end_date, end_part, duration, remark,
+             created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                user.id,
+                form.leave_type,
+                start_date,
+                form.start_part.to_string(),
+                end_date,
+                form.end_part().to_string(),
+                duration,
+                form.remark.trim(),
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+
+        for (step, approval) in approvals.iter().enumerate() {
+            let (approver, manager_level, role) = match &approval.approver {
+                Approver::Manager(level) => ("Manager", *level, ""),
+                Approver::Role(role) => ("Role", 1, role.as_str()),
+            };
+            tx.execute(
+                "INSERT INTO leave_approvals
+                 (leave_id, step, approver, manager_level, role, approver_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    id,
+                    step,
+                    approver,
+                    manager_level,
+                    role,
+                    approval.approver_user.as_ref().map(|user| &user.id),
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn delete(&self, id: i64) -> Result<(), LeaveError> {
+        let conn = self.db.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM leave_approvals WHERE leave_id = ?1",
+            params![id],
+        )?;
+        let deleted = tx.execute("DELETE FROM leave_requests WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            return Err(LeaveError::NotExist);
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn decide(
+        &self,
+        id: i64,
+        step: Option<(usize, ApprovalStatus)>,
+        status: Option<(LeaveStatus, LeaveStatus)>,
+        decided_by: &User,
+        comment: &str,
+        on_decided: &OnDecided,
+    ) -> Result<LeaveRequest, LeaveError> {
+        let conn = self.db.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let now = chrono::Utc::now().timestamp();
+
+        if let Some((step, approval)) = step {
+            let updated = tx.execute(
+                "UPDATE leave_approvals SET status = ?1, decided_by = ?2, comment = ?3, decided_at = ?4
+                 WHERE leave_id = ?5 AND step = ?6 AND status = ?7",
+                params![
+                    approval.to_string(),
+                    decided_by.id,
+                    comment,
+                    now,
+                    id,
+                    step,
+                    ApprovalStatus::Pending.to_string(),
+                ],
+            )?;
+            if updated == 0 {
+                return Err(LeaveError::AlreadyDecided);
+            }
+        }
+        if let Some((from, to)) = status {
+            let updated = tx.execute(
+                "UPDATE leave_requests SET status = ?1, decided_by = ?2, comment = ?3, decided_at = ?4
+                 WHERE id = ?5 AND status = ?6",
+                params![
+                    to.to_string(),
+                    decided_by.id,
+                    comment,
+                    now,
+                    id,
+                    from.to_string(),
+                ],
+            )?;
+            if updated == 0 {
+                return Err(LeaveError::InvalidTransition(from, to));
+            }
+        }
+        let leave = get_leave(&tx, id)?;
+        on_decided(&tx, &leave)?;
+        tx.commit()?;
+
+        Ok(leave)
+    }
+}
+
+fn get_leave(conn: &Connection, id: i64) -> Result<LeaveRequest, LeaveError> {
+    let mut leave = conn
+        .query_row(
+            &format!("SELECT {} WHERE leave_requests.id = ?1", LEAVE_COLUMNS),
+            params![id],
+            leave_from_row,
+        )
+        .optional()?
+        .ok_or(LeaveError::NotExist)?;
+
+    leave.approvals = load_approvals(conn, "leave_approvals.leave_id = ?1", params![id])?
+        .remove(&id)
+        .unwrap_or_default();
+    Ok(leave)
+}
+
+fn leave_from_row(row: &Row) -> rusqlite::Result<LeaveRequest> {
+    let status: String = row.get(7)?;
+    let decided_at: Option<i64> = row.get(11)?;
+    let start_part: String = row.get(12)?;
+    let end_part: String = row.get(13)?;
+
+    Ok(LeaveRequest {
+        id: row.get(0)?,
+        user: User {
+            id: row.get(1)?,
+            username: row.get(2)?,
+        },
+        leave_type: row.get(3)?,
+        start_date: row.get(4)?,
+        start_part: start_part.parse().unwrap_or_default(),
+        end_date: row.get(5)?,
+        end_part: end_part.parse().unwrap_or_default(),
+        duration: row.get(14)?,
+        remark: row.get(6)?,
+        status: status.parse().unwrap_or_default(),
+        decided_by: user_from_columns(row, 8, 9)?,
+        comment: row.get(10)?,
+        decided_at: decided_at.map(format_timestamp),
+        approvals: vec![],
+        staffing: vec![],
+        attachments: vec![],
+    })
+}
+
+/// approval steps of the leave requests matching `condition`, by leave request id
+fn load_approvals<P: Params>(
+    conn: &Connection,
+    condition: &str,
+    params: P,
+) -> Result<HashMap<i64, Vec<ApprovalStep>>, LeaveError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} WHERE {} ORDER BY leave_approvals.leave_id, leave_approvals.step",
+        APPROVAL_COLUMNS, condition
+    ))?;
+    let rows = stmt
+        .query_map(params, |row| {
+            Ok((row.get::<_, i64>(0)?, approval_from_row(row)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut approvals: HashMap<i64, Vec<ApprovalStep>> = HashMap::new();
+    for (leave_id, approval) in rows {
+        approvals.entry(leave_id).or_default().push(approval);
+    }
+    Ok(approvals)
+}
+
+fn approval_from_row(row: &Row) -> rusqlite::Result<ApprovalStep> {
+    let approver: String = row.get(1)?;
+    let status: String = row.get(6)?;
+    let decided_at: Option<i64> = row.get(10)?;
+
+    Ok(ApprovalStep {
+        approver: match approver.as_str() {
+            "Role" => Approver::Role(row.get(3)?),
+            _ => Approver::Manager(row.get(2)?),
+        },
+        approver_user: user_from_columns(row, 4, 5)?,
+        status: status.parse().unwrap_or_default(),
+        decided_by: user_from_columns(row, 7, 8)?,
+        comment: row.get(9)?,
+        decided_at: decided_at.map(format_timestamp),
+    })
+}
+
+/// the user of id at column `id` and username at column `username`,
+/// a purged user keeps the id as username
+fn user_from_columns(row: &Row, id: usize, username: usize) -> rusqlite::Result<Option<User>> {
+    Ok(match row.get::<_, Option<String>>(id)? {
+        Some(id) => Some(User {
+            username: row
+                .get::<_, Option<String>>(username)?
+                .unwrap_or_else(|| id.clone()),
+            id,
+        }),
+        None => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::new_test_db;
+    use actix_web::web::Data;
+    use chrono::NaiveDate;
+    use std::sync::Mutex;
+
+    #[test]
+    fn list_no_leaves_of_deleted_users() {
+        let repository = SqliteLeaveRepository::new(Data::new(Mutex::new(new_test_db())));
+        let form = LeaveForm {
+            leave_type: "Annual".to_string(),
+            start_date: NaiveDate::from_ymd_opt(2024, 3, 1),
+            end_date: NaiveDate::from_ymd_opt(2024, 3, 1),
+            ..Default::default()
+        };
+        let mut ids = vec![];
+        for id in ["alice", "bob"] {
+            repository
+                .db
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT INTO users (id, username, password_hash, password_set_at)
+                     VALUES (?1, ?1, '', 0)",
+                    [id],
+                )
+                .unwrap();
+            let user = User {
+                id: id.to_string(),
+                username: id.to_string(),
+            };
+            ids.push(
+                repository
+                    .create(&user, &form, 1.0, &[])
+                    .unwrap_or_default(),
+            );
+        }
+
+        assert!(User::delete(&repository.db.lock().unwrap(), "alice", "123456").is_ok());
+        let list = repository.list().unwrap_or_default();
+        assert!(list.len() == 1 && list[0].id == ids[1]);
+        assert!(repository
+            .list_of_user("alice")
+            .unwrap_or_default()
+            .is_empty());
+        //  still reachable by id, such as from the recycle bin
+        assert!(repository.get(ids[0]).is_ok());
+    }
+}
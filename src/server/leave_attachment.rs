@@ -0,0 +1,383 @@
+//! Leave Attachment
+//! files attached to leave requests such as sick notes, the content is kept through the
+//! [AttachmentStorage] trait and the rest in the `leave_attachments` table
+//!
+//! - [LocalAttachmentStorage] keeps files in [SETTINGS] `attachment_dir`, the default
+//! - a file is at most [SETTINGS] `attachment_max_bytes` of one of [SETTINGS] `attachment_types`,
+//! PDF, PNG and JPEG files must start like one
+//! - the [LeavePolicy] of a leave type makes attachments mandatory for longer requests
+//! - only the requester attaches files, and removes them while the request is pending,
+//! the requester, the approvers of its chain and HR view them
+//!
+//! to keep files in another place such as an object storage, please implement [AttachmentStorage]
+
+use super::user::format_timestamp;
+use super::SETTINGS;
+use crate::models::consts::HR_ROLE;
+use crate::models::{
+    format_size, AttachmentUpload, LeaveAttachment, LeaveList, LeaveRequest, User, UserError,
+};
+use actix_web::web::Data;
+use base64::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{fmt, fs, io, path::PathBuf, sync::Arc};
+
+/// the most files attached to a leave request
+pub const MAX_ATTACHMENTS: usize = 10;
+/// the longest file name kept
+const MAX_FILE_NAME_LENGTH: usize = 255;
+
+pub trait AttachmentStorage: Send + Sync {
+    /// keep `bytes` under `key`, replacing what was kept under it
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), AttachmentError>;
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, AttachmentError>;
+
+    /// nothing to do if nothing is kept under `key`
+    fn delete(&self, key: &str) -> Result<(), AttachmentError>;
+}
+
+/// app data attachment storage
+/// used in actix app_data
+pub type AppDataAttachmentStorage = Data<dyn AttachmentStorage>;
+
+pub fn new_app_data_attachment_storage() -> AppDataAttachmentStorage {
+    let storage: Arc<dyn AttachmentStorage> =
+        Arc::new(LocalAttachmentStorage::new(&SETTINGS.attachment_dir));
+    Data::from(storage)
+}
+
+/// files in a directory named by their keys
+pub struct LocalAttachmentStorage {
+    dir: PathBuf,
+}
+
+impl LocalAttachmentStorage {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, AttachmentError> {
+        //  keys are generated uuids, anything else must not reach outside the directory
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(AttachmentError::NotExist);
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+impl AttachmentStorage for LocalAttachmentStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), AttachmentError> {
+        let path = self.path(key)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, AttachmentError> {
+        fs::read(self.path(key)?).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AttachmentError::NotExist,
+            _ => AttachmentError::Storage,
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AttachmentError> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(AttachmentError::Storage),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub enum AttachmentError {
+    NotExist,
+    InvalidFileName,
+    InvalidType,
+    TooLarge,
+    /// the file is empty or not what its type says
+    InvalidFile,
+    TooManyAttachments,
+    PermissionDenied,
+    Storage,
+}
+
+impl fmt::Display for AttachmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachmentError::NotExist => write!(f, "attachment not exist"),
+            AttachmentError::InvalidFileName => write!(
+                f,
+                "file name must have 1 to {} characters",
+                MAX_FILE_NAME_LENGTH
+            ),
+            AttachmentError::InvalidType => write!(
+                f,
+                "attachment must be one of {}",
+                SETTINGS.attachment_types.join(", ")
+            ),
+            AttachmentError::TooLarge => write!(
+                f,
+                "attachment must be at most {}",
+                format_size(SETTINGS.attachment_max_bytes as i64)
+            ),
+            AttachmentError::InvalidFile => write!(f, "attachment is empty or broken"),
+            AttachmentError::TooManyAttachments => write!(
+                f,
+                "a leave request can have at most {} attachments",
+                MAX_ATTACHMENTS
+            ),
+            AttachmentError::PermissionDenied => write!(f, "permission denied"),
+            AttachmentError::Storage => write!(f, "attachment storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AttachmentError {
+    fn from(_: rusqlite::Error) -> Self {
+        AttachmentError::Storage
+    }
+}
+
+impl From<io::Error> for AttachmentError {
+    fn from(_: io::Error) -> Self {
+        AttachmentError::Storage
+    }
+}
+
+impl From<UserError> for AttachmentError {
+    fn from(_: UserError) -> Self {
+        AttachmentError::Storage
+    }
+}
+
+/// a file kept in the storage, not attached to a leave request yet
+pub struct StoredAttachment {
+    key: String,
+    file_name: String,
+    content_type: String,
+    size: i64,
+}
+
+impl AttachmentUpload {
+    /// decode the file and keep it in `storage`, to be attached by [LeaveAttachment::attach]
+    pub fn store(
+        &self,
+        storage: &dyn AttachmentStorage,
+    ) -> Result<StoredAttachment, AttachmentError> {
+        let (file_name, content_type, bytes) = self.decode()?;
+        let key = uuid::Uuid::new_v4().to_string();
+        storage.put(&key, &bytes)?;
+
+        Ok(StoredAttachment {
+            key,
+            file_name,
+            content_type,
+            size: bytes.len() as i64,
+        })
+    }
+
+    /// the file name, the content type and the content of the file, checked against the settings
+    pub fn decode(&self) -> Result<(String, String, Vec<u8>), AttachmentError> {
+        //  browsers send the path of the file, the name is the last part
+        let file_name: String = self
+            .file_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect();
+        let file_name = file_name.trim();
+        if file_name.is_empty() || file_name.chars().count() > MAX_FILE_NAME_LENGTH {
+            return Err(AttachmentError::InvalidFileName);
+        }
+
+        let (header, data) = self
+            .data
+            .strip_prefix("data:")
+            .and_then(|data| data.split_once(','))
+            .ok_or(AttachmentError::InvalidFile)?;
+        let content_type = header
+            .strip_suffix(";base64")
+            .ok_or(AttachmentError::InvalidFile)?
+            .to_lowercase();
+        if !SETTINGS.attachment_types.contains(&content_type) {
+            return Err(AttachmentError::InvalidType);
+        }
+
+        if data.len() > SETTINGS.attachment_max_bytes / 3 * 4 + 4 {
+            return Err(AttachmentError::TooLarge);
+        }
+        let bytes = BASE64_STANDARD
+            .decode(data.trim())
+            .map_err(|_| AttachmentError::InvalidFile)?;
+        if bytes.len() > SETTINGS.attachment_max_bytes {
+            return Err(AttachmentError::TooLarge);
+        }
+        if bytes.is_empty() || !looks_like(&content_type, &bytes) {
+            return Err(AttachmentError::InvalidFile);
+        }
+
+        Ok((file_name.to_string(), content_type, bytes))
+    }
+}
+
+impl LeaveAttachment {
+    /// attachments of leave request `leave_id`, the first attached first
+    pub fn list(conn: &Connection, leave_id: i64) -> Result<Vec<Self>, AttachmentError> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE leave_id = ?1 ORDER BY created_at, rowid",
+            ATTACHMENT_COLUMNS
+        ))?;
+        let list = stmt
+            .query_map(params![leave_id], from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    pub fn get(conn: &Connection, id: &str) -> Result<Self, AttachmentError> {
+        conn.query_row(
+            &format!("SELECT {} WHERE id = ?1", ATTACHMENT_COLUMNS),
+            params![id],
+            from_row,
+        )
+        .optional()?
+        .ok_or(AttachmentError::NotExist)
+    }
+
+    /// attach the files of `stored` to leave request `leave_id` of user `user_id`, all or none,
+    /// the files are not removed if failed, see [discard]
+    pub fn attach(
+        conn: &Connection,
+        leave_id: i64,
+        user_id: &str,
+        stored: &[StoredAttachment],
+    ) -> Result<(), AttachmentError> {
+        let tx = conn.unchecked_transaction()?;
+        let count: usize = tx.query_row(
+            "SELECT COUNT(*) FROM leave_attachments WHERE leave_id = ?1",
+            params![leave_id],
+            |row| row.get(0),
+        )?;
+        if count + stored.len() > MAX_ATTACHMENTS {
+            return Err(AttachmentError::TooManyAttachments);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        for file in stored {
+            tx.execute(
+                "INSERT INTO leave_attachments
+                 (id, leave_id, user_id, file_name, content_type, size, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    file.key,
+                    leave_id,
+                    user_id,
+                    file.file_name,
+                    file.content_type,
+                    file.size,
+                    now,
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// remove the attachment and its file
+    pub fn delete(
+        conn: &Connection,
+        storage: &dyn AttachmentStorage,
+        id: &str,
+    ) -> Result<(), AttachmentError> {
+        let deleted = conn.execute("DELETE FROM leave_attachments WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            return Err(AttachmentError::NotExist);
+        }
+        storage.delete(id)
+    }
+
+    /// the content as a data url, shown or downloaded in the browser
+    pub fn read(&self, storage: &dyn AttachmentStorage) -> Result<String, AttachmentError> {
+        let bytes = storage.get(&self.id)?;
+        Ok(format!(
+            "data:{};base64,{}",
+            self.content_type,
+            BASE64_STANDARD.encode(bytes)
+        ))
+    }
+
+    /// remove the attachments of the leave requests of user `user_id` being purged,
+    /// returns their keys, whose files are deleted after committed
+    pub fn purge_of_user(conn: &Connection, user_id: &str) -> Result<Vec<String>, AttachmentError> {
+        let mut stmt = conn.prepare("SELECT id FROM leave_attachments WHERE user_id = ?1")?;
+        let keys = stmt
+            .query_map(params![user_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        conn.execute(
+            "DELETE FROM leave_attachments WHERE user_id = ?1",
+            params![user_id],
+        )?;
+
+        Ok(keys)
+    }
+}
+
+/// remove the files of `stored` not attached after all
+pub fn discard(storage: &dyn AttachmentStorage, stored: &[StoredAttachment]) {
+    for file in stored {
+        _ = storage.delete(&file.key);
+    }
+}
+
+/// find the attachments of the leave requests of `list` user `user_id` may see
+pub fn fill_attachments(
+    conn: &Connection,
+    list: &mut LeaveList,
+    user_id: &str,
+) -> Result<(), AttachmentError> {
+    let roles = User::roles(conn, user_id)?;
+    for leave in list.iter_mut() {
+        if can_view(leave, user_id, &roles) {
+            leave.attachments = LeaveAttachment::list(conn, leave.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// whether user `user_id` having `roles` sees the attachments of `leave`, such as sick notes,
+/// as the requester, the manager of a step, having the role of a step, or HR,
+/// approvers of other chains do not
+pub fn can_view(leave: &LeaveRequest, user_id: &str, roles: &[String]) -> bool {
+    leave.user.id == user_id
+        || roles.iter().any(|role| role == HR_ROLE)
+        || leave.is_chain_approver(user_id, roles)
+}
+
+const ATTACHMENT_COLUMNS: &str =
+    "id, leave_id, file_name, content_type, size, created_at FROM leave_attachments";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<LeaveAttachment> {
+    Ok(LeaveAttachment {
+        id: row.get(0)?,
+        leave_id: row.get(1)?,
+        file_name: row.get(2)?,
+        content_type: row.get(3)?,
+        size: row.get(4)?,
+        created_at: format_timestamp(row.get(5)?),
+    })
+}
+
+/// whether `bytes` start like a file of `content_type`, other types are not checked
+fn looks_like(content_type: &str, bytes: &[u8]) -> bool {
+    match content_type {
+        "application/pdf" => bytes.starts_with(b"%PDF-"),
+        "image/png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => bytes.starts_with(b"\xff\xd8\xff"),
+        _ => true,
+    }
+}
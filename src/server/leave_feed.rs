@@ -0,0 +1,239 @@
+//! Leave Feed
+//! iCalendar feeds of approved leaves for calendar apps, served at [LEAVE_FEED_ROUTE]
+//!
+//! - calendar apps cannot log in, so a feed is fetched by a secret token in its url,
+//! only the digest of the token is kept and the url is shown once when created
+//! - a feed has the leaves of its user or of the members of a department and its sub departments,
+//! users subscribe to their own department and approvers to any, like the team calendar
+//! - resetting a feed replaces its token, the old url stops working at once
+//! - feeds of users no longer active stop working, and team feeds do not show remarks
+
+use super::department::{department_of, members_of, DepartmentError};
+use super::leave::{is_approver, AppDataLeaveRepository};
+use super::user::format_timestamp;
+use super::{AppDataDb, SETTINGS};
+use crate::models::consts::LEAVE_FEED_ROUTE;
+use crate::models::{
+    leaves_to_ics, Department, LeaveFeed, LeaveList, LeaveStatus, UserError, UserStatus,
+};
+use actix_web::{web, HttpResponse, Resource};
+use base64::prelude::*;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+pub enum LeaveFeedError {
+    NotExist,
+    PermissionDenied,
+    Storage,
+}
+
+impl fmt::Display for LeaveFeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaveFeedError::NotExist => write!(f, "leave feed not exist"),
+            LeaveFeedError::PermissionDenied => write!(f, "permission denied"),
+            LeaveFeedError::Storage => write!(f, "leave feed storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for LeaveFeedError {
+    fn from(_: rusqlite::Error) -> Self {
+        LeaveFeedError::Storage
+    }
+}
+
+impl From<DepartmentError> for LeaveFeedError {
+    fn from(e: DepartmentError) -> Self {
+        match e {
+            DepartmentError::NotExist => LeaveFeedError::NotExist,
+            _ => LeaveFeedError::Storage,
+        }
+    }
+}
+
+impl From<UserError> for LeaveFeedError {
+    fn from(_: UserError) -> Self {
+        LeaveFeedError::Storage
+    }
+}
+
+impl LeaveFeed {
+    /// feeds of user `user_id`, their own leaves first
+    pub fn list(conn: &Connection, user_id: &str) -> Result<Vec<Self>, LeaveFeedError> {
+        let mut stmt = conn.prepare(
+            "SELECT leave_feeds.id, leave_feeds.department_id, COALESCE(departments.name, ''),
+                leave_feeds.created_at
+             FROM leave_feeds LEFT JOIN departments ON departments.id = leave_feeds.department_id
+             WHERE leave_feeds.user_id = ?1
+             ORDER BY leave_feeds.department_id IS NOT NULL, departments.name",
+        )?;
+        let list = stmt
+            .query_map(params![user_id], |row| {
+                Ok(LeaveFeed {
+                    id: row.get(0)?,
+                    department_id: row.get(1)?,
+                    department: row.get(2)?,
+                    created_at: format_timestamp(row.get(3)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// create the feed of user `user_id` of the leaves of department `department_id`,
+    /// or of their own leaves if None, replacing the one created before,
+    /// returns the url of the feed, which cannot be shown again
+    pub fn create(
+        conn: &Connection,
+        user_id: &str,
+        department_id: Option<&str>,
+    ) -> Result<String, LeaveFeedError> {
+        if let Some(department_id) = department_id {
+            if !Department::exists(conn, department_id)? {
+                return Err(LeaveFeedError::NotExist);
+            }
+            if !can_subscribe(conn, user_id, department_id)? {
+                return Err(LeaveFeedError::PermissionDenied);
+            }
+        }
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = BASE64_URL_SAFE_NO_PAD.encode(bytes);
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM leave_feeds WHERE user_id = ?1 AND department_id IS ?2",
+            params![user_id, department_id],
+        )?;
+        tx.execute(
+            "INSERT INTO leave_feeds (id, user_id, department_id, token_digest, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                user_id,
+                department_id,
+                digest(&token),
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(format!(
+            "{}{}/{}.ics",
+            SETTINGS.public_url.trim_end_matches('/'),
+            LEAVE_FEED_ROUTE,
+            token
+        ))
+    }
+
+    /// delete feed `id` of user `user_id`, its url stops working
+    pub fn delete(conn: &Connection, user_id: &str, id: &str) -> Result<(), LeaveFeedError> {
+        let deleted = conn.execute(
+            "DELETE FROM leave_feeds WHERE id = ?1 AND user_id = ?2",
+            params![id, user_id],
+        )?;
+        if deleted == 0 {
+            return Err(LeaveFeedError::NotExist);
+        }
+        Ok(())
+    }
+}
+
+/// the service of leave feeds, to be registered in the actix app
+pub fn leave_feed_service() -> Resource {
+    web::resource(format!("{}/{{token}}.ics", LEAVE_FEED_ROUTE)).route(web::get().to(feed))
+}
+
+async fn feed(
+    token: web::Path<String>,
+    db: AppDataDb,
+    leaves: AppDataLeaveRepository,
+) -> HttpResponse {
+    //  bound first, the lock of the scrutinee would live through the arms
+    let subscription = feed_subscription(&db.lock().unwrap(), &token);
+    let (name, members, with_remarks) = match subscription {
+        Ok(subscription) => subscription,
+        Err(LeaveFeedError::Storage) => return HttpResponse::InternalServerError().finish(),
+        //  unknown and forbidden feeds look the same, so that tokens cannot be probed
+        Err(_) => return HttpResponse::NotFound().finish(),
+    };
+
+    //  only the leaves of the feed are loaded, once its token is known
+    let mut approved = LeaveList::new();
+    for member in &members {
+        let Ok(list) = leaves.list_of_user(member) else {
+            return HttpResponse::InternalServerError().finish();
+        };
+        approved.extend(
+            list.into_iter()
+                .filter(|leave| leave.status == LeaveStatus::Approved),
+        );
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(leaves_to_ics(&name, &approved, with_remarks))
+}
+
+/// the calendar name, the members whose leaves are in it, and whether remarks are shown,
+/// of the feed of `token`
+fn feed_subscription(
+    conn: &Connection,
+    token: &str,
+) -> Result<(String, Vec<String>, bool), LeaveFeedError> {
+    let (user_id, department_id) = feed_of_token(conn, token)?;
+    match department_id {
+        Some(department_id) => {
+            if !can_subscribe(conn, &user_id, &department_id)? {
+                return Err(LeaveFeedError::PermissionDenied);
+            }
+            let name: String = conn
+                .query_row(
+                    "SELECT name FROM departments WHERE id = ?1 AND deleted_at IS NULL",
+                    params![department_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or(LeaveFeedError::NotExist)?;
+            let members = members_of(conn, &department_id)?;
+            Ok((format!("{} leaves", name), members, false))
+        }
+        None => Ok(("My leaves".to_string(), vec![user_id], true)),
+    }
+}
+
+/// the user and the department of the feed of `token`, whose user must be active
+fn feed_of_token(
+    conn: &Connection,
+    token: &str,
+) -> Result<(String, Option<String>), LeaveFeedError> {
+    conn.query_row(
+        "SELECT leave_feeds.user_id, leave_feeds.department_id
+         FROM leave_feeds JOIN users ON users.id = leave_feeds.user_id
+         WHERE leave_feeds.token_digest = ?1 AND users.status = ?2 AND users.deleted_at IS NULL",
+        params![digest(token), UserStatus::Active.to_string()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()?
+    .ok_or(LeaveFeedError::NotExist)
+}
+
+/// whether user `user_id` sees the leaves of department `department_id`,
+/// approvers see every department and others their own
+fn can_subscribe(
+    conn: &Connection,
+    user_id: &str,
+    department_id: &str,
+) -> Result<bool, LeaveFeedError> {
+    Ok(is_approver(conn, user_id)?
+        || department_of(conn, user_id)?.as_deref() == Some(department_id))
+}
+
+fn digest(token: &str) -> String {
+    BASE64_STANDARD.encode(Sha256::digest(token.as_bytes()))
+}
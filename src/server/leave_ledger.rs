@@ -0,0 +1,751 @@
+//! Leave Ledger
+//! a leave balance is the sum of the ledger entries of a user, leave type and year,
+//! entries are only appended, so that every change of a balance can be explained
+//!
+//! - accruals are granted by the [LeavePolicy] of the leave type, yearly or monthly
+//! - usages are recorded when leave requests are approved, and given back when cancelled
+//! - types of [Balance::Unlimited] have no balance, so nothing is recorded for them
+//! - HR adjusts balances manually with a reason
+//! - at year end unused days up to `max_carry_over` are carried over and the rest expire,
+//! carried over days unused `carry_over_expiry_months` into the year expire too
+//!
+//! accruals, carry-overs and expiries are recorded once a day by [accrue_periodically],
+//! every period is recorded only once however many times it runs,
+//! days used or adjusted in a past year afterwards correct its carry-over by more entries
+
+use super::user::format_timestamp;
+use super::AppDataDb;
+use crate::models::{
+    round_days, Accrual, Balance, FieldErrors, LeaveAdjustmentForm, LeaveBalance, LeaveError,
+    LeaveList, LeavePolicy, LeaveRequest, LeaveStatus, LeaveType, LedgerEntry, LedgerKind,
+};
+use chrono::{Datelike, Months, NaiveDate, NaiveTime};
+use rusqlite::{params, Connection};
+use std::time::Duration;
+
+/// how often accruals are recorded
+const ACCRUAL_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// the most days of a policy or an adjustment
+const MAX_DAYS: f64 = 366.0;
+
+impl LeavePolicy {
+    /// errors of every invalid field, empty if the policy is valid
+    pub fn validate(&self) -> FieldErrors {
+        let mut errors = FieldErrors::new();
+        if !(0.0..=MAX_DAYS).contains(&self.days_per_year) {
+            errors.insert(
+                "days_per_year".into(),
+                format!("must be between 0 and {}", MAX_DAYS),
+            );
+        }
+        if !(0.0..=MAX_DAYS).contains(&self.max_carry_over) {
+            errors.insert(
+                "max_carry_over".into(),
+                format!("must be between 0 and {}", MAX_DAYS),
+            );
+        }
+        if self.carry_over_expiry_months > 12 {
+            errors.insert(
+                "carry_over_expiry_months".into(),
+                "must be between 0 and 12".into(),
+            );
+        }
+        if self
+            .attachment_over_days
+            .is_some_and(|days| !(0.0..=MAX_DAYS).contains(&days))
+        {
+            errors.insert(
+                "attachment_over_days".into(),
+                format!("must be between 0 and {}", MAX_DAYS),
+            );
+        }
+        errors
+    }
+}
+
+impl LedgerEntry {
+    /// ledger entries of user `user_id` in `year`, the latest first
+    pub fn list(conn: &Connection, user_id: &str, year: i32) -> Result<Vec<Self>, LeaveError> {
+        let mut stmt = conn.prepare(
+            "SELECT leave_ledger.id, leave_ledger.leave_type, leave_ledger.year, leave_ledger.kind,
+                leave_ledger.days, leave_ledger.reason, creators.username, leave_ledger.created_at
+             FROM leave_ledger LEFT JOIN users AS creators ON creators.id = leave_ledger.created_by
+             WHERE leave_ledger.user_id = ?1 AND leave_ledger.year = ?2
+             ORDER BY leave_ledger.id DESC",
+        )?;
+        let list = stmt
+            .query_map(params![user_id, year], |row| {
+                let kind: String = row.get(3)?;
+                Ok(LedgerEntry {
+                    id: row.get(0)?,
+                    leave_type: row.get(1)?,
+                    year: row.get(2)?,
+                    kind: kind.parse().unwrap_or_default(),
+                    days: row.get(4)?,
+                    reason: row.get(5)?,
+                    created_by: row.get(6)?,
+                    created_at: format_timestamp(row.get(7)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+}
+
+/// a ledger entry to be recorded
+struct NewEntry<'a> {
+    user_id: &'a str,
+    leave_type: &'a str,
+    year: i32,
+    kind: LedgerKind,
+    days: f64,
+    /// entries of the same period are recorded only once
+    period: Option<String>,
+    leave_id: Option<i64>,
+    reason: String,
+    created_by: Option<&'a str>,
+}
+
+impl NewEntry<'_> {
+    /// returns whether recorded, false if the period was recorded already
+    fn record(&self, conn: &Connection) -> Result<bool, LeaveError> {
+        let recorded = conn.execute(
+            "INSERT OR IGNORE INTO leave_ledger
+             (user_id, leave_type, year, kind, days, period, leave_id, reason, created_by, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                self.user_id,
+                self.leave_type,
+                self.year,
+                self.kind.to_string(),
+                round_days(self.days),
+                self.period,
+                self.leave_id,
+                self.reason,
+                self.created_by,
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        Ok(recorded > 0)
+    }
+
+    /// record the difference between the days of the entry and `recorded` days of its period,
+    /// as the period the first time and as its next correction afterwards,
+    /// returns how many entries recorded
+    fn record_difference(self, conn: &Connection, recorded: f64) -> Result<usize, LeaveError> {
+        let days = round_days(self.days - recorded);
+        if days == 0.0 {
+            return Ok(0);
+        }
+        let Some(period) = self.period.clone() else {
+            return Ok(0);
+        };
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM leave_ledger
+             WHERE user_id = ?1 AND leave_type = ?2 AND kind = ?3
+                AND (period = ?4 OR period LIKE ?4 || ' correction %')",
+            params![self.user_id, self.leave_type, self.kind.to_string(), period],
+            |row| row.get(0),
+        )?;
+        let entry = if count == 0 {
+            NewEntry { days, ..self }
+        } else {
+            NewEntry {
+                days,
+                period: Some(format!("{} correction {}", period, count)),
+                reason: format!("{}, corrected", self.reason),
+                ..self
+            }
+        };
+        Ok(usize::from(entry.record(conn)?))
+    }
+}
+
+/// balances of every leave type of user `user_id` in `year`,
+/// pending days are of `leaves` of the user waiting for approval
+pub fn leave_balances(
+    conn: &Connection,
+    leaves: &LeaveList,
+    user_id: &str,
+    year: i32,
+) -> Result<Vec<LeaveBalance>, LeaveError> {
+    let mut balances: Vec<LeaveBalance> = LeaveType::list(conn)?
+        .into_iter()
+        .filter(|leave_type| leave_type.active && leave_type.policy.balance == Balance::Tracked)
+        .map(|leave_type| LeaveBalance {
+            leave_type: leave_type.name,
+            year,
+            ..Default::default()
+        })
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT leave_type, kind, SUM(days) FROM leave_ledger
+         WHERE user_id = ?1 AND year = ?2 GROUP BY leave_type, kind",
+    )?;
+    let sums = stmt
+        .query_map(params![user_id, year], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (leave_type, kind, days) in sums {
+        //  inactive types are shown while they have days in the year
+        let index = match balances
+            .iter()
+            .position(|balance| balance.leave_type == leave_type)
+        {
+            Some(index) => index,
+            None => {
+                balances.push(LeaveBalance {
+                    leave_type,
+                    year,
+                    ..Default::default()
+                });
+                balances.len() - 1
+            }
+        };
+        let balance = &mut balances[index];
+        match kind.parse().unwrap_or_default() {
+            LedgerKind::Accrual | LedgerKind::CarryOver => balance.accrued += days,
+            LedgerKind::Usage => balance.used -= days,
+            LedgerKind::Adjustment => balance.adjusted += days,
+            LedgerKind::Expiry => balance.expired -= days,
+        }
+    }
+
+    for leave in leaves.iter() {
+        if leave.user.id != user_id
+            || leave.status != LeaveStatus::Pending
+            || leave.start_date.year() != year
+        {
+            continue;
+        }
+        if let Some(balance) = balances
+            .iter_mut()
+            .find(|balance| balance.leave_type == leave.leave_type)
+        {
+            balance.pending += leave.duration;
+        }
+    }
+
+    for balance in balances.iter_mut() {
+        balance.accrued = round_days(balance.accrued);
+        balance.used = round_days(balance.used);
+        balance.adjusted = round_days(balance.adjusted);
+        balance.expired = round_days(balance.expired);
+    }
+    Ok(balances)
+}
+
+/// record the days taken by `leave` just approved, or given back by `leave` just cancelled,
+/// all in the year it starts, as leave requests never span two years
+pub fn record_leave_usage(conn: &Connection, leave: &LeaveRequest) -> Result<(), LeaveError> {
+    let (days, action) = match leave.status {
+        LeaveStatus::Approved => {
+            let tracked = LeaveType::find(conn, &leave.leave_type)?
+                .is_some_and(|leave_type| leave_type.policy.balance == Balance::Tracked);
+            if !tracked {
+                return Ok(());
+            }
+            (-leave.duration, "approved")
+        }
+        //  only the days really taken are given back
+        LeaveStatus::Cancelled => {
+            let taken: Option<f64> = conn.query_row(
+                "SELECT SUM(days) FROM leave_ledger WHERE leave_id = ?1 AND kind = 'Usage'",
+                params![leave.id],
+                |row| row.get(0),
+            )?;
+            (-taken.unwrap_or_default(), "cancelled")
+        }
+        _ => return Ok(()),
+    };
+    if days == 0.0 {
+        return Ok(());
+    }
+
+    NewEntry {
+        user_id: &leave.user.id,
+        leave_type: &leave.leave_type,
+        year: leave.start_date.year(),
+        kind: LedgerKind::Usage,
+        days,
+        period: Some(format!("leave {} {}", leave.id, action)),
+        leave_id: Some(leave.id),
+        reason: format!(
+            "leave from {} to {} {}",
+            leave.start_date, leave.end_date, action
+        ),
+        created_by: leave.decided_by.as_ref().map(|user| user.id.as_str()),
+    }
+    .record(conn)?;
+    carry_over_again(
+        conn,
+        &leave.user.id,
+        &leave.leave_type,
+        leave.start_date.year(),
+    )
+}
+
+impl LeaveAdjustmentForm {
+    /// errors of every invalid field, empty if the form is valid
+    pub fn validate(&self, conn: &Connection) -> Result<FieldErrors, LeaveError> {
+        let mut errors = FieldErrors::new();
+
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM users WHERE id = ?1 AND deleted_at IS NULL)",
+            params![self.user_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            errors.insert("user_id".into(), "not exist".into());
+        }
+        if LeaveType::find(conn, &self.leave_type)?.is_none() {
+            errors.insert("leave_type".into(), "not exist".into());
+        }
+        if round_days(self.days) == 0.0 || self.days.abs() > MAX_DAYS {
+            errors.insert(
+                "days".into(),
+                format!("must be between -{} and {} except 0", MAX_DAYS, MAX_DAYS),
+            );
+        }
+        if !(1970..=9999).contains(&self.year) {
+            errors.insert("year".into(), "is invalid".into());
+        }
+        if self.reason.trim().is_empty() {
+            errors.insert("reason".into(), "is required".into());
+        }
+
+        Ok(errors)
+    }
+
+    /// the form must be validated first,
+    /// carry-overs of the years after are recomputed along if the adjustment is of a past year
+    pub fn save(&self, conn: &Connection, created_by: &str) -> Result<(), LeaveError> {
+        let tx = conn.unchecked_transaction()?;
+        NewEntry {
+            user_id: &self.user_id,
+            leave_type: &self.leave_type,
+            year: self.year,
+            kind: LedgerKind::Adjustment,
+            days: self.days,
+            period: None,
+            leave_id: None,
+            reason: self.reason.trim().to_string(),
+            created_by: Some(created_by),
+        }
+        .record(&tx)?;
+        carry_over_again(&tx, &self.user_id, &self.leave_type, self.year)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// record accruals, carry-overs and expiries of active users due by `today`,
+/// returns how many entries recorded
+pub fn accrue(conn: &Connection, today: NaiveDate) -> Result<usize, LeaveError> {
+    let policies = active_policies(conn)?;
+    let mut recorded = 0;
+    for user_id in active_users(conn)? {
+        recorded += accrue_user(conn, &user_id, &policies, today)?;
+    }
+    Ok(recorded)
+}
+
+/// record accruals due by `today` periodically, runs until the server stops,
+/// the database is locked for one user at a time, not to hold requests meanwhile
+pub async fn accrue_periodically(db: AppDataDb) {
+    let mut interval = actix_web::rt::time::interval(ACCRUAL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let today = chrono::Local::now().date_naive();
+        let loaded = {
+            let conn = db.lock().unwrap();
+            active_users(&conn).and_then(|users| Ok((users, active_policies(&conn)?)))
+        };
+        let (users, policies) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::warn!("record leave accruals fail: {}", e);
+                continue;
+            }
+        };
+
+        let mut count = 0;
+        for user_id in users.iter() {
+            let result = accrue_user(&db.lock().unwrap(), user_id, &policies, today);
+            match result {
+                Ok(recorded) => count += recorded,
+                Err(e) => log::warn!("record leave accruals of {} fail: {}", user_id, e),
+            }
+        }
+        if count > 0 {
+            log::info!("{} leave ledger entries recorded", count);
+        }
+    }
+}
+
+fn active_users(conn: &Connection) -> Result<Vec<String>, LeaveError> {
+    let mut stmt =
+        conn.prepare("SELECT id FROM users WHERE deleted_at IS NULL AND status = 'Active'")?;
+    let users = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(users)
+}
+
+/// policies of the active types with a balance
+fn active_policies(conn: &Connection) -> Result<Vec<LeavePolicy>, LeaveError> {
+    Ok(LeaveType::list(conn)?
+        .into_iter()
+        .filter(|leave_type| leave_type.active && leave_type.policy.balance == Balance::Tracked)
+        .map(|leave_type| leave_type.policy)
+        .collect())
+}
+
+/// record accruals, carry-overs and expiries of user `user_id` due by `today` in one transaction
+fn accrue_user(
+    conn: &Connection,
+    user_id: &str,
+    policies: &[LeavePolicy],
+    today: NaiveDate,
+) -> Result<usize, LeaveError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut recorded = 0;
+    for policy in policies.iter() {
+        recorded += carry_over(&tx, user_id, policy, today.year())?;
+        recorded += expire_carry_over(&tx, user_id, policy, today.year(), today)?;
+        recorded += accrue_policy(&tx, user_id, policy, today)?;
+    }
+    tx.commit()?;
+    Ok(recorded)
+}
+
+/// carry-overs and expiries of `leave_type` of user `user_id` into the years after `year`
+/// up to this one are recomputed, as days of `year` changed after they were carried over
+fn carry_over_again(
+    conn: &Connection,
+    user_id: &str,
+    leave_type: &str,
+    year: i32,
+) -> Result<(), LeaveError> {
+    let today = chrono::Local::now().date_naive();
+    if year >= today.year() {
+        return Ok(());
+    }
+    let Some(leave_type) = LeaveType::find(conn, leave_type)? else {
+        return Ok(());
+    };
+    if !leave_type.active || leave_type.policy.balance != Balance::Tracked {
+        return Ok(());
+    }
+
+    for next in year + 1..=today.year() {
+        carry_over(conn, user_id, &leave_type.policy, next)?;
+        expire_carry_over(conn, user_id, &leave_type.policy, next, today)?;
+    }
+    Ok(())
+}
+
+fn accrue_policy(
+    conn: &Connection,
+    user_id: &str,
+    policy: &LeavePolicy,
+    today: NaiveDate,
+) -> Result<usize, LeaveError> {
+    let year = today.year();
+    let periods = match policy.accrual {
+        Accrual::Yearly => vec![(year.to_string(), policy.days_per_year)],
+        Accrual::Monthly => (1..=today.month())
+            .map(|month| {
+                (
+                    format!("{}-{:02}", year, month),
+                    policy.days_per_year / 12.0,
+                )
+            })
+            .collect(),
+    };
+
+    let mut recorded = 0;
+    for (period, days) in periods {
+        if days <= 0.0 {
+            continue;
+        }
+        let entry = NewEntry {
+            user_id,
+            leave_type: &policy.leave_type,
+            year,
+            kind: LedgerKind::Accrual,
+            days,
+            reason: format!("{} accrual of {}", policy.accrual, period),
+            period: Some(period),
+            leave_id: None,
+            created_by: None,
+        };
+        if entry.record(conn)? {
+            recorded += 1;
+        }
+    }
+    Ok(recorded)
+}
+
+/// carry unused days of the last year over to `year`, the rest of them expire,
+/// both are recomputed every time, and only the difference from the days recorded is recorded
+fn carry_over(
+    conn: &Connection,
+    user_id: &str,
+    policy: &LeavePolicy,
+    year: i32,
+) -> Result<usize, LeaveError> {
+    let last_year = year - 1;
+    let total: Option<f64> = conn.query_row(
+        "SELECT SUM(days) FROM leave_ledger WHERE user_id = ?1 AND leave_type = ?2 AND year = ?3",
+        params![user_id, policy.leave_type, last_year],
+        |row| row.get(0),
+    )?;
+    let expired = period_days(
+        conn,
+        user_id,
+        &policy.leave_type,
+        last_year,
+        LedgerKind::Expiry,
+        &last_year.to_string(),
+    )?;
+    let unused = round_days(total.unwrap_or_default() - expired).max(0.0);
+    let carried = unused.min(policy.max_carry_over);
+    let carried_before = period_days(
+        conn,
+        user_id,
+        &policy.leave_type,
+        year,
+        LedgerKind::CarryOver,
+        &year.to_string(),
+    )?;
+
+    let expiry = NewEntry {
+        user_id,
+        leave_type: &policy.leave_type,
+        year: last_year,
+        kind: LedgerKind::Expiry,
+        days: carried - unused,
+        period: Some(last_year.to_string()),
+        leave_id: None,
+        reason: format!("unused days of {} expired", last_year),
+        created_by: None,
+    };
+    let carry_over = NewEntry {
+        user_id,
+        leave_type: &policy.leave_type,
+        year,
+        kind: LedgerKind::CarryOver,
+        days: carried,
+        period: Some(year.to_string()),
+        leave_id: None,
+        reason: format!("unused days carried over from {}", last_year),
+        created_by: None,
+    };
+    Ok(expiry.record_difference(conn, expired)?
+        + carry_over.record_difference(conn, carried_before)?)
+}
+
+/// carried over days of `year` not used `carry_over_expiry_months` into it expire by `today`,
+/// days used by then are taken from the carried over days first
+fn expire_carry_over(
+    conn: &Connection,
+    user_id: &str,
+    policy: &LeavePolicy,
+    year: i32,
+    today: NaiveDate,
+) -> Result<usize, LeaveError> {
+    if policy.carry_over_expiry_months == 0 {
+        return Ok(0);
+    }
+    let Some(expires_at) = NaiveDate::from_ymd_opt(year, 1, 1)
+        .and_then(|first| first.checked_add_months(Months::new(policy.carry_over_expiry_months)))
+    else {
+        return Ok(0);
+    };
+    if today < expires_at {
+        return Ok(0);
+    }
+
+    let carried = period_days(
+        conn,
+        user_id,
+        &policy.leave_type,
+        year,
+        LedgerKind::CarryOver,
+        &year.to_string(),
+    )?;
+    //  days used after the expiry are not taken from the carried over days anymore
+    let used: Option<f64> = conn.query_row(
+        "SELECT -SUM(days) FROM leave_ledger
+         WHERE user_id = ?1 AND leave_type = ?2 AND year = ?3 AND kind = 'Usage'
+            AND created_at < ?4",
+        params![
+            user_id,
+            policy.leave_type,
+            year,
+            expires_at.and_time(NaiveTime::MIN).and_utc().timestamp()
+        ],
+        |row| row.get(0),
+    )?;
+    let unused = round_days(carried - used.unwrap_or_default().max(0.0)).max(0.0);
+
+    let period = format!("{} carry-over", year);
+    let expired = period_days(
+        conn,
+        user_id,
+        &policy.leave_type,
+        year,
+        LedgerKind::Expiry,
+        &period,
+    )?;
+    NewEntry {
+        user_id,
+        leave_type: &policy.leave_type,
+        year,
+        kind: LedgerKind::Expiry,
+        days: -unused,
+        period: Some(period),
+        leave_id: None,
+        reason: format!("unused days carried over from {} expired", year - 1),
+        created_by: None,
+    }
+    .record_difference(conn, expired)
+}
+
+/// days of the entries of `kind` recorded for `period` in `year`, its corrections included
+fn period_days(
+    conn: &Connection,
+    user_id: &str,
+    leave_type: &str,
+    year: i32,
+    kind: LedgerKind,
+    period: &str,
+) -> Result<f64, LeaveError> {
+    let days: Option<f64> = conn.query_row(
+        "SELECT SUM(days) FROM leave_ledger
+         WHERE user_id = ?1 AND leave_type = ?2 AND year = ?3 AND kind = ?4
+            AND (period = ?5 OR period LIKE ?5 || ' correction %')",
+        params![user_id, leave_type, year, kind.to_string(), period],
+        |row| row.get(0),
+    )?;
+    Ok(round_days(days.unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::new_test_db;
+
+    fn annual(conn: &Connection) -> LeavePolicy {
+        LeaveType::find(conn, "Annual")
+            .ok()
+            .unwrap()
+            .unwrap()
+            .policy
+    }
+
+    fn entry(kind: LedgerKind, year: i32, days: f64, period: Option<&str>) -> NewEntry<'static> {
+        NewEntry {
+            user_id: "123456",
+            leave_type: "Annual",
+            year,
+            kind,
+            days,
+            period: period.map(str::to_string),
+            leave_id: None,
+            reason: String::new(),
+            created_by: None,
+        }
+    }
+
+    fn sum(conn: &Connection, year: i32, kind: LedgerKind) -> f64 {
+        let days: Option<f64> = conn
+            .query_row(
+                "SELECT SUM(days) FROM leave_ledger WHERE year = ?1 AND kind = ?2",
+                params![year, kind.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        round_days(days.unwrap_or_default())
+    }
+
+    #[test]
+    fn carry_over_corrected_by_days_used_afterwards() {
+        let conn = new_test_db();
+        let policy = annual(&conn);
+        entry(LedgerKind::Accrual, 2020, 20.0, Some("2020"))
+            .record(&conn)
+            .ok()
+            .unwrap();
+
+        assert!(carry_over(&conn, "123456", &policy, 2021).unwrap_or_default() == 2);
+        assert!(sum(&conn, 2021, LedgerKind::CarryOver) == 5.0);
+        assert!(sum(&conn, 2020, LedgerKind::Expiry) == -15.0);
+
+        //  a leave of the last year approved after the year end
+        entry(LedgerKind::Usage, 2020, -18.0, None)
+            .record(&conn)
+            .ok()
+            .unwrap();
+        assert!(carry_over(&conn, "123456", &policy, 2021).unwrap_or_default() == 2);
+        assert!(sum(&conn, 2021, LedgerKind::CarryOver) == 2.0);
+        assert!(sum(&conn, 2020, LedgerKind::Expiry) == 0.0);
+
+        //  nothing more once corrected
+        assert!(carry_over(&conn, "123456", &policy, 2021).unwrap_or_default() == 0);
+    }
+
+    #[test]
+    fn accrue_every_period_once() {
+        let conn = new_test_db();
+        let today = NaiveDate::from_ymd_opt(2030, 3, 15).unwrap();
+
+        //  3 months of annual leave of 1.67 days, and sick and personal leave of the year
+        assert!(accrue(&conn, today).unwrap_or_default() == 5);
+        assert!(accrue(&conn, today).unwrap_or_default() == 0);
+        assert!(sum(&conn, 2030, LedgerKind::Accrual) == 20.01);
+    }
+
+    #[test]
+    fn carried_over_days_unused_expire() {
+        let conn = new_test_db();
+        entry(LedgerKind::Accrual, 2029, 20.0, Some("2029"))
+            .record(&conn)
+            .ok()
+            .unwrap();
+
+        //  annual leave carried over expires 3 months into the year
+        accrue(&conn, NaiveDate::from_ymd_opt(2030, 2, 1).unwrap())
+            .ok()
+            .unwrap();
+        assert!(sum(&conn, 2030, LedgerKind::CarryOver) == 5.0);
+        assert!(sum(&conn, 2030, LedgerKind::Expiry) == 0.0);
+
+        entry(LedgerKind::Usage, 2030, -2.0, None)
+            .record(&conn)
+            .ok()
+            .unwrap();
+        let today = NaiveDate::from_ymd_opt(2030, 4, 2).unwrap();
+        accrue(&conn, today).ok().unwrap();
+        assert!(sum(&conn, 2030, LedgerKind::Expiry) == -3.0);
+
+        //  days used after the expiry are not taken from the carried over days
+        conn.execute(
+            "INSERT INTO leave_ledger (user_id, leave_type, year, kind, days, created_at)
+             VALUES ('123456', 'Annual', 2030, 'Usage', -1, ?1)",
+            params![today.and_time(NaiveTime::MIN).and_utc().timestamp()],
+        )
+        .unwrap();
+        accrue(&conn, today).ok().unwrap();
+        assert!(sum(&conn, 2030, LedgerKind::Expiry) == -3.0);
+    }
+}
@@ -0,0 +1,143 @@
+//! Leave Type
+//! leave types are defined by administrators in the `leave_types` table together with
+//! their [LeavePolicy], and referred to by name by leave requests, ledger entries and approval rules
+//!
+//! - a type cannot be renamed or deleted once created, as requests and ledger entries keep its name,
+//! it is made inactive instead, so that it cannot be requested any more
+//! - requests of [LeaveType] `backdated` types may start in the past, such as sick leave
+//! - [crate::models::Balance::Unlimited] types are requested without a balance,
+//! and neither accrue days nor record the days used
+//! - only active types accrue days and show up in balances, unless days are left in the year
+
+use crate::models::{FieldErrors, LeaveError, LeavePolicy, LeaveType};
+use rusqlite::{params, Connection};
+
+/// the longest name of a leave type
+const MAX_NAME_LENGTH: usize = 50;
+
+impl LeaveType {
+    /// all of leave types, in the order created
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, LeaveError> {
+        let mut stmt = conn.prepare(
+            "SELECT name, color, paid, backdated, active, accrual, days_per_year, max_carry_over,
+                carry_over_expiry_months, attachment_over_days, balance
+             FROM leave_types ORDER BY rowid",
+        )?;
+        let list = stmt
+            .query_map([], |row| {
+                let name: String = row.get(0)?;
+                let accrual: String = row.get(5)?;
+                let balance: String = row.get(10)?;
+                Ok(LeaveType {
+                    color: row.get(1)?,
+                    paid: row.get(2)?,
+                    backdated: row.get(3)?,
+                    active: row.get(4)?,
+                    policy: LeavePolicy {
+                        leave_type: name.clone(),
+                        balance: balance.parse().unwrap_or_default(),
+                        accrual: accrual.parse().unwrap_or_default(),
+                        days_per_year: row.get(6)?,
+                        max_carry_over: row.get(7)?,
+                        carry_over_expiry_months: row.get(8)?,
+                        attachment_over_days: row.get(9)?,
+                    },
+                    name,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// leave type `name`, None if not exist
+    pub fn find(conn: &Connection, name: &str) -> Result<Option<Self>, LeaveError> {
+        Ok(Self::list(conn)?
+            .into_iter()
+            .find(|leave_type| leave_type.name == name))
+    }
+
+    /// errors of every invalid field of creating a leave type if `create`, otherwise of editing one,
+    /// empty if valid
+    pub fn validate(&self, conn: &Connection, create: bool) -> Result<FieldErrors, LeaveError> {
+        let mut errors = self.policy.validate();
+
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            errors.insert(
+                "name".into(),
+                format!("must have 1 to {} characters", MAX_NAME_LENGTH),
+            );
+        } else {
+            //  names differing only in case are too alike to create
+            let sql = if create {
+                "SELECT EXISTS (SELECT 1 FROM leave_types WHERE name = ?1 COLLATE NOCASE)"
+            } else {
+                "SELECT EXISTS (SELECT 1 FROM leave_types WHERE name = ?1)"
+            };
+            let exists: bool = conn.query_row(sql, params![name], |row| row.get(0))?;
+            if create && exists {
+                errors.insert("name".into(), "already exists".into());
+            } else if !create && !exists {
+                errors.insert("name".into(), "not exist".into());
+            }
+        }
+
+        let is_color = self.color.len() == 7
+            && self.color.starts_with('#')
+            && self.color[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if !is_color {
+            errors.insert("color".into(), "must be a color like #3b82f6".into());
+        }
+
+        Ok(errors)
+    }
+
+    /// create the leave type if `create`, otherwise change it,
+    /// which must be validated first, changes of the policy apply to accruals recorded afterwards
+    pub fn save(&self, conn: &Connection, create: bool) -> Result<(), LeaveError> {
+        let name = self.name.trim();
+        if create {
+            conn.execute(
+                "INSERT INTO leave_types (name, color, paid, backdated, active, accrual,
+                    days_per_year, max_carry_over, carry_over_expiry_months, attachment_over_days,
+                    balance)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    name,
+                    self.color.to_lowercase(),
+                    self.paid,
+                    self.backdated,
+                    self.active,
+                    self.policy.accrual.to_string(),
+                    self.policy.days_per_year,
+                    self.policy.max_carry_over,
+                    self.policy.carry_over_expiry_months,
+                    self.policy.attachment_over_days,
+                    self.policy.balance.to_string(),
+                ],
+            )?;
+        } else {
+            conn.execute(
+                "UPDATE leave_types SET color = ?2, paid = ?3, backdated = ?4, active = ?5,
+                    accrual = ?6, days_per_year = ?7, max_carry_over = ?8,
+                    carry_over_expiry_months = ?9, attachment_over_days = ?10, balance = ?11
+                 WHERE name = ?1",
+                params![
+                    name,
+                    self.color.to_lowercase(),
+                    self.paid,
+                    self.backdated,
+                    self.active,
+                    self.policy.accrual.to_string(),
+                    self.policy.days_per_year,
+                    self.policy.max_carry_over,
+                    self.policy.carry_over_expiry_months,
+                    self.policy.attachment_over_days,
+                    self.policy.balance.to_string(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Mailer
+//! mails to users are sent through the [Mailer] trait,
+//! if you would like to send by SMTP or an API, please implement [Mailer] trait
+//!
+//! the default [FileMailer] writes every mail into a file of [SETTINGS] `mail_dir`,
+//! which is enough for development and tests
+
+use super::SETTINGS;
+use actix_web::web::Data;
+use std::{fmt, fs, path::PathBuf, sync::Arc};
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub enum MailError {
+    Send,
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::Send => write!(f, "send mail fail"),
+        }
+    }
+}
+
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> Result<(), MailError>;
+}
+
+/// app data mailer
+/// used in actix app_data
+pub type AppDataMailer = Data<dyn Mailer>;
+
+pub fn new_app_data_mailer() -> AppDataMailer {
+    let mailer: Arc<dyn Mailer> = Arc::new(FileMailer::new(&SETTINGS.mail_dir));
+    Data::from(mailer)
+}
+
+/// writes every mail as a `.eml` file into a directory
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        fs::create_dir_all(&self.dir).map_err(|_| MailError::Send)?;
+
+        let now = chrono::Utc::now();
+        let recipient: String = mail
+            .to
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            now.timestamp_nanos_opt().unwrap_or_default(),
+            recipient
+        ));
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            SETTINGS.mail_from,
+            mail.to,
+            mail.subject,
+            now.to_rfc2822(),
+            mail.body
+        );
+
+        fs::write(path, content).map_err(|_| MailError::Send)
+    }
+}
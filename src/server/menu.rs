@@ -1,3 +1,4 @@
+use crate::models::consts::{ADMIN_ROLE, HR_ROLE};
 use crate::models::{Menu, MenuList, SubMenu};
 
 pub async fn get_menu_list() -> MenuList {
@@ -7,6 +8,7 @@ pub async fn get_menu_list() -> MenuList {
                 id: 1,
                 title: "Forms".to_string(),
                 icon: "pencil".to_string(),
+                role: None,
                 sub_menu: vec![
                     SubMenu {
                         id: 11,
@@ -29,6 +31,7 @@ pub async fn get_menu_list() -> MenuList {
                 id: 2,
                 title: "Tables".to_string(),
                 icon: "table".to_string(),
+                role: None,
                 sub_menu: vec![
                     SubMenu {
                         id: 21,
@@ -42,6 +45,86 @@ pub async fn get_menu_list() -> MenuList {
                     },
                 ],
             },
+            Menu {
+                id: 5,
+                title: "Leave".to_string(),
+                icon: "calendar".to_string(),
+                role: None,
+                sub_menu: vec![
+                    SubMenu {
+                        id: 51,
+                        title: "new request".to_string(),
+                        link: "/admin/leaves/new".to_string(),
+                    },
+                    SubMenu {
+                        id: 52,
+                        title: "team calendar".to_string(),
+                        link: "/admin/leaves/calendar".to_string(),
+                    },
+                ],
+            },
+            Menu {
+                id: 6,
+                title: "HR".to_string(),
+                icon: "calendar".to_string(),
+                role: Some(HR_ROLE.to_string()),
+                sub_menu: vec![
+                    SubMenu {
+                        id: 61,
+                        title: "leave balances".to_string(),
+                        link: "/admin/leaves/balances".to_string(),
+                    },
+                    SubMenu {
+                        id: 62,
+                        title: "approval chain".to_string(),
+                        link: "/admin/leaves/approval-chain".to_string(),
+                    },
+                    SubMenu {
+                        id: 63,
+                        title: "holidays".to_string(),
+                        link: "/admin/holidays".to_string(),
+                    },
+                    SubMenu {
+                        id: 64,
+                        title: "leave types".to_string(),
+                        link: "/admin/leaves/types".to_string(),
+                    },
+                ],
+            },
+            Menu {
+                id: 3,
+                title: "Users".to_string(),
+                icon: "people".to_string(),
+                role: Some(ADMIN_ROLE.to_string()),
+                sub_menu: vec![
+                    SubMenu {
+                        id: 31,
+                        title: "users".to_string(),
+                        link: "/admin/users".to_string(),
+                    },
+                    SubMenu {
+                        id: 32,
+                        title: "departments".to_string(),
+                        link: "/admin/departments".to_string(),
+                    },
+                    SubMenu {
+                        id: 33,
+                        title: "recycle bin".to_string(),
+                        link: "/admin/recycle-bin".to_string(),
+                    },
+                ],
+            },
+            Menu {
+                id: 4,
+                title: "Security".to_string(),
+                icon: "shield".to_string(),
+                role: Some(ADMIN_ROLE.to_string()),
+                sub_menu: vec![SubMenu {
+                    id: 41,
+                    title: "session events".to_string(),
+                    link: "/admin/security/events".to_string(),
+                }],
+            },
         ]
     }
     .await
@@ -1,13 +1,33 @@
 //! server mod
 //! some modules and functions used in server side
 
+pub mod approval;
 mod authentication;
+pub mod avatar;
+pub mod calendar;
+pub mod captcha;
 mod cipher;
 mod cipher_server;
+mod db;
+pub mod department;
+pub mod invitation;
 pub mod leave;
+pub mod leave_attachment;
+pub mod leave_feed;
+pub mod leave_ledger;
+pub mod leave_type;
+pub mod mailer;
 mod menu;
+pub mod passkey;
+pub mod recycle_bin;
+pub mod scim;
+pub mod session;
+mod settings;
 pub mod user;
+pub mod user_csv;
 
 pub use authentication::*;
 pub use cipher_server::*;
+pub use db::*;
 pub use menu::*;
+pub use settings::*;
@@ -0,0 +1,284 @@
+//! Passkey
+//! WebAuthn registration and authentication of users, backed by webauthn-rs
+//!
+//! the ceremony state between start and finish is kept in memory by [AppDataPasskeyState]
+//! for [CEREMONY_TTL] seconds, registered credentials are stored per user in the `passkeys` table
+//!
+//! anyone may start signing in, so authentications are keyed by a random ceremony id
+//! returned to the browser, and one sign-in cannot replace the challenge of another
+
+use super::SETTINGS;
+use crate::models::{PasskeyInfo, PasskeyLoginOptions, User};
+use actix_web::web::Data;
+use rusqlite::{params, Connection};
+use std::{collections::HashMap, fmt, sync::Mutex};
+use webauthn_rs::prelude::*;
+
+/// seconds a ceremony may take between start and finish
+const CEREMONY_TTL: i64 = 300;
+
+/// app data WebAuthn relying party
+/// used in actix app_data
+pub type AppDataWebauthn = Data<Webauthn>;
+
+pub fn new_app_data_webauthn() -> AppDataWebauthn {
+    let origin = Url::parse(&SETTINGS.rp_origin).expect("invalid WebAuthn origin");
+    let webauthn = WebauthnBuilder::new(&SETTINGS.rp_id, &origin)
+        .expect("invalid WebAuthn relying party")
+        .rp_name("Dvorak Admin")
+        .build()
+        .expect("build WebAuthn fail");
+
+    Data::new(webauthn)
+}
+
+/// app data of pending passkey ceremonies
+/// used in actix app_data, must be shared by all workers
+pub type AppDataPasskeyState = Data<Mutex<PasskeyState>>;
+
+pub fn new_app_data_passkey_state() -> AppDataPasskeyState {
+    Data::new(Mutex::new(PasskeyState::default()))
+}
+
+/// pending ceremonies and their expiry,
+/// registrations are keyed by user id and authentications by ceremony id with the username
+#[derive(Default)]
+pub struct PasskeyState {
+    registrations: HashMap<String, (PasskeyRegistration, i64)>,
+    authentications: HashMap<String, (String, PasskeyAuthentication, i64)>,
+}
+
+impl PasskeyState {
+    /// forget ceremonies never finished
+    fn remove_expired(&mut self, now: i64) {
+        self.registrations
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        self.authentications
+            .retain(|_, (_, _, expires_at)| *expires_at > now);
+    }
+}
+
+pub enum PasskeyError {
+    NotRegistered,
+    NotStarted,
+    Verify,
+    Storage,
+}
+
+impl fmt::Display for PasskeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasskeyError::NotRegistered => write!(f, "no passkey registered"),
+            PasskeyError::NotStarted => write!(f, "passkey ceremony not started"),
+            PasskeyError::Verify => write!(f, "passkey verification fail"),
+            PasskeyError::Storage => write!(f, "passkey storage fail"),
+        }
+    }
+}
+
+impl From<WebauthnError> for PasskeyError {
+    fn from(_: WebauthnError) -> Self {
+        PasskeyError::Verify
+    }
+}
+
+impl From<rusqlite::Error> for PasskeyError {
+    fn from(_: rusqlite::Error) -> Self {
+        PasskeyError::Storage
+    }
+}
+
+impl PasskeyInfo {
+    /// all of passkeys registered by user
+    pub fn list(conn: &Connection, user_id: &str) -> Result<Vec<Self>, PasskeyError> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, created_at FROM passkeys WHERE user_id = ?1 ORDER BY id",
+        )?;
+        let list = stmt
+            .query_map(params![user_id], |row| {
+                Ok(PasskeyInfo {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    pub fn delete(conn: &Connection, user_id: &str, id: i64) -> Result<(), PasskeyError> {
+        conn.execute(
+            "DELETE FROM passkeys WHERE id = ?1 AND user_id = ?2",
+            params![id, user_id],
+        )?;
+        Ok(())
+    }
+}
+
+/// start registering a new passkey of `user`,
+/// returns the options for `navigator.credentials.create`
+pub fn start_registration(
+    webauthn: &Webauthn,
+    state: &mut PasskeyState,
+    conn: &Connection,
+    user: &User,
+) -> Result<CreationChallengeResponse, PasskeyError> {
+    let exclude = load_passkeys(conn, "user_id", &user.id)?
+        .into_iter()
+        .map(|(_, _, passkey)| passkey.cred_id().clone())
+        .collect();
+
+    let (options, registration) = webauthn.start_passkey_registration(
+        user_handle(&user.id),
+        &user.username,
+        &user.username,
+        Some(exclude),
+    )?;
+    let now = chrono::Utc::now().timestamp();
+    state.remove_expired(now);
+    state
+        .registrations
+        .insert(user.id.clone(), (registration, now + CEREMONY_TTL));
+
+    Ok(options)
+}
+
+/// verify the credential created by browser and store it as a passkey of `user`
+pub fn finish_registration(
+    webauthn: &Webauthn,
+    state: &mut PasskeyState,
+    conn: &Connection,
+    user: &User,
+    name: &str,
+    credential: &RegisterPublicKeyCredential,
+) -> Result<(), PasskeyError> {
+    state.remove_expired(chrono::Utc::now().timestamp());
+    let (registration, _) = state
+        .registrations
+        .remove(&user.id)
+        .ok_or(PasskeyError::NotStarted)?;
+    let passkey = webauthn.finish_passkey_registration(credential, &registration)?;
+
+    conn.execute(
+        "INSERT INTO passkeys (user_id, username, name, credential, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            user.id,
+            user.username,
+            name,
+            serde_json::to_string(&passkey).map_err(|_| PasskeyError::Storage)?,
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// start signing in `username` with one of the registered passkeys,
+/// returns the ceremony id and the options for `navigator.credentials.get`
+pub fn start_authentication(
+    webauthn: &Webauthn,
+    state: &mut PasskeyState,
+    conn: &Connection,
+    username: &str,
+) -> Result<PasskeyLoginOptions, PasskeyError> {
+    let passkeys: Vec<Passkey> = load_passkeys(conn, "username", username)?
+        .into_iter()
+        .map(|(_, _, passkey)| passkey)
+        .collect();
+    if passkeys.is_empty() {
+        return Err(PasskeyError::NotRegistered);
+    }
+
+    let (options, authentication) = webauthn.start_passkey_authentication(&passkeys)?;
+    let options = serde_json::to_string(&options).map_err(|_| PasskeyError::Verify)?;
+
+    let now = chrono::Utc::now().timestamp();
+    state.remove_expired(now);
+    let ceremony_id = Uuid::new_v4().to_string();
+    state.authentications.insert(
+        ceremony_id.clone(),
+        (username.to_string(), authentication, now + CEREMONY_TTL),
+    );
+
+    Ok(PasskeyLoginOptions {
+        ceremony_id,
+        options,
+    })
+}
+
+/// verify the assertion signed by browser for ceremony `ceremony_id`,
+/// returns the user who owns the passkey
+pub fn finish_authentication(
+    webauthn: &Webauthn,
+    state: &mut PasskeyState,
+    conn: &Connection,
+    ceremony_id: &str,
+    credential: &PublicKeyCredential,
+) -> Result<User, PasskeyError> {
+    state.remove_expired(chrono::Utc::now().timestamp());
+    let (username, authentication, _) = state
+        .authentications
+        .remove(ceremony_id)
+        .ok_or(PasskeyError::NotStarted)?;
+    let result = webauthn.finish_passkey_authentication(credential, &authentication)?;
+
+    for (id, user_id, mut passkey) in load_passkeys(conn, "username", &username)? {
+        //  the used passkey also keeps the signature counter up to date
+        if let Some(updated) = passkey.update_credential(&result) {
+            if updated {
+                conn.execute(
+                    "UPDATE passkeys SET credential = ?1 WHERE id = ?2",
+                    params![
+                        serde_json::to_string(&passkey).map_err(|_| PasskeyError::Storage)?,
+                        id
+                    ],
+                )?;
+            }
+
+            return Ok(User {
+                id: user_id,
+                username,
+            });
+        }
+    }
+
+    Err(PasskeyError::NotRegistered)
+}
+
+/// load passkeys of active users by `column`, which is either `user_id` or `username`
+fn load_passkeys(
+    conn: &Connection,
+    column: &str,
+    value: &str,
+) -> Result<Vec<(i64, String, Passkey)>, PasskeyError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT passkeys.id, passkeys.user_id, passkeys.credential FROM passkeys
+         JOIN users ON users.id = passkeys.user_id
+         WHERE passkeys.{} = ?1 AND users.status = 'Active' AND users.deleted_at IS NULL",
+        column
+    ))?;
+    let rows = stmt
+        .query_map(params![value], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    rows.into_iter()
+        .map(|(id, user_id, credential)| {
+            serde_json::from_str(&credential)
+                .map(|passkey| (id, user_id, passkey))
+                .map_err(|_| PasskeyError::Storage)
+        })
+        .collect()
+}
+
+/// WebAuthn user handle, stable for the same user id
+fn user_handle(user_id: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, user_id.as_bytes())
+}
@@ -0,0 +1,221 @@
+//! Recycle Bin
+//! deleted users and departments are only marked by `deleted_at` and `deleted_by`,
+//! so records referring to them such as leave requests are kept,
+//! they are hidden from other queries and can be restored or purged here
+//!
+//! records deleted more than [SETTINGS] `recycle_retention_days` ago are purged automatically
+//!
+//! files of a purged user, the avatar and the attachments of leave requests, are removed
+//! after the purge is committed
+
+use super::avatar::remove_files;
+use super::department::DepartmentError;
+use super::leave_attachment::{
+    AppDataAttachmentStorage, AttachmentError, AttachmentStorage, LeaveAttachment,
+};
+use super::user::format_timestamp;
+use super::{AppDataDb, SETTINGS};
+use crate::models::{DeletedRecord, Department, RecordKind, User, UserError};
+use rusqlite::{params, Connection};
+use std::{fmt, time::Duration};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/// how often expired records are purged
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub enum RecycleBinError {
+    NotExist,
+    Storage,
+}
+
+impl fmt::Display for RecycleBinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecycleBinError::NotExist => write!(f, "record not exist in recycle bin"),
+            RecycleBinError::Storage => write!(f, "recycle bin storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for RecycleBinError {
+    fn from(_: rusqlite::Error) -> Self {
+        RecycleBinError::Storage
+    }
+}
+
+impl From<UserError> for RecycleBinError {
+    fn from(e: UserError) -> Self {
+        match e {
+            UserError::NotExist => RecycleBinError::NotExist,
+            _ => RecycleBinError::Storage,
+        }
+    }
+}
+
+impl From<AttachmentError> for RecycleBinError {
+    fn from(_: AttachmentError) -> Self {
+        RecycleBinError::Storage
+    }
+}
+
+impl From<DepartmentError> for RecycleBinError {
+    fn from(e: DepartmentError) -> Self {
+        match e {
+            DepartmentError::NotExist => RecycleBinError::NotExist,
+            _ => RecycleBinError::Storage,
+        }
+    }
+}
+
+impl DeletedRecord {
+    /// all of deleted records, the latest deleted first
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, RecycleBinError> {
+        let mut stmt = conn.prepare(
+            "SELECT 'User', users.id, users.display_name || ' (' || users.username || ')',
+                users.deleted_at, COALESCE(deleters.username, users.deleted_by)
+             FROM users LEFT JOIN users AS deleters ON deleters.id = users.deleted_by
+             WHERE users.deleted_at IS NOT NULL
+             UNION ALL
+             SELECT 'Department', departments.id, departments.name,
+                departments.deleted_at, COALESCE(deleters.username, departments.deleted_by)
+             FROM departments LEFT JOIN users AS deleters ON deleters.id = departments.deleted_by
+             WHERE departments.deleted_at IS NOT NULL
+             ORDER BY 4 DESC",
+        )?;
+        let list = stmt
+            .query_map([], |row| {
+                let kind: String = row.get(0)?;
+                let deleted_at: i64 = row.get(3)?;
+                Ok(DeletedRecord {
+                    kind: kind.parse().unwrap_or(RecordKind::User),
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    deleted_at: format_timestamp(deleted_at),
+                    deleted_by: row.get(4)?,
+                    purge_at: purge_at(deleted_at).map(format_timestamp),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list)
+    }
+
+    /// bring a deleted record back
+    pub fn restore(conn: &Connection, kind: RecordKind, id: &str) -> Result<(), RecycleBinError> {
+        match kind {
+            RecordKind::User => User::restore(conn, id)?,
+            RecordKind::Department => Department::restore(conn, id)?,
+        }
+        Ok(())
+    }
+
+    /// remove a deleted record for ever
+    pub fn purge(
+        conn: &Connection,
+        attachments: &dyn AttachmentStorage,
+        kind: RecordKind,
+        id: &str,
+    ) -> Result<(), RecycleBinError> {
+        let tx = conn.unchecked_transaction()?;
+        let files = purge_record(&tx, kind, id)?;
+        tx.commit()?;
+
+        files.remove(attachments);
+        Ok(())
+    }
+}
+
+/// purge the records deleted longer than the retention period, returns how many purged
+pub fn purge_expired(
+    conn: &Connection,
+    attachments: &dyn AttachmentStorage,
+) -> Result<usize, RecycleBinError> {
+    if SETTINGS.recycle_retention_days <= 0 {
+        return Ok(0);
+    }
+    let before = chrono::Utc::now().timestamp() - SETTINGS.recycle_retention_days * SECONDS_PER_DAY;
+
+    let mut stmt = conn.prepare(
+        "SELECT 'User', id FROM users WHERE deleted_at < ?1
+         UNION ALL
+         SELECT 'Department', id FROM departments WHERE deleted_at < ?1",
+    )?;
+    let expired = stmt
+        .query_map(params![before], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut purged = vec![];
+    for (kind, id) in expired.iter() {
+        let kind = kind.parse().unwrap_or(RecordKind::User);
+        purged.push(purge_record(&tx, kind, id)?);
+    }
+    tx.commit()?;
+
+    for files in purged {
+        files.remove(attachments);
+    }
+    Ok(expired.len())
+}
+
+/// purge expired records at once and then every [PURGE_INTERVAL], runs until the server stops
+pub async fn purge_expired_periodically(db: AppDataDb, attachments: AppDataAttachmentStorage) {
+    let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match purge_expired(&db.lock().unwrap(), &**attachments) {
+            Ok(0) => {}
+            Ok(count) => log::info!("{} expired records purged from recycle bin", count),
+            Err(e) => log::warn!("purge recycle bin fail: {}", e),
+        }
+    }
+}
+
+/// files of a purged record, removed after committed
+#[derive(Default)]
+struct PurgedFiles {
+    avatar: Option<String>,
+    /// keys of leave attachments
+    attachments: Vec<String>,
+}
+
+impl PurgedFiles {
+    fn remove(self, attachments: &dyn AttachmentStorage) {
+        if let Some(avatar) = self.avatar {
+            remove_files(&avatar);
+        }
+        for key in self.attachments {
+            if let Err(e) = attachments.delete(&key) {
+                log::warn!("remove attachment {} fail: {}", key, e);
+            }
+        }
+    }
+}
+
+fn purge_record(
+    conn: &Connection,
+    kind: RecordKind,
+    id: &str,
+) -> Result<PurgedFiles, RecycleBinError> {
+    match kind {
+        RecordKind::User => {
+            let attachments = LeaveAttachment::purge_of_user(conn, id)?;
+            Ok(PurgedFiles {
+                avatar: Some(User::purge(conn, id)?),
+                attachments,
+            })
+        }
+        RecordKind::Department => {
+            Department::purge(conn, id)?;
+            Ok(PurgedFiles::default())
+        }
+    }
+}
+
+/// None if deleted records are kept forever
+fn purge_at(deleted_at: i64) -> Option<i64> {
+    (SETTINGS.recycle_retention_days > 0)
+        .then(|| deleted_at + SETTINGS.recycle_retention_days * SECONDS_PER_DAY)
+}
@@ -0,0 +1,293 @@
+//! SCIM
+//! SCIM 2.0 provisioning of users and groups by an identity provider, served at [SCIM_ROUTE]
+//!
+//! - every request must carry `Authorization: Bearer {token}` of [SETTINGS] `scim_token`,
+//! SCIM is disabled when the token is not configured
+//! - SCIM users are our users, `active` false disables the user and DELETE moves the user
+//! into the recycle bin, new active users are [UserStatus::Pending] and invited by email
+//! - SCIM groups are our roles, the members of a group are the users having the role
+//!
+//! only the `eq` filter on a single attribute is supported, which is what identity providers
+//! use to look up users and groups before provisioning
+
+mod group;
+mod user;
+
+use super::SETTINGS;
+use actix_web::{
+    body::BoxBody,
+    dev::Payload,
+    http::{header, StatusCode},
+    web, FromRequest, HttpRequest, HttpResponse, ResponseError, Scope,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// SCIM endpoints, Users and Groups are under it
+pub const SCIM_ROUTE: &str = "/scim/v2";
+
+const CONTENT_TYPE: &str = "application/scim+json";
+const LIST_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+/// the most resources returned in a page
+const MAX_RESULTS: usize = 200;
+
+/// the service of all SCIM endpoints, to be registered in the actix app
+pub fn scim_service() -> Scope {
+    web::scope(SCIM_ROUTE)
+        .route("/ServiceProviderConfig", web::get().to(service_provider_config))
+        .route("/Users", web::get().to(user::list))
+        .route("/Users", web::post().to(user::create))
+        .route("/Users/{id}", web::get().to(user::get))
+        .route("/Users/{id}", web::put().to(user::replace))
+        .route("/Users/{id}", web::patch().to(user::patch))
+        .route("/Users/{id}", web::delete().to(user::delete))
+        .route("/Groups", web::get().to(group::list))
+        .route("/Groups", web::post().to(group::create))
+        .route("/Groups/{id}", web::get().to(group::get))
+        .route("/Groups/{id}", web::put().to(group::replace))
+        .route("/Groups/{id}", web::patch().to(group::patch))
+        .route("/Groups/{id}", web::delete().to(group::delete))
+}
+
+#[derive(Debug)]
+pub enum ScimError {
+    Unauthorized,
+    NotFound,
+    InvalidSyntax(String),
+    InvalidValue(String),
+    InvalidFilter,
+    Mutability(String),
+    Uniqueness(String),
+    Storage,
+}
+
+impl fmt::Display for ScimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScimError::Unauthorized => write!(f, "provisioning token is missing or invalid"),
+            ScimError::NotFound => write!(f, "resource not exist"),
+            ScimError::InvalidSyntax(detail) => write!(f, "request is invalid: {}", detail),
+            ScimError::InvalidValue(detail) => write!(f, "{}", detail),
+            ScimError::InvalidFilter => write!(f, "only `attribute eq \"value\"` filter is supported"),
+            ScimError::Mutability(detail) => write!(f, "{}", detail),
+            ScimError::Uniqueness(detail) => write!(f, "{}", detail),
+            ScimError::Storage => write!(f, "provisioning storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ScimError {
+    fn from(_: rusqlite::Error) -> Self {
+        ScimError::Storage
+    }
+}
+
+impl ResponseError for ScimError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ScimError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ScimError::NotFound => StatusCode::NOT_FOUND,
+            ScimError::Uniqueness(_) => StatusCode::CONFLICT,
+            ScimError::Storage => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let scim_type = match self {
+            ScimError::InvalidSyntax(_) => Some("invalidSyntax"),
+            ScimError::InvalidValue(_) => Some("invalidValue"),
+            ScimError::InvalidFilter => Some("invalidFilter"),
+            ScimError::Mutability(_) => Some("mutability"),
+            ScimError::Uniqueness(_) => Some("uniqueness"),
+            _ => None,
+        };
+        let status = self.status_code();
+
+        HttpResponse::build(status)
+            .content_type(CONTENT_TYPE)
+            .json(json!({
+                "schemas": [ERROR_SCHEMA],
+                "status": status.as_u16().to_string(),
+                "scimType": scim_type,
+                "detail": self.to_string(),
+            }))
+    }
+}
+
+/// the identity provider calling SCIM endpoints,
+/// extracted only if the request carries the configured provisioning token
+pub struct ScimClient;
+
+impl FromRequest for ScimClient {
+    type Error = ScimError;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let authorized = match (token, &SETTINGS.scim_token) {
+            //  digests are compared, so that the time taken does not reveal the token
+            (Some(token), Some(expected)) => {
+                Sha256::digest(token.trim().as_bytes()) == Sha256::digest(expected.as_bytes())
+            }
+            _ => false,
+        };
+
+        futures::future::ready(if authorized {
+            Ok(ScimClient)
+        } else {
+            Err(ScimError::Unauthorized)
+        })
+    }
+}
+
+/// query of listing resources, `startIndex` is 1-based
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    pub filter: Option<String>,
+    pub start_index: Option<usize>,
+    pub count: Option<usize>,
+}
+
+impl ListQuery {
+    /// attribute and value of the `attribute eq "value"` filter, None if not filtered
+    pub fn parse_filter(&self) -> Result<Option<(String, String)>, ScimError> {
+        let Some(filter) = self.filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut parts = filter.splitn(3, ' ');
+        let (Some(attribute), Some(operator), Some(value)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ScimError::InvalidFilter);
+        };
+        if !operator.eq_ignore_ascii_case("eq") {
+            return Err(ScimError::InvalidFilter);
+        }
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .ok_or(ScimError::InvalidFilter)?;
+
+        Ok(Some((attribute.to_string(), value.replace("\\\"", "\""))))
+    }
+
+    /// a page of `resources` as a list response
+    pub fn respond<T: Serialize>(&self, resources: Vec<T>) -> HttpResponse {
+        let total = resources.len();
+        let start_index = self.start_index.unwrap_or(1).max(1);
+        let count = self.count.unwrap_or(MAX_RESULTS).min(MAX_RESULTS);
+        let page: Vec<T> = resources
+            .into_iter()
+            .skip(start_index - 1)
+            .take(count)
+            .collect();
+
+        respond(
+            StatusCode::OK,
+            &json!({
+                "schemas": [LIST_SCHEMA],
+                "totalResults": total,
+                "startIndex": start_index,
+                "itemsPerPage": page.len(),
+                "Resources": page,
+            }),
+        )
+    }
+}
+
+/// body of PATCH requests
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Deserialize)]
+pub struct PatchOperation {
+    /// `add`, `replace` or `remove`, case insensitive
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+/// resource metadata
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    pub resource_type: String,
+    pub location: String,
+}
+
+impl Meta {
+    pub fn new(resource_type: &str, id: &str) -> Self {
+        Self {
+            resource_type: resource_type.to_string(),
+            location: format!(
+                "{}{}/{}s/{}",
+                SETTINGS.public_url.trim_end_matches('/'),
+                SCIM_ROUTE,
+                resource_type,
+                id
+            ),
+        }
+    }
+}
+
+/// parse a JSON body, whatever the content type is,
+/// identity providers send both `application/json` and `application/scim+json`
+pub fn parse_body<T: DeserializeOwned>(body: &web::Bytes) -> Result<T, ScimError> {
+    serde_json::from_slice(body).map_err(|e| ScimError::InvalidSyntax(e.to_string()))
+}
+
+pub fn respond<T: Serialize>(status: StatusCode, body: &T) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(CONTENT_TYPE)
+        .json(body)
+}
+
+/// a created resource, located at `location`
+pub fn respond_created<T: Serialize>(location: &str, body: &T) -> HttpResponse {
+    HttpResponse::Created()
+        .content_type(CONTENT_TYPE)
+        .insert_header((header::LOCATION, location))
+        .json(body)
+}
+
+/// a JSON value as bool, identity providers send booleans both as JSON and as strings
+pub fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        Value::String(value) => value.to_ascii_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+async fn service_provider_config(_: ScimClient) -> HttpResponse {
+    respond(
+        StatusCode::OK,
+        &json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig"],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": MAX_RESULTS },
+            "changePassword": { "supported": false },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Bearer Token",
+                "description": "the provisioning token configured by DVORAK_SCIM_TOKEN",
+            }],
+        }),
+    )
+}
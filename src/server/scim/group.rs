@@ -0,0 +1,299 @@
+use super::{
+    parse_body, respond, respond_created, ListQuery, Meta, PatchOperation, PatchRequest,
+    ScimClient, ScimError,
+};
+use crate::models::consts::{ADMIN_ROLE, EMPLOYEE_ROLE, HR_ROLE, MANAGER_ROLE};
+use crate::models::Role;
+use crate::server::AppDataDb;
+use actix_web::{http::StatusCode, web, HttpResponse};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+/// roles the application relies on, which cannot be deleted by the identity provider
+const BUILTIN_ROLES: [&str; 4] = [ADMIN_ROLE, MANAGER_ROLE, HR_ROLE, EMPLOYEE_ROLE];
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ScimGroup {
+    #[serde(default)]
+    schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    display_name: String,
+    #[serde(default)]
+    members: Vec<Member>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta: Option<Meta>,
+}
+
+/// a user having the role
+#[derive(Serialize, Deserialize, Clone)]
+struct Member {
+    /// user id
+    value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display: Option<String>,
+}
+
+impl ScimGroup {
+    /// the group of role `name`, whose id is the role name
+    fn load(conn: &Connection, name: &str) -> Result<Self, ScimError> {
+        let name: String = conn
+            .query_row(
+                "SELECT name FROM roles WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(ScimError::NotFound)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT users.id, users.username FROM user_roles
+             JOIN users ON users.id = user_roles.user_id
+             WHERE user_roles.role = ?1 AND users.deleted_at IS NULL
+             ORDER BY users.username",
+        )?;
+        let members = stmt
+            .query_map(params![name], |row| {
+                Ok(Member {
+                    value: row.get(0)?,
+                    display: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(ScimGroup {
+            schemas: vec![GROUP_SCHEMA.to_string()],
+            id: Some(name.clone()),
+            meta: Some(Meta::new("Group", &name)),
+            display_name: name,
+            members,
+        })
+    }
+
+    fn apply(&mut self, operation: &PatchOperation) -> Result<(), ScimError> {
+        let op = operation.op.to_ascii_lowercase();
+        let path = operation.path.as_deref().unwrap_or_default();
+        let value = operation.value.clone().unwrap_or(Value::Null);
+
+        match (op.as_str(), path.to_ascii_lowercase().as_str()) {
+            ("add", "members") => {
+                for member in parse_members(value)? {
+                    if self.members.iter().all(|m| m.value != member.value) {
+                        self.members.push(member);
+                    }
+                }
+            }
+            ("replace", "members") => self.members = parse_members(value)?,
+            ("remove", "members") if value.is_null() => self.members.clear(),
+            ("remove", "members") => {
+                let removed = parse_members(value)?;
+                self.members
+                    .retain(|m| removed.iter().all(|removed| removed.value != m.value));
+            }
+            //  such as `members[value eq "{user id}"]`
+            ("remove", lowered) if lowered.starts_with("members[") => {
+                let id = path
+                    .split('"')
+                    .nth(1)
+                    .ok_or(ScimError::InvalidSyntax(format!("path {} is invalid", path)))?;
+                self.members.retain(|m| m.value != id);
+            }
+            ("add" | "replace", "displayname") => {
+                self.display_name = value.as_str().unwrap_or_default().to_string()
+            }
+            ("add" | "replace", "") => match value {
+                Value::Object(attributes) => {
+                    for (key, value) in attributes {
+                        self.apply(&PatchOperation {
+                            op: operation.op.clone(),
+                            path: Some(key),
+                            value: Some(value),
+                        })?;
+                    }
+                }
+                _ => {
+                    return Err(ScimError::InvalidSyntax(
+                        "value must be an object without path".into(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(ScimError::InvalidSyntax(format!(
+                    "op {} of path {} is not supported",
+                    operation.op, path
+                )))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_members(value: Value) -> Result<Vec<Member>, ScimError> {
+    serde_json::from_value(value).map_err(|e| ScimError::InvalidValue(e.to_string()))
+}
+
+pub async fn list(
+    _: ScimClient,
+    db: AppDataDb,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, ScimError> {
+    let filter = query.parse_filter()?;
+    let conn = db.lock().unwrap();
+
+    let mut groups = vec![];
+    for role in Role::list(&conn).map_err(|_| ScimError::Storage)? {
+        let matched = match &filter {
+            None => true,
+            Some((attribute, value)) => match attribute.to_ascii_lowercase().as_str() {
+                "id" | "displayname" => role.name.eq_ignore_ascii_case(value),
+                _ => return Err(ScimError::InvalidFilter),
+            },
+        };
+        if matched {
+            groups.push(ScimGroup::load(&conn, &role.name)?);
+        }
+    }
+
+    Ok(query.respond(groups))
+}
+
+pub async fn get(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ScimError> {
+    let conn = db.lock().unwrap();
+    Ok(respond(StatusCode::OK, &ScimGroup::load(&conn, &id)?))
+}
+
+/// a new group is a new role
+pub async fn create(
+    _: ScimClient,
+    db: AppDataDb,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let scim: ScimGroup = parse_body(&body)?;
+    let name = scim.display_name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ScimError::InvalidValue(
+            "displayName must have 1 to 64 characters".into(),
+        ));
+    }
+
+    let conn = db.lock().unwrap();
+    let tx = conn.unchecked_transaction()?;
+    let created = tx.execute(
+        "INSERT OR IGNORE INTO roles (name, description) VALUES (?1, ?2)",
+        params![name, "provisioned by SCIM"],
+    )?;
+    if created == 0 {
+        return Err(ScimError::Uniqueness(format!("group {} already exists", name)));
+    }
+    set_members(&tx, name, &scim.members)?;
+    tx.commit()?;
+
+    let group = ScimGroup::load(&conn, name)?;
+    let location = group.meta.clone().unwrap_or_default().location;
+    Ok(respond_created(&location, &group))
+}
+
+pub async fn replace(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let scim: ScimGroup = parse_body(&body)?;
+    let conn = db.lock().unwrap();
+
+    ScimGroup::load(&conn, &id)?;
+    save(&conn, &id, &scim)?;
+    Ok(respond(StatusCode::OK, &ScimGroup::load(&conn, &id)?))
+}
+
+pub async fn patch(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let request: PatchRequest = parse_body(&body)?;
+    let conn = db.lock().unwrap();
+
+    let mut scim = ScimGroup::load(&conn, &id)?;
+    for operation in request.operations.iter() {
+        scim.apply(operation)?;
+    }
+    save(&conn, &id, &scim)?;
+    Ok(respond(StatusCode::OK, &ScimGroup::load(&conn, &id)?))
+}
+
+/// only roles provisioned by the identity provider can be deleted
+pub async fn delete(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ScimError> {
+    if BUILTIN_ROLES.contains(&id.as_str()) {
+        return Err(ScimError::Mutability(format!("group {} cannot be deleted", id)));
+    }
+
+    let conn = db.lock().unwrap();
+    let tx = conn.unchecked_transaction()?;
+    let deleted = tx.execute("DELETE FROM roles WHERE name = ?1", params![id.as_str()])?;
+    if deleted == 0 {
+        return Err(ScimError::NotFound);
+    }
+    tx.execute("DELETE FROM user_roles WHERE role = ?1", params![id.as_str()])?;
+    tx.commit()?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// role names are referred to by users, so groups cannot be renamed
+fn save(conn: &Connection, name: &str, scim: &ScimGroup) -> Result<(), ScimError> {
+    if scim.display_name.trim() != name {
+        return Err(ScimError::Mutability("displayName cannot be changed".into()));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    set_members(&tx, name, &scim.members)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// the users having role `name` become exactly `members`
+fn set_members(conn: &Connection, name: &str, members: &[Member]) -> Result<(), ScimError> {
+    for member in members {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM users WHERE id = ?1 AND deleted_at IS NULL)",
+            params![member.value],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(ScimError::InvalidValue(format!(
+                "member {} not exist",
+                member.value
+            )));
+        }
+    }
+
+    //  deleted users keep their roles, in case they are restored
+    conn.execute(
+        "DELETE FROM user_roles WHERE role = ?1
+            AND user_id IN (SELECT id FROM users WHERE deleted_at IS NULL)",
+        params![name],
+    )?;
+    for member in members {
+        conn.execute(
+            "INSERT OR IGNORE INTO user_roles (user_id, role) VALUES (?1, ?2)",
+            params![member.value, name],
+        )?;
+    }
+
+    Ok(())
+}
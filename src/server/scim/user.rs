@@ -0,0 +1,405 @@
+use super::{
+    parse_body, respond, respond_created, value_as_bool, ListQuery, Meta, PatchOperation,
+    PatchRequest, ScimClient, ScimError,
+};
+use crate::models::consts::{DEFAULT_AVATAR, EMPLOYEE_ROLE};
+use crate::models::{FieldErrors, User, UserDetail, UserError, UserForm, UserStatus};
+use crate::server::invitation::{cancel, invite, is_invited};
+use crate::server::mailer::{AppDataMailer, Mail, Mailer};
+use crate::server::user::random_password;
+use crate::server::AppDataDb;
+use actix_web::{http::StatusCode, web, HttpResponse};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+/// who deleted and invited users, for records made by the identity provider
+const SCIM_ACTOR: &str = "scim";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ScimUser {
+    #[serde(default)]
+    schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+    user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<Name>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+    #[serde(default)]
+    emails: Vec<Email>,
+    #[serde(default = "active_by_default")]
+    active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta: Option<Meta>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct Name {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    family_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Email {
+    value: String,
+    #[serde(default)]
+    primary: bool,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+fn active_by_default() -> bool {
+    true
+}
+
+impl ScimUser {
+    fn load(conn: &Connection, id: &str) -> Result<Self, ScimError> {
+        let user = UserDetail::get(conn, id).map_err(user_error)?;
+        to_scim(conn, user)
+    }
+
+    /// display name, or the name, or the username if neither given
+    fn display_name(&self) -> String {
+        let name = self.name.clone().unwrap_or_default();
+        let full_name = [name.given_name, name.family_name]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        [self.display_name.clone(), name.formatted, Some(full_name)]
+            .into_iter()
+            .flatten()
+            .find(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| self.user_name.clone())
+    }
+
+    /// the primary email, or the first one
+    fn email(&self) -> String {
+        self.emails
+            .iter()
+            .find(|email| email.primary)
+            .or(self.emails.first())
+            .map(|email| email.value.clone())
+            .unwrap_or_default()
+    }
+
+    fn apply(&mut self, operation: &PatchOperation) -> Result<(), ScimError> {
+        match (operation.op.to_ascii_lowercase().as_str(), &operation.path) {
+            ("add" | "replace", Some(path)) => {
+                self.set(path, operation.value.clone().unwrap_or(Value::Null))
+            }
+            ("add" | "replace", None) => match operation.value.clone() {
+                Some(Value::Object(attributes)) => attributes
+                    .into_iter()
+                    .try_for_each(|(path, value)| self.set(&path, value)),
+                _ => Err(ScimError::InvalidSyntax("value must be an object without path".into())),
+            },
+            ("remove", Some(path)) => self.set(path, Value::Null),
+            _ => Err(ScimError::InvalidSyntax(format!("op {} is not supported", operation.op))),
+        }
+    }
+
+    /// set the attribute at `path` to `value`, null to clear it,
+    /// attributes not kept by us are ignored
+    fn set(&mut self, path: &str, value: Value) -> Result<(), ScimError> {
+        let text = || value.as_str().map(str::to_string);
+        let name = self.name.get_or_insert_with(Name::default);
+
+        match path.to_ascii_lowercase().as_str() {
+            "active" => {
+                self.active = value_as_bool(&value)
+                    .ok_or(ScimError::InvalidValue("active must be a boolean".into()))?
+            }
+            "username" => {
+                self.user_name = text().ok_or(ScimError::InvalidValue("userName is required".into()))?
+            }
+            "externalid" => self.external_id = text(),
+            "displayname" => self.display_name = text(),
+            "name" => *name = serde_json::from_value(value).unwrap_or_default(),
+            "name.formatted" => name.formatted = text(),
+            "name.givenname" => name.given_name = text(),
+            "name.familyname" => name.family_name = text(),
+            "emails" => {
+                self.emails = match value {
+                    Value::Array(_) => serde_json::from_value(value),
+                    _ => serde_json::from_value(Value::Array(vec![value])),
+                }
+                .map_err(|e| ScimError::InvalidValue(e.to_string()))?
+            }
+            //  such as `emails[type eq "work"].value`, only one email is kept by us
+            path if path.starts_with("emails[") && path.ends_with("].value") => {
+                let value = text().ok_or(ScimError::InvalidValue("email is required".into()))?;
+                self.emails = vec![Email {
+                    value,
+                    primary: true,
+                    kind: Some("work".into()),
+                }];
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn to_scim(conn: &Connection, user: UserDetail) -> Result<ScimUser, ScimError> {
+    let external_id: Option<String> = conn.query_row(
+        "SELECT external_id FROM users WHERE id = ?1",
+        params![user.id],
+        |row| row.get(0),
+    )?;
+    //  a pending user is active while invited, until the invitation expires
+    let active = match user.status {
+        UserStatus::Active => true,
+        UserStatus::Disabled => false,
+        UserStatus::Pending => is_invited(conn, &user.id).map_err(|_| ScimError::Storage)?,
+    };
+
+    Ok(ScimUser {
+        schemas: vec![USER_SCHEMA.to_string()],
+        meta: Some(Meta::new("User", &user.id)),
+        id: Some(user.id),
+        external_id,
+        user_name: user.username,
+        name: Some(Name {
+            formatted: Some(user.display_name.clone()),
+            ..Default::default()
+        }),
+        display_name: Some(user.display_name),
+        emails: vec![Email {
+            value: user.email,
+            primary: true,
+            kind: Some("work".into()),
+        }],
+        active,
+    })
+}
+
+pub async fn list(
+    _: ScimClient,
+    db: AppDataDb,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, ScimError> {
+    let filter = query.parse_filter()?;
+    let conn = db.lock().unwrap();
+
+    let users = UserDetail::list(&conn)
+        .map_err(user_error)?
+        .into_iter()
+        .map(|user| to_scim(&conn, user))
+        .collect::<Result<Vec<_>, _>>()?;
+    let users = match filter {
+        None => users,
+        Some((attribute, value)) => {
+            let matches = |user: &ScimUser| match attribute.to_ascii_lowercase().as_str() {
+                "id" => Ok(user.id.as_deref() == Some(value.as_str())),
+                "username" => Ok(user.user_name.eq_ignore_ascii_case(&value)),
+                "externalid" => Ok(user.external_id.as_deref() == Some(value.as_str())),
+                "emails" | "emails.value" => Ok(user
+                    .emails
+                    .iter()
+                    .any(|email| email.value.eq_ignore_ascii_case(&value))),
+                _ => Err(ScimError::InvalidFilter),
+            };
+            let mut filtered = vec![];
+            for user in users {
+                if matches(&user)? {
+                    filtered.push(user);
+                }
+            }
+            filtered
+        }
+    };
+
+    Ok(query.respond(users))
+}
+
+pub async fn get(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ScimError> {
+    let conn = db.lock().unwrap();
+    Ok(respond(StatusCode::OK, &ScimUser::load(&conn, &id)?))
+}
+
+/// a new user is pending until the invitation is accepted, invited at once if active
+pub async fn create(
+    _: ScimClient,
+    db: AppDataDb,
+    mailer: AppDataMailer,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let scim: ScimUser = parse_body(&body)?;
+    let conn = db.lock().unwrap();
+
+    let form = UserForm {
+        id: None,
+        username: scim.user_name.clone(),
+        email: scim.email(),
+        display_name: scim.display_name(),
+        department_id: None,
+        roles: vec![EMPLOYEE_ROLE.to_string()],
+        status: UserStatus::Pending,
+        avatar: DEFAULT_AVATAR.to_string(),
+        password: random_password(),
+        must_change_password: false,
+    };
+    check(form.validate(&conn).map_err(user_error)?)?;
+    let id = form.save(&conn).map_err(user_error)?;
+    set_external_id(&conn, &id, scim.external_id.as_deref())?;
+    let mail = if scim.active {
+        invitation(&conn, &id)
+    } else {
+        None
+    };
+
+    let user = ScimUser::load(&conn, &id)?;
+    drop(conn);
+    send_invitation(&**mailer, &id, mail);
+    let location = user.meta.clone().unwrap_or_default().location;
+    Ok(respond_created(&location, &user))
+}
+
+pub async fn replace(
+    _: ScimClient,
+    db: AppDataDb,
+    mailer: AppDataMailer,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let scim: ScimUser = parse_body(&body)?;
+    let conn = db.lock().unwrap();
+
+    let (user, mail) = update(&conn, &id, scim)?;
+    drop(conn);
+    send_invitation(&**mailer, &id, mail);
+    Ok(respond(StatusCode::OK, &user))
+}
+
+pub async fn patch(
+    _: ScimClient,
+    db: AppDataDb,
+    mailer: AppDataMailer,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ScimError> {
+    let request: PatchRequest = parse_body(&body)?;
+    let conn = db.lock().unwrap();
+
+    let mut scim = ScimUser::load(&conn, &id)?;
+    for operation in request.operations.iter() {
+        scim.apply(operation)?;
+    }
+    let (user, mail) = update(&conn, &id, scim)?;
+    drop(conn);
+    send_invitation(&**mailer, &id, mail);
+    Ok(respond(StatusCode::OK, &user))
+}
+
+/// deleted users go to the recycle bin, so that records referring to them are kept
+pub async fn delete(
+    _: ScimClient,
+    db: AppDataDb,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ScimError> {
+    let conn = db.lock().unwrap();
+    User::delete(&conn, &id, SCIM_ACTOR).map_err(user_error)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// update user `id` by `scim`, roles and department are kept as set by administrators,
+/// returns the user and the invitation mail to send if the user is invited
+fn update(
+    conn: &Connection,
+    id: &str,
+    scim: ScimUser,
+) -> Result<(ScimUser, Option<Mail>), ScimError> {
+    let was_active = ScimUser::load(conn, id)?.active;
+    let mut form = UserForm::from(UserDetail::get(conn, id).map_err(user_error)?);
+    form.username = scim.user_name.clone();
+    form.email = scim.email();
+    form.display_name = scim.display_name();
+    match (form.status, scim.active) {
+        (UserStatus::Active, false) => form.status = UserStatus::Disabled,
+        (UserStatus::Disabled, true) => form.status = UserStatus::Active,
+        _ => {}
+    }
+
+    check(form.validate(conn).map_err(user_error)?)?;
+    form.save(conn).map_err(user_error)?;
+    set_external_id(conn, id, scim.external_id.as_deref())?;
+
+    //  a pending user becomes active or inactive by the invitation
+    let mut mail = None;
+    if form.status == UserStatus::Pending {
+        if scim.active && !was_active {
+            mail = invitation(conn, id);
+        } else if !scim.active {
+            cancel(conn, id).map_err(|_| ScimError::Storage)?;
+        }
+    }
+
+    Ok((ScimUser::load(conn, id)?, mail))
+}
+
+fn set_external_id(conn: &Connection, id: &str, external_id: Option<&str>) -> Result<(), ScimError> {
+    conn.execute(
+        "UPDATE users SET external_id = ?1 WHERE id = ?2",
+        params![external_id, id],
+    )?;
+    Ok(())
+}
+
+/// the invitation mail of user `id`, the user is provisioned even if not invited
+fn invitation(conn: &Connection, id: &str) -> Option<Mail> {
+    invite(conn, id, SCIM_ACTOR)
+        .map_err(|e| log::warn!("invite provisioned user {} fail: {}", id, e))
+        .ok()
+}
+
+/// send `mail` after the database is released,
+/// the user is provisioned even if the mail cannot be sent, which can be resent by administrators
+fn send_invitation(mailer: &dyn Mailer, id: &str, mail: Option<Mail>) {
+    if let Some(Err(e)) = mail.map(|mail| mailer.send(&mail)) {
+        log::warn!("mail invitation of provisioned user {} fail: {}", id, e);
+    }
+}
+
+/// validation errors of the user form as a SCIM error
+fn check(errors: FieldErrors) -> Result<(), ScimError> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let detail = errors
+        .iter()
+        .map(|(field, e)| format!("{} {}", field, e))
+        .collect::<Vec<_>>()
+        .join(", ");
+    //  username and email are the only unique attributes
+    if errors.values().any(|e| e.starts_with("is already")) {
+        Err(ScimError::Uniqueness(detail))
+    } else {
+        Err(ScimError::InvalidValue(detail))
+    }
+}
+
+fn user_error(e: UserError) -> ScimError {
+    match e {
+        UserError::NotExist => ScimError::NotFound,
+        _ => ScimError::Storage,
+    }
+}
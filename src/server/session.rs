@@ -0,0 +1,544 @@
+//! Session
+//! every login creates a session, whose id is carried by [AuthenticationToken]
+//!
+//! - a session can be bound to the client fingerprint (ip subnet and user agent family),
+//! how strictly is configured by [SessionBinding]
+//! - anomalies (new country, new device, impossible travel) are recorded as [SessionEvent],
+//! an anomaly during an existing session revokes it, so that user must log in again,
+//! and a login by password from a new country or by impossible travel is refused,
+//! that user must log in by passkey instead
+//! - sessions not seen for [SESSION_LIFETIME] can never be used again, revoked or not,
+//! and are purged by [purge_sessions_periodically]
+
+mod geoip;
+
+pub use geoip::*;
+
+use super::{AppDataDb, AuthenticationToken, SessionBinding, SETTINGS};
+use crate::models::{SessionEvent, SessionEventKind, User};
+use actix_web::{http::header::USER_AGENT, HttpRequest};
+use base64::prelude::*;
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// how long a session lives without being seen, as long as the login cookie
+pub const SESSION_LIFETIME: i64 = 7 * 24 * 60 * 60;
+/// how often sessions no longer usable are purged
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub enum SessionError {
+    NotFound,
+    Revoked,
+    Anomaly(SessionEventKind),
+    /// the login is anomalous, and must be done by passkey
+    StepUp(SessionEventKind),
+    Idle,
+    Storage,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::NotFound => write!(f, "session not exist"),
+            SessionError::Revoked => write!(f, "session revoked"),
+            SessionError::Anomaly(kind) => write!(f, "session anomaly: {}", kind),
+            SessionError::StepUp(kind) => {
+                write!(f, "unusual login ({}), please log in with a passkey", kind)
+            }
+            SessionError::Idle => write!(f, "session idle timeout"),
+            SessionError::Storage => write!(f, "session storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SessionError {
+    fn from(_: rusqlite::Error) -> Self {
+        SessionError::Storage
+    }
+}
+
+/// the client who sends current request
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub user_agent: String,
+}
+
+impl ClientInfo {
+    /// the ip is the peer of the connection, or the nearest untrusted hop of `X-Forwarded-For`
+    /// when the peer is one of [SETTINGS] `trusted_proxies`
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        let ip = match peer {
+            Some(peer) if SETTINGS.trusted_proxies.contains(&peer) => {
+                Some(forwarded_for(req).unwrap_or(peer))
+            }
+            _ => peer,
+        };
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        Self { ip, user_agent }
+    }
+
+    pub fn ip_text(&self) -> String {
+        self.ip.map(|ip| ip.to_string()).unwrap_or_default()
+    }
+
+    /// `/24` for ipv4 and `/48` for ipv6
+    pub fn subnet(&self) -> String {
+        match self.ip {
+            Some(IpAddr::V4(ip)) => {
+                let o = ip.octets();
+                format!("{}.{}.{}.0/24", o[0], o[1], o[2])
+            }
+            Some(IpAddr::V6(ip)) => {
+                let s = ip.segments();
+                format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+            }
+            None => String::new(),
+        }
+    }
+
+    /// browser and operating system family of user agent, e.g. `Firefox on Windows`
+    pub fn device(&self) -> String {
+        let ua = self.user_agent.as_str();
+        let browser = if ua.contains("Edg/") {
+            "Edge"
+        } else if ua.contains("OPR/") {
+            "Opera"
+        } else if ua.contains("Firefox/") {
+            "Firefox"
+        } else if ua.contains("Chrome/") || ua.contains("CriOS/") {
+            "Chrome"
+        } else if ua.contains("Safari/") {
+            "Safari"
+        } else {
+            "Other"
+        };
+        let os = if ua.contains("Android") {
+            "Android"
+        } else if ua.contains("iPhone") || ua.contains("iPad") {
+            "iOS"
+        } else if ua.contains("Windows") {
+            "Windows"
+        } else if ua.contains("Mac OS X") {
+            "macOS"
+        } else if ua.contains("Linux") {
+            "Linux"
+        } else {
+            "Other"
+        };
+
+        format!("{} on {}", browser, os)
+    }
+}
+
+/// the client ip added by trusted proxies, the last hop of `X-Forwarded-For` not a proxy itself,
+/// as hops before it may be sent by the client
+fn forwarded_for(req: &HttpRequest) -> Option<IpAddr> {
+    let hops: Vec<IpAddr> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().parse::<IpAddr>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    hops.into_iter()
+        .rev()
+        .find(|ip| !SETTINGS.trusted_proxies.contains(ip))
+}
+
+struct SessionRow {
+    user_id: String,
+    ip: String,
+    subnet: String,
+    device: String,
+    location: Location,
+    last_seen_at: i64,
+    last_active_at: i64,
+    revoked: bool,
+}
+
+/// create the session of `user` who logged in just now, by `passkey` or by password,
+/// returns the session id
+///
+/// anomalies compared with previous sessions of the user are recorded,
+/// a new country or impossible travel refuses a login by password with [SessionError::StepUp],
+/// as the password may be stolen, while a login by passkey proves that user
+pub fn create_session(
+    conn: &Connection,
+    geoip: &GeoIp,
+    user: &User,
+    client: &ClientInfo,
+    passkey: bool,
+) -> Result<String, SessionError> {
+    let id = new_session_id();
+    let now = chrono::Utc::now().timestamp();
+    let location = geoip.locate(client.ip);
+
+    let anomaly = detect_login_anomalies(conn, &id, user, client, location.as_ref(), now)?;
+    if let Some(kind) = anomaly.filter(|_| !passkey) {
+        return Err(SessionError::StepUp(kind));
+    }
+
+    let location = location.unwrap_or(Location {
+        country: None,
+        latitude: None,
+        longitude: None,
+    });
+    conn.execute(
+        "INSERT INTO sessions
+         (id, user_id, username, ip, subnet, device, country, latitude, longitude, created_at, last_seen_at, last_active_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10, ?10)",
+        params![
+            id,
+            user.id,
+            user.username,
+            client.ip_text(),
+            client.subnet(),
+            client.device(),
+            location.country,
+            location.latitude,
+            location.longitude,
+            now,
+        ],
+    )?;
+
+    Ok(id)
+}
+
+/// check the session of `token` is still valid for `client`, and refresh its last seen,
+/// also its last active if the request is an `activity` of user
+///
+/// a session idle too long, used by another fingerprint or from an anomalous location is revoked
+pub fn check_session(
+    conn: &Connection,
+    geoip: &GeoIp,
+    token: &AuthenticationToken,
+    client: &ClientInfo,
+    activity: bool,
+) -> Result<(), SessionError> {
+    let session = load_session(conn, &token.session_id)?.ok_or(SessionError::NotFound)?;
+    if session.revoked || session.user_id != token.id {
+        return Err(SessionError::Revoked);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    if SETTINGS.idle_timeout > 0 && now - session.last_active_at > SETTINGS.idle_timeout {
+        revoke_session(conn, &token.session_id)?;
+        return Err(SessionError::Idle);
+    }
+    let device_changed = session.device != client.device();
+    let subnet_changed = session.subnet != client.subnet();
+    let mismatch = match SETTINGS.session_binding {
+        SessionBinding::Off => false,
+        SessionBinding::Lenient => device_changed,
+        SessionBinding::Strict => device_changed || subnet_changed,
+    };
+    if mismatch {
+        let detail = format!(
+            "session of {} ({}) used by {} ({})",
+            session.device,
+            session.subnet,
+            client.device(),
+            client.subnet()
+        );
+        return reject(conn, token, client, SessionEventKind::FingerprintMismatch, detail);
+    }
+
+    let mut location = session.location;
+    if session.ip != client.ip_text() {
+        if let Some(current) = geoip.locate(client.ip) {
+            if let (Some(from), Some(to)) = (&location.country, &current.country) {
+                if from != to {
+                    let detail = format!("moved from {} to {}", from, to);
+                    return reject(conn, token, client, SessionEventKind::NewCountry, detail);
+                }
+            }
+            if let Some(detail) = impossible_travel(&location, session.last_seen_at, &current, now) {
+                return reject(conn, token, client, SessionEventKind::ImpossibleTravel, detail);
+            }
+            location = current;
+        }
+    }
+
+    conn.execute(
+        "UPDATE sessions SET ip = ?1, country = ?2, latitude = ?3, longitude = ?4, last_seen_at = ?5,
+            last_active_at = CASE WHEN ?6 THEN ?5 ELSE last_active_at END
+         WHERE id = ?7",
+        params![
+            client.ip_text(),
+            location.country,
+            location.latitude,
+            location.longitude,
+            now,
+            activity,
+            token.session_id,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// seconds before the session is ended by idle timeout, None if sessions never time out
+pub fn idle_remaining(conn: &Connection, session_id: &str) -> Result<Option<i64>, SessionError> {
+    if SETTINGS.idle_timeout <= 0 {
+        return Ok(None);
+    }
+
+    let session = load_session(conn, session_id)?.ok_or(SessionError::NotFound)?;
+    let idle = chrono::Utc::now().timestamp() - session.last_active_at;
+
+    Ok(Some((SETTINGS.idle_timeout - idle).max(0)))
+}
+
+pub fn revoke_session(conn: &Connection, session_id: &str) -> Result<(), SessionError> {
+    conn.execute(
+        "UPDATE sessions SET revoked = 1 WHERE id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+/// remove the sessions not seen for [SESSION_LIFETIME], returns how many removed,
+/// revoked sessions are kept as long, so that logins are still compared with them
+pub fn purge_sessions(conn: &Connection) -> Result<usize, SessionError> {
+    let purged = conn.execute(
+        "DELETE FROM sessions WHERE last_seen_at < ?1",
+        params![chrono::Utc::now().timestamp() - SESSION_LIFETIME],
+    )?;
+    Ok(purged)
+}
+
+/// purge the sessions no longer usable every [PURGE_INTERVAL]
+pub async fn purge_sessions_periodically(db: AppDataDb) {
+    let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match purge_sessions(&db.lock().unwrap()) {
+            Ok(0) => {}
+            Ok(count) => log::info!("{} expired sessions purged", count),
+            Err(e) => log::warn!("purge sessions fail: {}", e),
+        }
+    }
+}
+
+/// end all of the sessions of a user
+pub fn revoke_user_sessions(conn: &Connection, user_id: &str) -> Result<(), SessionError> {
+    conn.execute(
+        "UPDATE sessions SET revoked = 1 WHERE user_id = ?1",
+        params![user_id],
+    )?;
+    Ok(())
+}
+
+impl SessionEvent {
+    /// the latest events of all users
+    pub fn latest(conn: &Connection, limit: usize) -> Result<Vec<Self>, SessionError> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, kind, detail, ip, created_at FROM session_events
+             ORDER BY id DESC LIMIT ?1",
+        )?;
+        let list = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(list
+            .into_iter()
+            .filter_map(|(id, username, kind, detail, ip, created_at)| {
+                Some(SessionEvent {
+                    id,
+                    username,
+                    kind: kind.parse().ok()?,
+                    detail,
+                    ip,
+                    created_at,
+                })
+            })
+            .collect())
+    }
+}
+
+/// record the anomalies of the login, returns the first refusing a login by password
+fn detect_login_anomalies(
+    conn: &Connection,
+    session_id: &str,
+    user: &User,
+    client: &ClientInfo,
+    location: Option<&Location>,
+    now: i64,
+) -> Result<Option<SessionEventKind>, SessionError> {
+    let known = |column: &str, value: &str| -> rusqlite::Result<bool> {
+        conn.query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM sessions WHERE user_id = ?1 AND {} = ?2)",
+                column
+            ),
+            params![user.id, value],
+            |row| row.get(0),
+        )
+    };
+
+    //  nothing to compare with on the first login
+    let latest = conn
+        .query_row(
+            "SELECT country, latitude, longitude, last_seen_at FROM sessions
+             WHERE user_id = ?1 ORDER BY last_seen_at DESC LIMIT 1",
+            params![user.id],
+            |row| {
+                Ok((
+                    Location {
+                        country: row.get(0)?,
+                        latitude: row.get(1)?,
+                        longitude: row.get(2)?,
+                    },
+                    row.get::<_, i64>(3)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((previous, previous_time)) = latest else {
+        return Ok(None);
+    };
+
+    let record = |kind: SessionEventKind, detail: String| {
+        record_event(conn, session_id, &user.id, &user.username, client, kind, &detail)
+    };
+
+    if !known("device", &client.device())? {
+        record(
+            SessionEventKind::NewDevice,
+            format!("first login from {}", client.device()),
+        )?;
+    }
+
+    let mut anomaly = None;
+    if let Some(location) = location {
+        if let Some(country) = &location.country {
+            if !known("country", country)? {
+                record(
+                    SessionEventKind::NewCountry,
+                    format!("first login from {}", country),
+                )?;
+                anomaly = Some(SessionEventKind::NewCountry);
+            }
+        }
+        if let Some(detail) = impossible_travel(&previous, previous_time, location, now) {
+            record(SessionEventKind::ImpossibleTravel, detail)?;
+            anomaly = anomaly.or(Some(SessionEventKind::ImpossibleTravel));
+        }
+    }
+
+    Ok(anomaly)
+}
+
+/// the travel is impossible if it is faster than the configured speed,
+/// short distances are ignored because of the inaccuracy of GeoIP
+fn impossible_travel(from: &Location, from_time: i64, to: &Location, to_time: i64) -> Option<String> {
+    let distance = from.distance_km(to)?;
+    let hours = (to_time - from_time).max(1) as f64 / 3600.0;
+
+    if distance > 100.0 && distance / hours > SETTINGS.max_travel_speed {
+        Some(format!("{:.0} km in {:.1} hours", distance, hours))
+    } else {
+        None
+    }
+}
+
+fn reject(
+    conn: &Connection,
+    token: &AuthenticationToken,
+    client: &ClientInfo,
+    kind: SessionEventKind,
+    detail: String,
+) -> Result<(), SessionError> {
+    record_event(
+        conn,
+        &token.session_id,
+        &token.id,
+        &token.username,
+        client,
+        kind,
+        &detail,
+    )?;
+    revoke_session(conn, &token.session_id)?;
+
+    Err(SessionError::Anomaly(kind))
+}
+
+fn record_event(
+    conn: &Connection,
+    session_id: &str,
+    user_id: &str,
+    username: &str,
+    client: &ClientInfo,
+    kind: SessionEventKind,
+    detail: &str,
+) -> Result<(), SessionError> {
+    conn.execute(
+        "INSERT INTO session_events (session_id, user_id, username, kind, detail, ip, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            session_id,
+            user_id,
+            username,
+            kind.to_string(),
+            detail,
+            client.ip_text(),
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn load_session(conn: &Connection, id: &str) -> Result<Option<SessionRow>, SessionError> {
+    let row = conn
+        .query_row(
+            "SELECT user_id, ip, subnet, device, country, latitude, longitude, last_seen_at,
+                last_active_at, revoked
+             FROM sessions WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(SessionRow {
+                    user_id: row.get(0)?,
+                    ip: row.get(1)?,
+                    subnet: row.get(2)?,
+                    device: row.get(3)?,
+                    location: Location {
+                        country: row.get(4)?,
+                        latitude: row.get(5)?,
+                        longitude: row.get(6)?,
+                    },
+                    last_seen_at: row.get(7)?,
+                    last_active_at: row.get(8)?,
+                    revoked: row.get(9)?,
+                })
+            },
+        )
+        .optional()?;
+
+    Ok(row)
+}
+
+fn new_session_id() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
@@ -0,0 +1,59 @@
+use super::super::SETTINGS;
+use actix_web::web::Data;
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+
+/// app data GeoIP database
+/// used in actix app_data
+pub type AppDataGeoIp = Data<GeoIp>;
+
+pub fn new_app_data_geoip() -> AppDataGeoIp {
+    let reader = SETTINGS
+        .geoip_path
+        .as_ref()
+        .map(|path| Reader::open_readfile(path).expect("open GeoIP database fail"));
+
+    Data::new(GeoIp(reader))
+}
+
+/// local GeoIP2/GeoLite2 City database,
+/// locates nothing when no database configured
+pub struct GeoIp(Option<Reader<Vec<u8>>>);
+
+pub struct Location {
+    /// ISO 3166 country code
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl GeoIp {
+    pub fn locate(&self, ip: Option<IpAddr>) -> Option<Location> {
+        let reader = self.0.as_ref()?;
+        let city: geoip2::City = reader.lookup(ip?).ok()?;
+
+        Some(Location {
+            country: city
+                .country
+                .and_then(|country| country.iso_code)
+                .map(|code| code.to_string()),
+            latitude: city.location.as_ref().and_then(|l| l.latitude),
+            longitude: city.location.as_ref().and_then(|l| l.longitude),
+        })
+    }
+}
+
+impl Location {
+    /// great-circle distance in kilometers, None if either has no coordinates
+    pub fn distance_km(&self, other: &Location) -> Option<f64> {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+
+        let (lat1, lon1) = (self.latitude?.to_radians(), self.longitude?.to_radians());
+        let (lat2, lon2) = (other.latitude?.to_radians(), other.longitude?.to_radians());
+
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+
+        Some(2.0 * EARTH_RADIUS_KM * a.sqrt().asin())
+    }
+}
@@ -0,0 +1,161 @@
+//! Settings
+//! server side configuration, read from environment variables once on first use,
+//! every setting has a default so the server can start without any configuration
+
+use chrono::Weekday;
+use once_cell::sync::Lazy;
+use std::env;
+use std::net::IpAddr;
+
+pub static SETTINGS: Lazy<Settings> = Lazy::new(Settings::from_env);
+
+pub struct Settings {
+    /// path of the sqlite database file, `DVORAK_DATABASE`
+    pub database_path: String,
+    /// WebAuthn relying party id, normally the domain of the site, `DVORAK_RP_ID`
+    pub rp_id: String,
+    /// WebAuthn relying party origin, `DVORAK_RP_ORIGIN`
+    pub rp_origin: String,
+    /// how strictly a session is bound to the client it created from, `DVORAK_SESSION_BINDING`
+    pub session_binding: SessionBinding,
+    /// ips of reverse proxies whose `X-Forwarded-For` is trusted, `DVORAK_TRUSTED_PROXIES`,
+    /// the header of anyone else is ignored, so that clients cannot choose their ip
+    pub trusted_proxies: Vec<IpAddr>,
+    /// path of the GeoIP2/GeoLite2 City database file, `DVORAK_GEOIP`,
+    /// location based anomaly detection is disabled when not set
+    pub geoip_path: Option<String>,
+    /// faster travel between two logins than this (km/h) is impossible, `DVORAK_MAX_TRAVEL_SPEED`
+    pub max_travel_speed: f64,
+    /// login needs a CAPTCHA after this many failures of an ip or username, `DVORAK_CAPTCHA_AFTER`
+    pub captcha_after_failures: i64,
+    /// failures older than this (seconds) are forgotten, `DVORAK_LOGIN_FAILURE_WINDOW`
+    pub login_failure_window: i64,
+    /// passwords older than this (days) must be changed, 0 means never expire, `DVORAK_PASSWORD_MAX_AGE_DAYS`
+    pub password_max_age_days: i64,
+    /// initial password of the administrator created on an empty database, `DVORAK_ADMIN_PASSWORD`
+    pub admin_password: String,
+    /// sessions inactive longer than this (seconds) are ended, 0 means never, `DVORAK_IDLE_TIMEOUT`
+    pub idle_timeout: i64,
+    /// the user is warned this many seconds before the idle timeout, `DVORAK_IDLE_WARNING`
+    pub idle_warning: i64,
+    /// directory of uploaded avatars, served at `/avatars`, `DVORAK_AVATAR_DIR`
+    pub avatar_dir: String,
+    /// url of the site in links sent to users, `DVORAK_PUBLIC_URL`, the WebAuthn origin if not set
+    pub public_url: String,
+    /// directory the file mailer writes mails into, `DVORAK_MAIL_DIR`
+    pub mail_dir: String,
+    /// sender of mails, `DVORAK_MAIL_FROM`
+    pub mail_from: String,
+    /// invitations expire after this many hours, `DVORAK_INVITATION_TTL_HOURS`
+    pub invitation_ttl_hours: i64,
+    /// deleted records are purged after this many days, 0 means kept forever,
+    /// `DVORAK_RECYCLE_RETENTION_DAYS`
+    pub recycle_retention_days: i64,
+    /// bearer token of the identity provider calling SCIM endpoints, `DVORAK_SCIM_TOKEN`,
+    /// SCIM is disabled when not set
+    pub scim_token: Option<String>,
+    /// where leave requests are kept, `DVORAK_LEAVE_STORAGE`
+    pub leave_storage: LeaveStorage,
+    /// days of the week leave requests do not count, `DVORAK_WEEKEND`, such as `Sat,Sun`
+    pub weekend: Vec<Weekday>,
+    /// teams with fewer members present than this percent on a day of a leave request are warned
+    /// about, `DVORAK_MIN_STAFFING`, 0 means never
+    pub min_staffing: u32,
+    /// directory of files attached to leave requests, `DVORAK_ATTACHMENT_DIR`
+    pub attachment_dir: String,
+    /// the largest attachment accepted, in bytes, `DVORAK_ATTACHMENT_MAX_BYTES`
+    pub attachment_max_bytes: usize,
+    /// content types of attachments accepted, `DVORAK_ATTACHMENT_TYPES`,
+    /// such as `application/pdf,image/png`
+    pub attachment_types: Vec<String>,
+}
+
+/// binding of a session to the client fingerprint
+#[derive(Clone, Copy, PartialEq)]
+pub enum SessionBinding {
+    /// session can be used by any client
+    Off,
+    /// user agent family must not change
+    Lenient,
+    /// both of user agent family and ip subnet must not change
+    Strict,
+}
+
+/// storage of leave requests
+#[derive(Clone, Copy, PartialEq)]
+pub enum LeaveStorage {
+    /// the shared sqlite database
+    Sqlite,
+    /// memory only, lost when the server stops
+    Memory,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self {
+            database_path: env_or("DVORAK_DATABASE", "dvorak_admin.db"),
+            rp_id: env_or("DVORAK_RP_ID", "localhost"),
+            rp_origin: env_or("DVORAK_RP_ORIGIN", "http://localhost:3000"),
+            session_binding: match env_or("DVORAK_SESSION_BINDING", "off").as_str() {
+                "strict" => SessionBinding::Strict,
+                "lenient" => SessionBinding::Lenient,
+                _ => SessionBinding::Off,
+            },
+            trusted_proxies: env_or("DVORAK_TRUSTED_PROXIES", "")
+                .split(',')
+                .filter_map(|ip| ip.trim().parse().ok())
+                .collect(),
+            geoip_path: env::var("DVORAK_GEOIP").ok(),
+            max_travel_speed: env_or("DVORAK_MAX_TRAVEL_SPEED", "1000")
+                .parse()
+                .unwrap_or(1000.0),
+            captcha_after_failures: env_or("DVORAK_CAPTCHA_AFTER", "3").parse().unwrap_or(3),
+            login_failure_window: env_or("DVORAK_LOGIN_FAILURE_WINDOW", "900")
+                .parse()
+                .unwrap_or(900),
+            password_max_age_days: env_or("DVORAK_PASSWORD_MAX_AGE_DAYS", "0")
+                .parse()
+                .unwrap_or(0),
+            admin_password: env_or("DVORAK_ADMIN_PASSWORD", "dvorak"),
+            idle_timeout: env_or("DVORAK_IDLE_TIMEOUT", "1800").parse().unwrap_or(1800),
+            idle_warning: env_or("DVORAK_IDLE_WARNING", "60").parse().unwrap_or(60),
+            avatar_dir: env_or("DVORAK_AVATAR_DIR", "avatars"),
+            public_url: env::var("DVORAK_PUBLIC_URL")
+                .unwrap_or_else(|_| env_or("DVORAK_RP_ORIGIN", "http://localhost:3000")),
+            mail_dir: env_or("DVORAK_MAIL_DIR", "mails"),
+            mail_from: env_or("DVORAK_MAIL_FROM", "Dvorak Admin <noreply@localhost>"),
+            invitation_ttl_hours: env_or("DVORAK_INVITATION_TTL_HOURS", "72")
+                .parse()
+                .unwrap_or(72),
+            recycle_retention_days: env_or("DVORAK_RECYCLE_RETENTION_DAYS", "30")
+                .parse()
+                .unwrap_or(30),
+            scim_token: env::var("DVORAK_SCIM_TOKEN").ok().filter(|token| !token.is_empty()),
+            leave_storage: match env_or("DVORAK_LEAVE_STORAGE", "sqlite").as_str() {
+                "memory" => LeaveStorage::Memory,
+                _ => LeaveStorage::Sqlite,
+            },
+            weekend: env_or("DVORAK_WEEKEND", "Sat,Sun")
+                .split(',')
+                .filter_map(|day| day.trim().parse().ok())
+                .collect(),
+            min_staffing: env_or("DVORAK_MIN_STAFFING", "50").parse().unwrap_or(50),
+            attachment_dir: env_or("DVORAK_ATTACHMENT_DIR", "attachments"),
+            attachment_max_bytes: env_or("DVORAK_ATTACHMENT_MAX_BYTES", "5242880")
+                .parse()
+                .unwrap_or(5 * 1024 * 1024),
+            attachment_types: env_or(
+                "DVORAK_ATTACHMENT_TYPES",
+                "application/pdf,image/png,image/jpeg",
+            )
+            .split(',')
+            .map(|content_type| content_type.trim().to_lowercase())
+            .filter(|content_type| !content_type.is_empty())
+            .collect(),
+        }
+    }
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
@@ -1,16 +1,615 @@
-use crate::models::{User, UserError};
+use super::session::revoke_user_sessions;
+use super::{AuthenticationToken, SETTINGS};
+use crate::models::consts::{ADMIN_ROLE, LANGUAGES, TIMEZONES};
+use crate::models::{
+    CurrentUser, Department, FieldErrors, ProfileForm, Role, User, UserDetail, UserError, UserForm,
+    UserProfile, UserStatus,
+};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{TimeZone, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use rusqlite::{params, Connection, OptionalExtension};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+impl From<rusqlite::Error> for UserError {
+    fn from(_: rusqlite::Error) -> Self {
+        UserError::Storage
+    }
+}
 
 impl User {
-    pub fn login(username: String, password: String) -> Result<Self, UserError> {
-        //  your logic of valid username and password
-        //  code below just some demonstrate 
+    pub fn login(conn: &Connection, username: String, password: String) -> Result<Self, UserError> {
         if username.is_empty() || password.is_empty() {
-            Err(UserError::NotExist)
-        } else {
-            Ok(User {
-                id: "123456".to_string(),
-                username: "Dvorak".to_string(),
-            })
+            return Err(UserError::NotExist);
+        }
+
+        let row = conn
+            .query_row(
+                "SELECT id, username, password_hash FROM users
+                 WHERE username = ?1 AND status = ?2 AND deleted_at IS NULL",
+                params![username, UserStatus::Active.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        //  wrong password and disabled user are also reported as not exist, not to reveal usernames
+        match row {
+            Some((id, username, hash)) if verify_password(&password, &hash) => {
+                Ok(User { id, username })
+            }
+            _ => Err(UserError::NotExist),
+        }
+    }
+
+    /// create the administrator on an empty database,
+    /// who must change the initial password at first login
+    pub fn seed(conn: &Connection) -> Result<(), UserError> {
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        if count > 0 {
+            return Ok(());
+        }
+
+        conn.execute(
+            "INSERT INTO users
+             (id, username, display_name, password_hash, password_set_at, must_change_password)
+             VALUES (?1, ?2, ?2, ?3, ?4, 1)",
+            params![
+                "123456",
+                "Dvorak",
+                hash_password(&SETTINGS.admin_password)?,
+                Utc::now().timestamp(),
+            ],
+        )?;
+        conn.execute(
+            "INSERT INTO user_roles (user_id, role) VALUES (?1, ?2)",
+            params!["123456", ADMIN_ROLE],
+        )?;
+
+        Ok(())
+    }
+
+    /// the password is flagged by administrator or older than the configured maximum age
+    pub fn password_expired(conn: &Connection, id: &str) -> Result<bool, UserError> {
+        let (password_set_at, must_change) = conn
+            .query_row(
+                "SELECT password_set_at, must_change_password FROM users WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .optional()?
+            .ok_or(UserError::NotExist)?;
+
+        let expired = password_expires_at(password_set_at)
+            .map(|expires_at| expires_at <= Utc::now().timestamp())
+            .unwrap_or(false);
+
+        Ok(must_change || expired)
+    }
+
+    pub fn change_password(
+        conn: &Connection,
+        id: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), UserError> {
+        let hash: String = conn
+            .query_row(
+                "SELECT password_hash FROM users WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(UserError::NotExist)?;
+
+        if !verify_password(current_password, &hash) {
+            return Err(UserError::WrongPassword);
+        }
+        if new_password.chars().count() < 8 || new_password == current_password {
+            return Err(UserError::WeakPassword);
+        }
+
+        conn.execute(
+            "UPDATE users SET password_hash = ?1, password_set_at = ?2, must_change_password = 0
+             WHERE id = ?3",
+            params![hash_password(new_password)?, Utc::now().timestamp(), id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn has_role(conn: &Connection, id: &str, role: &str) -> Result<bool, UserError> {
+        let has_role = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM user_roles WHERE user_id = ?1 AND role = ?2)",
+            params![id, role],
+            |row| row.get(0),
+        )?;
+        Ok(has_role)
+    }
+
+    /// roles of user `id`, in the order of names
+    pub fn roles(conn: &Connection, id: &str) -> Result<Vec<String>, UserError> {
+        roles_of(conn, id)
+    }
+
+    /// disabled user cannot log in, and all of the sessions are ended
+    pub fn set_status(conn: &Connection, id: &str, status: UserStatus) -> Result<(), UserError> {
+        let updated = conn.execute(
+            "UPDATE users SET status = ?1 WHERE id = ?2",
+            params![status.to_string(), id],
+        )?;
+        if updated == 0 {
+            return Err(UserError::NotExist);
+        }
+        if status != UserStatus::Active {
+            revoke_user_sessions(conn, id).map_err(|_| UserError::Storage)?;
+        }
+
+        Ok(())
+    }
+
+    /// move the user into the recycle bin, the user cannot log in any more
+    /// and all of the sessions are ended, but records referring to the user are kept
+    pub fn delete(conn: &Connection, id: &str, deleted_by: &str) -> Result<(), UserError> {
+        let updated = conn.execute(
+            "UPDATE users SET deleted_at = ?1, deleted_by = ?2 WHERE id = ?3 AND deleted_at IS NULL",
+            params![Utc::now().timestamp(), deleted_by, id],
+        )?;
+        if updated == 0 {
+            return Err(UserError::NotExist);
+        }
+        revoke_user_sessions(conn, id).map_err(|_| UserError::Storage)?;
+        conn.execute(
+            "DELETE FROM invitations WHERE user_id = ?1 AND accepted_at IS NULL",
+            params![id],
+        )?;
+
+        Ok(())
+    }
+
+    /// bring the user back from the recycle bin,
+    /// out of the department if the department was deleted meanwhile
+    pub fn restore(conn: &Connection, id: &str) -> Result<(), UserError> {
+        let updated = conn.execute(
+            "UPDATE users SET deleted_at = NULL, deleted_by = NULL,
+                department_id = (SELECT departments.id FROM departments
+                    WHERE departments.id = users.department_id AND departments.deleted_at IS NULL)
+             WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
+        if updated == 0 {
+            return Err(UserError::NotExist);
+        }
+
+        Ok(())
+    }
+
+    /// remove the user in the recycle bin and the roles, passkeys, sessions and avatar for ever,
+    /// returns the avatar url to remove the files after committed
+    pub fn purge(conn: &Connection, id: &str) -> Result<String, UserError> {
+        let avatar: String = conn
+            .query_row(
+                "SELECT avatar FROM users WHERE id = ?1 AND deleted_at IS NOT NULL",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(UserError::NotExist)?;
+
+        for sql in [
+            "DELETE FROM user_roles WHERE user_id = ?1",
+            "DELETE FROM passkeys WHERE user_id = ?1",
+            "DELETE FROM sessions WHERE user_id = ?1",
+            "DELETE FROM invitations WHERE user_id = ?1",
+            "DELETE FROM leave_approvals
+             WHERE leave_id IN (SELECT id FROM leave_requests WHERE user_id = ?1)",
+            "DELETE FROM leave_requests WHERE user_id = ?1",
+            "DELETE FROM leave_ledger WHERE user_id = ?1",
+            "DELETE FROM leave_feeds WHERE user_id = ?1",
+            "UPDATE departments SET manager_id = NULL WHERE manager_id = ?1",
+            "DELETE FROM users WHERE id = ?1",
+        ] {
+            conn.execute(sql, params![id])?;
+        }
+
+        Ok(avatar)
+    }
+}
+
+const USER_DETAIL_COLUMNS: &str = "users.id, users.username, users.email, users.display_name,
+    COALESCE(departments.name, ''), users.status, users.avatar, users.must_change_password,
+    users.password_set_at, users.department_id
+    FROM users LEFT JOIN departments ON departments.id = users.department_id";
+
+impl UserDetail {
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, UserError> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE users.deleted_at IS NULL ORDER BY users.username",
+            USER_DETAIL_COLUMNS
+        ))?;
+        let mut list = stmt
+            .query_map([], user_detail_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for user in list.iter_mut() {
+            user.roles = roles_of(conn, &user.id)?;
+        }
+
+        Ok(list)
+    }
+
+    /// at most `limit` users ordered by username, whose username is after `after`,
+    /// for reading all of users in chunks
+    pub fn list_after(conn: &Connection, after: &str, limit: usize) -> Result<Vec<Self>, UserError> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE users.username > ?1 AND users.deleted_at IS NULL
+             ORDER BY users.username LIMIT ?2",
+            USER_DETAIL_COLUMNS
+        ))?;
+        let mut list = stmt
+            .query_map(params![after, limit], user_detail_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for user in list.iter_mut() {
+            user.roles = roles_of(conn, &user.id)?;
         }
+
+        Ok(list)
+    }
+
+    pub fn get(conn: &Connection, id: &str) -> Result<Self, UserError> {
+        let mut user = conn
+            .query_row(
+                &format!(
+                    "SELECT {} WHERE users.id = ?1 AND users.deleted_at IS NULL",
+                    USER_DETAIL_COLUMNS
+                ),
+                params![id],
+                user_detail_from_row,
+            )
+            .optional()?
+            .ok_or(UserError::NotExist)?;
+        user.roles = roles_of(conn, &user.id)?;
+
+        Ok(user)
     }
 }
+
+fn user_detail_from_row(row: &rusqlite::Row) -> rusqlite::Result<UserDetail> {
+    let status: String = row.get(5)?;
+    let password_set_at: i64 = row.get(8)?;
+
+    Ok(UserDetail {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        display_name: row.get(3)?,
+        department_id: row.get(9)?,
+        department: row.get(4)?,
+        roles: vec![],
+        status: status.parse().unwrap_or(UserStatus::Disabled),
+        avatar: row.get(6)?,
+        must_change_password: row.get(7)?,
+        password_expires_at: password_expires_at(password_set_at).map(format_timestamp),
+    })
+}
+
+fn roles_of(conn: &Connection, id: &str) -> Result<Vec<String>, UserError> {
+    let mut stmt = conn.prepare("SELECT role FROM user_roles WHERE user_id = ?1 ORDER BY role")?;
+    let roles = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(roles)
+}
+
+impl UserForm {
+    /// errors of every invalid field, empty if the form is valid
+    pub fn validate(&self, conn: &Connection) -> Result<FieldErrors, UserError> {
+        let mut errors = FieldErrors::new();
+        let id = self.id.clone().unwrap_or_default();
+
+        let username = self.username.trim();
+        if username.len() < 3 || username.len() > 32 {
+            errors.insert("username".into(), "must have 3 to 32 characters".into());
+        } else if !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+        {
+            errors.insert(
+                "username".into(),
+                "can only contain letters, digits, '.', '_' and '-'".into(),
+            );
+        } else if taken(conn, "username", username, &id)? {
+            errors.insert("username".into(), "is already taken".into());
+        }
+
+        validate_email(conn, &self.email, &id, &mut errors)?;
+        validate_display_name(&self.display_name, &mut errors);
+
+        if let Some(department_id) = &self.department_id {
+            if !Department::exists(conn, department_id).map_err(|_| UserError::Storage)? {
+                errors.insert("department_id".into(), "not exist".into());
+            }
+        }
+
+        let known_roles: Vec<String> = Role::list(conn)?.into_iter().map(|r| r.name).collect();
+        if let Some(role) = self.roles.iter().find(|role| !known_roles.contains(role)) {
+            errors.insert("roles".into(), format!("role {} not exist", role));
+        }
+
+        //  password is kept unchanged if left empty on update
+        let password_given = self.id.is_none() || !self.password.is_empty();
+        if password_given && self.password.chars().count() < 8 {
+            errors.insert("password".into(), "must have at least 8 characters".into());
+        }
+
+        Ok(errors)
+    }
+
+    /// create or update the user, the form must be validated first, returns the user id
+    ///
+    /// all of the changes are applied in a transaction, or in the one of the caller,
+    /// such as an import saving many users at once
+    pub fn save(&self, conn: &Connection) -> Result<String, UserError> {
+        if !conn.is_autocommit() {
+            return self.write(conn);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        let id = self.write(&tx)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn write(&self, conn: &Connection) -> Result<String, UserError> {
+        let now = Utc::now().timestamp();
+        let id = match &self.id {
+            Some(id) => {
+                let updated = conn.execute(
+                    "UPDATE users SET username = ?1, email = ?2, display_name = ?3, department_id = ?4,
+                        avatar = ?5, must_change_password = ?6
+                     WHERE id = ?7",
+                    params![
+                        self.username.trim(),
+                        self.email.trim(),
+                        self.display_name.trim(),
+                        self.department_id,
+                        self.avatar.trim(),
+                        self.must_change_password,
+                        id,
+                    ],
+                )?;
+                if updated == 0 {
+                    return Err(UserError::NotExist);
+                }
+                conn.execute(
+                    "UPDATE passkeys SET username = ?1 WHERE user_id = ?2",
+                    params![self.username.trim(), id],
+                )?;
+                if !self.password.is_empty() {
+                    conn.execute(
+                        "UPDATE users SET password_hash = ?1, password_set_at = ?2 WHERE id = ?3",
+                        params![hash_password(&self.password)?, now, id],
+                    )?;
+                }
+                id.clone()
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO users
+                     (id, username, email, display_name, department_id, avatar, password_hash,
+                        password_set_at, must_change_password)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        id,
+                        self.username.trim(),
+                        self.email.trim(),
+                        self.display_name.trim(),
+                        self.department_id,
+                        self.avatar.trim(),
+                        hash_password(&self.password)?,
+                        now,
+                        self.must_change_password,
+                    ],
+                )?;
+                id
+            }
+        };
+
+        conn.execute("DELETE FROM user_roles WHERE user_id = ?1", params![id])?;
+        for role in self.roles.iter() {
+            conn.execute(
+                "INSERT OR IGNORE INTO user_roles (user_id, role) VALUES (?1, ?2)",
+                params![id, role],
+            )?;
+        }
+        User::set_status(conn, &id, self.status)?;
+
+        Ok(id)
+    }
+}
+
+fn validate_email(
+    conn: &Connection,
+    email: &str,
+    id: &str,
+    errors: &mut FieldErrors,
+) -> Result<(), UserError> {
+    let email = email.trim();
+    let valid = email
+        .split_once('@')
+        .map(|(name, domain)| !name.is_empty() && domain.contains('.'))
+        .unwrap_or(false);
+    if !valid {
+        errors.insert("email".into(), "is not a valid email address".into());
+    } else if taken(conn, "email", email, id)? {
+        errors.insert("email".into(), "is already used by another user".into());
+    }
+
+    Ok(())
+}
+
+pub(super) fn validate_display_name(display_name: &str, errors: &mut FieldErrors) {
+    let display_name = display_name.trim();
+    if display_name.is_empty() || display_name.chars().count() > 64 {
+        errors.insert("display_name".into(), "must have 1 to 64 characters".into());
+    }
+}
+
+pub(super) fn validate_preferences(timezone: &str, language: &str, errors: &mut FieldErrors) {
+    if !TIMEZONES.contains(&timezone) {
+        errors.insert("timezone".into(), "is not supported".into());
+    }
+    if !LANGUAGES.iter().any(|(code, _)| *code == language) {
+        errors.insert("language".into(), "is not supported".into());
+    }
+}
+
+/// `value` of `column` is used by a user other than `id`
+fn taken(conn: &Connection, column: &str, value: &str, id: &str) -> Result<bool, UserError> {
+    let taken = conn.query_row(
+        &format!(
+            "SELECT EXISTS (SELECT 1 FROM users WHERE {} = ?1 COLLATE NOCASE AND id != ?2)",
+            column
+        ),
+        params![value, id],
+        |row| row.get(0),
+    )?;
+    Ok(taken)
+}
+
+impl UserProfile {
+    pub fn get(conn: &Connection, id: &str) -> Result<Self, UserError> {
+        conn.query_row(
+            "SELECT id, username, display_name, email, timezone, language, avatar
+             FROM users WHERE id = ?1 AND deleted_at IS NULL",
+            params![id],
+            |row| {
+                Ok(UserProfile {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    display_name: row.get(2)?,
+                    email: row.get(3)?,
+                    timezone: row.get(4)?,
+                    language: row.get(5)?,
+                    avatar: row.get(6)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or(UserError::NotExist)
+    }
+}
+
+impl CurrentUser {
+    /// the user of `token`, with roles and preferences loaded from database
+    pub fn from_token(conn: &Connection, token: &AuthenticationToken) -> Result<Self, UserError> {
+        let profile = UserProfile::get(conn, &token.id)?;
+
+        Ok(CurrentUser {
+            id: profile.id,
+            username: profile.username,
+            display_name: profile.display_name,
+            avatar: profile.avatar,
+            roles: roles_of(conn, &token.id)?,
+            timezone: profile.timezone,
+            language: profile.language,
+        })
+    }
+}
+
+impl ProfileForm {
+    /// errors of every invalid field, empty if the form is valid
+    pub fn validate(&self, conn: &Connection, id: &str) -> Result<FieldErrors, UserError> {
+        let mut errors = FieldErrors::new();
+
+        validate_email(conn, &self.email, id, &mut errors)?;
+        validate_display_name(&self.display_name, &mut errors);
+        validate_preferences(&self.timezone, &self.language, &mut errors);
+
+        Ok(errors)
+    }
+
+    /// update the profile of user `id`, the form must be validated first
+    pub fn save(&self, conn: &Connection, id: &str) -> Result<(), UserError> {
+        let updated = conn.execute(
+            "UPDATE users SET display_name = ?1, email = ?2, timezone = ?3, language = ?4
+             WHERE id = ?5",
+            params![
+                self.display_name.trim(),
+                self.email.trim(),
+                self.timezone,
+                self.language,
+                id
+            ],
+        )?;
+        if updated == 0 {
+            return Err(UserError::NotExist);
+        }
+
+        Ok(())
+    }
+}
+
+impl Role {
+    pub fn list(conn: &Connection) -> Result<Vec<Self>, UserError> {
+        let mut stmt = conn.prepare("SELECT name, description FROM roles ORDER BY name")?;
+        let list = stmt
+            .query_map([], |row| {
+                Ok(Role {
+                    name: row.get(0)?,
+                    description: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(list)
+    }
+}
+
+/// None if passwords never expire
+fn password_expires_at(password_set_at: i64) -> Option<i64> {
+    (SETTINGS.password_max_age_days > 0)
+        .then(|| password_set_at + SETTINGS.password_max_age_days * SECONDS_PER_DAY)
+}
+
+pub(super) fn format_timestamp(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+pub fn hash_password(password: &str) -> Result<String, UserError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| UserError::Storage)
+}
+
+/// a password nobody knows, for users who set their own later
+pub fn random_password() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect()
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
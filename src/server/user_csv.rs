@@ -0,0 +1,218 @@
+//! User CSV
+//! export users to and import users from CSV files, columns are [USER_CSV_FIELDS]
+//!
+//! - export is read in chunks of users, so that large files can be streamed
+//! - import maps columns of the file to fields, validates every row like the user form,
+//! and creates users only if committed, a dry run changes nothing
+//! - users imported without password get a random one and must change it,
+//! administrators set it for them or invite them later
+
+use super::user::random_password;
+use crate::models::consts::{DEFAULT_AVATAR, EMPLOYEE_ROLE};
+use crate::models::{
+    Department, FieldErrors, ImportMapping, ImportReport, ImportRow, UserDetail, UserForm,
+    UserStatus, USER_CSV_FIELDS,
+};
+use base64::prelude::*;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// the most rows can be imported at once
+const MAX_IMPORT_ROWS: usize = 10_000;
+
+pub enum CsvError {
+    InvalidFile,
+    TooManyRows,
+    Storage,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::InvalidFile => write!(f, "file is not a valid UTF-8 CSV"),
+            CsvError::TooManyRows => write!(f, "at most {} rows can be imported", MAX_IMPORT_ROWS),
+            CsvError::Storage => write!(f, "user storage fail"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for CsvError {
+    fn from(_: rusqlite::Error) -> Self {
+        CsvError::Storage
+    }
+}
+
+/// the header line of exported files
+pub fn export_header() -> String {
+    //  password is never exported
+    let mut header = USER_CSV_FIELDS[..USER_CSV_FIELDS.len() - 1].join(",");
+    header.push('\n');
+    header
+}
+
+/// lines of `users` in exported files
+pub fn export_rows(users: &[UserDetail]) -> Result<String, CsvError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for user in users {
+        writer
+            .write_record([
+                user.username.as_str(),
+                user.email.as_str(),
+                user.display_name.as_str(),
+                user.department.as_str(),
+                user.roles.join(";").as_str(),
+                user.status.to_string().as_str(),
+            ])
+            .map_err(|_| CsvError::InvalidFile)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|_| CsvError::InvalidFile)?;
+    String::from_utf8(bytes).map_err(|_| CsvError::InvalidFile)
+}
+
+/// the header line of file `data`, which is a data url or the text itself
+pub fn read_headers(data: &str) -> Result<Vec<String>, CsvError> {
+    let text = decode(data)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(|_| CsvError::InvalidFile)?;
+
+    Ok(headers.iter().map(|header| header.to_string()).collect())
+}
+
+/// validate every row of file `data` mapped by `mapping`,
+/// and create users of the valid rows if `commit`
+pub fn import(
+    conn: &mut Connection,
+    data: &str,
+    mapping: &ImportMapping,
+    commit: bool,
+) -> Result<ImportReport, CsvError> {
+    let text = decode(data)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let departments: HashMap<String, String> = Department::list(conn)
+        .map_err(|_| CsvError::Storage)?
+        .into_iter()
+        .map(|department| (department.name.to_lowercase(), department.id))
+        .collect();
+
+    //  dropped without commit on dry run, which rolls back everything
+    let tx = conn.transaction()?;
+    let mut report = ImportReport::default();
+    let mut usernames = HashSet::new();
+    let mut emails = HashSet::new();
+
+    for (index, record) in reader.records().enumerate() {
+        if index >= MAX_IMPORT_ROWS {
+            return Err(CsvError::TooManyRows);
+        }
+        let line = index + 2;
+        let Ok(record) = record else {
+            report.rows.push(ImportRow {
+                line,
+                username: String::new(),
+                email: String::new(),
+                display_name: String::new(),
+                errors: FieldErrors::from([("row".to_string(), "cannot be read".to_string())]),
+            });
+            continue;
+        };
+        let field = |name: &str| {
+            mapping
+                .get(name)
+                .and_then(|&column| record.get(column))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let mut errors = FieldErrors::new();
+        let department = field("department");
+        let department_id = if department.is_empty() {
+            None
+        } else {
+            let id = departments.get(&department.to_lowercase()).cloned();
+            if id.is_none() {
+                errors.insert("department".into(), format!("{} not exist", department));
+            }
+            id
+        };
+        let mut roles: Vec<String> = field("roles")
+            .split(';')
+            .map(|role| role.trim().to_string())
+            .filter(|role| !role.is_empty())
+            .collect();
+        if roles.is_empty() {
+            roles.push(EMPLOYEE_ROLE.to_string());
+        }
+        let status = match field("status").as_str() {
+            "" => UserStatus::Active,
+            status => status.parse().unwrap_or_else(|_| {
+                errors.insert("status".into(), format!("{} is not a status", status));
+                UserStatus::Active
+            }),
+        };
+        let password = match field("password") {
+            password if password.is_empty() => random_password(),
+            password => password,
+        };
+
+        let form = UserForm {
+            id: None,
+            username: field("username"),
+            email: field("email"),
+            display_name: field("display_name"),
+            department_id,
+            roles,
+            status,
+            avatar: DEFAULT_AVATAR.to_string(),
+            password,
+            must_change_password: true,
+        };
+        errors.extend(form.validate(&tx).map_err(|_| CsvError::Storage)?);
+        if !usernames.insert(form.username.trim().to_lowercase()) {
+            errors.insert("username".into(), "is duplicated in the file".into());
+        }
+        if !emails.insert(form.email.trim().to_lowercase()) {
+            errors.insert("email".into(), "is duplicated in the file".into());
+        }
+
+        if commit && errors.is_empty() {
+            form.save(&tx).map_err(|_| CsvError::Storage)?;
+            report.created += 1;
+        }
+        report.rows.push(ImportRow {
+            line,
+            username: form.username,
+            email: form.email,
+            display_name: form.display_name,
+            errors,
+        });
+    }
+
+    if commit {
+        tx.commit()?;
+    }
+
+    Ok(report)
+}
+
+/// text of `data`, which is a data url or the text itself
+fn decode(data: &str) -> Result<String, CsvError> {
+    let Some((_, encoded)) = data.strip_prefix("data:").and_then(|data| data.split_once(",")) else {
+        return Ok(data.to_string());
+    };
+
+    let bytes = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|_| CsvError::InvalidFile)?;
+    let text = String::from_utf8(bytes).map_err(|_| CsvError::InvalidFile)?;
+
+    //  spreadsheet applications like to begin with a BOM
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}